@@ -1,15 +1,75 @@
-use std::cell::RefCell;
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashSet, VecDeque},
+    io::{self, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use crate::{
+    attach,
+    bounce::Bounce,
+    calendar::{self, Answer, Invite},
+    clipboard,
+    command::{self, Command, Marking},
+    config::{ColumnConfig, Config, Confirmation, Policy, Posting, PreviewPane, Startup, TooLarge},
+    contacts::{self, Contact},
+    draft::Draft,
+    editor,
     event::{AppEvent, Event, EventHandler},
-    message::{Message, MessageProvider, SqliteMessageProvider},
+    links::{self, Link, Target},
+    merge::MailMerge,
+    message::{
+        ARCHIVE, ConnectionState, Message, MessagePart, MessageProvider, Mute, PAGE_SIZE,
+        SmtpConfig, SqliteMessageProvider, TRASH, bare_address, invalid_addresses, parse_addresses,
+        split_addresses,
+    },
+    mime, notify,
+    outbox::{self, Scheduled},
+    passphrase,
+    pgp::{Protection, Verification},
+    pipe, preview,
+    profile::Profile,
+    query::SavedSearch,
+    rules::Rules,
+    settings::{SETTINGS, Setting},
+    signature,
+    sync::Schedule,
+    terminal,
+    theme::Theme,
+    thread::{self, ThreadEntry},
+    vcard,
 };
+use chrono::{DateTime, Local};
+use lettre::message::Mailbox;
 use ratatui::{
     DefaultTerminal,
     crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
-    widgets::{ScrollbarState, TableState},
+    widgets::{ListState, ScrollbarState, TableState},
 };
+use toml_edit::value;
 use tui_textarea::{CursorMove, Scrolling, TextArea};
+use zeroize::{Zeroize, Zeroizing};
+
+/// How long status notifications stay visible.
+const NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// How much a message table column grows or shrinks at a time.
+const COLUMN_STEP: i16 = 5;
+const MIN_COLUMN_WIDTH: u16 = 10;
+const MAX_COLUMN_WIDTH: u16 = 200;
+
+/// How often the provider's connection is checked again.
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Older messages are fetched once the selection comes this close to the top of the table.
+const PREFETCH_ROWS: usize = 10;
+
+/// How many columns `h` and `l` scroll unwrapped message lines sideways.
+const SIDE_SCROLL: u16 = 80;
+
+/// How dates are written out in full, e.g. in the message view and when quoting.
+const DATE_FORMAT: &str = "%a, %-d %b %Y at %H:%M";
 
 /// Application.
 #[derive(Debug)]
@@ -22,43 +82,447 @@ pub struct App<'a> {
     events: EventHandler,
     /// Current application mode.
     mode: Mode,
+    /// Settings from `eta.toml`.
+    config: Config,
     /// Message provider.
     messages: SqliteMessageProvider,
+    /// Whose settings and messages are shown.
+    profile: Profile,
+    /// Set from the command line to write messages to files here rather than send them, whatever
+    /// the profile.
+    dry_run_send: Option<PathBuf>,
+    /// Profiles listed in the profile switcher.
+    profiles: Vec<Profile>,
+    /// A profile to switch to once the current event has been handled.
+    pending_profile: Option<Profile>,
+    /// Whether to open the message being composed in an external editor once the current event
+    /// has been handled.
+    editor_pending: bool,
+    /// Folders listed in the sidebar.
+    folders: Vec<String>,
+    /// Folder sidebar state.
+    folder_list_state: RefCell<ListState>,
+    /// Folder whose messages are shown in the message table.
+    current_folder: String,
     /// Message table state.
     message_table_state: RefCell<TableState>,
-    /// Message table scrollbar state.
-    message_scroll_state: ScrollbarState,
+    /// Message table scrollbar state, kept up to date as the table is drawn.
+    message_scroll_state: RefCell<ScrollbarState>,
     compose_message_input: RefCell<TextArea<'a>>,
     compose_to_input: RefCell<TextArea<'a>>,
+    compose_cc_input: RefCell<TextArea<'a>>,
     compose_subject_input: RefCell<TextArea<'a>>,
+    /// The signature added to the message being composed, if any.
+    compose_signature: Option<ComposeSignature>,
+    /// The message being replied to, if any, so the reply can be threaded with it.
+    compose_reply_to: Option<Box<Message>>,
+    /// Files attached to the message being composed.
+    compose_attachments: Vec<MessagePart>,
+    /// How the message being composed is to be signed or encrypted.
+    compose_protection: Protection,
+    /// Which of the identities in `eta.toml` the message being composed is sent from, if not
+    /// the account's own address.
+    compose_identity: Option<usize>,
+    /// Text typed at the prompt below the composer.
+    compose_prompt_input: RefCell<TextArea<'a>>,
+    /// Output of the command being attached, while its filename and type are chosen.
+    command_output: Option<Vec<u8>>,
+    /// Commands that finished while the composer wasn't open, and their output, to be attached
+    /// once it is.
+    queued_command_output: VecDeque<(String, Result<Vec<u8>, String>)>,
+    /// Filename chosen for the command output being attached.
+    attachment_filename: String,
+    /// Start a reply once the selected message has loaded.
+    reply_pending: Option<ReplyTo>,
+    /// Addresses messages have been exchanged with, to catch mistyped recipients.
+    contacts: Vec<String>,
+    /// Everyone in the address book.
+    address_book: Vec<Contact>,
+    /// Messages waiting in the outbox to be sent later, soonest first.
+    scheduled: Vec<Scheduled>,
+    /// The message being composed as it was last saved, if it was, and the id of its draft once
+    /// it has one.
+    saved_draft: Option<Draft>,
+    compose_draft_id: Option<u64>,
+    /// Drafts left from before that wait for the composer to be free, newest last, each with its
+    /// id and the message it replies to.
+    recovered_drafts: Vec<(u64, Draft, Option<Box<Message>>)>,
+    /// When the message being composed is next saved.
+    next_autosave: Instant,
+    /// Addresses offered to complete the one being typed in the To or Cc field, and which is
+    /// selected.
+    completions: Vec<String>,
+    completion_index: usize,
+    /// The partly typed address completions were last asked for.
+    completion_query: String,
+    /// Name and address of the contact being added or edited.
+    contact_input: RefCell<TextArea<'a>>,
+    /// Senders whose messages may load images and other content from the internet.
+    remote_content_senders: Vec<String>,
+    /// `Message-ID`s of the first messages of threads to announce replies to.
+    watched_threads: Vec<String>,
+    /// The message being read, and when it will be marked as read if it is still open.
+    pending_read: Option<(u64, Instant)>,
+    /// Threads and senders whose new messages are marked as read straight away.
+    mutes: Vec<Mute>,
+    /// Every tag some message has, to pick from.
+    tags: Vec<String>,
+    /// The tag being typed in the tag picker, which narrows down the tags to pick from.
+    tag_input: RefCell<TextArea<'a>>,
+    /// The search whose results are shown in the table instead of the current folder.
+    search: Option<String>,
+    search_input: RefCell<TextArea<'a>>,
+    /// Text typed at the prompt below the message being read.
+    message_prompt_input: RefCell<TextArea<'a>>,
+    /// A shell command to pipe the message being read to once the current event has been
+    /// handled.
+    pipe_pending: Option<String>,
+    /// Text the From or Subject of messages in the table must contain, in lowercase.
+    filter: String,
+    filter_input: RefCell<TextArea<'a>>,
+    command_input: RefCell<TextArea<'a>>,
+    /// The SMTP password being typed, shown masked.
+    password_input: RefCell<TextArea<'a>>,
+    /// The highest message id loaded so far, so newly arrived messages can be told apart.
+    newest_id: Option<u64>,
+    /// An announcement shown above the message table until the next key press, e.g. a reply
+    /// to a watched thread.
+    alert: Option<String>,
+    /// The To and Cc fields last warned about as likely typos. Sending them again goes ahead.
+    typo_warning: Option<(String, String)>,
+    /// The size of the attachments last warned about as too large. Sending again goes ahead.
+    size_warning: Option<u64>,
+    /// A private directory for the HTML parts opened in the browser, removed when eta exits.
+    html_dir: Option<tempfile::TempDir>,
+    /// While attachments are being zipped, how many of them were, and how to send the message
+    /// once they are, if it is to be sent.
+    zipping: Option<(usize, Option<PendingAction>)>,
     message_textarea: RefCell<TextArea<'a>>,
+    /// Status text shown in the message view or the composer, e.g. the result of an action.
+    message_status: Option<String>,
+    /// When the current status notification should be cleared, if ever.
+    notification_expires: Option<Instant>,
+    /// Whether the provider could reach everything when last checked.
+    connection: ConnectionState,
+    /// When the connection is next checked.
+    next_connection_check: Instant,
+    /// When messages were last loaded from the provider.
+    last_sync: Option<chrono::DateTime<chrono::Local>>,
+    /// Work waited on in the background, most recently started last.
+    activities: Vec<Activity>,
+    /// Frame of the spinner shown alongside the activities, moved on each tick.
+    spinner_frame: usize,
+    /// Current time shown in the status bar.
+    clock: String,
     current_message: Box<Message>,
-    loaded_messages: Box<Vec<Message>>,
+    /// What went wrong, when the current message is a bounce.
+    bounce: Option<Bounce>,
+    /// The invite in the current message, if it has one.
+    invite: Option<Invite>,
+    loaded_messages: Vec<Message>,
+    /// How many messages there are in the current folder, loaded or not.
+    message_total: Option<usize>,
+    /// How many of the messages in the current folder are unread.
+    unread_total: usize,
+    /// Whether the current folder has messages older than those loaded, and whether they are
+    /// being fetched.
+    older_messages: bool,
+    loading_older: bool,
+    /// Only show flagged messages in the message table.
+    flagged_only: bool,
+    /// Group the message table into conversations.
+    threaded: bool,
+    /// Ids of the first messages of threads whose replies are shown.
+    expanded_threads: HashSet<u64>,
+    /// Ids of the messages marked in the table, which moving, deleting and marking as read act on
+    /// together.
+    marked: HashSet<u64>,
+    /// While marking a range with `V`, the message it started from and the marks made before it.
+    mark_anchor: Option<(u64, HashSet<u64>)>,
+    /// Show only the message body in the message view, without the status bar.
+    zen_mode: bool,
+    /// Wrap long lines of the message body to the width of the screen, rather than scroll
+    /// sideways to read them.
+    wrap_message: bool,
+    /// Lines of the message body scrolled past, how many fit on screen and how many there are
+    /// once wrapped, all kept up to date as the message is drawn.
+    message_scroll: Cell<u16>,
+    message_height: Cell<u16>,
+    message_lines: Cell<u16>,
+    /// Columns scrolled past sideways, when long lines aren't wrapped.
+    message_side_scroll: Cell<u16>,
+    /// How many rows of the message table fit on the screen, as last drawn.
+    table_height: Cell<u16>,
+    /// Keys typed on the main page towards a longer command, such as the `g` of `gg` or the
+    /// count of `42G`.
+    pending_keys: String,
+    /// Where the selected message is previewed next to the table.
+    preview_pane: PreviewPane,
+    /// The message whose body was last fetched for the preview pane.
+    preview_requested: Option<u64>,
+    /// Links found in the current message.
+    links: Vec<Link>,
+    /// The links and addresses labelled with link hints, in the order of their labels, found
+    /// as the message is drawn.
+    hint_targets: RefCell<Vec<Target>>,
+    /// Lines scrolled past in the attachment preview.
+    preview_scroll: u16,
+    /// Path to the CSV of mail merge recipients.
+    merge_csv_input: RefCell<TextArea<'a>>,
+    /// Messages generated by the current mail merge.
+    merge_messages: Vec<Message>,
+    /// The value of the setting being edited.
+    settings_input: RefCell<TextArea<'a>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Mode {
     LoadingMessages,
     MessageTable(MessageTableMode),
+    /// Choosing a folder from the sidebar.
+    Folders,
+    /// Choosing a profile to switch to, and the selected profile.
+    Profiles(usize),
+    /// Choosing a folder to move the messages with the given ids to, and the selected folder.
+    MoveMessage(Vec<u64>, usize),
     Message(usize),
+    /// Choosing a link from the message at the given row, and the selected link.
+    Links(usize, usize),
+    /// Reading the part at the given index of the message at the given row.
+    Attachment(usize, usize),
+    /// Typing at the prompt below the message at the given row.
+    MessagePrompt(usize, MessagePrompt),
+    /// Choosing what to copy to the clipboard from the message at the given row.
+    Copy(usize),
+    /// Picking a tag to give the message with the given id, or to take away from it, the
+    /// selected tag, and the mode to return to.
+    Tags(u64, usize, Box<Mode>),
+    /// Waiting for the key that follows `g` in the message at the given row.
+    Go(usize),
+    /// Typing the label of a link hint shown over the message at the given row, what to do with
+    /// the link, and the keys typed so far.
+    Hints(usize, HintAction, String),
     Compose(ComposeFocus),
+    /// Typing at the prompt below the composer, and the field to return to afterwards.
+    ComposePrompt(ComposeFocus, ComposePrompt),
+    MailMerge(MailMergeMode),
+    Settings(SettingsMode),
+    Contacts(ContactsMode),
+    /// Listing the messages waiting to be sent later, with the one at the given index selected.
+    Scheduled(usize),
+    /// Typing a search of every folder's messages.
+    Search,
+    /// Typing text to narrow the message table down to.
+    Filter,
+    /// Typing a command at the `:` command line.
+    CommandLine,
+    /// Asking whether to go ahead with an action, over the mode it was taken in.
+    Confirm(PendingAction, Box<Mode>),
+    /// Typing the SMTP password to send with, over the mode the send was started in.
+    Password(Sending, Box<Mode>),
+    /// Showing an error over the mode it happened in, until it is dismissed.
+    Error(String, Box<Mode>),
+}
+
+/// What to send once the SMTP password has been entered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Sending {
+    Message,
+    MailMerge,
+    Rsvp(Answer),
+    /// The message composed, at the given time.
+    Later(DateTime<Local>),
+    /// The scheduled message with this id, straight away.
+    Scheduled(u64),
+}
+
+/// An action that may need confirming, along with what it acts on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PendingAction {
+    Send,
+    /// Delete the messages with these ids for good.
+    Delete(Vec<u64>),
+    /// Delete messages that have been in the trash for this many days.
+    EmptyTrash(u32),
+    DiscardDraft,
+    /// Always load remote content from this sender.
+    RemoteContent(String),
+    /// Answer the invite in the current message.
+    Rsvp(Answer),
+    /// Send the message being composed at the given time.
+    SendLater(DateTime<Local>),
+    /// Take the message with this id out of the outbox without sending it.
+    CancelScheduled(u64),
+}
+
+impl PendingAction {
+    fn confirmation(&self) -> Confirmation {
+        match self {
+            PendingAction::Send | PendingAction::Rsvp(_) | PendingAction::SendLater(_) => {
+                Confirmation::Send
+            }
+            PendingAction::Delete(_) => Confirmation::Delete,
+            PendingAction::EmptyTrash(_) => Confirmation::EmptyTrash,
+            PendingAction::DiscardDraft | PendingAction::CancelScheduled(_) => {
+                Confirmation::DiscardDraft
+            }
+            PendingAction::RemoteContent(_) => Confirmation::RemoteContent,
+        }
+    }
+
+    /// Whether the action can't be undone, so that only `y` goes ahead with it, not `Enter`.
+    fn is_permanent(&self) -> bool {
+        matches!(
+            self,
+            PendingAction::Delete(_)
+                | PendingAction::EmptyTrash(_)
+                | PendingAction::DiscardDraft
+                | PendingAction::CancelScheduled(_)
+        )
+    }
+
+    pub fn question(&self) -> String {
+        match self {
+            PendingAction::Send => String::from("Send this message? (y/n)"),
+            PendingAction::Delete(ids) if ids.len() > 1 => {
+                format!("Delete these {} messages for good? (y/n)", ids.len())
+            }
+            PendingAction::Delete(_) => String::from("Delete this message for good? (y/n)"),
+            PendingAction::EmptyTrash(days) => {
+                format!("Delete messages in the Trash for over {days} days? (y/n)")
+            }
+            PendingAction::DiscardDraft => String::from("Discard this message? (y/n)"),
+            PendingAction::RemoteContent(sender) => {
+                format!("Always load remote content from {sender}? (y/n)")
+            }
+            PendingAction::Rsvp(answer) => {
+                format!(
+                    "Tell the organizer you have {}? (y/n)",
+                    answer.description()
+                )
+            }
+            PendingAction::SendLater(send_at) => format!(
+                "Send this message on {}? (y/n)",
+                send_at.format(outbox::SEND_AT_FORMAT)
+            ),
+            PendingAction::CancelScheduled(_) => {
+                String::from("Discard this message without sending it? (y/n)")
+            }
+        }
+    }
+
+    /// Why the action was refused, when it is set to `never`.
+    fn refusal(&self) -> &'static str {
+        match self {
+            PendingAction::Send | PendingAction::Rsvp(_) | PendingAction::SendLater(_) => {
+                "Sending is turned off in eta.toml"
+            }
+            PendingAction::Delete(_) => "Deleting for good is turned off in eta.toml",
+            PendingAction::EmptyTrash(_) => "Emptying the trash is turned off in eta.toml",
+            PendingAction::DiscardDraft | PendingAction::CancelScheduled(_) => {
+                "Discarding messages is turned off in eta.toml"
+            }
+            PendingAction::RemoteContent(_) => "Loading remote content is turned off in eta.toml",
+        }
+    }
+}
+
+/// A signature added to the message being composed, which can be taken out again.
+#[derive(Clone, Debug)]
+struct ComposeSignature {
+    lines: Vec<String>,
+    /// Line of the message the signature starts at, or would be put back at.
+    at: usize,
+    shown: bool,
+}
+
+/// How many messages there are in the current folder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageCounts {
+    /// How many are in the table.
+    pub loaded: usize,
+    pub unread: usize,
+    pub total: usize,
+}
+
+/// A row of the message table.
+#[derive(Clone, Copy, Debug)]
+pub struct MessageRow<'m> {
+    pub message: &'m Message,
+    /// How many replies deep the message is in its thread.
+    pub depth: usize,
+    /// Id of the first message in the thread.
+    pub thread: u64,
+    /// Number of replies collapsed into this row.
+    pub hidden_replies: usize,
+    /// Whether the message is in a muted thread or from a muted sender.
+    pub muted: bool,
+    /// Whether the message is marked, to act on along with the other marked messages.
+    pub marked: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum MessageTableMode {
     Normal,
     MessageSent(MessageSentStatus),
+    /// The result of some other action, e.g. moving a message.
+    Status(String),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum MessageSentStatus {
     Success,
     Failed(String),
+    /// A mail merge put in the outbox: the number of messages it holds, and the failures.
+    MailMergeQueued(usize, Vec<String>),
+}
+
+/// Work going on in the background, shown in the status bar with a spinner until it is done.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Activity {
+    /// Loading the first page of the named folder.
+    Loading(String),
+    LoadingOlder,
+    Searching,
+    Sending,
+    Zipping,
+    /// Putting a mail merge in the outbox.
+    MailMerge,
+}
+
+impl Activity {
+    pub fn text(&self) -> String {
+        match self {
+            Activity::Loading(folder) => format!("Loading {folder}…"),
+            Activity::LoadingOlder => String::from("Loading older messages…"),
+            Activity::Searching => String::from("Searching…"),
+            Activity::Sending => String::from("Sending…"),
+            Activity::Zipping => String::from("Zipping attachments…"),
+            Activity::MailMerge => String::from("Putting the mail merge in the outbox…"),
+        }
+    }
+
+    /// Whether `event` is the result of this activity.
+    fn is_done_by(&self, event: &AppEvent) -> bool {
+        matches!(
+            (self, event),
+            (Activity::Loading(_), AppEvent::MessagesLoaded(_))
+                | (Activity::LoadingOlder, AppEvent::OlderMessagesLoaded(..))
+                | (Activity::Searching, AppEvent::SearchResultsLoaded(..))
+                | (
+                    Activity::Sending,
+                    AppEvent::MessageSent(_) | AppEvent::MessageWritten(_)
+                )
+                | (Activity::Zipping, AppEvent::Zipped(_))
+                | (Activity::MailMerge, AppEvent::MailMergeQueued(..))
+        )
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ComposeFocus {
     To(ComposeMode),
+    Cc(ComposeMode),
     Subject(ComposeMode),
     Message(ComposeMode),
 }
@@ -69,69 +533,530 @@ pub enum ComposeMode {
     Editing,
 }
 
-impl<'a> Default for App<'a> {
-    fn default() -> Self {
+/// What is being typed at the prompt below the composer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComposePrompt {
+    /// A command, such as `attach-cmd git diff` or `attach-inline logo.png`.
+    Command,
+    /// The filename to attach the output of a command as.
+    Filename,
+    /// The MIME type to attach it as.
+    ContentType,
+    /// When to send the message.
+    SendAt,
+}
+
+/// What is being typed at the prompt below the message being read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessagePrompt {
+    /// Where to export the message to, as a `.eml` file.
+    Export,
+    /// Where to save its text to.
+    SaveText,
+    /// A shell command to pipe its text to.
+    Pipe,
+}
+
+/// What to do with the link whose hint is typed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HintAction {
+    /// Open a URL in the browser, or write to an email address.
+    Open,
+    Copy,
+}
+
+/// Who a reply is addressed to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplyTo {
+    /// Only the sender of the original message.
+    Sender,
+    /// The sender and everyone else the original was sent to, apart from ourselves.
+    All,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MailMergeMode {
+    /// Entering the path of the recipients CSV, along with any error from loading it.
+    SelectCsv(Option<String>),
+    /// Previewing the generated message at the given index.
+    Preview(usize),
+}
+
+/// A direction to move through the message table in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Next,
+    Previous,
+}
+
+/// What muting a message mutes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MuteBy {
+    Thread,
+    Sender,
+}
+
+/// A message table column that can be resized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Column {
+    From,
+    Subject,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SettingsMode {
+    /// Choosing the setting at the given index.
+    Normal(usize),
+    /// Editing the value of the setting at the given index.
+    Editing(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContactsMode {
+    /// Browsing the address book, with the contact at the given index selected.
+    Normal(usize),
+    /// Editing the contact at the given index, or adding one when it is past the end.
+    Editing(usize),
+    /// Entering the path of a vCard file to import contacts from.
+    Importing,
+    /// Choosing a contact to add to a field of the message being composed.
+    Picking(usize, ComposeField),
+}
+
+/// A field of the message being composed that contacts can be added to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComposeField {
+    To,
+    Cc,
+}
+
+impl<'a> App<'a> {
+    /// Constructs a new instance of [`App`], showing the given profile's messages. Messages are
+    /// written to files in `dry_run_send`, if given, rather than sent.
+    pub fn new(profile: Profile, dry_run_send: Option<PathBuf>) -> color_eyre::Result<Self> {
         let event_handler = EventHandler::new();
         let event_sender = event_handler.sender();
+        profile.create_dirs()?;
+        let config = Config::load(&profile)?;
+        let passphrase = passphrase::prompt(&profile, &config)?;
+        let messages = SqliteMessageProvider::new(
+            event_sender,
+            smtp_config(&config, dry_run_send.as_ref()),
+            rules(&config, &profile),
+            &profile.database_path(),
+            passphrase.as_deref(),
+        )?;
+        let preview_pane = config.preview_pane;
 
-        let mut app = Self {
+        let app = Self {
             running: true,
             needs_render: true,
             events: event_handler,
             mode: Mode::MessageTable(MessageTableMode::Normal),
-            messages: SqliteMessageProvider::new(event_sender).unwrap(),
+            messages,
+            config,
+            profile,
+            dry_run_send,
+            profiles: vec![],
+            pending_profile: None,
+            editor_pending: false,
+            folders: vec![],
+            folder_list_state: RefCell::new(ListState::default()),
+            current_folder: String::from("Inbox"),
             message_table_state: RefCell::new(TableState::default().with_selected(0)),
-            message_scroll_state: ScrollbarState::default(),
+            message_scroll_state: RefCell::new(ScrollbarState::default()),
             compose_message_input: RefCell::new(TextArea::default()),
             compose_to_input: RefCell::new(TextArea::default()),
+            compose_cc_input: RefCell::new(TextArea::default()),
             compose_subject_input: RefCell::new(TextArea::default()),
+            compose_signature: None,
+            compose_reply_to: None,
+            compose_attachments: vec![],
+            compose_protection: Protection::None,
+            compose_identity: None,
+            compose_prompt_input: RefCell::new(TextArea::default()),
+            command_output: None,
+            queued_command_output: VecDeque::new(),
+            attachment_filename: String::new(),
+            reply_pending: None,
+            contacts: vec![],
+            address_book: vec![],
+            scheduled: vec![],
+            saved_draft: None,
+            compose_draft_id: None,
+            recovered_drafts: vec![],
+            next_autosave: Instant::now(),
+            completions: vec![],
+            completion_index: 0,
+            completion_query: String::new(),
+            contact_input: RefCell::new(TextArea::default()),
+            remote_content_senders: vec![],
+            watched_threads: vec![],
+            pending_read: None,
+            mutes: vec![],
+            tags: vec![],
+            tag_input: RefCell::new(TextArea::default()),
+            search: None,
+            search_input: RefCell::new(TextArea::default()),
+            message_prompt_input: RefCell::new(TextArea::default()),
+            pipe_pending: None,
+            filter: String::new(),
+            filter_input: RefCell::new(TextArea::default()),
+            command_input: RefCell::new(TextArea::default()),
+            password_input: RefCell::new(TextArea::default()),
+            newest_id: None,
+            alert: None,
+            typo_warning: None,
+            size_warning: None,
+            html_dir: None,
+            zipping: None,
             message_textarea: RefCell::new(TextArea::default()),
+            message_status: None,
+            notification_expires: None,
+            connection: ConnectionState::Connecting,
+            next_connection_check: Instant::now(),
+            last_sync: None,
+            activities: vec![],
+            spinner_frame: 0,
+            clock: clock_text(),
             current_message: Box::new(Message::default()),
-            loaded_messages: Box::new(vec![]),
+            bounce: None,
+            invite: None,
+            loaded_messages: vec![],
+            message_total: None,
+            unread_total: 0,
+            older_messages: false,
+            loading_older: false,
+            flagged_only: false,
+            threaded: false,
+            expanded_threads: HashSet::new(),
+            marked: HashSet::new(),
+            mark_anchor: None,
+            zen_mode: false,
+            wrap_message: true,
+            message_scroll: Cell::new(0),
+            message_height: Cell::new(0),
+            message_lines: Cell::new(0),
+            message_side_scroll: Cell::new(0),
+            table_height: Cell::new(0),
+            pending_keys: String::new(),
+            preview_pane,
+            preview_requested: None,
+            links: vec![],
+            hint_targets: RefCell::new(vec![]),
+            preview_scroll: 0,
+            merge_csv_input: RefCell::new(TextArea::default()),
+            merge_messages: vec![],
+            settings_input: RefCell::new(TextArea::default()),
         };
-        app.message_scroll_state = ScrollbarState::new(app.loaded_messages.len().saturating_sub(1));
 
-        app
-    }
-}
-
-impl<'a> App<'a> {
-    /// Constructs a new instance of [`App`].
-    pub fn new() -> Self {
-        Self::default()
+        Ok(app)
     }
 
     /// Run the application's main loop.
     pub async fn run(mut self, mut terminal: DefaultTerminal) -> color_eyre::Result<()> {
         // allow the message provider to initialize
         self.messages.init().await?;
-
-        // start by loading messages, since we start on the message table
-        self.messages.get_messages();
+        self.open_startup_view();
+        self.load_account();
 
         while self.running {
             if self.needs_render {
                 terminal.draw(|frame| frame.render_widget(&self, frame.area()))?;
+                self.needs_render = false;
             }
             match self.events.next().await? {
                 Event::Tick => self.tick(),
-                Event::Crossterm(event) => match event {
-                    crossterm::event::Event::Key(key_event) => self.handle_key_events(key_event)?,
-                    _ => {}
-                },
+                Event::Crossterm(event) => {
+                    if let crossterm::event::Event::Key(key_event) = event {
+                        self.handle_key_events(key_event)?
+                    } else {
+                        // e.g. the terminal was resized
+                        self.needs_render = true;
+                    }
+                }
                 Event::App(app_event) => {
                     self.needs_render = true;
+                    self.finish_activity(&app_event);
                     match app_event {
-                        AppEvent::MessagesLoaded(messages) => self.set_loaded_messages(messages),
-                        AppEvent::MessageBodyLoaded(id, body) => self.set_current_message(id, body),
+                        AppEvent::FoldersLoaded(folders) => self.set_folders(folders),
+                        // a folder loaded alongside a search is only shown once it ends
+                        AppEvent::MessagesLoaded(_) if self.search.is_some() => {}
+                        AppEvent::MessagesLoaded(messages) => {
+                            self.last_sync = Some(chrono::Local::now());
+                            // a full page means there may be more
+                            self.older_messages = messages.len() == PAGE_SIZE;
+                            self.loading_older = false;
+                            self.set_loaded_messages(messages);
+                            self.announce_watched_replies();
+                        }
+                        AppEvent::OlderMessagesLoaded(folder, messages) => {
+                            if folder == self.current_folder && self.search.is_none() {
+                                self.add_older_messages(messages);
+                            }
+                        }
+                        AppEvent::NewMessagesLoaded(folder, messages) => {
+                            self.last_sync = Some(chrono::Local::now());
+                            if folder == self.current_folder && self.search.is_none() {
+                                self.add_new_messages(messages);
+                            }
+                        }
+                        AppEvent::MessageCountLoaded(folder, count, unread) => {
+                            if folder == self.current_folder && self.search.is_none() {
+                                self.message_total = Some(count);
+                                self.unread_total = unread;
+                                self.needs_render = true;
+                            }
+                        }
+                        AppEvent::SearchResultsLoaded(query, messages) => {
+                            // results of a search since left or replaced are no use
+                            if self.search.as_ref() == Some(&query) {
+                                self.message_table_state.get_mut().select(None);
+                                // results say nothing about what is new in the folder, so the
+                                // newest id seen is left for the next poll
+                                self.set_loaded_messages(messages);
+                            }
+                        }
+                        AppEvent::ContactsLoaded(contacts) => self.contacts = contacts,
+                        AppEvent::AddressBookLoaded(contacts) => self.set_address_book(contacts),
+                        AppEvent::ContactsImported(added) => {
+                            self.set_message_status(format!("Imported {added} new contacts"))
+                        }
+                        AppEvent::MessagesImported(imported, failures) => {
+                            self.messages_imported(imported, failures)
+                        }
+                        AppEvent::AddressCompletionsLoaded(query, completions) => {
+                            // completions for an address since typed over are no use
+                            if query == self.completion_query {
+                                self.completions = completions;
+                                self.completion_index = 0;
+                            }
+                        }
+                        AppEvent::RemoteContentSendersLoaded(senders) => {
+                            self.remote_content_senders = senders
+                        }
+                        AppEvent::WatchedThreadsLoaded(roots) => self.watched_threads = roots,
+                        AppEvent::TagsLoaded(tags) => self.tags = tags,
+                        AppEvent::MutesLoaded(mutes) => {
+                            self.mutes = mutes;
+                            self.mark_muted_read();
+                        }
+                        AppEvent::MessageBodyLoaded(id, parts, verification) => {
+                            self.set_current_message(id, parts, verification)
+                        }
                         AppEvent::MessageSent(option) => self.set_message_sent_status(option),
+                        AppEvent::MessageScheduled(result) => self.set_table_status(match result {
+                            Ok(send_at) => format!(
+                                "Message to be sent on {}",
+                                send_at.format(outbox::SEND_AT_FORMAT)
+                            ),
+                            Err(e) => format!("Error: {e}"),
+                        }),
+                        AppEvent::ScheduledLoaded(scheduled) => self.set_scheduled(scheduled),
+                        AppEvent::DraftsLoaded(drafts) => self.recover_drafts(drafts),
+                        AppEvent::MessageWritten(path) => self.set_table_status(format!(
+                            "Dry run: message written to {}",
+                            path.display()
+                        )),
+                        AppEvent::MessageExported(result) => {
+                            self.set_message_status(match result {
+                                Ok(path) => format!("Exported to {}", path.display()),
+                                Err(e) => format!("Error: {e}"),
+                            })
+                        }
+                        AppEvent::CommandOutput(command, result) => {
+                            self.queued_command_output.push_back((command, result))
+                        }
+                        AppEvent::Zipped(result) => self.attachments_zipped(result),
                         AppEvent::SendMessage => self.send_message(),
+                        AppEvent::SendMailMerge => self.send_mail_merge(),
+                        AppEvent::MailMergeQueued(queued, failures) => self
+                            .set_message_table_status(MessageSentStatus::MailMergeQueued(
+                                queued, failures,
+                            )),
+                        AppEvent::ConnectionState(state) => self.set_connection(state),
+                        AppEvent::RunCommand(command) => self.run_command(command),
                         AppEvent::Quit => self.quit(),
-                        AppEvent::Error(e) => self.show_error(e)?,
+                        AppEvent::Error(e) => self.show_error(e),
                     };
                 }
             }
+
+            // output of a command is attached once the composer is back in front
+            if matches!(self.mode, Mode::Compose(_))
+                && let Some((command, result)) = self.queued_command_output.pop_front()
+            {
+                self.attach_command_output(&command, result);
+                self.needs_render = true;
+            }
+
+            // switching needs the new database to be set up before anything else happens
+            if let Some(profile) = self.pending_profile.take()
+                && let Err(e) = self.open_profile(profile, &mut terminal).await
+            {
+                self.set_table_status(format!("Error: {e}"));
+            }
+
+            // the editor needs the terminal to itself, which only the event loop can give it
+            if self.editor_pending {
+                self.editor_pending = false;
+                self.events.pause().await;
+                let result = editor::edit(&mut terminal, &self.compose_message_text());
+                self.events.resume();
+                match result {
+                    Ok(text) => self.set_edited_body(&text),
+                    Err(e) => self.set_message_status(format!("Error: {e}")),
+                }
+                self.needs_render = true;
+            }
+
+            if let Some(command) = self.pipe_pending.take() {
+                self.events.pause().await;
+                let result = pipe::pipe(&mut terminal, &command, &self.current_message_text());
+                self.events.resume();
+                if let Err(e) = result {
+                    self.set_message_status(format!("Error: {e}"));
+                }
+                self.needs_render = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start on what the `startup` setting asks for rather than the inbox. Composing is left to
+    /// the command line when it already asked for it.
+    fn open_startup_view(&mut self) {
+        match self.config.startup.clone() {
+            Startup::Inbox => {}
+            Startup::Folder(folder) => self.current_folder = folder,
+            Startup::Search(query) => {
+                self.messages.search_messages(&query);
+                self.activities.push(Activity::Searching);
+                self.search = Some(query);
+            }
+            Startup::Compose => {
+                if let Mode::MessageTable(_) = self.mode {
+                    self.compose_message();
+                }
+            }
+        }
+    }
+
+    /// Load everything shown from the message provider, and tidy up the trash if configured to.
+    fn load_account(&mut self) {
+        if let Some(days) = self.config.empty_trash_after_days {
+            self.confirm(PendingAction::EmptyTrash(days));
+        }
+
+        // start by loading messages, since we start on the message table
+        self.messages.get_folders();
+        self.messages.get_messages(&self.current_folder);
+        self.activities
+            .push(Activity::Loading(self.current_folder.clone()));
+        self.messages.get_contacts();
+        self.messages.get_address_book();
+        self.messages.get_scheduled();
+        self.messages.get_drafts();
+        self.messages.get_remote_content_senders();
+        self.messages.get_watched_threads();
+        self.messages.get_mutes();
+        self.messages.get_tags();
+        self.messages.send_outbox();
+        self.poll_folders();
+        self.connection = ConnectionState::Connecting;
+        self.check_connection();
+
+        if self.config.smtp.tls().accepts_invalid_certs() {
+            self.show_error(String::from(
+                "accept_invalid_certs is set in [tls], so the SMTP server's certificate isn't \
+                 checked: anyone between you and the server could read your password and \
+                 messages.",
+            ));
+        }
+    }
+
+    /// Ask the provider whether it can reach everything, keeping the last answer up meanwhile.
+    fn check_connection(&mut self) {
+        self.messages.check_connection();
+        self.next_connection_check = Instant::now() + CONNECTION_CHECK_INTERVAL;
+    }
+
+    fn set_connection(&mut self, state: ConnectionState) {
+        // say why once, rather than after every check
+        if let ConnectionState::Offline(reason) = &state
+            && self.connection != state
+        {
+            self.set_table_status(format!("Offline: {reason}"));
+        }
+        self.connection = state;
+    }
+
+    /// Switch to another profile's settings and messages, leaving nothing of the current one
+    /// behind.
+    async fn open_profile(
+        &mut self,
+        profile: Profile,
+        terminal: &mut DefaultTerminal,
+    ) -> color_eyre::Result<()> {
+        profile.create_dirs()?;
+        let config = Config::load(&profile)?;
+        // the passphrase is asked for on the terminal, which the interface has to give up
+        let passphrase = if config.encrypt_database {
+            self.events.pause().await;
+            let passphrase = terminal::suspend(terminal, || passphrase::prompt(&profile, &config));
+            self.events.resume();
+            passphrase??
+        } else {
+            None
+        };
+        let messages = SqliteMessageProvider::new(
+            self.events.sender(),
+            smtp_config(&config, self.dry_run_send.as_ref()),
+            rules(&config, &profile),
+            &profile.database_path(),
+            passphrase.as_deref(),
+        )?;
+        messages.init().await?;
+
+        // the message being composed is kept with the profile it was started in
+        if self.config.autosave_seconds > 0 {
+            self.autosave_draft();
         }
+        self.saved_draft = None;
+        self.compose_draft_id = None;
+        self.recovered_drafts.clear();
+        self.preview_pane = config.preview_pane;
+        self.preview_requested = None;
+        self.config = config;
+        self.messages = messages;
+        self.profile = profile;
+        self.folders.clear();
+        self.current_folder = String::from("Inbox");
+        *self.folder_list_state.get_mut() = ListState::default();
+        *self.message_table_state.get_mut() = TableState::default().with_selected(0);
+        self.loaded_messages.clear();
+        *self.current_message = Message::default();
+        self.expanded_threads.clear();
+        self.contacts.clear();
+        self.address_book.clear();
+        self.scheduled.clear();
+        self.remote_content_senders.clear();
+        self.watched_threads.clear();
+        self.mutes.clear();
+        self.tags.clear();
+        self.clear_marks();
+        self.search = None;
+        self.clear_filter();
+        self.message_total = None;
+        self.older_messages = false;
+        self.loading_older = false;
+        self.newest_id = None;
+        self.alert = None;
+        self.reset_compose();
+        self.mode = Mode::MessageTable(MessageTableMode::Normal);
+        self.open_startup_view();
+        self.load_account();
+        self.set_table_status(format!("Switched to {}", self.profile.name()));
         Ok(())
     }
 
@@ -144,38 +1069,354 @@ impl<'a> App<'a> {
         }
 
         self.needs_render = true;
+        // statuses only last until the next key press
+        self.message_status = None;
+        self.alert = None;
 
+        // long messages are easier to write in a proper editor, from anywhere in the composer
+        if let Mode::Compose(_) = self.mode
+            && key_event.modifiers == KeyModifiers::CONTROL
+            && key_event.code == KeyCode::Char('e')
+        {
+            self.editor_pending = true;
+            return Ok(());
+        }
+
+        // a key that doesn't carry on from those before it starts afresh
+        let pending = std::mem::take(&mut self.pending_keys);
         match &self.mode {
             Mode::LoadingMessages => {}
             Mode::MessageTable(_) => match key_event.code {
-                KeyCode::Enter => self.view_message(),
+                // a count, as in `42G`
+                KeyCode::Char(digit)
+                    if digit.is_ascii_digit() && pending.chars().all(|c| c.is_ascii_digit()) =>
+                {
+                    self.pending_keys = format!("{pending}{digit}");
+                }
+                KeyCode::Char('g') if pending == "g" => self.select_row(0),
+                KeyCode::Char('g') if pending.is_empty() => self.pending_keys = String::from("g"),
+                KeyCode::Char('G') => match pending.parse() {
+                    Ok(id) => self.go_to_message(id),
+                    Err(_) => self.select_row(self.messages().len().saturating_sub(1)),
+                },
+                KeyCode::Char('d') if key_event.modifiers == KeyModifiers::CONTROL => {
+                    self.move_half_page(Direction::Next)
+                }
+                KeyCode::Char('u') if key_event.modifiers == KeyModifiers::CONTROL => {
+                    self.move_half_page(Direction::Previous)
+                }
+                KeyCode::Enter => {
+                    // open up a collapsed thread before reading any of it
+                    match self.selected_row() {
+                        Some(row) if row.hidden_replies > 0 => self.toggle_thread(),
+                        _ => self.view_message(),
+                    }
+                }
+                // conversations are opened and closed with space, so there `V` marks instead
+                KeyCode::Char(' ') if self.threaded => self.toggle_thread(),
+                KeyCode::Char(' ') => self.toggle_marked(),
+                KeyCode::Char('V') => self.toggle_mark_range(),
+                KeyCode::Char('*') => self.mark(Marking::All),
+                KeyCode::Char('!') => self.mark(Marking::Invert),
+                KeyCode::Char('u') => self.toggle_read(),
+                // the reply quotes the body, so load it first
+                KeyCode::Char('r') if self.selected_message_id().is_some() => {
+                    self.reply_pending = Some(ReplyTo::Sender);
+                    self.view_message();
+                }
+                KeyCode::Char('R') if self.selected_message_id().is_some() => {
+                    self.reply_pending = Some(ReplyTo::All);
+                    self.view_message();
+                }
+                KeyCode::Char('t') => self.toggle_threaded(),
+                KeyCode::Char('v') => {
+                    self.preview_pane = self.preview_pane.next();
+                    self.set_table_status(String::from(match self.preview_pane {
+                        PreviewPane::Off => "Preview off",
+                        PreviewPane::Right => "Preview on the right",
+                        PreviewPane::Below => "Preview below",
+                    }));
+                }
                 KeyCode::Char('c') => self.compose_message(),
-                KeyCode::Char('j') | KeyCode::Down => self.next_message(),
-                KeyCode::Char('k') | KeyCode::Up => self.previous_message(),
+                KeyCode::Char('/') => {
+                    self.search_input = RefCell::new(TextArea::default());
+                    self.mode = Mode::Search;
+                }
+                KeyCode::Char('f') => self.mode = Mode::Filter,
+                KeyCode::Char(':') => {
+                    self.command_input = RefCell::new(TextArea::default());
+                    self.mode = Mode::CommandLine;
+                }
+                KeyCode::Esc if !self.marked.is_empty() || self.mark_anchor.is_some() => {
+                    self.clear_marks()
+                }
+                KeyCode::Esc if !self.filter.is_empty() => self.clear_filter(),
+                KeyCode::Esc if self.search.is_some() => self.end_search(),
+                KeyCode::Char('C') => match clipboard::paste() {
+                    Ok(text) => self.compose_with_body(&text),
+                    Err(e) => self.set_table_status(format!("Error: {e}")),
+                },
+                KeyCode::Char(',') => self.mode = Mode::Settings(SettingsMode::Normal(0)),
+                KeyCode::Char('A') => self.mode = Mode::Contacts(ContactsMode::Normal(0)),
+                KeyCode::Char('O') => {
+                    self.messages.get_scheduled();
+                    self.mode = Mode::Scheduled(0);
+                }
+                KeyCode::Char('<') => self.resize_column(Column::From, -COLUMN_STEP),
+                KeyCode::Char('>') => self.resize_column(Column::From, COLUMN_STEP),
+                KeyCode::Char('{') => self.resize_column(Column::Subject, -COLUMN_STEP),
+                KeyCode::Char('}') => self.resize_column(Column::Subject, COLUMN_STEP),
+                KeyCode::Char('s') => {
+                    if let Some(id) = self.selected_message_id() {
+                        self.toggle_flagged(id);
+                    }
+                }
+                KeyCode::Char('F') => self.toggle_flagged_only(),
+                KeyCode::Char('#') => {
+                    if let Some(id) = self.selected_message_id() {
+                        self.open_tags(id);
+                    }
+                }
+                KeyCode::Char('n') => self.jump_to_unread(Direction::Next),
+                KeyCode::Char('N') => self.jump_to_unread(Direction::Previous),
+                KeyCode::Char('w') => {
+                    if let Some(id) = self.selected_message_id() {
+                        let status = self.toggle_watched(id);
+                        self.set_table_status(status);
+                    }
+                }
+                KeyCode::Char('x') => {
+                    if let Some(id) = self.selected_message_id() {
+                        let status = self.toggle_muted(id, MuteBy::Thread);
+                        self.set_table_status(status);
+                    }
+                }
+                KeyCode::Char('X') => {
+                    if let Some(id) = self.selected_message_id() {
+                        let status = self.toggle_muted(id, MuteBy::Sender);
+                        self.set_table_status(status);
+                    }
+                }
+                KeyCode::Char('m') => {
+                    let ids = self.chosen_ids();
+                    if !ids.is_empty() {
+                        self.mode = Mode::MoveMessage(ids, 0);
+                    }
+                }
+                KeyCode::Char('P') => {
+                    self.profiles = Profile::list();
+                    let current = self
+                        .profiles
+                        .iter()
+                        .position(|p| p.name() == self.profile.name());
+                    self.mode = Mode::Profiles(current.unwrap_or(0));
+                }
+                KeyCode::Char('d') => self.move_messages(self.chosen_ids(), String::from(TRASH)),
+                KeyCode::Char('a') => self.move_messages(self.chosen_ids(), String::from(ARCHIVE)),
+                KeyCode::Char('D') if self.current_folder == TRASH => {
+                    let ids = self.chosen_ids();
+                    if !ids.is_empty() {
+                        self.confirm(PendingAction::Delete(ids));
+                    }
+                }
+                KeyCode::Char('h') | KeyCode::Left => self.mode = Mode::Folders,
+                KeyCode::Char('j') | KeyCode::Down => {
+                    self.next_message();
+                    self.mark_range();
+                }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    self.previous_message();
+                    self.mark_range();
+                }
+                KeyCode::Char('q') => self.events.send(AppEvent::Quit),
+                _ => {}
+            },
+            Mode::Folders => match key_event.code {
+                KeyCode::Enter | KeyCode::Char('l') | KeyCode::Right => self.open_folder(),
+                KeyCode::Char('j') | KeyCode::Down => self.next_folder(),
+                KeyCode::Char('k') | KeyCode::Up => {
+                    self.folder_list_state.get_mut().select_previous()
+                }
+                KeyCode::Esc => self.mode = Mode::MessageTable(MessageTableMode::Normal),
                 KeyCode::Char('q') => self.events.send(AppEvent::Quit),
                 _ => {}
             },
-            Mode::Message(_) => match key_event.code {
+            Mode::Profiles(index) => {
+                let index = *index;
+                match key_event.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        self.mode = Mode::MessageTable(MessageTableMode::Normal)
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        let last = self.profiles.len().saturating_sub(1);
+                        self.mode = Mode::Profiles((index + 1).min(last))
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        self.mode = Mode::Profiles(index.saturating_sub(1))
+                    }
+                    KeyCode::Enter => {
+                        self.mode = Mode::MessageTable(MessageTableMode::Normal);
+                        self.pending_profile = self
+                            .profiles
+                            .get(index)
+                            .filter(|profile| profile.name() != self.profile.name())
+                            .cloned();
+                    }
+                    _ => {}
+                }
+            }
+            Mode::MoveMessage(ids, index) => {
+                let (ids, index) = (ids.clone(), *index);
+                match key_event.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        self.mode = Mode::MessageTable(MessageTableMode::Normal)
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        let last = self.folders.len().saturating_sub(1);
+                        self.mode = Mode::MoveMessage(ids, (index + 1).min(last))
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        self.mode = Mode::MoveMessage(ids, index.saturating_sub(1))
+                    }
+                    KeyCode::Enter => {
+                        self.mode = Mode::MessageTable(MessageTableMode::Normal);
+                        if let Some(folder) = self.folders.get(index).cloned() {
+                            self.move_messages(ids, folder);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Mode::Links(row, selected) => {
+                let (row, selected) = (*row, *selected);
+                match key_event.code {
+                    KeyCode::Esc | KeyCode::Char('q') => self.mode = Mode::Message(row),
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        let last = self.links.len().saturating_sub(1);
+                        self.mode = Mode::Links(row, (selected + 1).min(last))
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        self.mode = Mode::Links(row, selected.saturating_sub(1))
+                    }
+                    KeyCode::Enter | KeyCode::Char('o') => self.open_link(row, selected),
+                    KeyCode::Char('y') => self.copy_link(row, selected),
+                    KeyCode::Char(c @ '1'..='9') => {
+                        let index = c as usize - '1' as usize;
+                        if index < self.links.len() {
+                            self.open_link(row, index);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Mode::Attachment(row, index) => {
+                let (row, index) = (*row, *index);
+                match key_event.code {
+                    KeyCode::Esc | KeyCode::Char('q') => self.mode = Mode::Message(row),
+                    KeyCode::Char('j') | KeyCode::Down => self.scroll_preview(1),
+                    KeyCode::Char('k') | KeyCode::Up => self.scroll_preview(-1),
+                    KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_preview(20),
+                    KeyCode::PageUp => self.scroll_preview(-20),
+                    KeyCode::Tab => self.preview_attachment(row, index + 1),
+                    _ => {}
+                }
+            }
+            Mode::Message(row) => match key_event.code {
                 KeyCode::Esc | KeyCode::Char('q') => {
+                    self.reply_pending = None;
                     self.mode = Mode::MessageTable(MessageTableMode::Normal)
                 }
+                KeyCode::Char('o') => self.open_html_part(),
+                KeyCode::Char('O') => self.find_bounced_message(),
+                KeyCode::Char('u') => self.show_links(*row),
+                KeyCode::Char('r') => self.reply(ReplyTo::Sender),
+                KeyCode::Char('R') => self.reply(ReplyTo::All),
+                KeyCode::Char('f') => self.forward(),
+                KeyCode::Char('L') if self.loads_remote_content() => self.toggle_remote_content(),
+                KeyCode::Char('L') => {
+                    let sender = sender_address(&self.current_message);
+                    self.confirm(PendingAction::RemoteContent(sender))
+                }
+                KeyCode::Char('A') if self.can_rsvp() => {
+                    self.confirm(PendingAction::Rsvp(Answer::Accept))
+                }
+                KeyCode::Char('T') if self.can_rsvp() => {
+                    self.confirm(PendingAction::Rsvp(Answer::Tentative))
+                }
+                KeyCode::Char('D') if self.can_rsvp() => {
+                    self.confirm(PendingAction::Rsvp(Answer::Decline))
+                }
+                KeyCode::Char('J') => self.open_adjacent(Direction::Next),
+                KeyCode::Char('K') => self.open_adjacent(Direction::Previous),
+                KeyCode::Char('n') => {
+                    if self.select_unread(Direction::Next) {
+                        self.view_message();
+                    } else {
+                        self.set_message_status(String::from("No more unread messages"));
+                    }
+                }
+                KeyCode::Char('w') => {
+                    let status = self.toggle_watched(self.current_message.id());
+                    self.set_message_status(status);
+                }
+                KeyCode::Char('x') => {
+                    let status = self.toggle_muted(self.current_message.id(), MuteBy::Thread);
+                    self.set_message_status(status);
+                }
+                KeyCode::Char('X') => {
+                    let status = self.toggle_muted(self.current_message.id(), MuteBy::Sender);
+                    self.set_message_status(status);
+                }
+                KeyCode::Char('a') => self.preview_attachment(*row, 0),
+                KeyCode::Char('e') => self.open_message_prompt(*row, MessagePrompt::Export),
+                KeyCode::Char('>') => self.open_message_prompt(*row, MessagePrompt::SaveText),
+                KeyCode::Char('|') => self.open_message_prompt(*row, MessagePrompt::Pipe),
+                KeyCode::Char('y') => self.mode = Mode::Copy(*row),
+                KeyCode::Char('g') => self.mode = Mode::Go(*row),
+                KeyCode::Char('#') => self.open_tags(self.current_message.id()),
+                KeyCode::Char('z') => self.zen_mode = !self.zen_mode,
+                KeyCode::Char('W') => {
+                    self.wrap_message = !self.wrap_message;
+                    self.set_message_status(String::from(if self.wrap_message {
+                        "Wrapping long lines"
+                    } else {
+                        "Not wrapping long lines"
+                    }));
+                }
+                KeyCode::Char('d') => {
+                    self.mode = Mode::MessageTable(MessageTableMode::Normal);
+                    self.move_messages(vec![self.current_message.id()], String::from(TRASH));
+                }
+                KeyCode::Char('s') => {
+                    let id = self.current_message.id();
+                    self.toggle_flagged(id);
+                    self.set_message_status(String::from(if self.current_message.is_flagged() {
+                        "Flagged"
+                    } else {
+                        "Unflagged"
+                    }));
+                }
                 KeyCode::Up | KeyCode::PageUp | KeyCode::Char('k') => {
-                    self.message_textarea
-                        .get_mut()
-                        .scroll(Scrolling::HalfPageUp);
+                    let half_page = (self.message_height.get() / 2).max(1);
+                    self.message_scroll
+                        .set(self.message_scroll.get().saturating_sub(half_page));
                 }
                 KeyCode::Down | KeyCode::PageDown | KeyCode::Char('j') => {
-                    self.message_textarea
-                        .get_mut()
-                        .scroll(Scrolling::HalfPageDown);
+                    // drawing stops it going past the end
+                    let half_page = (self.message_height.get() / 2).max(1);
+                    self.message_scroll
+                        .set(self.message_scroll.get().saturating_add(half_page));
                 }
+                // wrapped lines have nothing to the side to scroll to
+                KeyCode::Left | KeyCode::Right | KeyCode::Char('h' | 'l') if self.wrap_message => {}
                 KeyCode::Left | KeyCode::Char('h') => {
-                    self.message_textarea.get_mut().scroll((0, -40));
-                    self.message_textarea.get_mut().scroll((0, -40));
+                    let scroll = self.message_side_scroll.get().saturating_sub(SIDE_SCROLL);
+                    self.message_side_scroll.set(scroll);
                 }
                 KeyCode::Right | KeyCode::Char('l') => {
-                    self.message_textarea.get_mut().scroll((0, 40));
-                    self.message_textarea.get_mut().scroll((0, 40));
+                    // drawing stops it going past the longest line
+                    let scroll = self.message_side_scroll.get().saturating_add(SIDE_SCROLL);
+                    self.message_side_scroll.set(scroll);
                 }
                 _ => {}
             },
@@ -188,25 +1429,97 @@ impl<'a> App<'a> {
                         KeyCode::Char('S') => {
                             self.events.send(AppEvent::SendMessage);
                         }
+                        KeyCode::Char('M') => self.start_mail_merge(),
+                        KeyCode::Char('L') => self.open_compose_prompt(ComposePrompt::SendAt, ""),
+                        KeyCode::Char('g') => self.toggle_signature(),
+                        KeyCode::Char('p') => self.cycle_protection(),
+                        KeyCode::Char('f') => self.cycle_identity(),
+                        KeyCode::Char(':') => self.open_compose_prompt(ComposePrompt::Command, ""),
+                        KeyCode::Char('D') => self.confirm(PendingAction::DiscardDraft),
+                        KeyCode::Char('b') => {
+                            self.mode = Mode::Contacts(ContactsMode::Picking(0, ComposeField::To))
+                        }
                         KeyCode::Enter => {
                             self.mode = Mode::Compose(ComposeFocus::To(ComposeMode::Editing))
                         }
                         KeyCode::Tab => {
-                            self.mode = Mode::Compose(ComposeFocus::Subject(ComposeMode::Normal))
+                            self.mode = Mode::Compose(ComposeFocus::Cc(ComposeMode::Normal))
                         }
                         _ => {}
                     },
                     ComposeMode::Editing => match key_event.code {
+                        KeyCode::Enter | KeyCode::Tab if !self.completions.is_empty() => {
+                            self.accept_completion(ComposeField::To)
+                        }
+                        KeyCode::Down if !self.completions.is_empty() => self.move_completion(1),
+                        KeyCode::Up if !self.completions.is_empty() => self.move_completion(-1),
+                        KeyCode::Esc if !self.completions.is_empty() => self.completions.clear(),
                         KeyCode::Esc => {
+                            self.stop_completing();
+                            self.validate_recipients();
                             self.mode = Mode::Compose(ComposeFocus::To(ComposeMode::Normal))
                         }
                         KeyCode::Enter | KeyCode::Tab => {
-                            self.mode = Mode::Compose(ComposeFocus::Subject(ComposeMode::Normal))
+                            self.stop_completing();
+                            self.validate_recipients();
+                            self.mode = Mode::Compose(ComposeFocus::Cc(ComposeMode::Normal))
                         }
                         _ => {
                             self.compose_to_input
                                 .get_mut()
                                 .input_without_shortcuts(key_event);
+                            self.complete_address(ComposeField::To);
+                        }
+                    },
+                },
+                ComposeFocus::Cc(compose_mode) => match compose_mode {
+                    ComposeMode::Normal => match key_event.code {
+                        KeyCode::Esc | KeyCode::Char('q') => {
+                            self.mode = Mode::MessageTable(MessageTableMode::Normal)
+                        }
+                        KeyCode::Char('S') => {
+                            self.events.send(AppEvent::SendMessage);
+                        }
+                        KeyCode::Char('M') => self.start_mail_merge(),
+                        KeyCode::Char('L') => self.open_compose_prompt(ComposePrompt::SendAt, ""),
+                        KeyCode::Char('g') => self.toggle_signature(),
+                        KeyCode::Char('p') => self.cycle_protection(),
+                        KeyCode::Char('f') => self.cycle_identity(),
+                        KeyCode::Char(':') => self.open_compose_prompt(ComposePrompt::Command, ""),
+                        KeyCode::Char('D') => self.confirm(PendingAction::DiscardDraft),
+                        KeyCode::Char('b') => {
+                            self.mode = Mode::Contacts(ContactsMode::Picking(0, ComposeField::Cc))
+                        }
+                        KeyCode::Enter => {
+                            self.mode = Mode::Compose(ComposeFocus::Cc(ComposeMode::Editing))
+                        }
+                        KeyCode::Tab => {
+                            self.mode = Mode::Compose(ComposeFocus::Subject(ComposeMode::Normal))
+                        }
+                        _ => {}
+                    },
+                    ComposeMode::Editing => match key_event.code {
+                        KeyCode::Enter | KeyCode::Tab if !self.completions.is_empty() => {
+                            self.accept_completion(ComposeField::Cc)
+                        }
+                        KeyCode::Down if !self.completions.is_empty() => self.move_completion(1),
+                        KeyCode::Up if !self.completions.is_empty() => self.move_completion(-1),
+                        KeyCode::Esc if !self.completions.is_empty() => self.completions.clear(),
+                        KeyCode::Esc => {
+                            self.stop_completing();
+                            self.validate_recipients();
+                            self.mode = Mode::Compose(ComposeFocus::Cc(ComposeMode::Normal))
+                        }
+                        KeyCode::Enter | KeyCode::Tab => {
+                            self.stop_completing();
+                            self.validate_recipients();
+                            self.mode = Mode::Compose(ComposeFocus::Subject(ComposeMode::Normal))
+                        }
+                        _ => {
+                            self.compose_cc_input
+                                .get_mut()
+                                .input_without_shortcuts(key_event);
+                            self.complete_address(ComposeField::Cc);
                         }
                     },
                 },
@@ -218,6 +1531,16 @@ impl<'a> App<'a> {
                         KeyCode::Char('S') => {
                             self.events.send(AppEvent::SendMessage);
                         }
+                        KeyCode::Char('M') => self.start_mail_merge(),
+                        KeyCode::Char('L') => self.open_compose_prompt(ComposePrompt::SendAt, ""),
+                        KeyCode::Char('g') => self.toggle_signature(),
+                        KeyCode::Char('p') => self.cycle_protection(),
+                        KeyCode::Char('f') => self.cycle_identity(),
+                        KeyCode::Char(':') => self.open_compose_prompt(ComposePrompt::Command, ""),
+                        KeyCode::Char('D') => self.confirm(PendingAction::DiscardDraft),
+                        KeyCode::Char('b') => {
+                            self.mode = Mode::Contacts(ContactsMode::Picking(0, ComposeField::To))
+                        }
                         KeyCode::Enter => {
                             self.mode = Mode::Compose(ComposeFocus::Subject(ComposeMode::Editing))
                         }
@@ -248,6 +1571,16 @@ impl<'a> App<'a> {
                         KeyCode::Char('S') => {
                             self.events.send(AppEvent::SendMessage);
                         }
+                        KeyCode::Char('M') => self.start_mail_merge(),
+                        KeyCode::Char('L') => self.open_compose_prompt(ComposePrompt::SendAt, ""),
+                        KeyCode::Char('g') => self.toggle_signature(),
+                        KeyCode::Char('p') => self.cycle_protection(),
+                        KeyCode::Char('f') => self.cycle_identity(),
+                        KeyCode::Char(':') => self.open_compose_prompt(ComposePrompt::Command, ""),
+                        KeyCode::Char('D') => self.confirm(PendingAction::DiscardDraft),
+                        KeyCode::Char('b') => {
+                            self.mode = Mode::Contacts(ContactsMode::Picking(0, ComposeField::To))
+                        }
                         KeyCode::Enter => {
                             self.mode = Mode::Compose(ComposeFocus::Message(ComposeMode::Editing))
                         }
@@ -288,111 +1621,3102 @@ impl<'a> App<'a> {
                     },
                 },
             },
-        }
-
-        Ok(())
-    }
-
-    /// Handles the tick event of the terminal.
-    ///
-    /// The tick event is where you can update the state of your application with any logic that
-    /// needs to be updated at a fixed frame rate. E.g. polling a server, updating an animation.
-    fn tick(&self) {}
-
-    fn show_error(&self, error_message: String) -> color_eyre::Result<()> {
-        Err(color_eyre::eyre::eyre!(error_message))
-    }
-
-    fn send_message(&mut self) {
-        let mut message = Box::new(Message::default());
-
-        message.set_to(self.compose_to_input.borrow().lines()[0].clone());
-        message.set_subject(self.compose_subject_input.borrow().lines()[0].clone());
-        message.set_body(self.compose_message_input.borrow().lines().join("\n"));
-
-        self.messages.send_message(&message);
-
-        // Reset state of compose fields
-        self.compose_to_input = RefCell::new(TextArea::default());
-        self.compose_subject_input = RefCell::new(TextArea::default());
-        self.compose_message_input = RefCell::new(TextArea::default());
-
-        // return to message table
-        self.mode = Mode::MessageTable(MessageTableMode::Normal);
-    }
-
-    /// Set running to false to quit the application.
+            Mode::Settings(SettingsMode::Normal(index)) => {
+                let index = *index;
+                match key_event.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        self.mode = Mode::MessageTable(MessageTableMode::Normal)
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        let index = (index + 1).min(SETTINGS.len() - 1);
+                        self.mode = Mode::Settings(SettingsMode::Normal(index))
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        self.mode = Mode::Settings(SettingsMode::Normal(index.saturating_sub(1)))
+                    }
+                    KeyCode::Enter => self.edit_setting(index),
+                    _ => {}
+                }
+            }
+            Mode::Settings(SettingsMode::Editing(index)) => {
+                let index = *index;
+                match key_event.code {
+                    KeyCode::Esc => self.mode = Mode::Settings(SettingsMode::Normal(index)),
+                    KeyCode::Enter if !SETTINGS[index].is_multiline() => self.save_setting(index),
+                    KeyCode::Char('s') if key_event.modifiers == KeyModifiers::CONTROL => {
+                        self.save_setting(index)
+                    }
+                    _ => {
+                        self.settings_input
+                            .get_mut()
+                            .input_without_shortcuts(key_event);
+                    }
+                }
+            }
+            Mode::Scheduled(index) => {
+                let index = *index;
+                let last = self.scheduled.len().saturating_sub(1);
+                match key_event.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        self.mode = Mode::MessageTable(MessageTableMode::Normal)
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        self.mode = Mode::Scheduled((index + 1).min(last))
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        self.mode = Mode::Scheduled(index.saturating_sub(1))
+                    }
+                    KeyCode::Char('s') => {
+                        if let Some(scheduled) = self.scheduled.get(index) {
+                            self.send_scheduled_now(scheduled.id);
+                        }
+                    }
+                    KeyCode::Char('d') => {
+                        if let Some(scheduled) = self.scheduled.get(index) {
+                            self.confirm(PendingAction::CancelScheduled(scheduled.id));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Mode::Contacts(ContactsMode::Normal(index)) => {
+                let index = *index;
+                let last = self.address_book.len().saturating_sub(1);
+                match key_event.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        self.mode = Mode::MessageTable(MessageTableMode::Normal)
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        self.mode = Mode::Contacts(ContactsMode::Normal((index + 1).min(last)))
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        self.mode = Mode::Contacts(ContactsMode::Normal(index.saturating_sub(1)))
+                    }
+                    KeyCode::Char('a') => self.edit_contact(self.address_book.len()),
+                    KeyCode::Char('e') | KeyCode::Enter if index < self.address_book.len() => {
+                        self.edit_contact(index)
+                    }
+                    KeyCode::Char('d') => self.delete_contact(index),
+                    KeyCode::Char('i') => {
+                        self.contact_input = RefCell::new(TextArea::default());
+                        self.mode = Mode::Contacts(ContactsMode::Importing);
+                    }
+                    _ => {}
+                }
+            }
+            Mode::ComposePrompt(focus, prompt) => {
+                let (focus, prompt) = (focus.clone(), prompt.clone());
+                match key_event.code {
+                    KeyCode::Esc => {
+                        self.command_output = None;
+                        self.mode = Mode::Compose(focus)
+                    }
+                    KeyCode::Enter => self.submit_compose_prompt(focus, prompt),
+                    _ => {
+                        self.compose_prompt_input
+                            .get_mut()
+                            .input_without_shortcuts(key_event);
+                    }
+                }
+            }
+            Mode::Confirm(action, previous) => {
+                let (action, previous) = (action.clone(), (**previous).clone());
+                match key_event.code {
+                    KeyCode::Char('y') => {
+                        self.mode = previous;
+                        self.perform(action);
+                    }
+                    KeyCode::Enter if !action.is_permanent() => {
+                        self.mode = previous;
+                        self.perform(action);
+                    }
+                    KeyCode::Char('n') | KeyCode::Char('q') | KeyCode::Esc => self.mode = previous,
+                    _ => {}
+                }
+            }
+            Mode::Error(_, previous) => {
+                if let KeyCode::Enter | KeyCode::Esc | KeyCode::Char('q') = key_event.code {
+                    self.mode = (**previous).clone();
+                }
+            }
+            Mode::Password(sending, previous) => {
+                let (sending, previous) = (sending.clone(), (**previous).clone());
+                match key_event.code {
+                    KeyCode::Esc => {
+                        self.mode = previous;
+                        self.set_message_status(String::from("Not sent"));
+                    }
+                    KeyCode::Enter => {
+                        // taken out of the input, so the password isn't left behind in it
+                        let mut lines =
+                            std::mem::replace(self.password_input.get_mut(), password_textarea())
+                                .into_lines();
+                        let password = Zeroizing::new(lines.concat());
+                        lines.zeroize();
+                        if !password.is_empty() {
+                            self.messages.set_password(password);
+                            self.mode = previous;
+                            match sending {
+                                Sending::Message => self.send_composed_message(),
+                                Sending::MailMerge => self.send_mail_merge(),
+                                Sending::Rsvp(answer) => self.send_rsvp(answer),
+                                Sending::Later(send_at) => self.schedule_composed_message(send_at),
+                                Sending::Scheduled(id) => self.send_scheduled_now(id),
+                            }
+                        }
+                    }
+                    _ => {
+                        self.password_input
+                            .get_mut()
+                            .input_without_shortcuts(key_event);
+                    }
+                }
+            }
+            Mode::Filter => match key_event.code {
+                KeyCode::Esc => {
+                    self.clear_filter();
+                    self.mode = Mode::MessageTable(MessageTableMode::Normal)
+                }
+                KeyCode::Enter => self.mode = Mode::MessageTable(MessageTableMode::Normal),
+                _ => {
+                    self.filter_input
+                        .get_mut()
+                        .input_without_shortcuts(key_event);
+                    self.set_filter();
+                }
+            },
+            Mode::CommandLine => match key_event.code {
+                KeyCode::Esc => self.mode = Mode::MessageTable(MessageTableMode::Normal),
+                KeyCode::Enter => {
+                    self.mode = Mode::MessageTable(MessageTableMode::Normal);
+                    let line = self.command_input.get_mut().lines().join(" ");
+                    match Command::parse(&line) {
+                        Ok(command) => self.events.send(AppEvent::RunCommand(command)),
+                        Err(e) => self.set_table_status(e),
+                    }
+                }
+                _ => {
+                    self.command_input
+                        .get_mut()
+                        .input_without_shortcuts(key_event);
+                }
+            },
+            Mode::Search => match key_event.code {
+                KeyCode::Esc => self.mode = Mode::MessageTable(MessageTableMode::Normal),
+                KeyCode::Enter => self.search_messages(),
+                _ => {
+                    self.search_input
+                        .get_mut()
+                        .input_without_shortcuts(key_event);
+                }
+            },
+            Mode::Copy(row) => {
+                let row = *row;
+                match key_event.code {
+                    KeyCode::Esc | KeyCode::Char('q') => self.mode = Mode::Message(row),
+                    KeyCode::Char('f') => {
+                        let sender = sender_address(&self.current_message);
+                        self.copy_from_message(row, "sender address", &sender)
+                    }
+                    KeyCode::Char('s') => {
+                        let subject = self.current_message.subject().to_string();
+                        self.copy_from_message(row, "subject", &subject)
+                    }
+                    KeyCode::Char('b') => {
+                        let body = self.current_message.text_body();
+                        self.copy_from_message(row, "body", &body)
+                    }
+                    _ => {}
+                }
+            }
+            Mode::Tags(id, selected, previous) => {
+                let (id, selected, previous) = (*id, *selected, (**previous).clone());
+                match key_event.code {
+                    KeyCode::Esc => self.mode = previous,
+                    KeyCode::Down => {
+                        let last = self.tag_choices().len().saturating_sub(1);
+                        self.mode = Mode::Tags(id, (selected + 1).min(last), Box::new(previous));
+                    }
+                    KeyCode::Up => {
+                        self.mode = Mode::Tags(id, selected.saturating_sub(1), Box::new(previous));
+                    }
+                    KeyCode::Enter => {
+                        self.mode = previous;
+                        if let Some(tag) = self.tag_choices().into_iter().nth(selected) {
+                            self.toggle_tag(id, tag);
+                        }
+                    }
+                    _ => {
+                        self.tag_input.get_mut().input_without_shortcuts(key_event);
+                        // the choices change as the tag is typed
+                        self.mode = Mode::Tags(id, 0, Box::new(previous));
+                    }
+                }
+            }
+            Mode::Go(row) => {
+                let row = *row;
+                match key_event.code {
+                    KeyCode::Char('f') => self.show_hints(row, HintAction::Open),
+                    KeyCode::Char('y') => self.show_hints(row, HintAction::Copy),
+                    _ => self.mode = Mode::Message(row),
+                }
+            }
+            Mode::Hints(row, action, typed) => {
+                let (row, action, mut typed) = (*row, *action, typed.clone());
+                match key_event.code {
+                    KeyCode::Esc => self.mode = Mode::Message(row),
+                    KeyCode::Backspace => {
+                        typed.pop();
+                        self.mode = Mode::Hints(row, action, typed);
+                    }
+                    KeyCode::Char(c) => {
+                        typed.push(c);
+                        self.type_hint(row, action, typed);
+                    }
+                    _ => {}
+                }
+            }
+            Mode::MessagePrompt(row, prompt) => match key_event.code {
+                KeyCode::Esc => self.mode = Mode::Message(*row),
+                KeyCode::Enter => self.submit_message_prompt(*row, *prompt),
+                _ => {
+                    self.message_prompt_input
+                        .get_mut()
+                        .input_without_shortcuts(key_event);
+                }
+            },
+            Mode::Contacts(ContactsMode::Importing) => match key_event.code {
+                KeyCode::Esc => self.mode = Mode::Contacts(ContactsMode::Normal(0)),
+                KeyCode::Enter => self.import_contacts(),
+                _ => {
+                    self.contact_input
+                        .get_mut()
+                        .input_without_shortcuts(key_event);
+                }
+            },
+            Mode::Contacts(ContactsMode::Editing(index)) => {
+                let index = *index;
+                match key_event.code {
+                    KeyCode::Esc => {
+                        let index = index.min(self.address_book.len().saturating_sub(1));
+                        self.mode = Mode::Contacts(ContactsMode::Normal(index))
+                    }
+                    KeyCode::Enter => self.save_contact(index),
+                    _ => {
+                        self.contact_input
+                            .get_mut()
+                            .input_without_shortcuts(key_event);
+                    }
+                }
+            }
+            Mode::Contacts(ContactsMode::Picking(index, field)) => {
+                let (index, field) = (*index, *field);
+                let last = self.address_book.len().saturating_sub(1);
+                match key_event.code {
+                    KeyCode::Esc | KeyCode::Char('q') => self.mode = compose_mode(field),
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        let index = (index + 1).min(last);
+                        self.mode = Mode::Contacts(ContactsMode::Picking(index, field))
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        let index = index.saturating_sub(1);
+                        self.mode = Mode::Contacts(ContactsMode::Picking(index, field))
+                    }
+                    KeyCode::Enter => self.pick_contact(index, field),
+                    _ => {}
+                }
+            }
+            Mode::MailMerge(merge_mode) => match merge_mode {
+                MailMergeMode::SelectCsv(_) => match key_event.code {
+                    KeyCode::Esc => {
+                        self.mode = Mode::Compose(ComposeFocus::To(ComposeMode::Normal))
+                    }
+                    KeyCode::Enter => self.load_mail_merge(),
+                    _ => {
+                        self.merge_csv_input
+                            .get_mut()
+                            .input_without_shortcuts(key_event);
+                    }
+                },
+                MailMergeMode::Preview(index) => {
+                    let index = *index;
+                    match key_event.code {
+                        KeyCode::Esc | KeyCode::Char('q') => {
+                            self.mode = Mode::MailMerge(MailMergeMode::SelectCsv(None))
+                        }
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            let last = self.merge_messages.len().saturating_sub(1);
+                            self.mode =
+                                Mode::MailMerge(MailMergeMode::Preview((index + 1).min(last)))
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            self.mode =
+                                Mode::MailMerge(MailMergeMode::Preview(index.saturating_sub(1)))
+                        }
+                        KeyCode::Char('S') => {
+                            self.events.send(AppEvent::SendMailMerge);
+                        }
+                        _ => {}
+                    }
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Handles the tick event of the terminal.
+    ///
+    /// The tick event is where you can update the state of your application with any logic that
+    /// needs to be updated at a fixed frame rate. E.g. polling a server, updating an animation.
+    ///
+    /// Ticks are frequent, so only ask for a render when something visible has changed.
+    fn tick(&mut self) {
+        // a new minute also moves on relative dates in the message table
+        let clock = clock_text();
+        if clock != self.clock {
+            self.clock = clock;
+            self.needs_render = true;
+        }
+
+        if let Some((id, at)) = self.pending_read
+            && at <= Instant::now()
+        {
+            self.pending_read = None;
+            // only if the message wasn't closed in the meantime
+            if let Mode::Message(_)
+            | Mode::Links(..)
+            | Mode::Attachment(..)
+            | Mode::MessagePrompt(..)
+            | Mode::Copy(_)
+            | Mode::Go(_)
+            | Mode::Hints(..) = self.mode
+            {
+                self.mark_read(id);
+                self.needs_render = true;
+            }
+        }
+
+        self.update_preview();
+
+        if self.next_connection_check <= Instant::now() {
+            self.check_connection();
+        }
+
+        if self.config.autosave_seconds > 0 && self.next_autosave <= Instant::now() {
+            self.next_autosave = Instant::now() + Duration::from_secs(self.config.autosave_seconds);
+            self.autosave_draft();
+        }
+
+        if !self.activities.is_empty() {
+            self.spinner_frame = self.spinner_frame.wrapping_add(1);
+            self.needs_render = true;
+        }
+
+        if self
+            .notification_expires
+            .is_some_and(|expires| expires <= Instant::now())
+        {
+            self.clear_notifications();
+            self.needs_render = true;
+        }
+    }
+
+    /// Fetch the selected message's body for the preview pane, once the selection has rested on
+    /// it until the next tick rather than for every message passed on the way.
+    fn update_preview(&mut self) {
+        if self.preview_pane == PreviewPane::Off || !matches!(self.mode, Mode::MessageTable(_)) {
+            return;
+        }
+        if let Some(id) = self.selected_message_id()
+            && self.preview_requested != Some(id)
+        {
+            self.preview_requested = Some(id);
+            self.messages.get_message(id);
+        }
+    }
+
+    /// Whether the selected message's body is loaded for the preview pane, or `None` when no
+    /// message is selected.
+    pub fn preview_loaded(&self) -> Option<bool> {
+        self.selected_message_id()
+            .map(|id| id == self.current_message.id())
+    }
+
+    pub fn preview_pane(&self) -> PreviewPane {
+        self.preview_pane
+    }
+
+    fn clear_notifications(&mut self) {
+        self.notification_expires = None;
+        self.message_status = None;
+        if let Mode::MessageTable(MessageTableMode::MessageSent(_) | MessageTableMode::Status(_)) =
+            self.mode
+        {
+            self.mode = Mode::MessageTable(MessageTableMode::Normal);
+        }
+    }
+
+    /// Show a status in the message view until it expires or a key is pressed.
+    fn set_message_status(&mut self, status: String) {
+        self.message_status = Some(status);
+        self.notification_expires = Some(Instant::now() + NOTIFICATION_TIMEOUT);
+    }
+
+    /// Show an error over whatever is on screen until it is dismissed, carrying on as before.
+    fn show_error(&mut self, error_message: String) {
+        let previous = match &self.mode {
+            // a later error replaces the one shown, rather than waiting behind it
+            Mode::Error(_, previous) => previous.clone(),
+            mode => Box::new(mode.clone()),
+        };
+        self.mode = Mode::Error(error_message, previous);
+    }
+
+    /// Build a message from the contents of the compose fields.
+    fn composed_message(&self) -> Message {
+        let mut message = Message::default();
+
+        message.set_to(self.compose_to_input.borrow().lines()[0].clone());
+        message.set_cc(self.compose_cc_input.borrow().lines()[0].clone());
+        message.set_subject(self.compose_subject_input.borrow().lines()[0].clone());
+        message.set_body(self.compose_message_input.borrow().lines().join("\n"));
+        message.set_parts(self.compose_attachments.clone());
+        message.set_protection(self.compose_protection);
+        message.set_from(self.compose_from());
+        if let Some(original) = &self.compose_reply_to {
+            message.set_in_reply_to(original.message_id().map(String::from));
+            // the original's references, followed by the original itself
+            let mut references = original.references().to_vec();
+            references.extend(original.message_id().map(String::from));
+            message.set_references(references);
+        }
+
+        message
+    }
+
+    fn reset_compose(&mut self) {
+        self.compose_to_input = RefCell::new(TextArea::default());
+        self.compose_cc_input = RefCell::new(TextArea::default());
+        self.compose_subject_input = RefCell::new(TextArea::default());
+        self.compose_message_input = RefCell::new(TextArea::default());
+        self.compose_signature = None;
+        self.compose_reply_to = None;
+        self.compose_attachments.clear();
+        self.compose_protection = Protection::None;
+        self.compose_identity = None;
+        self.command_output = None;
+        self.queued_command_output.clear();
+        self.typo_warning = None;
+        self.size_warning = None;
+        self.zipping = None;
+        self.stop_completing();
+    }
+
+    /// Compose a reply to the current message, quoting it.
+    fn reply(&mut self, reply_to: ReplyTo) {
+        let original = self.current_message.clone();
+        let subject = match original.subject().get(..3) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("re:") => original.subject().to_string(),
+            _ => format!("Re: {}", original.subject()),
+        };
+        let reply_config = &self.config.reply;
+        let mut quote = vec![];
+        if !reply_config.attribution.is_empty() {
+            let date = original
+                .date()
+                .map(|date| date.format(DATE_FORMAT).to_string())
+                .unwrap_or_default();
+            quote.push(
+                reply_config
+                    .attribution
+                    .replace("{from}", original.from())
+                    .replace("{date}", &date)
+                    .replace("{subject}", original.subject()),
+            );
+        }
+        quote.extend(original.text_body().lines().map(|line| format!("> {line}")));
+        // the signature goes after the reply, whichever side of the quote that is
+        let quote_length = quote.len();
+        let (body, cursor, signature_at) = match reply_config.posting {
+            Posting::Bottom => (
+                [quote, vec![String::new()]].concat(),
+                quote_length,
+                quote_length + 1,
+            ),
+            Posting::Top => ([vec![String::new(), String::new()], quote].concat(), 0, 1),
+        };
+        // answer from whichever identity the original was sent to
+        self.compose_identity = [original.to(), original.cc()]
+            .into_iter()
+            .filter_map(|list| parse_addresses(list).ok())
+            .flatten()
+            .find_map(|mailbox| self.config.smtp.identity_index(mailbox.email.as_ref()));
+        let signature = self.compose_signature_text(Some(&original.text_body()));
+
+        let (to, cc) = match reply_to {
+            ReplyTo::Sender => (original.from().to_string(), String::new()),
+            ReplyTo::All => self.reply_all_recipients(&original),
+        };
+
+        self.compose_to_input = RefCell::new(TextArea::new(vec![to]));
+        self.compose_cc_input = RefCell::new(TextArea::new(vec![cc]));
+        self.compose_subject_input = RefCell::new(TextArea::new(vec![subject]));
+        self.set_compose_body(body, cursor, signature_at, signature);
+        self.compose_reply_to = Some(original);
+        self.mode = Mode::Compose(ComposeFocus::Message(ComposeMode::Normal));
+    }
+
+    /// Compose a message forwarding the current one, with the original included below its headers.
+    fn forward(&mut self) {
+        let original = &self.current_message;
+        let subject = match original.subject().get(..4) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("fwd:") => original.subject().to_string(),
+            _ => format!("Fwd: {}", original.subject()),
+        };
+        let mut body = vec![
+            String::new(),
+            String::new(),
+            String::from("---------- Forwarded message ----------"),
+            format!("From: {}", original.from()),
+        ];
+        if let Some(date) = original.date() {
+            body.push(format!("Date: {}", date.format(DATE_FORMAT)));
+        }
+        body.push(format!("Subject: {}", original.subject()));
+        body.push(format!("To: {}", original.to()));
+        if !original.cc().is_empty() {
+            body.push(format!("Cc: {}", original.cc()));
+        }
+        body.push(String::new());
+        body.extend(original.text_body().lines().map(String::from));
+
+        self.reset_compose();
+        self.compose_subject_input = RefCell::new(TextArea::new(vec![subject]));
+        let signature = self.compose_signature_text(None);
+        self.set_compose_body(body, 0, 1, signature);
+        self.mode = Mode::Compose(ComposeFocus::To(ComposeMode::Normal));
+    }
+
+    /// Start the body of a new message, with the cursor on line `cursor` and the signature, if
+    /// any, inserted before line `signature_at`.
+    fn set_compose_body(
+        &mut self,
+        mut body: Vec<String>,
+        mut cursor: usize,
+        signature_at: usize,
+        signature: Option<String>,
+    ) {
+        self.compose_signature = signature.map(|text| {
+            let lines = signature::block(&text);
+            if cursor >= signature_at {
+                cursor += lines.len();
+            }
+            body.splice(signature_at..signature_at, lines.iter().cloned());
+            ComposeSignature {
+                lines,
+                at: signature_at,
+                shown: true,
+            }
+        });
+
+        let mut message_input = TextArea::new(body);
+        message_input.move_cursor(CursorMove::Jump(cursor as u16, 0));
+        self.compose_message_input = RefCell::new(message_input);
+    }
+
+    /// Replace the body of the message being composed with `text` from the external editor, and
+    /// return to the body.
+    fn set_edited_body(&mut self, text: &str) {
+        // editors end the file with a newline that wasn't in the body
+        let body = text.lines().map(String::from).collect();
+        self.compose_message_input = RefCell::new(TextArea::new(body));
+        self.mode = Mode::Compose(ComposeFocus::Message(ComposeMode::Normal));
+    }
+
+    /// Move on to the next way of signing or encrypting the message being composed.
+    fn cycle_protection(&mut self) {
+        self.compose_protection = self.compose_protection.next();
+        self.set_message_status(format!("PGP: {}", self.compose_protection.label()));
+    }
+
+    /// Send the message being composed from the next identity, going round them all and the
+    /// account's own address.
+    fn cycle_identity(&mut self) {
+        let identities = self.config.smtp.identities().len();
+        if identities == 0 {
+            self.set_message_status(String::from("No identities in eta.toml"));
+            return;
+        }
+        self.compose_identity = match self.compose_identity {
+            None => Some(0),
+            Some(index) if index + 1 < identities => Some(index + 1),
+            Some(_) => None,
+        };
+        let reply_to = self
+            .compose_reply_to
+            .as_ref()
+            .map(|original| original.text_body());
+        let signature = self.compose_signature_text(reply_to.as_deref());
+        self.replace_signature(signature);
+        self.set_message_status(format!("From: {}", self.compose_from()));
+    }
+
+    /// The signature of the identity the message being composed is sent from, or else that of
+    /// the account it is on: the `[signature]` table's for the account's own server, for a reply
+    /// to a message with the body `original` if it is one.
+    fn compose_signature_text(&self, original: Option<&str>) -> Option<String> {
+        let identity = self
+            .compose_identity
+            .and_then(|index| self.config.smtp.identities().get(index));
+        if let Some(identity) = identity {
+            if let Some(text) = identity.signature() {
+                return Some(text.to_string());
+            }
+            // another account's messages don't take this one's signature
+            if identity.is_own_account() {
+                return None;
+            }
+        }
+        match original {
+            Some(original) => self.config.signature.for_reply(original),
+            None => self.config.signature.default_text(),
+        }
+        .map(String::from)
+    }
+
+    /// Put `text` in place of the signature of the message being composed, where it was, or at
+    /// the end if there was none. A signature that has been edited is left alone.
+    fn replace_signature(&mut self, text: Option<String>) {
+        let lines = text.as_deref().map(signature::block);
+        let old = self.compose_signature.as_ref();
+        if old.map(|signature| &signature.lines) == lines.as_ref() {
+            return;
+        }
+        let was_shown = old.is_some_and(|signature| signature.shown);
+        let shown = old.is_none_or(|signature| signature.shown);
+        // the old one comes out first, and the new one goes in if it was showing
+        if was_shown {
+            self.toggle_signature();
+            if let Some(ComposeSignature { shown: true, .. }) = self.compose_signature {
+                return;
+            }
+        }
+        let at = match &self.compose_signature {
+            Some(signature) => signature.at,
+            None => self.compose_message_input.get_mut().lines().len(),
+        };
+        self.compose_signature = lines.map(|lines| ComposeSignature {
+            lines,
+            at,
+            shown: false,
+        });
+        if shown && self.compose_signature.is_some() {
+            self.toggle_signature();
+        }
+    }
+
+    /// Take the signature out of the message being composed, or put it back.
+    fn toggle_signature(&mut self) {
+        let Some(signature) = &mut self.compose_signature else {
+            self.set_message_status(String::from("No signature"));
+            return;
+        };
+
+        let input = self.compose_message_input.get_mut();
+        let mut body = input.lines().to_vec();
+        let (mut row, column) = input.cursor();
+        let length = signature.lines.len();
+        if signature.shown {
+            // it may have moved if lines were added above it
+            let found = match body.get(signature.at..signature.at + length) {
+                Some(lines) if lines == signature.lines => Some(signature.at),
+                _ => body
+                    .windows(length)
+                    .rposition(|lines| lines == signature.lines),
+            };
+            let Some(at) = found else {
+                self.set_message_status(String::from("Signature has been edited"));
+                return;
+            };
+            body.drain(at..at + length);
+            if row >= at + length {
+                row -= length;
+            } else if row >= at {
+                row = at;
+            }
+            signature.at = at;
+        } else {
+            signature.at = signature.at.min(body.len());
+            body.splice(signature.at..signature.at, signature.lines.iter().cloned());
+            if row >= signature.at {
+                row += length;
+            }
+        }
+        signature.shown = !signature.shown;
+
+        let mut message_input = TextArea::new(body);
+        message_input.move_cursor(CursorMove::Jump(row as u16, column as u16));
+        self.compose_message_input = RefCell::new(message_input);
+    }
+
+    /// To and Cc for replying to everyone on `original`: the sender and the original recipients
+    /// in To, the original Cc in Cc, leaving out our own addresses and anyone listed twice.
+    fn reply_all_recipients(&self, original: &Message) -> (String, String) {
+        reply_all_recipients(original, &self.config.smtp.own_addresses())
+    }
+
+    fn send_message(&mut self) {
+        if !self.validate_recipients() {
+            return;
+        }
+        let message = Box::new(self.composed_message());
+        if message.to().trim().is_empty() && message.cc().trim().is_empty() {
+            self.set_message_status(String::from("Add a recipient to send the message"));
+            return;
+        }
+
+        // sending again after a warning means the recipients are intended
+        let recipients = (message.to().to_string(), message.cc().to_string());
+        if self.typo_warning.as_ref() != Some(&recipients)
+            && let Some(suggestion) = self.typo_suggestion(&message)
+        {
+            self.set_message_status(format!("Did you mean {suggestion}? S to send anyway"));
+            self.typo_warning = Some(recipients);
+            return;
+        }
+        self.typo_warning = None;
+
+        if self.attachments_fit(PendingAction::Send) {
+            self.confirm(PendingAction::Send);
+        }
+    }
+
+    /// Check the message being composed, then put it in the outbox to be sent at `send_at`.
+    fn send_later(&mut self, send_at: DateTime<Local>) {
+        if !self.validate_recipients() {
+            return;
+        }
+        let message = self.composed_message();
+        if message.to().trim().is_empty() && message.cc().trim().is_empty() {
+            self.set_message_status(String::from("Add a recipient to send the message"));
+            return;
+        }
+        if self.attachments_fit(PendingAction::SendLater(send_at)) {
+            self.confirm(PendingAction::SendLater(send_at));
+        }
+    }
+
+    /// Whether the message's attachments are within `[attachments]`'s limit, or are to be sent
+    /// anyway. When they aren't, warns, refuses or zips them as set, sending the message with
+    /// `action` once they are zipped.
+    fn attachments_fit(&mut self, action: PendingAction) -> bool {
+        let size = self.compose_attachments_size();
+        if size <= self.config.attachments.limit_bytes() {
+            self.size_warning = None;
+            return true;
+        }
+        let over = format!(
+            "The attachments come to {}, over the {} MB limit",
+            attach::megabytes(size),
+            self.config.attachments.limit_mb
+        );
+        match self.config.attachments.too_large {
+            TooLarge::Warn if self.size_warning == Some(size) => {
+                self.size_warning = None;
+                return true;
+            }
+            TooLarge::Warn => {
+                self.set_message_status(format!(
+                    "{over}: :zip compresses them, or send again to send anyway"
+                ));
+                self.size_warning = Some(size);
+            }
+            TooLarge::Zip if self.zipping.is_some() => {
+                self.set_message_status(String::from("The attachments are being zipped"))
+            }
+            TooLarge::Zip => {
+                if self.zip_attachments(Some(action)) {
+                    self.set_message_status(format!("{over}, zipping them before sending"));
+                } else {
+                    self.set_message_status(format!("{over}, even zipped"));
+                }
+            }
+            TooLarge::Refuse => self.set_message_status(over),
+        }
+        false
+    }
+
+    /// Zip up the attachments that aren't shown inline or zipped already, then send the message
+    /// with `then`, if given. False if there are none to zip.
+    fn zip_attachments(&mut self, then: Option<PendingAction>) -> bool {
+        let files: Vec<(String, Vec<u8>)> = self
+            .compose_attachments
+            .iter()
+            .filter(|part| is_zippable(part))
+            .map(|part| {
+                let filename = part.filename().unwrap_or("attachment").to_string();
+                (filename, part.content().to_vec())
+            })
+            .collect();
+        if files.is_empty() {
+            return false;
+        }
+        attach::zip(files, self.events.sender());
+        self.zipping = Some((self.compose_attachments.len(), then));
+        self.activities.push(Activity::Zipping);
+        true
+    }
+
+    /// Replace the attachments that were zipped with the archive, and send the message if it
+    /// was waiting for them.
+    fn attachments_zipped(&mut self, result: Result<Vec<u8>, String>) {
+        // the message may have been sent or discarded while zip ran
+        let Some((count, then)) = self.zipping.take() else {
+            return;
+        };
+        let archive = match result {
+            Ok(archive) => archive,
+            Err(e) => {
+                self.set_message_status(format!("Couldn't zip the attachments: {e}"));
+                return;
+            }
+        };
+
+        let before = self.compose_attachments_size();
+        // anything attached while zip ran wasn't zipped, so is kept
+        let added = self.compose_attachments.split_off(count);
+        self.compose_attachments.retain(|part| !is_zippable(part));
+        self.compose_attachments.push(MessagePart::new(
+            String::from("application/zip"),
+            Some(String::from("attachments.zip")),
+            archive,
+        ));
+        self.compose_attachments.extend(added);
+        self.set_message_status(format!(
+            "Zipped the attachments from {} to {}",
+            attach::megabytes(before),
+            attach::megabytes(self.compose_attachments_size())
+        ));
+
+        match then {
+            Some(PendingAction::Send) => self.send_message(),
+            Some(PendingAction::SendLater(send_at)) => self.send_later(send_at),
+            _ => {}
+        }
+    }
+
+    /// Send the message being composed, once it has been checked and confirmed.
+    fn send_composed_message(&mut self) {
+        if self.messages.needs_password() {
+            self.ask_password(Sending::Message);
+            return;
+        }
+        self.messages.send_message(&self.composed_message());
+        self.activities.push(Activity::Sending);
+
+        // Reset state of compose fields
+        self.reset_compose();
+
+        // return to message table
+        self.mode = Mode::MessageTable(MessageTableMode::Normal);
+        self.autosave_draft();
+    }
+
+    /// Go ahead with `action`, ask about it first, or refuse, as the `[confirm]` table says.
+    fn confirm(&mut self, action: PendingAction) {
+        match self.config.confirm.policy(action.confirmation()) {
+            Policy::Always => self.perform(action),
+            Policy::Ask => self.mode = Mode::Confirm(action, Box::new(self.mode.clone())),
+            Policy::Never => {
+                let refusal = String::from(action.refusal());
+                if let Mode::MessageTable(_) = self.mode {
+                    self.set_table_status(refusal);
+                } else {
+                    self.set_message_status(refusal);
+                }
+            }
+        }
+    }
+
+    fn perform(&mut self, action: PendingAction) {
+        match action {
+            PendingAction::Send => self.send_composed_message(),
+            PendingAction::Delete(ids) => self.delete_messages(ids),
+            PendingAction::EmptyTrash(days) => self.messages.empty_trash(days),
+            PendingAction::DiscardDraft => {
+                self.reset_compose();
+                self.mode = Mode::MessageTable(MessageTableMode::Normal);
+                self.set_table_status(String::from("Message discarded"));
+                self.autosave_draft();
+            }
+            PendingAction::RemoteContent(_) => self.toggle_remote_content(),
+            PendingAction::Rsvp(answer) => self.send_rsvp(answer),
+            PendingAction::SendLater(send_at) => self.schedule_composed_message(send_at),
+            PendingAction::CancelScheduled(id) => {
+                self.messages.cancel_scheduled(id);
+                self.set_message_status(String::from("Cancelled"));
+            }
+        }
+    }
+
+    /// Put the message being composed in the outbox to be sent at `send_at`, once it has been
+    /// checked and confirmed.
+    fn schedule_composed_message(&mut self, send_at: DateTime<Local>) {
+        if self.messages.needs_password() {
+            self.ask_password(Sending::Later(send_at));
+            return;
+        }
+        self.messages
+            .schedule_message(&self.composed_message(), send_at);
+        self.reset_compose();
+        self.mode = Mode::MessageTable(MessageTableMode::Normal);
+        self.autosave_draft();
+    }
+
+    /// Send the scheduled message with id `id` straight away.
+    fn send_scheduled_now(&mut self, id: u64) {
+        if self.messages.needs_password() {
+            self.ask_password(Sending::Scheduled(id));
+            return;
+        }
+        self.messages.send_scheduled_now(id);
+        self.activities.push(Activity::Sending);
+    }
+
+    /// The message being composed, as it is saved.
+    fn draft(&self) -> Draft {
+        Draft {
+            to: self.compose_to_input.borrow().lines()[0].clone(),
+            cc: self.compose_cc_input.borrow().lines()[0].clone(),
+            subject: self.compose_subject_input.borrow().lines()[0].clone(),
+            body: self.compose_message_input.borrow().lines().join("\n"),
+            reply_to: self.compose_reply_to.as_ref().map(|original| original.id()),
+            attachments: self.compose_attachments.clone(),
+        }
+    }
+
+    /// Whether nothing has been written in the composer, apart from the signature.
+    fn compose_is_empty(&self) -> bool {
+        let signature = match &self.compose_signature {
+            Some(signature) if signature.shown => signature.lines.as_slice(),
+            _ => &[],
+        };
+        [
+            &self.compose_to_input,
+            &self.compose_cc_input,
+            &self.compose_subject_input,
+        ]
+        .iter()
+        .all(|field| field.borrow().lines()[0].trim().is_empty())
+            && self.compose_attachments.is_empty()
+            && self
+                .compose_message_input
+                .borrow()
+                .lines()
+                .iter()
+                .all(|line| line.trim().is_empty() || signature.contains(line))
+    }
+
+    /// Save the message being composed if it has changed since it was last saved, or forget its
+    /// draft once nothing is being composed and put back the next draft left from before.
+    fn autosave_draft(&mut self) {
+        let draft = (!self.compose_is_empty()).then(|| self.draft());
+        if draft == self.saved_draft {
+            return;
+        }
+        match &draft {
+            Some(draft) => {
+                // each message gets a draft of its own, so one never replaces another
+                let id = *self
+                    .compose_draft_id
+                    .get_or_insert_with(|| Local::now().timestamp_micros() as u64);
+                self.messages.save_draft(id, draft);
+            }
+            None => {
+                if let Some(id) = self.compose_draft_id.take() {
+                    self.messages.delete_draft(id);
+                }
+            }
+        }
+        self.saved_draft = draft;
+        if self.saved_draft.is_none() {
+            self.recover_next_draft();
+        }
+    }
+
+    /// Put back the newest of the messages left being composed when eta last closed, keeping the
+    /// rest until the composer is free again.
+    fn recover_drafts(&mut self, drafts: Vec<(u64, Draft, Option<Box<Message>>)>) {
+        self.recovered_drafts = drafts;
+        if self.compose_is_empty() {
+            self.recover_next_draft();
+        } else {
+            self.set_table_status(String::from(
+                "Unsent messages are kept: they come back once this one is sent or discarded",
+            ));
+        }
+    }
+
+    /// Put the next draft left from before in the composer, if one is waiting.
+    fn recover_next_draft(&mut self) {
+        let Some((id, draft, reply_to)) = self.recovered_drafts.pop() else {
+            return;
+        };
+        self.compose_to_input = RefCell::new(TextArea::new(vec![draft.to.clone()]));
+        self.compose_cc_input = RefCell::new(TextArea::new(vec![draft.cc.clone()]));
+        self.compose_subject_input = RefCell::new(TextArea::new(vec![draft.subject.clone()]));
+        // whatever signature it has is part of its body
+        self.compose_message_input = RefCell::new(TextArea::new(
+            draft.body.lines().map(String::from).collect(),
+        ));
+        self.compose_signature = None;
+        self.compose_reply_to = reply_to;
+        self.compose_attachments = draft.attachments.clone();
+        self.compose_draft_id = Some(id);
+        self.saved_draft = Some(draft);
+
+        if let Mode::Compose(_) = self.mode {
+            self.set_message_status(String::from("Recovered the message you were writing"));
+        } else {
+            self.set_table_status(String::from(
+                "Recovered an unsent message: press c to carry on writing it",
+            ));
+        }
+    }
+
+    fn set_scheduled(&mut self, scheduled: Vec<Scheduled>) {
+        self.scheduled = scheduled;
+        // keep the selection on a message after one is sent or cancelled
+        if let Mode::Scheduled(index) = &mut self.mode {
+            *index = (*index).min(self.scheduled.len().saturating_sub(1));
+        }
+    }
+
+    /// Whether the current message is an invitation that can be answered.
+    pub fn can_rsvp(&self) -> bool {
+        self.invite
+            .as_ref()
+            .is_some_and(|invite| invite.reply_to().is_some())
+    }
+
+    /// Send the organizer of the current message's invite an iTIP reply with `answer`.
+    fn send_rsvp(&mut self, answer: Answer) {
+        let Some(invite) = self.invite.clone() else {
+            return;
+        };
+        let Some(organizer) = invite.reply_to() else {
+            return;
+        };
+        if self.messages.needs_password() {
+            self.ask_password(Sending::Rsvp(answer));
+            return;
+        }
+
+        // answer as, and from, whichever of the account's addresses was invited, since that is
+        // the one the organizer knows
+        let smtp = &self.config.smtp;
+        let own = smtp.own_addresses();
+        let attendee = invite.attendee(&own).unwrap_or(smtp.username());
+        let mut reply = Message::default();
+        reply.set_from(attendee.to_string());
+        reply.set_to(organizer.to_string());
+        reply.set_subject(format!("{}: {}", answer.label(), invite.summary()));
+        reply.set_body(format!(
+            "{attendee} has {} the invitation.",
+            answer.description()
+        ));
+        reply.set_parts(vec![MessagePart::new(
+            String::from("text/calendar; method=REPLY; charset=utf-8"),
+            None,
+            invite.reply(attendee, answer).into_bytes(),
+        )]);
+        self.messages.send_message(&reply);
+        self.activities.push(Activity::Sending);
+    }
+
+    /// Open the prompt below the composer, starting with `text`.
+    fn open_compose_prompt(&mut self, prompt: ComposePrompt, text: &str) {
+        let focus = match &self.mode {
+            Mode::Compose(focus) | Mode::ComposePrompt(focus, _) => focus.clone(),
+            _ => return,
+        };
+        let mut input = TextArea::new(vec![text.to_string()]);
+        input.move_cursor(CursorMove::End);
+        self.compose_prompt_input = RefCell::new(input);
+        self.mode = Mode::ComposePrompt(focus, prompt);
+    }
+
+    /// Act on what was typed at the prompt below the composer.
+    fn submit_compose_prompt(&mut self, focus: ComposeFocus, prompt: ComposePrompt) {
+        let text = self.compose_prompt_input.get_mut().lines().join("");
+        let text = text.trim();
+        self.mode = Mode::Compose(focus);
+
+        match prompt {
+            ComposePrompt::Command => self.run_compose_command(text),
+            ComposePrompt::Filename if text.is_empty() => {
+                self.open_compose_prompt(ComposePrompt::Filename, "")
+            }
+            ComposePrompt::Filename => {
+                self.attachment_filename = text.to_string();
+                self.open_compose_prompt(ComposePrompt::ContentType, attach::content_type(text));
+            }
+            ComposePrompt::ContentType if !attach::is_content_type(text) => {
+                self.set_message_status(format!("Not a MIME type: {text}"));
+                self.open_compose_prompt(ComposePrompt::ContentType, text);
+            }
+            ComposePrompt::SendAt => match outbox::parse_send_at(text, Local::now()) {
+                Some(send_at) if send_at <= Local::now() => {
+                    self.set_message_status(String::from("That time has already passed"));
+                    self.open_compose_prompt(ComposePrompt::SendAt, text);
+                }
+                Some(send_at) => self.send_later(send_at),
+                None => {
+                    self.set_message_status(String::from(
+                        "Send at a time such as 17:30, tomorrow 9:00, 2026-03-01 08:00 or +2h",
+                    ));
+                    self.open_compose_prompt(ComposePrompt::SendAt, text);
+                }
+            },
+            ComposePrompt::ContentType => {
+                if let Some(content) = self.command_output.take() {
+                    let filename = std::mem::take(&mut self.attachment_filename);
+                    self.set_message_status(format!("Attached {filename}"));
+                    let part = MessagePart::new(text.to_string(), Some(filename), content);
+                    self.compose_attachments.push(part);
+                }
+            }
+        }
+    }
+
+    /// Run a command typed at the composer's prompt, e.g. `attach-cmd git diff`.
+    fn run_compose_command(&mut self, command: &str) {
+        let (name, argument) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        match (name, argument.trim()) {
+            ("", _) => {}
+            ("attach-cmd", "") => {
+                self.set_message_status(String::from("Usage: attach-cmd <shell command>"))
+            }
+            ("attach-cmd", shell_command) => {
+                self.set_message_status(format!("Running {shell_command}"));
+                attach::run(shell_command, self.events.sender());
+            }
+            ("attach-inline", "") => {
+                self.set_message_status(String::from("Usage: attach-inline <image file>"))
+            }
+            ("attach-inline", path) => self.attach_inline_image(path),
+            ("zip", _) if self.zipping.is_some() => {
+                self.set_message_status(String::from("The attachments are being zipped"))
+            }
+            ("zip", _) => {
+                if self.zip_attachments(None) {
+                    self.set_message_status(String::from("Zipping the attachments"));
+                } else {
+                    self.set_message_status(String::from("There are no attachments to zip"));
+                }
+            }
+            (name, _) => self.set_message_status(format!("Unknown command: {name}")),
+        }
+    }
+
+    /// Attach the image at `path` to be shown inline, marking its place at the cursor.
+    fn attach_inline_image(&mut self, path: &str) {
+        let path = attach::expand_home(path);
+        let Some(filename) = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+        else {
+            self.set_message_status(format!("Not a file: {}", path.display()));
+            return;
+        };
+        let content_type = attach::content_type(&filename);
+        if !content_type.starts_with("image/") {
+            self.set_message_status(format!("Not an image: {filename}"));
+            return;
+        }
+        let content = match std::fs::read(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                self.set_message_status(format!("Error: {e}"));
+                return;
+            }
+        };
+
+        let content_id = format!(
+            "image{}.{}@eta",
+            self.compose_attachments.len() + 1,
+            chrono::Local::now().timestamp()
+        );
+        self.compose_message_input
+            .get_mut()
+            .insert_str(mime::image_marker(&filename));
+        let part = MessagePart::new(String::from(content_type), Some(filename.clone()), content);
+        self.compose_attachments
+            .push(part.with_content_id(content_id));
+        self.set_message_status(format!("Attached {filename} inline"));
+    }
+
+    /// Ask what to call the output of `command`, run with `attach-cmd`, once it has finished and
+    /// the composer is open.
+    fn attach_command_output(&mut self, command: &str, result: Result<Vec<u8>, String>) {
+        match result {
+            Ok(output) if output.is_empty() => {
+                self.set_message_status(format!("`{command}` printed nothing to attach"))
+            }
+            Ok(output) => {
+                self.command_output = Some(output);
+                self.open_compose_prompt(
+                    ComposePrompt::Filename,
+                    &attach::default_filename(command),
+                );
+            }
+            Err(e) => self.set_message_status(format!("Error: {e}")),
+        }
+    }
+
+    /// Check that the To and Cc fields only list valid addresses, and if not, say which aren't.
+    fn validate_recipients(&mut self) -> bool {
+        let to = self.compose_to_input.borrow().lines().join("");
+        let cc = self.compose_cc_input.borrow().lines().join("");
+        let problem = [("To", &to), ("Cc", &cc)]
+            .into_iter()
+            .map(|(field, list)| (field, invalid_addresses(list)))
+            .find(|(_, invalid)| !invalid.is_empty())
+            .map(|(field, invalid)| format!("Invalid address in {field}: {}", invalid.join(", ")));
+
+        match problem {
+            Some(problem) => {
+                self.set_message_status(problem);
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// A known contact that one of the recipients of `message` looks like a mistyped version of.
+    fn typo_suggestion(&self, message: &Message) -> Option<String> {
+        let mut known = self.contacts.clone();
+        known.extend(self.address_book.iter().map(|c| c.email.to_lowercase()));
+        [message.to(), message.cc()]
+            .into_iter()
+            .filter_map(|list| parse_addresses(list).ok())
+            .flatten()
+            .find_map(|mailbox| contacts::suggestion(mailbox.email.as_ref(), &known))
+            .map(String::from)
+    }
+
+    fn set_address_book(&mut self, contacts: Vec<Contact>) {
+        self.address_book = contacts;
+        // keep the selection on a contact after one is added or removed
+        let last = self.address_book.len().saturating_sub(1);
+        if let Mode::Contacts(ContactsMode::Normal(index) | ContactsMode::Picking(index, _)) =
+            &mut self.mode
+        {
+            *index = (*index).min(last)
+        }
+    }
+
+    /// Start editing the contact at `index`, or adding a new one if it is past the end.
+    fn edit_contact(&mut self, index: usize) {
+        let address = self
+            .address_book
+            .get(index)
+            .map(Contact::address)
+            .unwrap_or_default();
+        let mut input = TextArea::new(vec![address]);
+        input.move_cursor(CursorMove::End);
+        self.contact_input = RefCell::new(input);
+        self.mode = Mode::Contacts(ContactsMode::Editing(index));
+    }
+
+    /// Save the contact entered for `index`, as long as it is a valid address.
+    fn save_contact(&mut self, index: usize) {
+        let text = self.contact_input.get_mut().lines().join("");
+        let id = self.address_book.get(index).and_then(|c| c.id);
+        match Contact::parse(id, &text) {
+            Ok(contact) => {
+                self.messages.save_contact(&contact);
+                self.set_message_status(format!("Saved {}", contact.address()));
+                self.mode = Mode::Contacts(ContactsMode::Normal(index));
+            }
+            Err(e) => self.set_message_status(format!("Not a valid address: {e}")),
+        }
+    }
+
+    /// Import the contacts from the vCard file whose path was entered.
+    fn import_contacts(&mut self) {
+        let path = self.contact_input.get_mut().lines().join("");
+        match vcard::read(path.trim()) {
+            Ok(contacts) => {
+                self.messages.import_contacts(contacts);
+                self.mode = Mode::Contacts(ContactsMode::Normal(0));
+            }
+            Err(e) => self.set_message_status(format!("Error: {e}")),
+        }
+    }
+
+    fn delete_contact(&mut self, index: usize) {
+        if index >= self.address_book.len() {
+            return;
+        }
+        let contact = self.address_book.remove(index);
+        if let Some(id) = contact.id {
+            self.messages.delete_contact(id);
+        }
+        self.set_message_status(format!("Deleted {}", contact.address()));
+        let index = index.min(self.address_book.len().saturating_sub(1));
+        self.mode = Mode::Contacts(ContactsMode::Normal(index));
+    }
+
+    /// Ask for completions of the address being typed at the end of `field`.
+    fn complete_address(&mut self, field: ComposeField) {
+        let list = self.compose_field(field).lines().join("");
+        let query = split_addresses(&list)
+            .last()
+            .map(|entry| entry.trim().to_string())
+            .unwrap_or_default();
+        if query == self.completion_query {
+            return;
+        }
+
+        self.completions.clear();
+        if !query.is_empty() {
+            self.messages.complete_address(&query);
+        }
+        self.completion_query = query;
+    }
+
+    fn stop_completing(&mut self) {
+        self.completions.clear();
+        self.completion_query.clear();
+    }
+
+    fn move_completion(&mut self, change: isize) {
+        let last = self.completions.len().saturating_sub(1);
+        self.completion_index = self
+            .completion_index
+            .saturating_add_signed(change)
+            .min(last);
+    }
+
+    /// Replace the address being typed at the end of `field` with the selected completion.
+    fn accept_completion(&mut self, field: ComposeField) {
+        let completion = self.completions.swap_remove(self.completion_index);
+        self.completions.clear();
+
+        let input = self.compose_field(field);
+        let list = input.lines().join("");
+        let typed = split_addresses(&list).last().map_or(0, |entry| entry.len());
+        let rest = list[..list.len() - typed].trim_end();
+        let list = if rest.is_empty() {
+            format!("{completion}, ")
+        } else {
+            format!("{rest} {completion}, ")
+        };
+        let mut new_input = TextArea::new(vec![list]);
+        new_input.move_cursor(CursorMove::End);
+        *input = new_input;
+        // nothing is being typed until the next key
+        self.completion_query.clear();
+    }
+
+    fn compose_field(&mut self, field: ComposeField) -> &mut TextArea<'a> {
+        match field {
+            ComposeField::To => self.compose_to_input.get_mut(),
+            ComposeField::Cc => self.compose_cc_input.get_mut(),
+        }
+    }
+
+    pub fn completions(&self) -> &[String] {
+        &self.completions
+    }
+
+    pub fn completion_index(&self) -> usize {
+        self.completion_index
+    }
+
+    /// Add the contact at `index` to a field of the message being composed, and go back to it.
+    fn pick_contact(&mut self, index: usize, field: ComposeField) {
+        self.mode = compose_mode(field);
+        let Some(contact) = self.address_book.get(index) else {
+            return;
+        };
+
+        let address = contact.address();
+        let input = self.compose_field(field);
+        let list = input.lines().join("");
+        let list = match list.trim_end().trim_end_matches(',') {
+            "" => address,
+            list => format!("{list}, {address}"),
+        };
+        let mut new_input = TextArea::new(vec![list]);
+        new_input.move_cursor(CursorMove::End);
+        *input = new_input;
+    }
+
+    /// Make a message table column wider or narrower, and remember its new width.
+    fn resize_column(&mut self, column: Column, change: i16) {
+        let columns = &mut self.config.columns;
+        let (width, key) = match column {
+            Column::From => (&mut columns.from, "from"),
+            Column::Subject => (&mut columns.subject, "subject"),
+        };
+        *width = width
+            .saturating_add_signed(change)
+            .clamp(MIN_COLUMN_WIDTH, MAX_COLUMN_WIDTH);
+
+        let width = *width as i64;
+        if let Err(e) = self
+            .config
+            .save_setting(&["columns", key], Some(value(width)))
+        {
+            self.set_table_status(format!("Error: {e}"));
+        }
+    }
+
+    /// The current value of `setting`, as it is shown and edited.
+    pub fn setting_value(&self, setting: Setting) -> String {
+        let config = &self.config;
+        match setting {
+            Setting::GroupByDate => String::from(if config.group_by_date { "on" } else { "off" }),
+            Setting::RelativeDates => {
+                String::from(if config.relative_dates { "on" } else { "off" })
+            }
+            Setting::DesktopNotifications => String::from(if config.desktop_notifications {
+                "on"
+            } else {
+                "off"
+            }),
+            Setting::EmptyTrashAfterDays => config
+                .empty_trash_after_days
+                .map(|days| days.to_string())
+                .unwrap_or_default(),
+            Setting::MarkReadAfterSeconds => config.mark_read_after_seconds.to_string(),
+            Setting::PollInterval => config.poll_interval.to_string(),
+            Setting::AutosaveSeconds => config.autosave_seconds.to_string(),
+            Setting::ReplyAttribution => config.reply.attribution.clone(),
+            Setting::ReplyPosting => String::from(match config.reply.posting {
+                Posting::Bottom => "bottom",
+                Posting::Top => "top",
+            }),
+            Setting::Signature => config
+                .signature
+                .default_text()
+                .unwrap_or_default()
+                .to_string(),
+            Setting::RemoteContentSenders => self.remote_content_senders.join(", "),
+            Setting::Confirm(action) => String::from(config.confirm.policy(action).name()),
+        }
+    }
+
+    /// Change the setting at `index`: switch it when it only has two values, and otherwise start
+    /// editing it.
+    fn edit_setting(&mut self, index: usize) {
+        let setting = SETTINGS[index];
+        if setting.is_toggle() {
+            let result = match setting {
+                Setting::GroupByDate => {
+                    self.config.group_by_date = !self.config.group_by_date;
+                    self.config
+                        .save_setting(&["group_by_date"], Some(value(self.config.group_by_date)))
+                }
+                Setting::RelativeDates => {
+                    self.config.relative_dates = !self.config.relative_dates;
+                    let relative = self.config.relative_dates;
+                    self.config
+                        .save_setting(&["relative_dates"], Some(value(relative)))
+                }
+                Setting::DesktopNotifications => {
+                    self.config.desktop_notifications = !self.config.desktop_notifications;
+                    let enabled = self.config.desktop_notifications;
+                    self.config
+                        .save_setting(&["desktop_notifications"], Some(value(enabled)))
+                }
+                Setting::Confirm(action) => {
+                    let policy = self.config.confirm.policy_mut(action);
+                    *policy = policy.next();
+                    let name = policy.name();
+                    self.config
+                        .save_setting(&["confirm", action.key()], Some(value(name)))
+                }
+                _ => {
+                    self.config.reply.posting = match self.config.reply.posting {
+                        Posting::Bottom => Posting::Top,
+                        Posting::Top => Posting::Bottom,
+                    };
+                    let posting = self.setting_value(Setting::ReplyPosting);
+                    self.config
+                        .save_setting(&["reply", "posting"], Some(value(posting)))
+                }
+            };
+            self.show_settings_result(result);
+            return;
+        }
+
+        let lines = self
+            .setting_value(setting)
+            .lines()
+            .map(String::from)
+            .collect();
+        let mut input = TextArea::new(lines);
+        input.move_cursor(CursorMove::Bottom);
+        input.move_cursor(CursorMove::End);
+        self.settings_input = RefCell::new(input);
+        self.mode = Mode::Settings(SettingsMode::Editing(index));
+    }
+
+    /// Apply the value entered for the setting at `index`, and save it to `eta.toml`.
+    fn save_setting(&mut self, index: usize) {
+        let entered = self.settings_input.get_mut().lines().join("\n");
+        let text = entered.trim();
+        self.mode = Mode::Settings(SettingsMode::Normal(index));
+
+        let result = match SETTINGS[index] {
+            Setting::EmptyTrashAfterDays if text.is_empty() => {
+                self.config.empty_trash_after_days = None;
+                self.config.save_setting(&["empty_trash_after_days"], None)
+            }
+            Setting::EmptyTrashAfterDays => match text.parse::<u32>() {
+                Ok(days) => {
+                    self.config.empty_trash_after_days = Some(days);
+                    self.config
+                        .save_setting(&["empty_trash_after_days"], Some(value(days as i64)))
+                }
+                Err(_) => {
+                    self.set_message_status(format!("Not a number of days: {text}"));
+                    return;
+                }
+            },
+            Setting::MarkReadAfterSeconds => match text.parse::<u64>() {
+                Ok(seconds) => {
+                    self.config.mark_read_after_seconds = seconds;
+                    self.config
+                        .save_setting(&["mark_read_after_seconds"], Some(value(seconds as i64)))
+                }
+                Err(_) => {
+                    self.set_message_status(format!("Not a number of seconds: {text}"));
+                    return;
+                }
+            },
+            Setting::PollInterval => match text.parse::<u64>() {
+                Ok(seconds) => {
+                    self.config.poll_interval = seconds;
+                    self.poll_folders();
+                    self.config
+                        .save_setting(&["poll_interval"], Some(value(seconds as i64)))
+                }
+                Err(_) => {
+                    self.set_message_status(format!("Not a number of seconds: {text}"));
+                    return;
+                }
+            },
+            Setting::AutosaveSeconds => match text.parse::<u64>() {
+                Ok(seconds) => {
+                    self.config.autosave_seconds = seconds;
+                    self.config
+                        .save_setting(&["autosave_seconds"], Some(value(seconds as i64)))
+                }
+                Err(_) => {
+                    self.set_message_status(format!("Not a number of seconds: {text}"));
+                    return;
+                }
+            },
+            Setting::ReplyAttribution => {
+                self.config.reply.attribution = text.to_string();
+                self.config
+                    .save_setting(&["reply", "attribution"], Some(value(text)))
+            }
+            Setting::Signature => {
+                // kept as it was typed, as its lines are laid out on purpose
+                self.config.signature.default.text =
+                    Some(entered.clone()).filter(|_| !text.is_empty());
+                match &self.config.signature.default.file {
+                    // a signature read from a file stays in it
+                    Some(file) => {
+                        std::fs::write(self.config.dir().join(file), &entered).map_err(Into::into)
+                    }
+                    None => self.config.save_setting(
+                        &["signature", "text"],
+                        Some(value(&entered)).filter(|_| !text.is_empty()),
+                    ),
+                }
+            }
+            Setting::RemoteContentSenders => {
+                let senders: Vec<String> = text
+                    .split(',')
+                    .map(|sender| sender.trim().to_lowercase())
+                    .filter(|sender| !sender.is_empty())
+                    .collect();
+                for sender in &self.remote_content_senders {
+                    if !senders.contains(sender) {
+                        self.messages.set_remote_content_allowed(sender, false);
+                    }
+                }
+                for sender in &senders {
+                    if !self.remote_content_senders.contains(sender) {
+                        self.messages.set_remote_content_allowed(sender, true);
+                    }
+                }
+                self.remote_content_senders = senders;
+                self.set_message_status(String::from("Saved"));
+                return;
+            }
+            Setting::GroupByDate
+            | Setting::RelativeDates
+            | Setting::DesktopNotifications
+            | Setting::ReplyPosting
+            | Setting::Confirm(_) => return,
+        };
+        self.show_settings_result(result);
+    }
+
+    fn show_settings_result(&mut self, result: color_eyre::Result<()>) {
+        self.set_message_status(match result {
+            Ok(_) => String::from("Saved to eta.toml"),
+            Err(e) => format!("Error: {e}"),
+        });
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    pub fn address_book(&self) -> &[Contact] {
+        &self.address_book
+    }
+
+    pub fn scheduled(&self) -> &[Scheduled] {
+        &self.scheduled
+    }
+
+    pub fn contact_input(&self) -> &RefCell<TextArea<'a>> {
+        &self.contact_input
+    }
+
+    pub fn settings_input(&self) -> &RefCell<TextArea<'a>> {
+        &self.settings_input
+    }
+
+    /// Use the message being composed as a mail merge template, and ask for a CSV of recipients.
+    fn start_mail_merge(&mut self) {
+        self.mode = Mode::MailMerge(MailMergeMode::SelectCsv(None));
+    }
+
+    fn load_mail_merge(&mut self) {
+        let path = self.merge_csv_input.borrow().lines()[0].trim().to_string();
+
+        self.mode = match MailMerge::from_csv(self.composed_message(), &path) {
+            Ok(merge) => {
+                self.merge_messages = merge.messages();
+                Mode::MailMerge(MailMergeMode::Preview(0))
+            }
+            Err(e) => Mode::MailMerge(MailMergeMode::SelectCsv(Some(e.to_string()))),
+        };
+    }
+
+    fn send_mail_merge(&mut self) {
+        if self.messages.needs_password() {
+            self.ask_password(Sending::MailMerge);
+            return;
+        }
+        let messages = std::mem::take(&mut self.merge_messages);
+        self.messages.send_messages(messages);
+        self.activities.push(Activity::MailMerge);
+
+        // Reset state of compose and merge fields
+        self.reset_compose();
+        self.merge_csv_input = RefCell::new(TextArea::default());
+
+        // return to message table, where the outcome is shown
+        self.mode = Mode::MessageTable(MessageTableMode::Normal);
+        self.autosave_draft();
+    }
+
+    /// Ask for the SMTP password, which isn't set in `eta.toml`, before `sending`. It is kept for
+    /// the rest of the session.
+    fn ask_password(&mut self, sending: Sending) {
+        self.password_input = RefCell::new(password_textarea());
+        self.mode = Mode::Password(sending, Box::new(self.mode.clone()));
+    }
+
+    /// Set running to false to quit the application.
     fn quit(&mut self) {
         self.running = false;
     }
 
-    fn view_message(&mut self) {
-        match self.message_table_state.borrow().selected() {
-            Some(id) => {
-                self.message_textarea = RefCell::new(TextArea::default());
-                let message_id = (id + 1) as u64;
-                // ask the provider to load the message body
-                self.messages.get_message(message_id);
-                self.mode = Mode::Message(id);
-                self.needs_render = true;
+    fn view_message(&mut self) {
+        let Some(index) = self.message_table_state.borrow().selected() else {
+            return;
+        };
+        let Some(message) = self
+            .selected_message_id()
+            .and_then(|id| self.loaded_messages.iter_mut().find(|m| m.id() == id))
+        else {
+            return;
+        };
+
+        self.message_textarea = RefCell::new(TextArea::default());
+        self.bounce = None;
+        self.invite = None;
+        // ask the provider to load the message body
+        self.messages.get_message(message.id());
+        self.pending_read = None;
+        if message.is_unread() {
+            match self.config.mark_read_after_seconds {
+                0 => {
+                    message.set_unread(false);
+                    self.messages.set_unread(&[message.id()], false);
+                }
+                seconds => {
+                    let at = Instant::now() + Duration::from_secs(seconds);
+                    self.pending_read = Some((message.id(), at));
+                }
+            }
+        }
+        self.mode = Mode::Message(index);
+        self.needs_render = true;
+    }
+
+    /// Write the HTML part of the current message to a temporary file and open it in a browser.
+    fn open_html_part(&mut self) {
+        let Some(part) = self.current_message.part("text/html") else {
+            self.set_message_status(String::from("No HTML part"));
+            return;
+        };
+
+        let html = if self.remote_content_blocked() {
+            mime::block_remote_content(&part.text()).into_bytes()
+        } else {
+            part.content().to_vec()
+        };
+        let result = self
+            .html_file(&html)
+            .and_then(|path| open::that_detached(&path));
+
+        self.set_message_status(match result {
+            Ok(_) => String::from("Opened in browser"),
+            Err(e) => format!("Error: {e}"),
+        });
+    }
+
+    /// Write `html` to a new file only the user can read, in eta's private directory.
+    fn html_file(&mut self, html: &[u8]) -> io::Result<PathBuf> {
+        if self.html_dir.is_none() {
+            self.html_dir = Some(tempfile::Builder::new().prefix("eta-").tempdir()?);
+        }
+        let dir = self.html_dir.as_ref().expect("created above");
+        let mut file = tempfile::Builder::new()
+            .prefix("message-")
+            .suffix(".html")
+            .tempfile_in(dir.path())?;
+        file.write_all(html)?;
+        // the browser reads it later, so it outlives the handle and goes with the directory
+        let (_, path) = file.keep().map_err(|e| e.error)?;
+        Ok(path)
+    }
+
+    /// Whether the current message would load remote content, but its sender isn't allowed to.
+    fn remote_content_blocked(&self) -> bool {
+        self.current_message
+            .part("text/html")
+            .is_some_and(|part| mime::has_remote_content(&part.text()))
+            && !self.loads_remote_content()
+    }
+
+    /// Whether remote content is always loaded from the current message's sender.
+    fn loads_remote_content(&self) -> bool {
+        self.remote_content_senders
+            .contains(&sender_address(&self.current_message))
+    }
+
+    /// Always load remote content from the current message's sender, or stop doing so.
+    fn toggle_remote_content(&mut self) {
+        let sender = sender_address(&self.current_message);
+        let allowed = !self.remote_content_senders.contains(&sender);
+        self.messages.set_remote_content_allowed(&sender, allowed);
+        if allowed {
+            self.remote_content_senders.push(sender.clone());
+        } else {
+            self.remote_content_senders.retain(|s| *s != sender);
+        }
+
+        // show the message again, with or without the banner
+        let parts = self.current_message.parts().to_vec();
+        let verification = self.current_message.verification().cloned();
+        self.set_current_message(self.current_message.id(), parts, verification);
+        self.set_message_status(if allowed {
+            format!("Always loading remote content from {sender}")
+        } else {
+            format!("Blocking remote content from {sender}")
+        });
+    }
+
+    /// List the URLs in the current message, so they can be opened or copied.
+    fn show_links(&mut self, row: usize) {
+        self.links =
+            links::find_links(&self.current_message.text_body(), &self.config.ticket_links);
+        if self.links.is_empty() {
+            self.set_message_status(String::from("No links"));
+        } else {
+            self.mode = Mode::Links(row, 0);
+        }
+    }
+
+    /// Open the prompt below the message at `row`. Files are suggested a name made from the
+    /// message's subject, in the current directory.
+    fn open_message_prompt(&mut self, row: usize, prompt: MessagePrompt) {
+        let text = match prompt {
+            MessagePrompt::Export => message_filename(self.current_message.subject(), "eml"),
+            MessagePrompt::SaveText => message_filename(self.current_message.subject(), "txt"),
+            MessagePrompt::Pipe => String::new(),
+        };
+        let mut input = TextArea::new(vec![text]);
+        input.move_cursor(CursorMove::End);
+        self.message_prompt_input = RefCell::new(input);
+        self.mode = Mode::MessagePrompt(row, prompt);
+    }
+
+    /// Act on what was typed at the prompt below the message at `row`.
+    fn submit_message_prompt(&mut self, row: usize, prompt: MessagePrompt) {
+        self.mode = Mode::Message(row);
+        let text = self.message_prompt_input.get_mut().lines()[0]
+            .trim()
+            .to_string();
+        if text.is_empty() {
+            return;
+        }
+        match prompt {
+            MessagePrompt::Export => {
+                let path = self.message_path(&text, "eml");
+                self.messages
+                    .export_message(self.current_message.id(), &path);
+            }
+            MessagePrompt::SaveText => self.save_message_text(&text),
+            // the command may need the terminal, which only the event loop can give it
+            MessagePrompt::Pipe => self.pipe_pending = Some(text),
+        }
+    }
+
+    /// The file at `path`, or a file in it named after the message being read if it is a
+    /// directory.
+    fn message_path(&self, path: &str, extension: &str) -> PathBuf {
+        let mut path = attach::expand_home(path);
+        if path.is_dir() {
+            path.push(message_filename(self.current_message.subject(), extension));
+        }
+        path
+    }
+
+    /// Write the text of the message being read to a new file at `path`.
+    fn save_message_text(&mut self, path: &str) {
+        let path = self.message_path(path, "txt");
+        // rather than overwrite whatever is there
+        let result = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(self.current_message_text().as_bytes()));
+        self.set_message_status(match result {
+            Ok(()) => format!("Saved to {}", path.display()),
+            Err(e) => format!("Error: {e}"),
+        });
+    }
+
+    /// The message being read as text, below its headers, as it is saved or piped.
+    fn current_message_text(&self) -> String {
+        let message = &self.current_message;
+        format!("{}\n{}", message_headers(message), message.text_body())
+    }
+
+    /// Show the first attachment that can be read as text, starting from the part at `from` and
+    /// wrapping around.
+    fn preview_attachment(&mut self, row: usize, from: usize) {
+        let parts = self.current_message.parts();
+        let found = (0..parts.len())
+            .map(|i| (from + i) % parts.len())
+            .find(|&i| preview::is_previewable(&parts[i]));
+
+        match found {
+            Some(index) => {
+                self.preview_scroll = 0;
+                self.mode = Mode::Attachment(row, index);
+            }
+            None => self.set_message_status(String::from("No text attachments")),
+        }
+    }
+
+    fn scroll_preview(&mut self, lines: i32) {
+        let Mode::Attachment(_, index) = self.mode else {
+            return;
+        };
+        let line_count = self.current_message.parts()[index].text().lines().count();
+        let last = line_count.saturating_sub(1).min(u16::MAX as usize) as i32;
+        self.preview_scroll = (self.preview_scroll as i32 + lines).clamp(0, last) as u16;
+    }
+
+    fn open_link(&mut self, row: usize, index: usize) {
+        self.mode = Mode::Message(row);
+        let Some(url) = self.links.get(index).map(|link| link.url.clone()) else {
+            return;
+        };
+
+        self.set_message_status(match open::that_detached(&url) {
+            Ok(_) => format!("Opened {url}"),
+            Err(e) => format!("Error: {e}"),
+        });
+    }
+
+    fn copy_link(&mut self, row: usize, index: usize) {
+        self.mode = Mode::Message(row);
+        let Some(url) = self.links.get(index).map(|link| link.url.clone()) else {
+            return;
+        };
+
+        self.set_message_status(match clipboard::copy(&url) {
+            Ok(_) => format!("Copied {url}"),
+            Err(e) => format!("Error: {e}"),
+        });
+    }
+
+    /// Open the tag picker over the current view, for the message with id `id`.
+    fn open_tags(&mut self, id: u64) {
+        self.tag_input = RefCell::new(TextArea::default());
+        let previous = std::mem::replace(&mut self.mode, Mode::LoadingMessages);
+        self.mode = Mode::Tags(id, 0, Box::new(previous));
+    }
+
+    /// Give the message with id `id` `tag`, or take it away if it has it already.
+    fn toggle_tag(&mut self, id: u64, tag: String) {
+        let Some(mut tags) = self.loaded_message(id).map(|m| m.tags().to_vec()) else {
+            return;
+        };
+        let tagged = !tags.contains(&tag);
+        if tagged {
+            tags.push(tag.clone());
+        } else {
+            tags.retain(|t| *t != tag);
+        }
+
+        for message in self.loaded_messages.iter_mut().filter(|m| m.id() == id) {
+            message.set_tags(tags.clone());
+        }
+        if self.current_message.id() == id {
+            self.current_message.set_tags(tags);
+        }
+        self.messages.set_tagged(id, &tag, tagged);
+        // show the message again, with its tags as they are now
+        if let Mode::Message(_) = self.mode {
+            let parts = self.current_message.parts().to_vec();
+            let verification = self.current_message.verification().cloned();
+            self.set_current_message(id, parts, verification);
+        }
+
+        let status = if tagged {
+            format!("Tagged {tag}")
+        } else {
+            format!("Untagged {tag}")
+        };
+        if let Mode::Message(_) = self.mode {
+            self.set_message_status(status);
+        } else {
+            self.set_table_status(status);
+        }
+    }
+
+    /// Label every link and email address in view, to follow one by typing its label.
+    fn show_hints(&mut self, row: usize, action: HintAction) {
+        let text = self.message_textarea.get_mut().lines().join("\n");
+        if links::find_targets(&text).is_empty() {
+            self.mode = Mode::Message(row);
+            self.set_message_status(String::from("No links"));
+            return;
+        }
+        self.mode = Mode::Hints(row, action, String::new());
+    }
+
+    /// Follow the link whose label is `typed`, once a whole label has been typed.
+    fn type_hint(&mut self, row: usize, action: HintAction, typed: String) {
+        let labels = links::hint_labels(self.hint_targets.get_mut().len());
+        if let Some(index) = labels.iter().position(|label| *label == typed) {
+            let target = self.hint_targets.get_mut()[index].clone();
+            self.follow_hint(row, action, target);
+        } else if labels.iter().any(|label| label.starts_with(&typed)) {
+            self.mode = Mode::Hints(row, action, typed);
+        } else {
+            self.mode = Mode::Message(row);
+            self.set_message_status(format!("No link labelled {typed}"));
+        }
+    }
+
+    fn follow_hint(&mut self, row: usize, action: HintAction, target: Target) {
+        match (action, target) {
+            (HintAction::Open, Target::Url(url)) => {
+                self.mode = Mode::Message(row);
+                self.set_message_status(match open::that_detached(&url) {
+                    Ok(_) => format!("Opened {url}"),
+                    Err(e) => format!("Error: {e}"),
+                });
+            }
+            (HintAction::Open, Target::Email(address)) => self.compose_draft(Draft {
+                to: address,
+                ..Draft::default()
+            }),
+            (HintAction::Copy, Target::Url(url)) => self.copy_from_message(row, "link", &url),
+            (HintAction::Copy, Target::Email(address)) => {
+                self.copy_from_message(row, "address", &address)
+            }
+        }
+    }
+
+    /// Copy `text`, the `what` of the message at `row`, to the clipboard.
+    fn copy_from_message(&mut self, row: usize, what: &str, text: &str) {
+        self.mode = Mode::Message(row);
+        self.set_message_status(match clipboard::copy(text) {
+            Ok(_) => format!("Copied {what}"),
+            Err(e) => format!("Error: {e}"),
+        });
+    }
+
+    fn next_message(&mut self) {
+        let mut state = self.message_table_state.borrow_mut();
+        let i = match state.selected() {
+            Some(i) => {
+                if i >= self.messages().len().saturating_sub(1) {
+                    0
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        state.select(Some(i));
+
+        // clear any status messages
+        self.mode = Mode::MessageTable(MessageTableMode::Normal);
+        self.needs_render = true;
+    }
+
+    /// Open the message after or before the current one in the table, selecting it there too.
+    fn open_adjacent(&mut self, direction: Direction) {
+        let len = self.messages().len();
+        let selected = self.message_table_state.borrow().selected();
+        let index = match (direction, selected) {
+            (Direction::Next, Some(i)) if i + 1 < len => i + 1,
+            (Direction::Previous, Some(i)) if i > 0 => i - 1,
+            _ => {
+                self.set_message_status(String::from(match direction {
+                    Direction::Next => "This is the last message",
+                    Direction::Previous => "This is the first message",
+                }));
+                return;
+            }
+        };
+        self.message_table_state.borrow_mut().select(Some(index));
+        self.view_message();
+    }
+
+    /// Select the next or previous unread message in the table, or say there isn't one.
+    fn jump_to_unread(&mut self, direction: Direction) {
+        if !self.select_unread(direction) {
+            self.set_table_status(String::from(match direction {
+                Direction::Next => "No more unread messages",
+                Direction::Previous => "No earlier unread messages",
+            }));
+        }
+    }
+
+    /// Select the next or previous unread message in the table, skipping muted ones. Returns
+    /// whether there was one.
+    fn select_unread(&mut self, direction: Direction) -> bool {
+        let messages = self.messages();
+        let selected = self.message_table_state.borrow().selected();
+        let is_unread = |&i: &usize| messages[i].is_unread() && !self.is_muted(messages[i]);
+        let found = match (direction, selected) {
+            (Direction::Next, Some(i)) => (i + 1..messages.len()).find(is_unread),
+            (Direction::Next, None) => (0..messages.len()).find(is_unread),
+            (Direction::Previous, Some(i)) => (0..i).rev().find(is_unread),
+            (Direction::Previous, None) => None,
+        };
+
+        let Some(index) = found else {
+            return false;
+        };
+        self.message_table_state.borrow_mut().select(Some(index));
+        self.needs_render = true;
+        true
+    }
+
+    /// Select the message at `index` in the table, fetching older messages when it is near the
+    /// top.
+    fn select_row(&mut self, index: usize) {
+        self.message_table_state.get_mut().select(Some(index));
+        self.clamp_selection();
+        if index < PREFETCH_ROWS {
+            self.load_older_messages();
+        }
+        self.mark_range();
+        self.mode = Mode::MessageTable(MessageTableMode::Normal);
+    }
+
+    /// Select the message with id `id`, if the table shows it.
+    fn go_to_message(&mut self, id: u64) {
+        match self.messages().iter().position(|m| m.id() == id) {
+            Some(index) => self.select_row(index),
+            None => self.set_table_status(format!("Message {id} isn't in the table")),
+        }
+    }
+
+    /// Move the selection half a screen down or up the table.
+    fn move_half_page(&mut self, direction: Direction) {
+        let half_page = (self.table_height.get() as usize / 2).max(1);
+        let last = self.messages().len().saturating_sub(1);
+        let selected = self.message_table_state.get_mut().selected().unwrap_or(0);
+        self.select_row(match direction {
+            Direction::Next => (selected + half_page).min(last),
+            Direction::Previous => selected.saturating_sub(half_page),
+        });
+    }
+
+    fn previous_message(&mut self) {
+        let selected = self.message_table_state.borrow().selected();
+        let i = match selected {
+            // wait at the top for older messages rather than wrapping around
+            Some(0) if self.older_messages => 0,
+            Some(0) => self.messages().len().saturating_sub(1),
+            Some(i) => i - 1,
+            None => 0,
+        };
+        self.message_table_state.get_mut().select(Some(i));
+        if i < PREFETCH_ROWS {
+            self.load_older_messages();
+        }
+
+        // clear any status messages
+        self.mode = Mode::MessageTable(MessageTableMode::Normal)
+    }
+
+    /// Fetch the page of messages before the oldest one loaded, if there is one and it isn't
+    /// already on its way.
+    fn load_older_messages(&mut self) {
+        if !self.older_messages || self.loading_older || self.search.is_some() {
+            return;
+        }
+        if let Some(oldest) = self.loaded_messages.iter().map(Message::id).min() {
+            self.loading_older = true;
+            self.messages
+                .get_older_messages(&self.current_folder, oldest);
+            self.activities.push(Activity::LoadingOlder);
+        }
+    }
+
+    /// Put a page of older messages above those loaded, keeping the same message selected.
+    fn add_older_messages(&mut self, messages: Vec<Message>) {
+        self.loading_older = false;
+        self.older_messages = messages.len() == PAGE_SIZE;
+        let selected = self.selected_message_id();
+        self.loaded_messages.splice(0..0, messages);
+        self.mark_muted_read();
+
+        if let Some(index) =
+            selected.and_then(|id| self.messages().iter().position(|m| m.id() == id))
+        {
+            self.message_table_state.get_mut().select(Some(index));
+        }
+    }
+
+    /// Check each folder for new messages on the intervals set in `eta.toml`, in the background.
+    fn poll_folders(&self) {
+        let schedule = Schedule::new(&self.config.polling, self.config.poll_interval);
+        self.messages.poll_folders(schedule);
+    }
+
+    /// Add messages that have arrived since the folder was loaded to the end of the table.
+    fn add_new_messages(&mut self, messages: Vec<Message>) {
+        let messages: Vec<Message> = messages
+            .into_iter()
+            .filter(|m| {
+                !self
+                    .loaded_messages
+                    .iter()
+                    .any(|loaded| loaded.id() == m.id())
+            })
+            .collect();
+        if messages.is_empty() {
+            return;
+        }
+
+        let count = messages.len();
+        self.message_total = self.message_total.map(|total| total + count);
+        self.loaded_messages.extend(messages);
+        self.mark_muted_read();
+        self.announce_watched_replies();
+        self.set_table_status(match count {
+            1 => String::from("1 new message"),
+            n => format!("{n} new messages"),
+        });
+        self.needs_render = true;
+    }
+
+    /// How many messages are loaded, out of how many there are in the current folder, and how
+    /// many of those are unread.
+    pub fn message_counts(&self) -> Option<MessageCounts> {
+        self.message_total.map(|total| MessageCounts {
+            loaded: self.loaded_messages.len(),
+            unread: self.unread_total,
+            total,
+        })
+    }
+
+    /// Move down the sidebar, through the folders and then the saved searches.
+    fn next_folder(&mut self) {
+        let last = (self.folders.len() + self.config.searches.len()).saturating_sub(1);
+        let state = self.folder_list_state.get_mut();
+        state.select(state.selected().map(|i| (i + 1).min(last)));
+    }
+
+    /// Load the folder selected in the sidebar, or run the saved search, and return to the
+    /// message table.
+    fn open_folder(&mut self) {
+        let selected = self.folder_list_state.borrow().selected();
+        let saved = selected.and_then(|i| i.checked_sub(self.folders.len()));
+        if let Some(search) = saved.and_then(|i| self.config.searches.get(i)) {
+            let query = search.query.clone();
+            self.search_input = RefCell::new(TextArea::new(vec![query]));
+            self.search_messages();
+            return;
+        }
+        if let Some(folder) = selected.and_then(|i| self.folders.get(i))
+            && (*folder != self.current_folder || self.search.is_some())
+        {
+            self.current_folder = folder.clone();
+            self.search = None;
+            self.reload_folder();
+        }
+        self.mode = Mode::MessageTable(MessageTableMode::Normal);
+    }
+
+    /// Add the `.eml` file at `path`, or every one in the directory at `path`, to the current
+    /// folder.
+    fn import_messages(&mut self, path: &str) {
+        let path = attach::expand_home(path);
+        let paths = if path.is_dir() {
+            let entries = match std::fs::read_dir(&path) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    self.set_table_status(format!("Error: {e}"));
+                    return;
+                }
+            };
+            let mut paths: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension.eq_ignore_ascii_case("eml"))
+                })
+                .collect();
+            paths.sort();
+            paths
+        } else {
+            vec![path]
+        };
+        if paths.is_empty() {
+            self.set_table_status(String::from("No .eml files to import"));
+            return;
+        }
+        self.messages.import_messages(paths, &self.current_folder);
+    }
+
+    fn messages_imported(&mut self, imported: usize, failures: Vec<String>) {
+        let messages = if imported == 1 { "message" } else { "messages" };
+        self.set_table_status(match (imported, failures.first()) {
+            (_, None) => format!("Imported {imported} {messages}"),
+            (0, Some(failure)) => format!("Nothing imported: {failure}"),
+            (_, Some(failure)) => format!(
+                "Imported {imported} {messages}, {} not ({failure})",
+                failures.len()
+            ),
+        });
+        if imported > 0 && self.search.is_none() {
+            self.reload_folder();
+        }
+    }
+
+    /// Load the current folder's messages into the table afresh.
+    fn reload_folder(&mut self) {
+        self.loaded_messages.clear();
+        self.clear_marks();
+        self.message_total = None;
+        self.message_table_state.get_mut().select(None);
+        self.messages.get_messages(&self.current_folder);
+        self.activities
+            .push(Activity::Loading(self.current_folder.clone()));
+    }
+
+    /// Search every folder for the text that was typed, and show the results in the table.
+    fn search_messages(&mut self) {
+        let query = self.search_input.get_mut().lines().join(" ");
+        let query = query.trim();
+        self.mode = Mode::MessageTable(MessageTableMode::Normal);
+        if query.is_empty() {
+            return;
+        }
+
+        self.messages.search_messages(query);
+        self.activities.push(Activity::Searching);
+        self.clear_marks();
+        self.search = Some(query.to_string());
+        // search results come all at once
+        self.message_total = None;
+        self.older_messages = false;
+    }
+
+    /// Search every folder for the message the current bounce is about.
+    fn find_bounced_message(&mut self) {
+        let Some(original) = self
+            .bounce
+            .as_ref()
+            .and_then(|bounce| bounce.original.clone())
+        else {
+            self.set_message_status(String::from("Not a bounce of a message that can be found"));
+            return;
+        };
+        self.search_input = RefCell::new(TextArea::new(vec![format!("msgid:{original}")]));
+        self.search_messages();
+    }
+
+    /// Carry out a command from the `:` command line.
+    fn run_command(&mut self, command: Command) {
+        match command {
+            Command::Compose => self.compose_message(),
+            Command::Search(query) => {
+                self.search_input = RefCell::new(TextArea::new(vec![query]));
+                self.search_messages();
+            }
+            Command::Goto(id) => self.go_to_message(id),
+            Command::Folder(folder) => match self.folders.iter().position(|f| *f == folder) {
+                Some(index) => {
+                    self.folder_list_state.get_mut().select(Some(index));
+                    self.open_folder();
+                }
+                None => self.set_table_status(format!("No folder called {folder}")),
+            },
+            Command::Move(folder) => match self.chosen_ids() {
+                ids if ids.is_empty() => self.set_table_status(String::from("No message selected")),
+                _ if !self.folders.contains(&folder) => {
+                    self.set_table_status(format!("No folder called {folder}"))
+                }
+                ids => self.move_messages(ids, folder),
+            },
+            Command::Import(path) => self.import_messages(&path),
+            Command::Mark(marking) => self.mark(marking),
+            Command::Save(name) => self.save_search(name),
+            Command::Unsave(name) => self.unsave_search(&name),
+            Command::Help => self.set_table_status(format!("Commands: {}", command::USAGE)),
+            Command::Quit => self.quit(),
+        }
+    }
+
+    /// Keep the current search in the sidebar as `name`, replacing a saved search of that name.
+    fn save_search(&mut self, name: String) {
+        let Some(query) = self.search.clone() else {
+            self.set_table_status(String::from("Search for something to save first"));
+            return;
+        };
+        let saved = SavedSearch { name, query };
+        match self
+            .config
+            .searches
+            .iter_mut()
+            .find(|s| s.name == saved.name)
+        {
+            Some(search) => *search = saved.clone(),
+            None => self.config.searches.push(saved.clone()),
+        }
+        match self.config.save_searches() {
+            Ok(()) => self.set_table_status(format!("Saved search {}", saved.name)),
+            Err(e) => self.set_table_status(format!("Error: {e}")),
+        }
+    }
+
+    fn unsave_search(&mut self, name: &str) {
+        let Some(index) = self.config.searches.iter().position(|s| s.name == name) else {
+            self.set_table_status(format!("No saved search called {name}"));
+            return;
+        };
+        self.config.searches.remove(index);
+        // keep the sidebar's selection within it
+        let last = (self.folders.len() + self.config.searches.len()).saturating_sub(1);
+        let state = self.folder_list_state.get_mut();
+        state.select(state.selected().map(|i| i.min(last)));
+        match self.config.save_searches() {
+            Ok(()) => self.set_table_status(format!("Removed saved search {name}")),
+            Err(e) => self.set_table_status(format!("Error: {e}")),
+        }
+    }
+
+    pub fn command_input(&self) -> &RefCell<TextArea<'a>> {
+        &self.command_input
+    }
+
+    pub fn password_input(&self) -> &RefCell<TextArea<'a>> {
+        &self.password_input
+    }
+
+    /// Go back from search results to the current folder.
+    fn end_search(&mut self) {
+        self.search = None;
+        self.reload_folder();
+    }
+
+    pub fn bounce(&self) -> Option<&Bounce> {
+        self.bounce.as_ref()
+    }
+
+    pub fn search(&self) -> Option<&str> {
+        self.search.as_deref()
+    }
+
+    pub fn message_prompt_input(&self) -> &RefCell<TextArea<'a>> {
+        &self.message_prompt_input
+    }
+
+    pub fn search_input(&self) -> &RefCell<TextArea<'a>> {
+        &self.search_input
+    }
+
+    /// Narrow the table down to messages matching the text typed at the filter prompt.
+    fn set_filter(&mut self) {
+        let filter = self.filter_input.get_mut().lines().join("").to_lowercase();
+        if filter != self.filter {
+            self.filter = filter;
+            self.message_table_state.get_mut().select(Some(0));
+            self.clamp_selection();
+        }
+    }
+
+    fn clear_filter(&mut self) {
+        self.filter_input = RefCell::new(TextArea::default());
+        self.set_filter();
+    }
+
+    /// Whether `message` is from someone or about something containing the filter text, and has
+    /// every tag the filter asks for with `tag:`, e.g. `tag:finance invoice`.
+    fn matches_filter(&self, message: &Message) -> bool {
+        let (tags, words): (Vec<&str>, Vec<&str>) = self
+            .filter
+            .split_whitespace()
+            .partition(|word| word.starts_with("tag:"));
+        let text = words.join(" ");
+        let tagged = tags.iter().all(|tag| {
+            let tag = &tag["tag:".len()..];
+            message.tags().iter().any(|t| t.to_lowercase() == tag)
+        });
+        tagged
+            && (text.is_empty()
+                || message.from().to_lowercase().contains(&text)
+                || message.subject().to_lowercase().contains(&text))
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    pub fn filter_input(&self) -> &RefCell<TextArea<'a>> {
+        &self.filter_input
+    }
+
+    fn move_messages(&mut self, ids: Vec<u64>, folder: String) {
+        if ids.is_empty() || folder == self.current_folder {
+            return;
+        }
+
+        self.messages.move_messages(&ids, &folder);
+        // they no longer belong in the table
+        self.remove_messages(&ids);
+        self.set_table_status(match ids.len() {
+            1 => format!("Moved to {folder}"),
+            count => format!("Moved {count} messages to {folder}"),
+        });
+    }
+
+    /// Delete messages for good, rather than moving them to the trash.
+    fn delete_messages(&mut self, ids: Vec<u64>) {
+        self.messages.delete_messages(&ids);
+        self.remove_messages(&ids);
+        self.set_table_status(match ids.len() {
+            1 => String::from("Deleted"),
+            count => format!("Deleted {count} messages"),
+        });
+    }
+
+    /// Take messages that were moved or deleted out of the table, and out of the marks.
+    fn remove_messages(&mut self, ids: &[u64]) {
+        let before = self.loaded_messages.len();
+        self.loaded_messages.retain(|m| !ids.contains(&m.id()));
+        let removed = before - self.loaded_messages.len();
+        self.message_total = self
+            .message_total
+            .map(|total| total.saturating_sub(removed));
+        self.marked.retain(|id| !ids.contains(id));
+        self.mark_anchor = None;
+        self.clamp_selection();
+    }
+
+    /// The marked messages the table shows, in its order, or else the selected one. Marked
+    /// messages hidden by the filter are left alone.
+    fn chosen_ids(&self) -> Vec<u64> {
+        if self.marked.is_empty() {
+            return self.selected_message_id().into_iter().collect();
+        }
+        self.messages()
+            .iter()
+            .map(|m| m.id())
+            .filter(|id| self.marked.contains(id))
+            .collect()
+    }
+
+    /// Mark every message the table shows, so within the folder or search and the filter, unmark
+    /// every message, or swap which of those shown are marked.
+    fn mark(&mut self, marking: Marking) {
+        let shown: Vec<u64> = self.messages().iter().map(|m| m.id()).collect();
+        self.mark_anchor = None;
+        match marking {
+            Marking::All => self.marked.extend(shown),
+            Marking::None => self.marked.clear(),
+            Marking::Invert => {
+                for id in shown {
+                    if !self.marked.remove(&id) {
+                        self.marked.insert(id);
+                    }
+                }
             }
-            None => {}
         }
+        let count = self
+            .messages()
+            .iter()
+            .filter(|m| self.marked.contains(&m.id()))
+            .count();
+        self.set_table_status(format!("{count} marked"));
     }
 
-    fn next_message(&mut self) {
-        let mut state = self.message_table_state.borrow_mut();
-        let i = match state.selected() {
-            Some(i) => {
-                if i >= self.loaded_messages.len().saturating_sub(1) {
-                    0
-                } else {
-                    i + 1
-                }
+    /// Mark the selected message, or unmark it, and move on to the next.
+    fn toggle_marked(&mut self) {
+        let Some(id) = self.selected_message_id() else {
+            return;
+        };
+        if !self.marked.remove(&id) {
+            self.marked.insert(id);
+        }
+        let last = self.messages().len().saturating_sub(1);
+        let state = self.message_table_state.get_mut();
+        state.select(state.selected().map(|i| (i + 1).min(last)));
+    }
+
+    /// Start marking every message between the selected one and wherever the selection moves
+    /// to, or stop, keeping the marks.
+    fn toggle_mark_range(&mut self) {
+        if self.mark_anchor.take().is_some() {
+            return;
+        }
+        if let Some(id) = self.selected_message_id() {
+            self.mark_anchor = Some((id, self.marked.clone()));
+            self.mark_range();
+        }
+    }
+
+    /// While marking a range, mark the messages from where it started to the selected one, on top
+    /// of the marks made before it.
+    fn mark_range(&mut self) {
+        let Some((anchor, before)) = &self.mark_anchor else {
+            return;
+        };
+        let messages = self.messages();
+        let start = messages.iter().position(|m| m.id() == *anchor);
+        let end = self.message_table_state.borrow().selected();
+        let (Some(start), Some(end)) = (start, end) else {
+            return;
+        };
+        let range = messages[start.min(end)..=start.max(end).min(messages.len() - 1)]
+            .iter()
+            .map(|m| m.id());
+        self.marked = before.iter().copied().chain(range).collect();
+    }
+
+    fn clear_marks(&mut self) {
+        self.marked.clear();
+        self.mark_anchor = None;
+    }
+
+    /// Mark the marked or selected messages as read, or as unread when they all are read already.
+    fn toggle_read(&mut self) {
+        let ids = self.chosen_ids();
+        if ids.is_empty() {
+            return;
+        }
+        let unread = self
+            .loaded_messages
+            .iter()
+            .filter(|m| ids.contains(&m.id()))
+            .all(|m| !m.is_unread());
+        for message in &mut self.loaded_messages {
+            if ids.contains(&message.id()) {
+                message.set_unread(unread);
             }
-            None => 0,
+        }
+        if ids.contains(&self.current_message.id()) {
+            self.current_message.set_unread(unread);
+        }
+        self.messages.set_unread(&ids, unread);
+        let state = if unread { "unread" } else { "read" };
+        self.set_table_status(match ids.len() {
+            1 => format!("Marked as {state}"),
+            count => format!("Marked {count} messages as {state}"),
+        });
+    }
+
+    pub fn marked(&self) -> &HashSet<u64> {
+        &self.marked
+    }
+
+    fn selected_row(&self) -> Option<MessageRow<'_>> {
+        let index = self.message_table_state.borrow().selected()?;
+        self.message_rows().into_iter().nth(index)
+    }
+
+    fn toggle_threaded(&mut self) {
+        self.threaded = !self.threaded;
+        self.message_table_state.borrow_mut().select(Some(0));
+        self.clamp_selection();
+    }
+
+    /// Show or hide the replies in the selected message's thread.
+    fn toggle_thread(&mut self) {
+        let Some(thread) = self.selected_row().map(|row| row.thread) else {
+            return;
         };
-        state.select(Some(i));
 
-        // clear any status messages
-        self.mode = Mode::MessageTable(MessageTableMode::Normal);
-        self.needs_render = true;
+        if !self.expanded_threads.remove(&thread) {
+            self.expanded_threads.insert(thread);
+        }
+        // a collapsed thread only has the one row left to select
+        let row = self
+            .message_rows()
+            .iter()
+            .position(|row| row.message.id() == thread);
+        self.message_table_state.borrow_mut().select(row);
     }
 
-    fn previous_message(&mut self) {
-        let mut state = self.message_table_state.borrow_mut();
-        let i = match state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.loaded_messages.len().saturating_sub(1)
-                } else {
-                    i - 1
-                }
+    fn selected_message_id(&self) -> Option<u64> {
+        let index = self.message_table_state.borrow().selected()?;
+        self.messages().get(index).map(|m| m.id())
+    }
+
+    fn toggle_flagged(&mut self, id: u64) {
+        let Some(message) = self.loaded_messages.iter_mut().find(|m| m.id() == id) else {
+            return;
+        };
+
+        let flagged = !message.is_flagged();
+        message.set_flagged(flagged);
+        if self.current_message.id() == id {
+            self.current_message.set_flagged(flagged);
+        }
+        self.messages.set_flagged(id, flagged);
+        // an unflagged message drops out of the filtered table
+        self.clamp_selection();
+    }
+
+    /// Start or stop watching the thread of the message with the given id. Returns a status
+    /// describing the result.
+    fn toggle_watched(&mut self, id: u64) -> String {
+        let message = self
+            .loaded_messages
+            .iter()
+            .find(|m| m.id() == id)
+            .unwrap_or(&self.current_message);
+        let Some(root) = message.thread_root().map(String::from) else {
+            return String::from("Can't watch a message without a Message-ID");
+        };
+
+        let watched = !self.watched_threads.contains(&root);
+        self.messages.set_thread_watched(&root, watched);
+        if watched {
+            self.watched_threads.push(root);
+            String::from("Watching thread")
+        } else {
+            self.watched_threads.retain(|r| *r != root);
+            String::from("Stopped watching thread")
+        }
+    }
+
+    /// Mute or unmute the thread or sender of the message with the given id. Returns a status
+    /// describing the result.
+    fn toggle_muted(&mut self, id: u64, by: MuteBy) -> String {
+        let message = self
+            .loaded_messages
+            .iter()
+            .find(|m| m.id() == id)
+            .unwrap_or(&self.current_message);
+        let mute = match by {
+            MuteBy::Thread => match message.thread_root() {
+                Some(root) => Mute::Thread(root.to_string()),
+                None => return String::from("Can't mute a message without a Message-ID"),
+            },
+            MuteBy::Sender => Mute::Sender(sender_address(message)),
+        };
+
+        let muted = !self.mutes.contains(&mute);
+        self.messages.set_muted(&mute, muted);
+        let status = match (&mute, muted) {
+            (Mute::Thread(_), true) => String::from("Muted thread"),
+            (Mute::Thread(_), false) => String::from("Unmuted thread"),
+            (Mute::Sender(sender), true) => format!("Muted {sender}"),
+            (Mute::Sender(sender), false) => format!("Unmuted {sender}"),
+        };
+        if muted {
+            self.mutes.push(mute);
+            self.mark_muted_read();
+        } else {
+            self.mutes.retain(|m| *m != mute);
+        }
+        status
+    }
+
+    fn mark_read(&mut self, id: u64) {
+        if let Some(message) = self.loaded_messages.iter_mut().find(|m| m.id() == id) {
+            message.set_unread(false);
+            self.messages.set_unread(&[id], false);
+        }
+    }
+
+    /// Whether the message is in a muted thread or from a muted sender.
+    pub fn is_muted(&self, message: &Message) -> bool {
+        self.mutes.iter().any(|mute| match mute {
+            Mute::Thread(root) => message.is_in_thread(root),
+            Mute::Sender(sender) => sender_address(message) == *sender,
+        })
+    }
+
+    /// Mark unread messages in muted threads or from muted senders as read, so they don't stand
+    /// out as new.
+    fn mark_muted_read(&mut self) {
+        let muted: Vec<u64> = self
+            .loaded_messages
+            .iter()
+            .filter(|m| m.is_unread() && self.is_muted(m))
+            .map(Message::id)
+            .collect();
+        if muted.is_empty() {
+            return;
+        }
+        for message in &mut self.loaded_messages {
+            if muted.contains(&message.id()) {
+                message.set_unread(false);
             }
-            None => 0,
+        }
+        self.messages.set_unread(&muted, false);
+    }
+
+    /// Announce unread messages that arrived since the last load and belong to a watched thread.
+    fn announce_watched_replies(&mut self) {
+        let newest_id = self.loaded_messages.iter().map(Message::id).max();
+        // everything is new on the first load
+        let Some(previous) = self
+            .newest_id
+            .replace(newest_id.max(self.newest_id).unwrap_or(0))
+        else {
+            return;
         };
-        state.select(Some(i));
 
-        // clear any status messages
-        self.mode = Mode::MessageTable(MessageTableMode::Normal)
+        let replies: Vec<&Message> = self
+            .loaded_messages
+            .iter()
+            .filter(|m| m.id() > previous && m.is_unread())
+            .filter(|m| self.watched_threads.iter().any(|root| m.is_in_thread(root)))
+            .collect();
+        let Some(latest) = replies.last() else {
+            return;
+        };
+
+        let alert = match replies.len() {
+            1 => format!("New reply in a watched thread: {}", latest.subject()),
+            n => format!(
+                "{n} new replies in watched threads, latest: {}",
+                latest.subject()
+            ),
+        };
+        if self.config.desktop_notifications {
+            notify::desktop("eta", &alert);
+        }
+        self.alert = Some(alert);
+    }
+
+    pub fn alert(&self) -> Option<&str> {
+        self.alert.as_deref()
+    }
+
+    fn toggle_flagged_only(&mut self) {
+        self.flagged_only = !self.flagged_only;
+        self.message_table_state.borrow_mut().select(Some(0));
+        self.clamp_selection();
+    }
+
+    /// Keep the table selection on a visible message, if there are any.
+    fn clamp_selection(&mut self) {
+        let len = self.messages().len();
+        let mut state = self.message_table_state.borrow_mut();
+        let selected = match (state.selected(), len) {
+            (_, 0) => None,
+            (Some(i), len) => Some(i.min(len - 1)),
+            (None, _) => Some(0),
+        };
+        state.select(selected);
     }
 
     fn compose_message(&mut self) {
+        // a draft left from before keeps whatever signature it has
+        let body = self.compose_message_input.get_mut().lines();
+        if self.compose_signature.is_none() && body.len() == 1 && body[0].is_empty() {
+            let signature = self.compose_signature_text(None);
+            self.set_compose_body(vec![String::new()], 0, 1, signature);
+        }
+        self.mode = Mode::Compose(ComposeFocus::To(ComposeMode::Normal));
+    }
+
+    /// Start a new message with `body` already written, e.g. a log snippet, followed by the
+    /// signature.
+    fn compose_with_body(&mut self, body: &str) {
+        self.reset_compose();
+        let mut lines: Vec<String> = body.trim_end().lines().map(String::from).collect();
+        // leave a line to write on, which also keeps the signature apart from the body
+        lines.push(String::new());
+        let signature = self.compose_signature_text(None);
+        let signature_at = lines.len();
+        self.set_compose_body(lines, 0, signature_at, signature);
         self.mode = Mode::Compose(ComposeFocus::To(ComposeMode::Normal));
     }
 
+    /// Start a new message with the fields of `draft` filled in, e.g. from a mailto: link, on the
+    /// first field left to fill in.
+    pub fn compose_draft(&mut self, draft: Draft) {
+        self.compose_with_body(&draft.body);
+        let focus = if draft.to.is_empty() {
+            ComposeFocus::To(ComposeMode::Normal)
+        } else if draft.subject.is_empty() {
+            ComposeFocus::Subject(ComposeMode::Normal)
+        } else {
+            ComposeFocus::Message(ComposeMode::Normal)
+        };
+        self.compose_to_input = RefCell::new(TextArea::new(vec![draft.to]));
+        self.compose_cc_input = RefCell::new(TextArea::new(vec![draft.cc]));
+        self.compose_subject_input = RefCell::new(TextArea::new(vec![draft.subject]));
+        self.mode = Mode::Compose(focus);
+    }
+
     pub fn mode(&self) -> &Mode {
         &self.mode
     }
 
-    pub fn messages(&self) -> &Vec<Message> {
-        &self.loaded_messages
+    /// The messages shown in the message table.
+    pub fn messages(&self) -> Vec<&Message> {
+        self.message_rows()
+            .into_iter()
+            .map(|row| row.message)
+            .collect()
+    }
+
+    /// The rows of the message table, along with where each message sits in its thread.
+    pub fn message_rows(&self) -> Vec<MessageRow<'_>> {
+        let messages: Vec<&Message> = self
+            .loaded_messages
+            .iter()
+            .filter(|m| !self.flagged_only || m.is_flagged())
+            .filter(|m| self.matches_filter(m))
+            .collect();
+
+        if !self.threaded {
+            return messages
+                .into_iter()
+                .map(|message| MessageRow {
+                    message,
+                    depth: 0,
+                    thread: message.id(),
+                    hidden_replies: 0,
+                    muted: self.is_muted(message),
+                    marked: self.marked.contains(&message.id()),
+                })
+                .collect();
+        }
+
+        let entries = thread::thread(&messages);
+        let mut replies = vec![0; messages.len()];
+        for entry in &entries {
+            if entry.depth > 0 {
+                replies[entry.root] += 1;
+            }
+        }
+
+        entries
+            .into_iter()
+            .filter_map(|ThreadEntry { index, depth, root }| {
+                let thread = messages[root].id();
+                let expanded = self.expanded_threads.contains(&thread);
+                if depth > 0 && !expanded {
+                    return None;
+                }
+
+                Some(MessageRow {
+                    message: messages[index],
+                    depth,
+                    thread,
+                    hidden_replies: if expanded { 0 } else { replies[index] },
+                    muted: self.is_muted(messages[index]),
+                    marked: self.marked.contains(&messages[index].id()),
+                })
+            })
+            .collect()
+    }
+
+    pub fn threaded(&self) -> bool {
+        self.threaded
+    }
+
+    pub fn folders(&self) -> &[String] {
+        &self.folders
+    }
+
+    pub fn saved_searches(&self) -> &[SavedSearch] {
+        &self.config.searches
+    }
+
+    pub fn folder_list_state(&self) -> &RefCell<ListState> {
+        &self.folder_list_state
+    }
+
+    pub fn current_folder(&self) -> &str {
+        &self.current_folder
+    }
+
+    pub fn group_by_date(&self) -> bool {
+        self.config.group_by_date
+    }
+
+    pub fn relative_dates(&self) -> bool {
+        self.config.relative_dates
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.config.theme
+    }
+
+    pub fn columns(&self) -> &ColumnConfig {
+        &self.config.columns
+    }
+
+    pub fn preview_scroll(&self) -> u16 {
+        self.preview_scroll
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn tag_input(&self) -> &RefCell<TextArea<'a>> {
+        &self.tag_input
+    }
+
+    /// The message with the given id, if it is loaded.
+    pub fn loaded_message(&self, id: u64) -> Option<&Message> {
+        if self.current_message.id() == id {
+            return Some(&self.current_message);
+        }
+        self.loaded_messages.iter().find(|m| m.id() == id)
+    }
+
+    /// The tags that can be picked in the tag picker: those containing what has been typed, and
+    /// what has been typed as a new tag if there is none by that name yet. Tags are single words,
+    /// so spaces become dashes.
+    pub fn tag_choices(&self) -> Vec<String> {
+        let typed = self.tag_input.borrow().lines().join(" ");
+        let typed = typed.split_whitespace().collect::<Vec<_>>().join("-");
+        let mut choices: Vec<String> = self
+            .tags
+            .iter()
+            .filter(|tag| tag.to_lowercase().contains(&typed.to_lowercase()))
+            .cloned()
+            .collect();
+        if !typed.is_empty() && !self.tags.contains(&typed) {
+            choices.push(typed);
+        }
+        choices
+    }
+
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    pub fn zen_mode(&self) -> bool {
+        self.zen_mode
+    }
+
+    pub fn wrap_message(&self) -> bool {
+        self.wrap_message
+    }
+
+    pub fn message_scroll(&self) -> &Cell<u16> {
+        &self.message_scroll
+    }
+
+    pub fn message_height(&self) -> &Cell<u16> {
+        &self.message_height
+    }
+
+    pub fn message_lines(&self) -> &Cell<u16> {
+        &self.message_lines
+    }
+
+    pub fn message_side_scroll(&self) -> &Cell<u16> {
+        &self.message_side_scroll
+    }
+
+    pub fn table_height(&self) -> &Cell<u16> {
+        &self.table_height
+    }
+
+    pub fn pending_keys(&self) -> &str {
+        &self.pending_keys
+    }
+
+    pub fn hint_targets(&self) -> &RefCell<Vec<Target>> {
+        &self.hint_targets
+    }
+
+    pub fn flagged_only(&self) -> bool {
+        self.flagged_only
+    }
+
+    pub fn unread_count(&self) -> usize {
+        self.loaded_messages
+            .iter()
+            .filter(|m| m.is_unread())
+            .count()
     }
 
     pub fn message_table_state(&self) -> &RefCell<TableState> {
         &self.message_table_state
     }
 
+    pub fn message_scroll_state(&self) -> &RefCell<ScrollbarState> {
+        &self.message_scroll_state
+    }
+
     pub fn compose_message_text(&self) -> String {
         self.compose_message_input.borrow().lines().join("\n")
     }
@@ -401,10 +4725,56 @@ impl<'a> App<'a> {
         &self.compose_message_input
     }
 
+    pub fn compose_attachments(&self) -> &[MessagePart] {
+        &self.compose_attachments
+    }
+
+    /// What the attachments come to once encoded for sending.
+    pub fn compose_attachments_size(&self) -> u64 {
+        self.compose_attachments
+            .iter()
+            .map(|part| attach::encoded_size(part.content()))
+            .sum()
+    }
+
+    /// Whether the attachments are over `[attachments]`'s limit.
+    pub fn attachments_too_large(&self) -> bool {
+        self.compose_attachments_size() > self.config.attachments.limit_bytes()
+    }
+
+    pub fn compose_protection(&self) -> Protection {
+        self.compose_protection
+    }
+
+    /// The address the message being composed is sent from.
+    pub fn compose_from(&self) -> String {
+        let identities = self.config.smtp.identities();
+        match self
+            .compose_identity
+            .and_then(|index| identities.get(index))
+        {
+            Some(identity) => identity.from(),
+            None => self.config.smtp.username().to_string(),
+        }
+    }
+
+    /// Whether messages may be sent from more than the account's own address.
+    pub fn has_identities(&self) -> bool {
+        !self.config.smtp.identities().is_empty()
+    }
+
+    pub fn compose_prompt_input(&self) -> &RefCell<TextArea<'a>> {
+        &self.compose_prompt_input
+    }
+
     pub fn compose_to_input(&self) -> &RefCell<TextArea<'a>> {
         &self.compose_to_input
     }
 
+    pub fn compose_cc_input(&self) -> &RefCell<TextArea<'a>> {
+        &self.compose_cc_input
+    }
+
     pub fn compose_subject_input(&self) -> &RefCell<TextArea<'a>> {
         &self.compose_subject_input
     }
@@ -413,60 +4783,349 @@ impl<'a> App<'a> {
         &self.message_textarea
     }
 
+    pub fn clock(&self) -> &str {
+        &self.clock
+    }
+
+    pub fn connection(&self) -> &ConnectionState {
+        &self.connection
+    }
+
+    pub fn last_sync(&self) -> Option<chrono::DateTime<chrono::Local>> {
+        self.last_sync
+    }
+
+    /// The most recently started activity still going on, if any.
+    pub fn activity(&self) -> Option<&Activity> {
+        self.activities.last()
+    }
+
+    pub fn spinner_frame(&self) -> usize {
+        self.spinner_frame
+    }
+
+    pub fn message_status(&self) -> Option<&str> {
+        self.message_status.as_deref()
+    }
+
     pub fn current_message(&self) -> &Message {
         &self.current_message
     }
 
+    pub fn merge_csv_input(&self) -> &RefCell<TextArea<'a>> {
+        &self.merge_csv_input
+    }
+
+    pub fn merge_messages(&self) -> &Vec<Message> {
+        &self.merge_messages
+    }
+
+    fn set_folders(&mut self, folders: Vec<String>) {
+        self.folders = folders;
+        let current = self.folders.iter().position(|f| *f == self.current_folder);
+        self.folder_list_state.get_mut().select(current.or(Some(0)));
+    }
+
     fn set_loaded_messages(&mut self, messages: Vec<Message>) {
-        self.loaded_messages = Box::new(messages);
+        self.loaded_messages = messages;
+        self.mark_muted_read();
         // also set the first row of the message table as selected if there is
         // not yet anything selected.
         let mut table_state = self.message_table_state.borrow_mut();
-        if self.loaded_messages.len() > 0 && table_state.selected() == None {
+        if !self.loaded_messages.is_empty() && table_state.selected().is_none() {
             table_state.select(Some(0));
         }
     }
 
-    fn set_current_message(&mut self, id: u64, body: String) {
+    fn set_current_message(
+        &mut self,
+        id: u64,
+        parts: Vec<MessagePart>,
+        verification: Option<Verification>,
+    ) {
+        // a preview fetched just before another message was opened is too late to show
+        if !matches!(self.mode, Mode::MessageTable(_)) && self.selected_message_id() != Some(id) {
+            return;
+        }
         if id != self.current_message.id() {
             for message in self.loaded_messages.iter() {
                 if id == message.id() {
-                    self.current_message = Box::new(message.clone());
-                    self.current_message.set_body(body);
+                    *self.current_message = message.clone();
                     break;
                 }
             }
         }
+        self.current_message.set_parts(parts);
+        self.current_message.set_verification(verification);
+        // bounces are explained up front rather than leaving the reader to dig through the report
+        self.bounce = self.current_message.bounce();
+        self.invite = self
+            .current_message
+            .part("text/calendar")
+            .and_then(|part| calendar::parse(&part.text()));
+        self.message_textarea = RefCell::new(TextArea::default());
+        self.message_scroll.set(0);
+        self.message_side_scroll.set(0);
+        self.needs_render = true;
 
-        match self.message_table_state.borrow().selected() {
-            Some(table_id) => {
-                self.mode = Mode::Message(table_id);
-                self.needs_render = true;
-            }
-            None => {}
+        // a body fetched for the preview pane leaves the table open
+        if !matches!(self.mode, Mode::MessageTable(_))
+            && let Some(table_id) = self.message_table_state.borrow().selected()
+        {
+            self.mode = Mode::Message(table_id);
         }
 
+        let remote_content = if self.remote_content_blocked() {
+            "Remote content blocked: L to always load it from this sender\n"
+        } else {
+            ""
+        };
         let message = &self.current_message;
+        let body = message.text_body();
+        let invite = match &self.invite {
+            Some(invite) => format!("{}\n\n", invite.text()),
+            None => String::new(),
+        };
+        let attachments = message
+            .attachments()
+            .map(|part| {
+                format!(
+                    "Attachment: {} ({}, {} bytes)\n",
+                    part.filename().unwrap_or_default(),
+                    part.content_type(),
+                    part.content().len()
+                )
+            })
+            .collect::<String>();
         self.message_textarea.get_mut().insert_str(format!(
-            "From: {}\nTo: {}\nSubject: {}\n\n{}",
-            message.from(),
-            message.to(),
-            message.subject(),
-            message.body()
+            "{}{}{}\n{}{}",
+            message_headers(message),
+            attachments,
+            remote_content,
+            invite,
+            body
         ));
+        // start at the headers, however long the message
+        self.message_textarea.get_mut().move_cursor(CursorMove::Top);
+
+        if let Some(reply_to) = self.reply_pending.take() {
+            self.reply(reply_to);
+        }
+    }
+
+    /// Take the activity `event` is the result of off the status bar. Which one an error comes
+    /// from can't be told, so it ends them all.
+    fn finish_activity(&mut self, event: &AppEvent) {
+        if let AppEvent::Error(_) = event {
+            self.activities.clear();
+        } else if let Some(index) = self
+            .activities
+            .iter()
+            .position(|activity| activity.is_done_by(event))
+        {
+            self.activities.remove(index);
+        }
     }
 
     fn set_message_sent_status(&mut self, status: Option<String>) {
+        // replies to invites are sent from the message view
+        if let Mode::Message(_) = self.mode {
+            self.set_message_status(match status {
+                Some(e) => format!("Error: {e}"),
+                None => String::from("Message sent"),
+            });
+            return;
+        }
         let sent_status = match status {
             Some(str) => MessageSentStatus::Failed(str),
             None => MessageSentStatus::Success,
         };
+        self.set_message_table_status(sent_status);
+    }
+
+    /// Show a status in the message table until it expires.
+    fn set_table_status(&mut self, status: String) {
+        self.notification_expires = Some(Instant::now() + NOTIFICATION_TIMEOUT);
+        if let Mode::MessageTable(_) = self.mode {
+            self.mode = Mode::MessageTable(MessageTableMode::Status(status));
+        }
+    }
+
+    fn set_message_table_status(&mut self, sent_status: MessageSentStatus) {
+        self.notification_expires = Some(Instant::now() + NOTIFICATION_TIMEOUT);
         let table_mode = MessageTableMode::MessageSent(sent_status);
         let app_mode = Mode::MessageTable(table_mode);
 
-        match self.mode {
-            Mode::MessageTable(_) => self.mode = app_mode,
-            _ => {}
+        if let Mode::MessageTable(_) = self.mode {
+            self.mode = app_mode;
         }
     }
 }
+
+/// How to send messages with `config`, writing them to `dry_run_send` instead if given.
+fn smtp_config(config: &Config, dry_run_send: Option<&PathBuf>) -> SmtpConfig {
+    let mut smtp = config.smtp.clone();
+    if let Some(dir) = dry_run_send {
+        smtp.set_dry_run_send(dir.clone());
+    }
+    smtp
+}
+
+/// The rules in `config`, logged to `rules.log` next to the profile's messages.
+fn rules(config: &Config, profile: &Profile) -> Rules {
+    Rules {
+        rules: config.rules.clone(),
+        dry_run: config.dry_run_rules,
+        log: profile.database_path().with_file_name("rules.log"),
+    }
+}
+
+fn clock_text() -> String {
+    chrono::Local::now().format("%H:%M").to_string()
+}
+
+/// The From, To, Cc, Date and Subject headers of `message`, as they are shown above its body,
+/// and the tags rules gave it.
+fn message_headers(message: &Message) -> String {
+    let cc = match message.cc() {
+        "" => String::new(),
+        cc => format!("Cc: {cc}\n"),
+    };
+    let date = message
+        .date()
+        .map(|date| format!("Date: {}\n", date.format(DATE_FORMAT)))
+        .unwrap_or_default();
+    let tags = match message.tags() {
+        [] => String::new(),
+        tags => format!("Tags: {}\n", tags.join(", ")),
+    };
+    format!(
+        "From: {}\nTo: {}\n{cc}{date}Subject: {}\n{tags}",
+        message.from(),
+        message.to(),
+        message.subject()
+    )
+}
+
+/// A file name for a message saved with `extension`, made from its subject.
+fn message_filename(subject: &str, extension: &str) -> String {
+    let name: String = subject
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    match name.trim() {
+        "" => format!("message.{extension}"),
+        name => format!("{name}.{extension}"),
+    }
+}
+
+/// Whether `part` is an attachment that zipping would make smaller: not an image shown inline,
+/// which has to stay as it is, and not an archive already.
+fn is_zippable(part: &MessagePart) -> bool {
+    part.content_id().is_none() && part.content_type() != "application/zip"
+}
+
+/// The composer, with the given field selected.
+fn compose_mode(field: ComposeField) -> Mode {
+    match field {
+        ComposeField::To => Mode::Compose(ComposeFocus::To(ComposeMode::Normal)),
+        ComposeField::Cc => Mode::Compose(ComposeFocus::Cc(ComposeMode::Normal)),
+    }
+}
+
+/// To and Cc for replying to everyone on `original`, leaving out anyone listed twice and the
+/// `own` addresses, which are bare and in lowercase.
+fn reply_all_recipients(original: &Message, own: &[String]) -> (String, String) {
+    let mut seen: Vec<String> = own.to_vec();
+    let mut recipients = |list: &str| -> Vec<String> {
+        split_addresses(list)
+            .into_iter()
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .filter(|entry| {
+                let address = bare_address(entry);
+                if seen.contains(&address) {
+                    return false;
+                }
+                seen.push(address);
+                true
+            })
+            // an entry that doesn't parse is left as it is, for the user to fix
+            .map(|entry| match entry.parse::<Mailbox>() {
+                Ok(mailbox) => mailbox.to_string(),
+                Err(_) => entry.to_string(),
+            })
+            .collect()
+    };
+
+    let mut to = recipients(original.from());
+    to.extend(recipients(original.to()));
+    let cc = recipients(original.cc());
+    (to.join(", "), cc.join(", "))
+}
+
+/// An empty input for a password, masked, and without the undo history that would keep copies
+/// of what was typed.
+fn password_textarea<'a>() -> TextArea<'a> {
+    let mut input = TextArea::default();
+    input.set_mask_char('\u{2022}');
+    input.set_max_histories(0);
+    input
+}
+
+/// The address part of a message's From field, in lowercase.
+fn sender_address(message: &Message) -> String {
+    parse_addresses(message.from())
+        .ok()
+        .and_then(|mailboxes| mailboxes.into_iter().next())
+        .map(|mailbox| mailbox.email.to_string())
+        .unwrap_or_else(|| message.from().to_string())
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reply_all_leaves_out_own_addresses_and_repeats() {
+        let mut original = Message::new(
+            1,
+            String::from("Jane <jane@example.com>"),
+            String::from(
+                "Bob <BOB@example.com>, john@example.org, \"Doe, Jane\" <jane@example.com>",
+            ),
+            String::from("Plans"),
+            String::new(),
+        );
+        original.set_cc(String::from("bob+lists@example.com, ann@example.net"));
+        let own = [
+            String::from("bob@example.com"),
+            String::from("bob+lists@example.com"),
+        ];
+
+        let (to, cc) = reply_all_recipients(&original, &own);
+
+        assert_eq!(to, "Jane <jane@example.com>, john@example.org");
+        assert_eq!(cc, "ann@example.net");
+    }
+
+    #[test]
+    fn reply_all_leaves_out_own_address_among_invalid_entries() {
+        let original = Message::new(
+            1,
+            String::from("jane@example.com"),
+            String::from("Bob <bob@example.com>, not an address"),
+            String::from("Plans"),
+            String::new(),
+        );
+
+        let (to, _) = reply_all_recipients(&original, &[String::from("bob@example.com")]);
+
+        assert_eq!(to, "jane@example.com, not an address");
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use lettre::message::header::ContentType;
+use tokio::{io::AsyncWriteExt, process::Command, sync::mpsc};
+
+use crate::event::{AppEvent, Event};
+
+/// How long a command run to attach its output may take before it is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Run `command` with the shell, and send its standard output back as an
+/// [`AppEvent::CommandOutput`] to be attached to the message being composed.
+pub fn run(command: &str, event_sender: mpsc::UnboundedSender<Event>) {
+    let command = command.to_string();
+
+    tokio::spawn(async move {
+        // dropping the output's future once the time is up kills the command
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .output();
+        let output = tokio::time::timeout(COMMAND_TIMEOUT, output).await;
+
+        let result = match output {
+            Err(_) => Err(format!(
+                "`{command}` took longer than {} seconds and was stopped",
+                COMMAND_TIMEOUT.as_secs()
+            )),
+            Ok(Ok(output)) if output.status.success() => Ok(output.stdout),
+            Ok(Ok(output)) => {
+                // the last line of the error output usually says what went wrong
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(match stderr.lines().rfind(|line| !line.trim().is_empty()) {
+                    Some(line) => line.trim().to_string(),
+                    None => format!("`{command}` failed with {}", output.status),
+                })
+            }
+            Ok(Err(e)) => Err(e.to_string()),
+        };
+
+        let _ = event_sender.send(Event::App(AppEvent::CommandOutput(command, result)));
+    });
+}
+
+/// How large `content` is once base64 encoded for sending, which is what servers' size limits
+/// count.
+pub fn encoded_size(content: &[u8]) -> u64 {
+    content.len().div_ceil(3) as u64 * 4
+}
+
+/// A size in megabytes, e.g. "31.2 MB".
+pub fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_048_576.0)
+}
+
+/// Put `files`, named by their filenames, into a zip archive with the `zip` program, and send the
+/// archive back as an [`AppEvent::Zipped`].
+pub fn zip(files: Vec<(String, Vec<u8>)>, event_sender: mpsc::UnboundedSender<Event>) {
+    tokio::spawn(async move {
+        // a new directory with a random name that only the user can enter, removed once dropped
+        let result = match tempfile::Builder::new().prefix("eta-zip-").tempdir() {
+            Ok(dir) => zip_in(dir.path(), files).await,
+            Err(e) => Err(e.to_string()),
+        };
+        let _ = event_sender.send(Event::App(AppEvent::Zipped(result)));
+    });
+}
+
+/// Write `files` to `dir` and zip them from there, so the archive holds only their names.
+async fn zip_in(dir: &Path, files: Vec<(String, Vec<u8>)>) -> Result<Vec<u8>, String> {
+    let mut names: Vec<String> = vec![];
+    for (filename, content) in files {
+        let name = unique_name(&filename, &names);
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dir.join(&name))
+            .await
+            .map_err(|e| e.to_string())?;
+        file.write_all(&content).await.map_err(|e| e.to_string())?;
+        names.push(name);
+    }
+
+    let output = Command::new("zip")
+        .args(["-q", "-", "--"])
+        .args(&names)
+        .current_dir(dir)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .map_err(|e| format!("couldn't run zip: {e}"))?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(match stderr.lines().rfind(|line| !line.trim().is_empty()) {
+            Some(line) => line.trim().to_string(),
+            None => format!("zip failed with {}", output.status),
+        })
+    }
+}
+
+/// The last part of `filename`'s path, numbered if one of the `names` already has it.
+fn unique_name(filename: &str, names: &[String]) -> String {
+    let name = match filename.rsplit('/').next().unwrap_or_default() {
+        "" | "." | ".." => "attachment",
+        name => name,
+    };
+    let mut unique = name.to_string();
+    let mut n = 1;
+    while names.contains(&unique) {
+        n += 1;
+        unique = format!("{n}-{name}");
+    }
+    unique
+}
+
+/// A filename for the output of `command`, made from the program and its first argument, e.g.
+/// `git-diff.patch` for `git diff --stat`.
+pub fn default_filename(command: &str) -> String {
+    let words: Vec<&str> = command
+        .split_whitespace()
+        .take_while(|word| {
+            word.chars()
+                .all(|c| c.is_alphanumeric() || "-_/.".contains(c))
+        })
+        .filter(|word| !word.starts_with('-'))
+        .take(2)
+        .map(|word| word.rsplit('/').next().unwrap_or(word))
+        .collect();
+    let stem = match words.join("-") {
+        stem if stem.is_empty() => String::from("output"),
+        stem => stem,
+    };
+    let extension = if command.contains("diff") || command.contains("format-patch") {
+        "patch"
+    } else {
+        "txt"
+    };
+    format!("{stem}.{extension}")
+}
+
+/// The MIME type to attach a file called `filename` as, going by its extension.
+pub fn content_type(filename: &str) -> &'static str {
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "txt" | "log" | "md" | "toml" | "yaml" | "yml" => "text/plain",
+        "patch" | "diff" => "text/x-diff",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// `path` with a leading `~/` standing for the home directory, as the shell would read it.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Whether `content_type` is a MIME type that can be put in a `Content-Type` header.
+pub fn is_content_type(content_type: &str) -> bool {
+    content_type.contains('/') && ContentType::parse(content_type).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_names_are_numbered() {
+        let mut names = vec![];
+        for filename in ["a.txt", "dir/a.txt", "2-a.txt", "a.txt", "..", ""] {
+            names.push(unique_name(filename, &names));
+        }
+
+        assert_eq!(
+            names,
+            [
+                "a.txt",
+                "2-a.txt",
+                "2-2-a.txt",
+                "3-a.txt",
+                "attachment",
+                "2-attachment"
+            ]
+        );
+    }
+
+    #[test]
+    fn default_filenames_come_from_the_command() {
+        assert_eq!(default_filename("git diff --stat"), "git-diff.patch");
+        assert_eq!(default_filename("/usr/bin/uname -a"), "uname.txt");
+        assert_eq!(default_filename("ls -l | sort"), "ls.txt");
+        assert_eq!(
+            default_filename("git format-patch -1"),
+            "git-format-patch.patch"
+        );
+        assert_eq!(default_filename("$(date)"), "output.txt");
+    }
+
+    #[test]
+    fn content_types_follow_the_extension() {
+        assert_eq!(content_type("git-diff.patch"), "text/x-diff");
+        assert_eq!(content_type("Photo.JPG"), "image/jpeg");
+        assert_eq!(content_type("notes"), "application/octet-stream");
+        assert_eq!(content_type("archive.tar.gz"), "application/gzip");
+        assert!(is_content_type("text/plain"));
+        assert!(!is_content_type("text"));
+    }
+
+    #[test]
+    fn encoded_size_counts_base64() {
+        assert_eq!(encoded_size(b""), 0);
+        assert_eq!(encoded_size(b"a"), 4);
+        assert_eq!(encoded_size(&[0; 300]), 400);
+        assert_eq!(megabytes(1_572_864), "1.5 MB");
+    }
+}
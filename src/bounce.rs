@@ -0,0 +1,403 @@
+use std::fmt;
+
+use lettre::transport::smtp;
+
+/// The broad reason a message could not be delivered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The receiving server deferred the message until it is retried, to deter spammers.
+    Greylisted,
+    /// The recipient's address does not exist.
+    MailboxNotFound,
+    /// The recipient's mailbox is over its quota.
+    MailboxFull,
+    /// The receiving server refused the message, e.g. as spam or for failing SPF/DKIM/DMARC.
+    PolicyRejection,
+    /// Any other temporary (4xx) failure.
+    Temporary,
+    /// Any other permanent (5xx) failure.
+    Permanent,
+    /// A failure without an SMTP status, e.g. the server could not be reached.
+    Unknown,
+}
+
+impl FailureKind {
+    /// What happened, and what the user could do about it.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            FailureKind::Greylisted => {
+                "greylisted by the recipient's server, send again in a few minutes"
+            }
+            FailureKind::MailboxNotFound => {
+                "the recipient's mailbox does not exist, check the address for typos"
+            }
+            FailureKind::MailboxFull => "the recipient's mailbox is full, try again later",
+            FailureKind::PolicyRejection => {
+                "rejected by the recipient's server policy, contact their postmaster"
+            }
+            FailureKind::Temporary => "temporary failure, try again later",
+            FailureKind::Permanent => "permanently rejected by the recipient's server",
+            FailureKind::Unknown => "delivery failed",
+        }
+    }
+}
+
+/// A classified delivery failure, from either an SMTP response or a delivery status notification.
+#[derive(Clone, Debug)]
+pub struct DeliveryFailure {
+    kind: FailureKind,
+    /// Basic SMTP reply code, e.g. `550`.
+    code: Option<String>,
+    /// Enhanced status code (RFC 3463), e.g. `5.1.1`.
+    status: Option<String>,
+    /// The raw error text.
+    detail: String,
+    /// Whether sending again later might succeed.
+    transient: bool,
+    /// Who the message couldn't be delivered to, when a bounce says.
+    recipient: Option<String>,
+}
+
+/// A delivery status notification, as it is summed up above its body.
+#[derive(Clone, Debug)]
+pub struct Bounce {
+    /// A failure for each recipient the message couldn't be delivered to.
+    pub failures: Vec<DeliveryFailure>,
+    /// The `Message-ID` of the message that bounced, from the copy or headers sent back with it.
+    pub original: Option<String>,
+}
+
+impl Bounce {
+    /// A line for each recipient, e.g. "Delivery failed to jane@example.com: the recipient's
+    /// mailbox is full, try again later (4.2.2)".
+    pub fn lines(&self) -> Vec<String> {
+        self.failures
+            .iter()
+            .map(|failure| {
+                let what = if failure.transient {
+                    "Delivery delayed"
+                } else {
+                    "Delivery failed"
+                };
+                match &failure.recipient {
+                    Some(recipient) => format!("{what} to {recipient}: {failure}"),
+                    None => format!("{what}: {failure}"),
+                }
+            })
+            .collect()
+    }
+}
+
+impl DeliveryFailure {
+    /// Classify an error returned while sending a message over SMTP.
+    pub fn from_smtp_error(error: &smtp::Error) -> Self {
+        let detail = error.to_string();
+        let code = error.status().map(|code| code.to_string());
+        let status = enhanced_status(&detail).map(String::from);
+        let kind = classify(code.as_deref(), status.as_deref(), &detail);
+        // 4xx replies, and failing to reach the server or losing the connection to it, which
+        // can also cut a reply short
+        let transient =
+            error.is_transient() || !(error.is_permanent() || error.is_client() || error.is_tls());
+
+        Self {
+            kind,
+            code,
+            status,
+            detail,
+            transient,
+            recipient: None,
+        }
+    }
+
+    /// Classify each failed recipient of a delivery status notification (bounce), if `report` is
+    /// one.
+    ///
+    /// This reads the per-recipient fields of a `message/delivery-status` part (RFC 3464), which
+    /// are kept apart by blank lines: the `Status`, `Diagnostic-Code` and `Final-Recipient`.
+    /// Recipients the message was delivered or relayed to are left out.
+    pub fn from_dsn(report: &str) -> Vec<Self> {
+        blocks(report)
+            .iter()
+            .filter_map(|fields| Self::from_recipient_fields(fields))
+            .collect()
+    }
+
+    fn from_recipient_fields(fields: &[String]) -> Option<Self> {
+        let field = |name: &str| {
+            fields.iter().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.trim()
+                    .eq_ignore_ascii_case(name)
+                    .then(|| value.trim().to_string())
+            })
+        };
+        // typed fields, such as `rfc822; jane@example.com`, without their type
+        let untyped = |value: String| match value.split_once(';') {
+            Some((_, text)) => text.trim().to_string(),
+            None => value,
+        };
+
+        let status = field("Status")
+            .as_deref()
+            .and_then(enhanced_status)
+            .filter(|status| !status.starts_with('2'))
+            .map(String::from)?;
+        let detail = field("Diagnostic-Code").map(untyped).unwrap_or_default();
+        let code = detail
+            .split_whitespace()
+            .next()
+            .filter(|word| word.len() == 3 && word.chars().all(|c| c.is_ascii_digit()))
+            .map(String::from);
+        let kind = classify(code.as_deref(), Some(&status), &detail);
+        let transient = status.starts_with('4');
+        let recipient = field("Final-Recipient")
+            .or_else(|| field("Original-Recipient"))
+            .map(untyped);
+
+        Some(Self {
+            kind,
+            code,
+            status: Some(status),
+            detail,
+            transient,
+            recipient,
+        })
+    }
+
+    /// Whether the failure may pass, so that sending again is worth a try.
+    pub fn is_transient(&self) -> bool {
+        self.transient
+    }
+}
+
+impl fmt::Display for DeliveryFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.kind == FailureKind::Unknown {
+            return write!(f, "{}: {}", self.kind.explanation(), self.detail);
+        }
+
+        let codes = [self.code.as_deref(), self.status.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        write!(f, "{} ({codes})", self.kind.explanation())
+    }
+}
+
+fn classify(code: Option<&str>, status: Option<&str>, text: &str) -> FailureKind {
+    let text = text.to_lowercase();
+    let mentions = |words: &[&str]| words.iter().any(|word| text.contains(word));
+    // the enhanced status is more specific, so prefer it to the basic reply code
+    let class = status.or(code).and_then(|c| c.chars().next());
+
+    match (class, status) {
+        (Some('4'), _) if mentions(&["greylist", "graylist", "grey-list", "gray-list"]) => {
+            FailureKind::Greylisted
+        }
+        (Some('4'), Some("4.7.1" | "4.2.0")) if mentions(&["try again", "retry", "later"]) => {
+            FailureKind::Greylisted
+        }
+        (_, Some("5.1.1" | "5.1.10" | "5.1.2" | "5.1.3")) => FailureKind::MailboxNotFound,
+        (_, Some("4.2.2" | "5.2.2")) => FailureKind::MailboxFull,
+        (_, Some(status)) if status.starts_with("5.7.") => FailureKind::PolicyRejection,
+        (Some('5'), _) if mentions(&["user unknown", "no such user", "does not exist"]) => {
+            FailureKind::MailboxNotFound
+        }
+        (Some('4' | '5'), _) if mentions(&["quota", "mailbox full", "mailbox is full"]) => {
+            FailureKind::MailboxFull
+        }
+        (Some('5'), _) if mentions(&["spam", "policy", "blocked", "spf", "dmarc", "dkim"]) => {
+            FailureKind::PolicyRejection
+        }
+        (Some('4'), _) => FailureKind::Temporary,
+        (Some('5'), _) => FailureKind::Permanent,
+        _ => FailureKind::Unknown,
+    }
+}
+
+/// The blocks of fields in `report`, kept apart by blank lines, with folded lines joined back
+/// onto the ones they continue.
+fn blocks(report: &str) -> Vec<Vec<String>> {
+    let mut blocks: Vec<Vec<String>> = vec![vec![]];
+    for line in report.lines() {
+        if line.trim().is_empty() {
+            blocks.push(vec![]);
+            continue;
+        }
+        let block = blocks.last_mut().expect("there is always a block");
+        match block.last_mut() {
+            Some(last) if line.starts_with([' ', '\t']) => {
+                last.push(' ');
+                last.push_str(line.trim());
+            }
+            _ => block.push(line.trim_end().to_string()),
+        }
+    }
+    blocks
+}
+
+/// Find the first enhanced status code (e.g. `4.7.1`) in `text`.
+fn enhanced_status(text: &str) -> Option<&str> {
+    text.split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == ':' || c == ',')
+        .find(|word| {
+            let parts: Vec<&str> = word.split('.').collect();
+            parts.len() == 3
+                && matches!(parts[0], "2" | "4" | "5")
+                && parts[1..]
+                    .iter()
+                    .all(|p| (1..=3).contains(&p.len()) && p.chars().all(|c| c.is_ascii_digit()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use lettre::{
+        Address,
+        address::Envelope,
+        transport::smtp::{client::AsyncSmtpConnection, extension::ClientId},
+    };
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// The error from sending a message to a server that greets with the first of `replies` and
+    /// answers each command with the next, closing the connection once it runs out.
+    fn smtp_error(replies: &'static [&'static str]) -> smtp::Error {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(async {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let address = listener.local_addr().unwrap();
+                tokio::spawn(async move {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    for reply in replies {
+                        writer
+                            .write_all(format!("{reply}\r\n").as_bytes())
+                            .await
+                            .unwrap();
+                        if lines.next_line().await.unwrap().is_none() {
+                            break;
+                        }
+                    }
+                });
+
+                let mut connection = AsyncSmtpConnection::connect_tokio1(
+                    address,
+                    None,
+                    &ClientId::default(),
+                    None,
+                    None,
+                )
+                .await?;
+                let sender: Address = "jane@example.com".parse().unwrap();
+                let recipient: Address = "john@example.org".parse().unwrap();
+                let envelope = Envelope::new(Some(sender), vec![recipient]).unwrap();
+                connection
+                    .send(&envelope, b"Subject: Hi\r\n\r\nHello\r\n")
+                    .await
+                    .map(|_| ())
+            })
+            .unwrap_err()
+    }
+
+    #[test]
+    fn smtp_replies_are_classified() {
+        let greylisted = DeliveryFailure::from_smtp_error(&smtp_error(&[
+            "220 mx.example.org",
+            "250 mx.example.org",
+            "250 2.1.0 Ok",
+            "450 4.2.0 Greylisted, please try again later",
+        ]));
+        assert_eq!(greylisted.kind, FailureKind::Greylisted);
+        assert!(greylisted.is_transient());
+
+        let unknown = DeliveryFailure::from_smtp_error(&smtp_error(&[
+            "220 mx.example.org",
+            "250 mx.example.org",
+            "250 2.1.0 Ok",
+            "550 5.1.1 <john@example.org>: User unknown",
+        ]));
+        assert_eq!(unknown.kind, FailureKind::MailboxNotFound);
+        assert_eq!(unknown.code.as_deref(), Some("550"));
+        assert!(!unknown.is_transient());
+
+        let busy =
+            DeliveryFailure::from_smtp_error(&smtp_error(&["421 4.3.2 Service shutting down"]));
+        assert_eq!(busy.kind, FailureKind::Temporary);
+        assert!(busy.is_transient());
+    }
+
+    #[test]
+    fn lost_connections_are_transient() {
+        let dropped = DeliveryFailure::from_smtp_error(&smtp_error(&[
+            "220 mx.example.org",
+            "250 mx.example.org",
+        ]));
+        assert!(dropped.is_transient());
+    }
+
+    const REPORT: &str = "Reporting-MTA: dns; mx.example.com\r
+Arrival-Date: Mon, 5 Oct 2026 10:00:00 +0000\r
+\r
+Final-Recipient: rfc822; jane@example.com\r
+Action: delivered\r
+Status: 2.0.0\r
+\r
+Final-Recipient: rfc822; nobody@example.org\r
+Action: failed\r
+Status: 5.1.1\r
+Diagnostic-Code: smtp; 550 5.1.1 <nobody@example.org>: Recipient\r
+ address rejected: User unknown\r
+\r
+Original-Recipient: rfc822; full@example.net\r
+Action: delayed\r
+Status: 4.2.2\r
+Diagnostic-Code: smtp; 452 4.2.2 Mailbox full\r
+";
+
+    #[test]
+    fn dsn_reports_each_failed_recipient() {
+        let failures = DeliveryFailure::from_dsn(REPORT);
+
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].recipient.as_deref(), Some("nobody@example.org"));
+        assert_eq!(failures[0].kind, FailureKind::MailboxNotFound);
+        assert_eq!(failures[0].code.as_deref(), Some("550"));
+        assert!(!failures[0].is_transient());
+        assert_eq!(failures[1].recipient.as_deref(), Some("full@example.net"));
+        assert_eq!(failures[1].kind, FailureKind::MailboxFull);
+        assert!(failures[1].is_transient());
+    }
+
+    #[test]
+    fn dsn_joins_folded_fields() {
+        let failures = DeliveryFailure::from_dsn(REPORT);
+
+        assert_eq!(
+            failures[0].detail,
+            "550 5.1.1 <nobody@example.org>: Recipient address rejected: User unknown"
+        );
+    }
+
+    #[test]
+    fn dsn_of_delivered_messages_has_no_failures() {
+        let report = "Final-Recipient: rfc822; jane@example.com\nAction: relayed\nStatus: 2.1.5\n";
+
+        assert!(DeliveryFailure::from_dsn(report).is_empty());
+        assert!(DeliveryFailure::from_dsn("not a report at all").is_empty());
+    }
+
+    #[test]
+    fn enhanced_status_is_found_in_text() {
+        assert_eq!(enhanced_status("550 5.7.1 (denied)"), Some("5.7.1"));
+        assert_eq!(enhanced_status("version 1.2.3"), None);
+        assert_eq!(enhanced_status("421 4.7.0, try later"), Some("4.7.0"));
+    }
+}
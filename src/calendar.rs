@@ -0,0 +1,442 @@
+use std::fmt;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::vcard::{unescape, unfold};
+
+/// How the days and times of events are shown, e.g. "Thu 15 Jan 2026" and "15:00".
+const DAY_FORMAT: &str = "%a %e %b %Y";
+const HOUR_FORMAT: &str = "%H:%M";
+
+/// The event of an iCalendar invite, such as a `text/calendar` part.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Invite {
+    /// What the invite asks for, e.g. `REQUEST` for an invitation or `CANCEL`.
+    method: Option<String>,
+    /// Identifies the event, and with the sequence number which version of it, in replies.
+    uid: Option<String>,
+    sequence: Option<String>,
+    /// Which occurrence of a repeating event the invite is for, as the whole property.
+    recurrence_id: Option<String>,
+    summary: Option<String>,
+    start: Option<Time>,
+    end: Option<Time>,
+    location: Option<String>,
+    organizer: Option<String>,
+    /// The organizer's address, which replies are sent to.
+    organizer_address: Option<String>,
+    /// Each attendee, with their answer if they have given one.
+    attendees: Vec<String>,
+    /// Each attendee's address, in lowercase.
+    attendee_addresses: Vec<String>,
+}
+
+/// An answer to an invitation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Answer {
+    Accept,
+    Tentative,
+    Decline,
+}
+
+impl Answer {
+    /// The answer as a reply's subject starts with it, e.g. "Accepted: Quarterly review".
+    pub fn label(self) -> &'static str {
+        match self {
+            Answer::Accept => "Accepted",
+            Answer::Tentative => "Tentative",
+            Answer::Decline => "Declined",
+        }
+    }
+
+    /// The answer as a sentence gives it, e.g. "tentatively accepted".
+    pub fn description(self) -> &'static str {
+        match self {
+            Answer::Accept => "accepted",
+            Answer::Tentative => "tentatively accepted",
+            Answer::Decline => "declined",
+        }
+    }
+
+    fn participation_status(self) -> &'static str {
+        match self {
+            Answer::Accept => "ACCEPTED",
+            Answer::Tentative => "TENTATIVE",
+            Answer::Decline => "DECLINED",
+        }
+    }
+}
+
+/// When an event starts or ends.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Time {
+    /// A day, for events lasting all day.
+    Day(NaiveDate),
+    /// A moment, shown in local time.
+    Utc(DateTime<Utc>),
+    /// A time in the named zone, or floating in whatever zone the reader is in.
+    Zoned(NaiveDateTime, Option<String>),
+}
+
+impl Invite {
+    /// A summary of the event to show above the body, one line per detail.
+    pub fn text(&self) -> String {
+        let kind = match self.method.as_deref() {
+            Some("CANCEL") => "Cancelled",
+            Some("REPLY") => "Reply to invitation",
+            _ => "Invitation",
+        };
+        let mut lines = vec![format!("{kind}: {}", self.summary())];
+        if let Some(start) = &self.start {
+            lines.push(format!("When: {}", when(start, self.end.as_ref())));
+        }
+        if let Some(location) = &self.location {
+            lines.push(format!("Where: {location}"));
+        }
+        if let Some(organizer) = &self.organizer {
+            lines.push(format!("Organizer: {organizer}"));
+        }
+        if !self.attendees.is_empty() {
+            lines.push(format!("Attendees: {}", self.attendees.join(", ")));
+        }
+        lines.join("\n")
+    }
+
+    pub fn summary(&self) -> &str {
+        self.summary.as_deref().unwrap_or("(no title)")
+    }
+
+    /// The organizer's address, if the invite can be answered.
+    pub fn reply_to(&self) -> Option<&str> {
+        let asks = self.method.as_deref() == Some("REQUEST") && self.uid.is_some();
+        self.organizer_address.as_deref().filter(|_| asks)
+    }
+
+    /// The first of `own`, addresses in lowercase, that the invite lists as an attendee.
+    pub fn attendee<'a>(&self, own: &'a [String]) -> Option<&'a str> {
+        own.iter()
+            .find(|address| self.attendee_addresses.contains(address))
+            .map(String::as_str)
+    }
+
+    /// An iTIP reply (RFC 5546) giving `attendee`'s answer, to send to the organizer.
+    pub fn reply(&self, attendee: &str, answer: Answer) -> String {
+        let mut lines = vec![
+            String::from("BEGIN:VCALENDAR"),
+            String::from("VERSION:2.0"),
+            String::from("PRODID:-//eta//EN"),
+            String::from("METHOD:REPLY"),
+            String::from("BEGIN:VEVENT"),
+            format!("UID:{}", self.uid.as_deref().unwrap_or_default()),
+            format!("SEQUENCE:{}", self.sequence.as_deref().unwrap_or("0")),
+            format!("DTSTAMP:{}", Utc::now().format("%Y%m%dT%H%M%SZ")),
+            format!(
+                "ORGANIZER:mailto:{}",
+                self.organizer_address.as_deref().unwrap_or_default()
+            ),
+            format!(
+                "ATTENDEE;PARTSTAT={}:mailto:{attendee}",
+                answer.participation_status()
+            ),
+        ];
+        lines.extend(self.recurrence_id.clone());
+        if let Some(summary) = &self.summary {
+            lines.push(format!("SUMMARY:{}", escape(summary)));
+        }
+        lines.extend([String::from("END:VEVENT"), String::from("END:VCALENDAR")]);
+        lines.iter().map(|line| fold(line)).collect()
+    }
+}
+
+/// The first event in `text`, an iCalendar object, if it has one.
+pub fn parse(text: &str) -> Option<Invite> {
+    let mut invite: Option<Invite> = None;
+    let mut method = None;
+
+    for line in unfold(text) {
+        let Some((name, parameters, value)) = split_property(&line) else {
+            continue;
+        };
+        match (name.to_ascii_uppercase().as_str(), &mut invite) {
+            ("METHOD", _) => method = Some(value.trim().to_ascii_uppercase()),
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                invite = Some(Invite::default())
+            }
+            // only the first event is shown, so it is finished at its end
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => break,
+            ("UID", Some(invite)) => invite.uid = Some(value.to_string()),
+            ("SEQUENCE", Some(invite)) => invite.sequence = Some(value.trim().to_string()),
+            ("RECURRENCE-ID", Some(invite)) => invite.recurrence_id = Some(line.clone()),
+            ("SUMMARY", Some(invite)) => invite.summary = Some(unescape(value)),
+            ("LOCATION", Some(invite)) => {
+                invite.location = Some(unescape(value)).filter(|l| !l.is_empty())
+            }
+            ("DTSTART", Some(invite)) => invite.start = time(&parameters, value),
+            ("DTEND", Some(invite)) => invite.end = time(&parameters, value),
+            ("ORGANIZER", Some(invite)) => {
+                invite.organizer = Some(person(&parameters, value));
+                invite.organizer_address = Some(address(value).to_string());
+            }
+            ("ATTENDEE", Some(invite)) => {
+                let mut attendee = person(&parameters, value);
+                let answer = parameter(&parameters, "PARTSTAT").map(str::to_ascii_uppercase);
+                match answer.as_deref() {
+                    Some("ACCEPTED") => attendee.push_str(" (accepted)"),
+                    Some("DECLINED") => attendee.push_str(" (declined)"),
+                    Some("TENTATIVE") => attendee.push_str(" (tentative)"),
+                    _ => {}
+                }
+                invite.attendees.push(attendee);
+                invite
+                    .attendee_addresses
+                    .push(address(value).trim().to_lowercase());
+            }
+            _ => {}
+        }
+    }
+
+    invite.map(|invite| Invite { method, ..invite })
+}
+
+/// Split a content line into its name, parameters and value, e.g.
+/// `ORGANIZER;CN="Doe, Jane":mailto:jane@example.com`. Quoted parameter values may hold `;` and
+/// `:`.
+fn split_property(line: &str) -> Option<(&str, Vec<&str>, &str)> {
+    let mut quoted = false;
+    let mut fields = vec![];
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                fields.push(&line[start..i]);
+                start = i + 1;
+            }
+            ':' if !quoted => {
+                fields.push(&line[start..i]);
+                let name = fields.remove(0);
+                return Some((name, fields, &line[i + 1..]));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The value of the parameter `name`, without quotes.
+fn parameter<'a>(parameters: &[&'a str], name: &str) -> Option<&'a str> {
+    parameters.iter().find_map(|parameter| {
+        let (key, value) = parameter.split_once('=')?;
+        key.eq_ignore_ascii_case(name)
+            .then(|| value.trim_matches('"'))
+    })
+}
+
+/// The address of an organizer or attendee, given as a `mailto:` URI.
+fn address(value: &str) -> &str {
+    value
+        .strip_prefix("mailto:")
+        .or_else(|| value.strip_prefix("MAILTO:"))
+        .unwrap_or(value)
+}
+
+/// An organizer or attendee, as `Name <address>` when they are named.
+fn person(parameters: &[&str], value: &str) -> String {
+    let address = address(value);
+    match parameter(parameters, "CN") {
+        Some(name) if !name.is_empty() && name != address => format!("{name} <{address}>"),
+        _ => address.to_string(),
+    }
+}
+
+/// A `DTSTART` or `DTEND` value, e.g. `20260115T150000Z`, or `20260115` for a whole day.
+fn time(parameters: &[&str], value: &str) -> Option<Time> {
+    let value = value.trim();
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(Time::Utc(Utc.from_utc_datetime(&time)));
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        let zone = parameter(parameters, "TZID").map(String::from);
+        return Some(Time::Zoned(time, zone));
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .ok()
+        .map(Time::Day)
+}
+
+/// When an event takes place, leaving out the end's date when it ends the same day.
+fn when(start: &Time, end: Option<&Time>) -> String {
+    let start = Shown::from(start);
+    let Some(end) = end else {
+        return start.to_string();
+    };
+    let end = Shown::from(end);
+    match (&start.time, &end.time) {
+        // whole day events end on the day after their last
+        (None, None) => match end.day.pred_opt() {
+            Some(last) if last > start.day => {
+                format!("{start} – {}", last.format(DAY_FORMAT))
+            }
+            _ => start.to_string(),
+        },
+        (Some(start_time), Some(end_time)) if start.day == end.day => format!(
+            "{} {start_time} – {end_time}{}",
+            start.day.format(DAY_FORMAT),
+            end.zone
+        ),
+        _ => format!("{start} – {end}"),
+    }
+}
+
+/// A start or end time as it is shown.
+struct Shown {
+    day: NaiveDate,
+    /// The time of day, e.g. "15:00", unless it is a whole day.
+    time: Option<String>,
+    /// The zone the time is in, e.g. " (Europe/Berlin)", when it isn't local.
+    zone: String,
+}
+
+impl From<&Time> for Shown {
+    fn from(time: &Time) -> Self {
+        match time {
+            Time::Day(day) => Shown {
+                day: *day,
+                time: None,
+                zone: String::new(),
+            },
+            Time::Utc(time) => {
+                let local = time.with_timezone(&Local);
+                Shown {
+                    day: local.date_naive(),
+                    time: Some(local.format(HOUR_FORMAT).to_string()),
+                    zone: String::new(),
+                }
+            }
+            Time::Zoned(time, zone) => Shown {
+                day: time.date(),
+                time: Some(time.format(HOUR_FORMAT).to_string()),
+                zone: zone
+                    .as_ref()
+                    .map(|zone| format!(" ({zone})"))
+                    .unwrap_or_default(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Shown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.day.format(DAY_FORMAT))?;
+        if let Some(time) = &self.time {
+            write!(f, " {time}")?;
+        }
+        write!(f, "{}", self.zone)
+    }
+}
+
+/// Escape the characters that are special in a text value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// A content line ending in CRLF, folded so no line is longer than 75 bytes.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST: &str = "BEGIN:VCALENDAR\r
+METHOD:REQUEST\r
+BEGIN:VEVENT\r
+UID:review-42@example.com\r
+SEQUENCE:2\r
+SUMMARY:Quarterly review\\, Q1\r
+DTSTART:20260115\r
+DTEND:20260117\r
+LOCATION:Room 4\r
+ORGANIZER;CN=\"Doe, Jane\":mailto:jane@example.com\r
+ATTENDEE;CN=Bob;PARTSTAT=ACCEPTED:mailto:bob@example.com\r
+ATTENDEE;PARTSTAT=NEEDS-ACTION:MAILTO:Team@Example.com\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn invites_are_summarised() {
+        let invite = parse(REQUEST).unwrap();
+
+        assert_eq!(
+            invite.text(),
+            "Invitation: Quarterly review, Q1\n\
+             When: Thu 15 Jan 2026 – Fri 16 Jan 2026\n\
+             Where: Room 4\n\
+             Organizer: Doe, Jane <jane@example.com>\n\
+             Attendees: Bob <bob@example.com> (accepted), Team@Example.com"
+        );
+        assert_eq!(invite.reply_to(), Some("jane@example.com"));
+        assert_eq!(parse("BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n"), None);
+    }
+
+    #[test]
+    fn only_requests_are_answered() {
+        let cancel = REQUEST.replace("METHOD:REQUEST", "METHOD:CANCEL");
+        assert_eq!(parse(&cancel).unwrap().reply_to(), None);
+
+        let without_uid = REQUEST.replace("UID:review-42@example.com\r\n", "");
+        assert_eq!(parse(&without_uid).unwrap().reply_to(), None);
+    }
+
+    #[test]
+    fn the_invited_address_answers() {
+        let invite = parse(REQUEST).unwrap();
+        let own = [
+            String::from("me@example.com"),
+            String::from("team@example.com"),
+        ];
+
+        assert_eq!(invite.attendee(&own), Some("team@example.com"));
+        assert_eq!(invite.attendee(&own[..1]), None);
+    }
+
+    #[test]
+    fn replies_keep_the_event() {
+        let invite = parse(REQUEST).unwrap();
+        let reply = invite.reply("team@example.com", Answer::Tentative);
+
+        assert!(reply.contains("METHOD:REPLY\r\n"));
+        assert!(reply.contains("UID:review-42@example.com\r\nSEQUENCE:2\r\n"));
+        assert!(reply.contains("ORGANIZER:mailto:jane@example.com\r\n"));
+        assert!(reply.contains("ATTENDEE;PARTSTAT=TENTATIVE:mailto:team@example.com\r\n"));
+        assert!(reply.contains("SUMMARY:Quarterly review\\, Q1\r\n"));
+        assert!(reply.lines().all(|line| line.len() <= 75));
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let line = "x".repeat(100);
+        assert_eq!(
+            fold(&line),
+            format!("{}\r\n {}\r\n", "x".repeat(75), "x".repeat(25))
+        );
+    }
+}
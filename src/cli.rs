@@ -0,0 +1,99 @@
+use std::{io::Read, path::PathBuf};
+
+use clap::{Parser, Subcommand};
+use tracing_subscriber::filter::LevelFilter;
+
+use crate::{draft::Draft, mailto, profile::Profile};
+
+/// Where `--dry-run-send` writes messages when not given a directory.
+const DRY_RUN_DIR: &str = "dry-run";
+
+/// What `eta` was asked to do on the command line.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Use a profile's settings and messages instead of the default profile's
+    #[arg(long, global = true, visible_alias = "profile", value_name = "NAME")]
+    account: Option<String>,
+    /// Read settings from this file instead of the profile's eta.toml
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Keep messages in this database instead of the profile's messages.db
+    #[arg(long, global = true, value_name = "PATH")]
+    database: Option<PathBuf>,
+    /// Write messages to files in a directory instead of sending them
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DRY_RUN_DIR
+    )]
+    pub dry_run_send: Option<PathBuf>,
+    /// How much to write to the log file: off, error, warn, info, debug or trace
+    #[arg(long, global = true, value_name = "LEVEL", default_value = "info")]
+    pub log_level: LevelFilter,
+    /// Start composing the message a mailto: link asks for, e.g. mailto:jane@example.com?subject=Hi
+    #[arg(value_name = "MAILTO", value_parser = mailto::parse)]
+    mailto: Option<Draft>,
+    /// Start composing a message to these addresses, separated by commas
+    #[arg(long, global = true, value_name = "ADDRESSES")]
+    to: Option<String>,
+    /// Start composing a message copied to these addresses, separated by commas
+    #[arg(long, global = true, value_name = "ADDRESSES")]
+    cc: Option<String>,
+    /// Start composing a message with this subject
+    #[arg(long, global = true)]
+    subject: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Start on the composer instead of the message table
+    Compose {
+        /// `-` to read the message body from standard input
+        #[arg(value_parser = ["-"])]
+        body: Option<String>,
+    },
+}
+
+impl Args {
+    /// The profile chosen with `--account`, or the default profile, with any files given in
+    /// its place.
+    pub fn profile(&self) -> color_eyre::Result<Profile> {
+        let profile = match &self.account {
+            Some(name) => Profile::named(name).map_err(|e| color_eyre::eyre::eyre!(e))?,
+            None => Profile::default(),
+        };
+        Ok(profile.with_paths(self.config.clone(), self.database.clone()))
+    }
+
+    /// The message to start on the composer with, if asked to: the mailto: link's, with the
+    /// fields given as options in place of the link's own, and the body read from standard
+    /// input for `eta compose -`.
+    pub fn draft(&self) -> color_eyre::Result<Option<Draft>> {
+        let options = [&self.to, &self.cc, &self.subject];
+        if self.command.is_none() && self.mailto.is_none() && options.iter().all(|o| o.is_none()) {
+            return Ok(None);
+        }
+
+        let mut draft = self.mailto.clone().unwrap_or_default();
+        draft.to = self.to.clone().unwrap_or(draft.to);
+        draft.cc = self.cc.clone().unwrap_or(draft.cc);
+        draft.subject = self.subject.clone().unwrap_or(draft.subject);
+        if let Some(Command::Compose { body: Some(_) }) = self.command {
+            draft.body = read_stdin()?;
+        }
+        Ok(Some(draft))
+    }
+}
+
+/// Read all of standard input, e.g. the output of a command piped to `eta compose -`.
+fn read_stdin() -> color_eyre::Result<String> {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)?;
+    Ok(text)
+}
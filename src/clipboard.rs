@@ -0,0 +1,55 @@
+use std::{io::Write, sync::Mutex};
+
+use arboard::Clipboard;
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+/// On some platforms, e.g. X11, copied text is only available while the clipboard that copied it
+/// is alive, so keep it around for the life of the application.
+static CLIPBOARD: Mutex<Option<Clipboard>> = Mutex::new(None);
+
+/// Read the text on the system clipboard.
+pub fn paste() -> Result<String, arboard::Error> {
+    let mut clipboard = CLIPBOARD.lock().unwrap_or_else(|e| e.into_inner());
+    if clipboard.is_none() {
+        *clipboard = Some(Clipboard::new()?);
+    }
+
+    match clipboard.as_mut() {
+        Some(clipboard) => clipboard.get_text(),
+        None => Ok(String::new()),
+    }
+}
+
+/// Copy `text` to the system clipboard. Over SSH, or where there is no system clipboard to use,
+/// the terminal is asked to copy it instead, to the clipboard of the machine it runs on.
+pub fn copy(text: &str) -> std::io::Result<()> {
+    if is_remote() || copy_to_system(text).is_err() {
+        return copy_with_terminal(text);
+    }
+    Ok(())
+}
+
+fn copy_to_system(text: &str) -> Result<(), arboard::Error> {
+    let mut clipboard = CLIPBOARD.lock().unwrap_or_else(|e| e.into_inner());
+    if clipboard.is_none() {
+        *clipboard = Some(Clipboard::new()?);
+    }
+
+    if let Some(clipboard) = clipboard.as_mut() {
+        clipboard.set_text(text)?;
+    }
+    Ok(())
+}
+
+/// Whether `eta` runs in an SSH session, where the system clipboard is the remote machine's.
+fn is_remote() -> bool {
+    std::env::var_os("SSH_TTY").is_some() || std::env::var_os("SSH_CONNECTION").is_some()
+}
+
+/// Copy `text` with an OSC 52 escape sequence, which most terminals, and tmux with
+/// `set-clipboard` on, put on their own clipboard.
+fn copy_with_terminal(text: &str) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()
+}
@@ -0,0 +1,132 @@
+/// An action typed at the `:` command line on the main page, e.g. `:move Archive`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// `compose`: start a new message.
+    Compose,
+    /// `search <text>`: search every folder's messages.
+    Search(String),
+    /// `goto <id>`: select the message with this id in the table.
+    Goto(u64),
+    /// `folder <name>`: open a folder.
+    Folder(String),
+    /// `move <folder>`: move the selected message to a folder.
+    Move(String),
+    /// `import <path>`: add a `.eml` file, or every one in a directory, to the current folder.
+    Import(String),
+    /// `mark all`, `mark none` or `mark invert`: change which messages in the table are marked.
+    Mark(Marking),
+    /// `save <name>`: keep the current search in the sidebar.
+    Save(String),
+    /// `unsave <name>`: take a saved search out of the sidebar.
+    Unsave(String),
+    /// `help`: list the commands.
+    Help,
+    /// `quit`, or `q`.
+    Quit,
+}
+
+/// Which messages `:mark` marks, of those the table shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Marking {
+    All,
+    None,
+    /// Mark those that aren't marked, and unmark those that are.
+    Invert,
+}
+
+/// Every command, as listed by `:help`.
+pub const USAGE: &str = "compose, search <text>, goto <id>, folder <name>, move <folder>, \
+    import <path>, mark all|none|invert, save <name>, unsave <name>, quit";
+
+impl Command {
+    /// Read a command line, without its `:`.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (name, argument) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(name, argument)| (name, argument.trim()));
+
+        let command = match name {
+            "compose" => Command::Compose,
+            "search" => Command::Search(required(name, argument)?),
+            "goto" => match argument.parse() {
+                Ok(n) if n > 0 => Command::Goto(n),
+                _ => return Err(format!("Not a message id: {argument}")),
+            },
+            "folder" => Command::Folder(required(name, argument)?),
+            "move" => Command::Move(required(name, argument)?),
+            "import" => Command::Import(required(name, argument)?),
+            "mark" => match argument {
+                "all" => Command::Mark(Marking::All),
+                "none" => Command::Mark(Marking::None),
+                "invert" => Command::Mark(Marking::Invert),
+                _ => return Err(String::from("mark takes all, none or invert")),
+            },
+            "save" => Command::Save(required(name, argument)?),
+            "unsave" => Command::Unsave(required(name, argument)?),
+            "help" => Command::Help,
+            "quit" | "q" => Command::Quit,
+            "" => return Err(format!("Commands: {USAGE}")),
+            _ => return Err(format!("Unknown command: {name}")),
+        };
+        // only some commands take an argument
+        match command {
+            Command::Compose | Command::Help | Command::Quit if !argument.is_empty() => {
+                Err(format!("{name} takes no arguments"))
+            }
+            command => Ok(command),
+        }
+    }
+}
+
+fn required(name: &str, argument: &str) -> Result<String, String> {
+    if argument.is_empty() {
+        Err(format!("{name} needs an argument"))
+    } else {
+        Ok(argument.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_read_with_their_arguments() {
+        assert_eq!(Command::parse("compose"), Ok(Command::Compose));
+        assert_eq!(Command::parse(" goto 42 "), Ok(Command::Goto(42)));
+        assert_eq!(
+            Command::parse("move  Old mail "),
+            Ok(Command::Move(String::from("Old mail")))
+        );
+        assert_eq!(
+            Command::parse("mark invert"),
+            Ok(Command::Mark(Marking::Invert))
+        );
+        assert_eq!(Command::parse("q"), Ok(Command::Quit));
+    }
+
+    #[test]
+    fn arguments_must_be_given_where_they_are_needed_only() {
+        assert_eq!(
+            Command::parse("search"),
+            Err(String::from("search needs an argument"))
+        );
+        assert_eq!(
+            Command::parse("quit now"),
+            Err(String::from("quit takes no arguments"))
+        );
+        assert!(Command::parse("goto 0").is_err());
+        assert!(Command::parse("goto x").is_err());
+        assert!(Command::parse("mark some").is_err());
+    }
+
+    #[test]
+    fn unknown_and_empty_commands_are_refused() {
+        assert_eq!(
+            Command::parse("frobnicate"),
+            Err(String::from("Unknown command: frobnicate"))
+        );
+        assert_eq!(Command::parse(""), Err(format!("Commands: {USAGE}")));
+    }
+}
@@ -0,0 +1,411 @@
+use regex::Regex;
+use serde::Deserialize;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, value};
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    links::TicketPattern, message::SmtpConfig, profile::Profile, query::SavedSearch, rules::Rule,
+    signature::SignatureConfig, theme::Theme,
+};
+
+/// Settings read from `eta.toml`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Config {
+    /// The file the settings were read from, and are saved to.
+    #[serde(skip)]
+    path: PathBuf,
+    /// How to connect to the SMTP server.
+    #[serde(flatten)]
+    pub smtp: SmtpConfig,
+    /// What to show first, rather than the inbox.
+    #[serde(default)]
+    pub startup: Startup,
+    /// Where to preview the selected message alongside the table, if at all.
+    #[serde(default)]
+    pub preview_pane: PreviewPane,
+    /// Show headers such as "Today" and "Yesterday" between messages in the table.
+    #[serde(default)]
+    pub group_by_date: bool,
+    /// Show how long ago messages were sent in the table, e.g. "5m ago", rather than when.
+    #[serde(default)]
+    pub relative_dates: bool,
+    /// Delete messages for good once they have been in the trash for this many days.
+    #[serde(default)]
+    pub empty_trash_after_days: Option<u32>,
+    /// Only mark a message as read once it has been open for this many seconds, so paging
+    /// quickly through messages leaves them unread.
+    #[serde(default)]
+    pub mark_read_after_seconds: u64,
+    /// Also announce replies to watched threads with a desktop notification.
+    #[serde(default)]
+    pub desktop_notifications: bool,
+    /// Ticket references to turn into links, e.g. `JIRA-123`.
+    #[serde(default)]
+    pub ticket_links: Vec<TicketPattern>,
+    /// How replies quote the original message.
+    #[serde(default)]
+    pub reply: ReplyConfig,
+    /// Added to the end of new messages, replies and forwards.
+    #[serde(default)]
+    pub signature: SignatureConfig,
+    /// Which columns the message table shows, in order, and how wide.
+    #[serde(default)]
+    pub columns: ColumnConfig,
+    /// Colours of the table header, selection, status bar, unread messages, compose labels and
+    /// tags.
+    #[serde(default)]
+    pub theme: Theme,
+    /// Keep the message database encrypted with a passphrase asked for on opening it.
+    #[serde(default)]
+    pub encrypt_database: bool,
+    /// Which actions go ahead straight away, ask first, or are refused.
+    #[serde(default)]
+    pub confirm: ConfirmConfig,
+    /// What to do when a message's attachments are too large to send.
+    #[serde(default)]
+    pub attachments: AttachmentConfig,
+    /// How often to save the message being composed, in seconds, so it isn't lost if eta closes
+    /// before it is sent. 0 means never.
+    #[serde(default = "default_autosave_seconds")]
+    pub autosave_seconds: u64,
+    /// How often to check folders not listed in `polling` for new messages, in seconds. 0 means
+    /// never.
+    #[serde(default)]
+    pub poll_interval: u64,
+    /// How often to check each folder for new messages, in seconds, e.g. `Inbox = 60`. Folders
+    /// set to 0 are never checked.
+    #[serde(default)]
+    pub polling: HashMap<String, u64>,
+    /// Rules sorting messages as they arrive or are imported, applied in order.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Only log what the rules would do, without doing it, to try them out.
+    #[serde(default)]
+    pub dry_run_rules: bool,
+    /// Searches listed after the folders in the sidebar.
+    #[serde(default)]
+    pub searches: Vec<SavedSearch>,
+}
+
+fn default_autosave_seconds() -> u64 {
+    30
+}
+
+/// Where the selected message's body is shown next to the message table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewPane {
+    #[default]
+    Off,
+    Right,
+    Below,
+}
+
+impl PreviewPane {
+    /// The placement after this one, as `v` cycles through them.
+    pub fn next(self) -> Self {
+        match self {
+            PreviewPane::Off => PreviewPane::Right,
+            PreviewPane::Right => PreviewPane::Below,
+            PreviewPane::Below => PreviewPane::Off,
+        }
+    }
+}
+
+/// What `eta` opens into.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Startup {
+    #[default]
+    Inbox,
+    /// The messages in this folder.
+    Folder(String),
+    /// The results of searching every folder for this text.
+    Search(String),
+    /// The composition page, with a new message.
+    Compose,
+}
+
+/// The `[columns]` table.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ColumnConfig {
+    /// The columns shown, from left to right.
+    pub show: Vec<TableColumn>,
+    /// Widths of the columns that can be resized. The others are as wide as their contents.
+    pub from: u16,
+    pub to: u16,
+    pub subject: u16,
+    pub folder: u16,
+}
+
+impl Default for ColumnConfig {
+    fn default() -> Self {
+        ColumnConfig {
+            show: vec![
+                TableColumn::Flags,
+                TableColumn::Id,
+                TableColumn::From,
+                TableColumn::Subject,
+                TableColumn::Date,
+            ],
+            from: 25,
+            to: 25,
+            subject: 50,
+            folder: 12,
+        }
+    }
+}
+
+/// A column of the message table, as named in `show`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableColumn {
+    /// Whether the message is flagged or muted.
+    Flags,
+    Id,
+    Date,
+    From,
+    To,
+    Subject,
+    Size,
+    Folder,
+}
+
+/// The `[reply]` table.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ReplyConfig {
+    /// Line introducing the quoted message. `{from}`, `{date}` and `{subject}` are replaced by
+    /// the original's; an empty attribution leaves the line out.
+    pub attribution: String,
+    /// Whether the reply is written above or below the quoted message.
+    pub posting: Posting,
+}
+
+impl Default for ReplyConfig {
+    fn default() -> Self {
+        ReplyConfig {
+            attribution: String::from("On {date}, {from} wrote:"),
+            posting: Posting::default(),
+        }
+    }
+}
+
+/// Where a reply goes relative to the quoted message.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Posting {
+    /// Below the quote.
+    #[default]
+    Bottom,
+    /// Above the quote.
+    Top,
+}
+
+/// An action whose confirmation can be configured in the `[confirm]` table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Confirmation {
+    Send,
+    /// Deleting a message from the trash for good.
+    Delete,
+    EmptyTrash,
+    DiscardDraft,
+    /// Always loading remote content from a sender.
+    RemoteContent,
+}
+
+impl Confirmation {
+    /// The action's key in the `[confirm]` table.
+    pub fn key(self) -> &'static str {
+        match self {
+            Confirmation::Send => "send",
+            Confirmation::Delete => "delete",
+            Confirmation::EmptyTrash => "empty_trash",
+            Confirmation::DiscardDraft => "discard_draft",
+            Confirmation::RemoteContent => "remote_content",
+        }
+    }
+}
+
+/// What to do when an action that may need confirming is taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    /// Go ahead without asking.
+    Always,
+    /// Ask first.
+    Ask,
+    /// Refuse to do it.
+    Never,
+}
+
+impl Policy {
+    pub fn name(self) -> &'static str {
+        match self {
+            Policy::Always => "always",
+            Policy::Ask => "ask",
+            Policy::Never => "never",
+        }
+    }
+
+    /// The policy after this one, for cycling through them on the settings screen.
+    pub fn next(self) -> Self {
+        match self {
+            Policy::Always => Policy::Ask,
+            Policy::Ask => Policy::Never,
+            Policy::Never => Policy::Always,
+        }
+    }
+}
+
+/// The `[confirm]` table.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ConfirmConfig {
+    pub send: Policy,
+    pub delete: Policy,
+    pub empty_trash: Policy,
+    pub discard_draft: Policy,
+    pub remote_content: Policy,
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        ConfirmConfig {
+            send: Policy::Always,
+            delete: Policy::Ask,
+            empty_trash: Policy::Always,
+            discard_draft: Policy::Ask,
+            remote_content: Policy::Always,
+        }
+    }
+}
+
+impl ConfirmConfig {
+    pub fn policy(&self, action: Confirmation) -> Policy {
+        match action {
+            Confirmation::Send => self.send,
+            Confirmation::Delete => self.delete,
+            Confirmation::EmptyTrash => self.empty_trash,
+            Confirmation::DiscardDraft => self.discard_draft,
+            Confirmation::RemoteContent => self.remote_content,
+        }
+    }
+
+    pub fn policy_mut(&mut self, action: Confirmation) -> &mut Policy {
+        match action {
+            Confirmation::Send => &mut self.send,
+            Confirmation::Delete => &mut self.delete,
+            Confirmation::EmptyTrash => &mut self.empty_trash,
+            Confirmation::DiscardDraft => &mut self.discard_draft,
+            Confirmation::RemoteContent => &mut self.remote_content,
+        }
+    }
+}
+
+/// The `[attachments]` table.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct AttachmentConfig {
+    /// The most a message's attachments may come to, in megabytes, once encoded for sending.
+    pub limit_mb: u64,
+    /// What to do with a message over the limit.
+    pub too_large: TooLarge,
+}
+
+impl Default for AttachmentConfig {
+    fn default() -> Self {
+        AttachmentConfig {
+            limit_mb: 25,
+            too_large: TooLarge::default(),
+        }
+    }
+}
+
+impl AttachmentConfig {
+    pub fn limit_bytes(&self) -> u64 {
+        self.limit_mb * 1_048_576
+    }
+}
+
+/// What sending a message whose attachments are over the limit does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TooLarge {
+    /// Suggest zipping them, and send anyway if asked again.
+    #[default]
+    Warn,
+    /// Zip them before sending.
+    Zip,
+    /// Don't send it.
+    Refuse,
+}
+
+impl Config {
+    /// Read the profile's `eta.toml`.
+    pub fn load(profile: &Profile) -> color_eyre::Result<Self> {
+        let path = profile.config_path();
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| color_eyre::eyre::eyre!("{}: {e}", path.display()))?;
+        let mut config: Config = toml::from_str(&text)?;
+        config.path = path;
+        let dir = config.dir().to_path_buf();
+        config.signature.load_files(&dir)?;
+        config.smtp.load_signature_files(&dir)?;
+        for ticket in &config.ticket_links {
+            Regex::new(&ticket.pattern)?;
+        }
+        for rule in &mut config.rules {
+            rule.check().map_err(|e| color_eyre::eyre::eyre!(e))?;
+        }
+        Ok(config)
+    }
+
+    /// The directory `eta.toml` is in, which files it names are read relative to.
+    pub fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// Write one setting to `eta.toml`, leaving the rest of the file, comments included, as it
+    /// is. `key` is the path to the setting, e.g. `["reply", "posting"]`; `None` removes it.
+    pub fn save_setting(&self, key: &[&str], value: Option<Item>) -> color_eyre::Result<()> {
+        let mut document: DocumentMut = std::fs::read_to_string(&self.path)?.parse()?;
+
+        let (name, tables) = key.split_last().expect("setting key is empty");
+        let mut table = document.as_table_mut();
+        for &name in tables {
+            table = table
+                .entry(name)
+                .or_insert_with(|| Item::Table(Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| {
+                    color_eyre::eyre::eyre!("{name} in {} is not a table", self.path.display())
+                })?;
+        }
+        match value {
+            Some(value) => table.insert(name, value),
+            None => table.remove(name),
+        };
+
+        std::fs::write(&self.path, document.to_string())?;
+        Ok(())
+    }
+
+    /// Write the saved searches to `eta.toml` as `[[searches]]` tables, in place of those there.
+    pub fn save_searches(&self) -> color_eyre::Result<()> {
+        let mut searches = ArrayOfTables::new();
+        for search in &self.searches {
+            let mut table = Table::new();
+            table.insert("name", value(search.name.as_str()));
+            table.insert("query", value(search.query.as_str()));
+            searches.push(table);
+        }
+        let searches = (!searches.is_empty()).then_some(Item::ArrayOfTables(searches));
+        self.save_setting(&["searches"], searches)
+    }
+}
@@ -0,0 +1,171 @@
+use lettre::{address::AddressError, message::Mailbox};
+
+use crate::message::parse_addresses;
+
+/// Addresses differing from a contact by at most this many edits are taken to be typos of it.
+const MAX_TYPO_DISTANCE: usize = 2;
+
+/// An entry in the address book.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Contact {
+    /// Set once the contact has been saved.
+    pub id: Option<u64>,
+    pub name: String,
+    pub email: String,
+}
+
+impl Contact {
+    /// Read a contact from an address such as `Jane Doe <jane@example.com>`, keeping `id`.
+    pub fn parse(id: Option<u64>, address: &str) -> Result<Self, AddressError> {
+        let mailbox: Mailbox = address.trim().parse()?;
+        Ok(Self {
+            id,
+            name: mailbox.name.unwrap_or_default(),
+            email: mailbox.email.to_string(),
+        })
+    }
+
+    /// The contact as it is written in a To or Cc field, quoting the name if needed.
+    pub fn address(&self) -> String {
+        match self.email.parse() {
+            Ok(email) if !self.name.is_empty() => {
+                Mailbox::new(Some(self.name.clone()), email).to_string()
+            }
+            _ => self.email.clone(),
+        }
+    }
+}
+
+/// Most completions offered for an address being typed.
+const MAX_COMPLETIONS: usize = 8;
+
+/// Addresses to offer for `query`, the start of an address being typed: contacts whose name or
+/// address contains it, then addresses from `seen` that do. `seen` is best first, and contacts
+/// come in the same order, ahead of any it doesn't have.
+pub fn completions(query: &str, address_book: &[Contact], seen: &[String]) -> Vec<String> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return vec![];
+    }
+
+    let mut contacts: Vec<&Contact> = address_book
+        .iter()
+        .filter(|contact| {
+            contact.name.to_lowercase().contains(&query)
+                || contact.email.to_lowercase().contains(&query)
+        })
+        .collect();
+    contacts.sort_by_key(|contact| {
+        let email = contact.email.to_lowercase();
+        seen.iter()
+            .position(|address| *address == email)
+            .unwrap_or(usize::MAX)
+    });
+    let mut emails: Vec<String> = vec![];
+    let mut completions = vec![];
+    for contact in contacts {
+        emails.push(contact.email.to_lowercase());
+        completions.push(contact.address());
+    }
+    for address in seen {
+        if address.contains(&query) && !emails.contains(address) {
+            completions.push(address.clone());
+        }
+    }
+    completions.truncate(MAX_COMPLETIONS);
+    completions
+}
+
+/// Every address in `lists` of comma separated addresses, such as the From, To and Cc fields of
+/// messages, in lowercase and without duplicates.
+pub fn collect<'l>(lists: impl IntoIterator<Item = &'l str>) -> Vec<String> {
+    let mut contacts: Vec<String> = lists
+        .into_iter()
+        .filter_map(|list| parse_addresses(list).ok())
+        .flatten()
+        .map(|mailbox| mailbox.email.to_string().to_lowercase())
+        .collect();
+    contacts.sort();
+    contacts.dedup();
+    contacts
+}
+
+/// The contact `address` was probably meant to be, when it is not a contact itself but is only a
+/// typo or two away from one.
+pub fn suggestion<'c>(address: &str, contacts: &'c [String]) -> Option<&'c str> {
+    let address = address.to_lowercase();
+    if contacts.contains(&address) {
+        return None;
+    }
+    contacts
+        .iter()
+        .map(|contact| (edit_distance(&address, contact), contact))
+        .filter(|(distance, _)| *distance <= MAX_TYPO_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, contact)| contact.as_str())
+}
+
+/// The number of insertions, deletions, substitutions and swaps of adjacent characters needed to
+/// turn `a` into `b`. Swaps count as a single edit, since transposed letters are a common typo.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // distances[i][j] is the distance between the first i characters of a and the first j of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_each_kind_of_edit() {
+        assert_eq!(edit_distance("jane", "jane"), 0);
+        assert_eq!(edit_distance("jane", "jade"), 1);
+        assert_eq!(edit_distance("jane", "jan"), 1);
+        assert_eq!(edit_distance("jane", "jeane"), 1);
+        assert_eq!(edit_distance("jane", "jnae"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn suggestion_is_the_closest_contact() {
+        let contacts = [
+            String::from("jane@example.com"),
+            String::from("john@example.com"),
+        ];
+
+        assert_eq!(
+            suggestion("jnae@example.com", &contacts),
+            Some("jane@example.com")
+        );
+        assert_eq!(
+            suggestion("Jane@example.con", &contacts),
+            Some("jane@example.com")
+        );
+        assert_eq!(suggestion("JANE@example.com", &contacts), None);
+        assert_eq!(suggestion("someone@elsewhere.org", &contacts), None);
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use serde::Deserialize;
+use zeroize::Zeroizing;
+
+/// The SMTP password, either written out in `eta.toml` or kept in the system keyring under a
+/// service name, e.g. `password = { keyring = "eta" }`.
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Password {
+    Inline(String),
+    Keyring {
+        keyring: String,
+    },
+    /// Typed in while `eta` runs, and only ever kept in memory. Copies of the settings share
+    /// it, so that it is entered and forgotten for them all.
+    #[serde(skip)]
+    Entered(Arc<Mutex<Option<Zeroizing<String>>>>),
+}
+
+impl Default for Password {
+    fn default() -> Self {
+        Password::Inline(String::new())
+    }
+}
+
+// keeps the password out of logs and error reports
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Password::Inline(_) => f.write_str("Inline(..)"),
+            Password::Keyring { keyring } => {
+                f.debug_struct("Keyring").field("keyring", keyring).finish()
+            }
+            Password::Entered(_) => f.write_str("Entered(..)"),
+        }
+    }
+}
+
+impl Password {
+    /// The password, looked up in the keyring for `user` if it is kept there.
+    pub async fn read(&self, user: &str) -> Result<String, String> {
+        match self {
+            Password::Inline(password) => Ok(password.clone()),
+            Password::Keyring { keyring } => read_keyring(keyring, user).await,
+            Password::Entered(slot) => match &*lock(slot) {
+                Some(password) => Ok(password.to_string()),
+                None => Err(String::from("No password has been entered")),
+            },
+        }
+    }
+
+    /// A password to be entered when it is first needed.
+    pub fn entered() -> Self {
+        Password::Entered(Arc::default())
+    }
+
+    /// Whether the password is still to be entered.
+    pub fn is_missing(&self) -> bool {
+        matches!(self, Password::Entered(slot) if lock(slot).is_none())
+    }
+
+    /// Keep `password` as the one entered.
+    pub fn enter(&self, password: Zeroizing<String>) {
+        if let Password::Entered(slot) = self {
+            *lock(slot) = Some(password);
+        }
+    }
+
+    /// Forget the password entered, e.g. once the server turned it down, so that it is asked
+    /// for again.
+    pub fn forget(&self) {
+        if let Password::Entered(slot) = self {
+            *lock(slot) = None;
+        }
+    }
+}
+
+// a panic while it was held leaves nothing half-written, as the password is only ever replaced
+fn lock(slot: &Mutex<Option<Zeroizing<String>>>) -> MutexGuard<'_, Option<Zeroizing<String>>> {
+    slot.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(feature = "keyring")]
+async fn read_keyring(service: &str, user: &str) -> Result<String, String> {
+    let (service, owned_user) = (service.to_string(), user.to_string());
+    // the keyrings are read synchronously, and the Secret Service one blocks on its own executor
+    tokio::task::spawn_blocking(move || {
+        keyring::Entry::new(&service, &owned_user).and_then(|entry| entry.get_password())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Couldn't read the password for {user} from the keyring: {e}"))
+}
+
+#[cfg(not(feature = "keyring"))]
+async fn read_keyring(_service: &str, _user: &str) -> Result<String, String> {
+    Err(String::from("eta was built without keyring support"))
+}
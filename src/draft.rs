@@ -0,0 +1,13 @@
+use crate::message::MessagePart;
+
+/// A message being composed, as it is saved so it survives eta closing before it is sent.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Draft {
+    pub to: String,
+    pub cc: String,
+    pub subject: String,
+    pub body: String,
+    /// The id of the message it replies to, if any, so the reply is still threaded with it.
+    pub reply_to: Option<u64>,
+    pub attachments: Vec<MessagePart>,
+}
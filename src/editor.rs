@@ -0,0 +1,37 @@
+use std::{io::Write, process::Command};
+
+use color_eyre::eyre::eyre;
+use ratatui::DefaultTerminal;
+
+use crate::terminal;
+
+/// Edit `text` in `$VISUAL` or `$EDITOR`, or `vi` if neither is set, handing the terminal over to
+/// it until it exits. Returns the text as it was saved.
+pub fn edit(terminal: &mut DefaultTerminal, text: &str) -> color_eyre::Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    // a new file with a random name that only the user can read, removed once it is dropped
+    let mut file = tempfile::Builder::new()
+        .prefix("eta-")
+        .suffix(".txt")
+        .tempfile()?;
+    file.write_all(text.as_bytes())?;
+    let path = file.path().to_path_buf();
+
+    // through the shell, since editors are often set along with arguments, e.g. `code --wait`
+    let status = terminal::suspend(terminal, || {
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!("{editor} \"$1\""))
+            .arg("sh")
+            .arg(&path)
+            .status()
+    })?;
+
+    match status {
+        Ok(status) if status.success() => std::fs::read_to_string(&path).map_err(Into::into),
+        Ok(status) => Err(eyre!("{editor} exited with {status}")),
+        Err(e) => Err(eyre!("couldn't run {editor}: {e}")),
+    }
+}
@@ -1,10 +1,18 @@
+use chrono::{DateTime, Local};
 use color_eyre::eyre::OptionExt;
 use futures::{FutureExt, StreamExt};
 use ratatui::crossterm::event::Event as CrosstermEvent;
-use std::time::Duration;
-use tokio::sync::mpsc;
+use std::{path::PathBuf, time::Duration};
+use tokio::{sync::mpsc, task::JoinHandle};
 
-use crate::message::Message;
+use crate::{
+    command::Command,
+    contacts::Contact,
+    draft::Draft,
+    message::{ConnectionState, Message, MessagePart, Mute},
+    outbox::Scheduled,
+    pgp::Verification,
+};
 
 /// The frequency at which tick events are emitted.
 const TICK_FPS: f64 = 4.0;
@@ -33,11 +41,66 @@ pub enum Event {
 /// You can extend this enum with your own custom events.
 #[derive(Clone, Debug)]
 pub enum AppEvent {
+    /// Names of the folders available from the message provider.
+    FoldersLoaded(Vec<String>),
+    /// The newest page of messages in the current folder.
     MessagesLoaded(Vec<Message>),
-    MessageBodyLoaded(u64, String),
+    /// The page of messages in the given folder before those already loaded.
+    OlderMessagesLoaded(String, Vec<Message>),
+    /// Messages that have arrived in the given folder since it was loaded.
+    NewMessagesLoaded(String, Vec<Message>),
+    /// How many messages there are in the given folder, and how many of them are unread.
+    MessageCountLoaded(String, usize, usize),
+    /// The messages found by a search for the given text.
+    SearchResultsLoaded(String, Vec<Message>),
+    /// Addresses of everyone messages have been exchanged with.
+    ContactsLoaded(Vec<String>),
+    /// Everyone in the address book.
+    AddressBookLoaded(Vec<Contact>),
+    /// The number of contacts added to the address book by an import.
+    ContactsImported(usize),
+    /// Addresses to complete the given partly typed address with.
+    AddressCompletionsLoaded(String, Vec<String>),
+    /// `Message-ID`s of the first messages of watched threads.
+    WatchedThreadsLoaded(Vec<String>),
+    /// Muted threads and senders.
+    MutesLoaded(Vec<Mute>),
+    /// Senders whose messages may load remote content.
+    RemoteContentSendersLoaded(Vec<String>),
+    /// Every tag some message has, in alphabetical order.
+    TagsLoaded(Vec<String>),
+    /// A message's parts, decrypted if need be, and what checking its signature found.
+    MessageBodyLoaded(u64, Vec<MessagePart>, Option<Verification>),
     MessageSent(Option<String>),
+    /// A message was put in the outbox to be sent at this time, or why it couldn't be.
+    MessageScheduled(Result<DateTime<Local>, String>),
+    /// The messages waiting in the outbox, soonest first.
+    ScheduledLoaded(Vec<Scheduled>),
+    /// The messages left being composed when eta last closed, oldest first, each with its id and
+    /// the message it replies to.
+    DraftsLoaded(Vec<(u64, Draft, Option<Box<Message>>)>),
+    /// A message was written to this file instead of being sent, as `dry_run_send` is set.
+    MessageWritten(PathBuf),
+    /// A message was exported to this file, or why it couldn't be.
+    MessageExported(Result<PathBuf, String>),
+    /// How many messages were imported, and why any others weren't.
+    MessagesImported(usize, Vec<String>),
+    /// The output of a command run to attach it to the message being composed, or why it failed,
+    /// and the command.
+    CommandOutput(String, Result<Vec<u8>, String>),
+    /// The message's attachments zipped up, or why they couldn't be.
+    Zipped(Result<Vec<u8>, String>),
     /// Send a message to an SMTP server
     SendMessage,
+    /// Send every message generated by the current mail merge.
+    SendMailMerge,
+    /// A mail merge was put in the outbox: the number of messages it holds, and why each of the
+    /// others couldn't be.
+    MailMergeQueued(usize, Vec<String>),
+    /// Whether the provider could reach the message store and the SMTP server when last checked.
+    ConnectionState(ConnectionState),
+    /// Carry out a command typed at the `:` command line.
+    RunCommand(Command),
     /// Quit the application.
     Quit,
     Error(String),
@@ -50,6 +113,8 @@ pub struct EventHandler {
     sender: mpsc::UnboundedSender<Event>,
     /// Event receiver channel.
     receiver: mpsc::UnboundedReceiver<Event>,
+    /// The task reading terminal events.
+    task: JoinHandle<color_eyre::Result<()>>,
 }
 
 impl EventHandler {
@@ -57,8 +122,24 @@ impl EventHandler {
     pub fn new() -> Self {
         let (sender, receiver) = mpsc::unbounded_channel();
         let actor = EventTask::new(sender.clone());
-        tokio::spawn(async { actor.run().await });
-        Self { sender, receiver }
+        let task = tokio::spawn(async { actor.run().await });
+        Self {
+            sender,
+            receiver,
+            task,
+        }
+    }
+
+    /// Stop reading terminal events, so that another program can have the terminal's input.
+    pub async fn pause(&mut self) {
+        self.task.abort();
+        let _ = (&mut self.task).await;
+    }
+
+    /// Start reading terminal events again after [`EventHandler::pause`].
+    pub fn resume(&mut self) {
+        let actor = EventTask::new(self.sender.clone());
+        self.task = tokio::spawn(async { actor.run().await });
     }
 
     /// Receives an event from the sender.
@@ -0,0 +1,317 @@
+use crate::{
+    app::{
+        App, ComposeFocus, ComposeMode, ContactsMode, HintAction, MailMergeMode, Mode, SettingsMode,
+    },
+    message::TRASH,
+    settings::SETTINGS,
+};
+
+/// A key binding, as shown in the hint bar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hint {
+    pub key: &'static str,
+    pub action: &'static str,
+    /// Lower is more important. The least important hints are dropped first when space is short.
+    pub priority: u8,
+}
+
+const fn hint(key: &'static str, action: &'static str, priority: u8) -> Hint {
+    Hint {
+        key,
+        action,
+        priority,
+    }
+}
+
+/// The key bindings available in the app's current mode, in the order they should be shown.
+pub fn hints(app: &App) -> Vec<Hint> {
+    match app.mode() {
+        Mode::LoadingMessages => vec![],
+        Mode::Search
+        | Mode::Filter
+        | Mode::CommandLine
+        | Mode::MessagePrompt(..)
+        | Mode::ComposePrompt(..) => vec![],
+        Mode::Confirm(..) => vec![hint("y", "Yes", 0), hint("n", "No", 0)],
+        Mode::Error(..) => vec![hint("Esc", "Dismiss", 0)],
+        Mode::Password(..) => vec![hint("Enter", "Send", 0), hint("Esc", "Cancel", 0)],
+        Mode::MessageTable(_) => {
+            let mut hints = vec![
+                hint("q", "Quit", 0),
+                hint("j", "Down", 2),
+                hint("k", "Up", 2),
+                hint("Enter", "View", 1),
+                hint("gg/G", "First/last", 4),
+                hint("Ctrl+d/u", "Half page", 4),
+                hint("c", "Compose", 1),
+                hint("/", "Search", 2),
+                hint("C", "Compose from clipboard", 3),
+                hint("r", "Reply", 1),
+                hint("R", "Reply all", 2),
+                hint("n", "Next unread", 2),
+                hint("N", "Previous unread", 3),
+                hint("s", "Flag", 2),
+                hint("f", "Filter", 2),
+                hint("F", "Flagged only", 3),
+                hint("h", "Folders", 2),
+                hint("m", "Move", 3),
+                hint("a", "Archive", 3),
+                hint("u", "Read/unread", 3),
+                hint("V", "Mark range", 4),
+                hint("*", "Mark all", 4),
+                hint("!", "Invert marks", 4),
+                hint("#", "Tag", 3),
+                hint("t", "Threads", 3),
+                hint("v", "Preview", 3),
+                hint("w", "Watch", 3),
+                hint("x", "Mute", 3),
+                hint("X", "Mute sender", 3),
+                hint(",", "Settings", 3),
+                hint(":", "Command", 3),
+                hint("A", "Address book", 3),
+                hint("O", "Scheduled", 3),
+                hint("P", "Profiles", 3),
+                hint("</>", "From width", 4),
+                hint("{/}", "Subject width", 4),
+            ];
+            if app.threaded() {
+                hints.push(hint("Space", "Expand", 2));
+            } else {
+                hints.push(hint("Space", "Mark", 3));
+            }
+            if !app.marked().is_empty() {
+                hints.push(hint("Esc", "Clear marks", 1));
+            } else if !app.filter().is_empty() {
+                hints.push(hint("Esc", "Clear filter", 1));
+            } else if app.search().is_some() {
+                hints.push(hint("Esc", "Leave search", 1));
+            }
+            if app.current_folder() == TRASH {
+                hints.push(hint("D", "Delete forever", 1));
+            } else {
+                hints.push(hint("d", "Delete", 2));
+            }
+            hints
+        }
+        Mode::Tags(..) => vec![
+            hint("Esc", "Cancel", 0),
+            hint("Down", "Down", 2),
+            hint("Up", "Up", 2),
+            hint("Enter", "Tag or untag", 0),
+        ],
+        Mode::Profiles(_) => vec![
+            hint("Esc", "Cancel", 0),
+            hint("j", "Down", 2),
+            hint("k", "Up", 2),
+            hint("Enter", "Switch", 0),
+        ],
+        Mode::MoveMessage(..) => vec![
+            hint("Esc", "Cancel", 0),
+            hint("j", "Down", 2),
+            hint("k", "Up", 2),
+            hint("Enter", "Move", 0),
+        ],
+        Mode::Folders => vec![
+            hint("Esc", "Back", 0),
+            hint("j", "Down", 2),
+            hint("k", "Up", 2),
+            hint("Enter", "Open", 0),
+        ],
+        Mode::Message(_) => {
+            let mut hints = vec![
+                hint("q", "Quit", 0),
+                hint("j", "Down", 2),
+                hint("k", "Up", 2),
+            ];
+            if !app.wrap_message() {
+                hints.extend([hint("h", "Left", 3), hint("l", "Right", 3)]);
+            }
+            hints.extend([
+                hint("s", "Flag", 2),
+                hint("r", "Reply", 1),
+                hint("R", "Reply all", 2),
+                hint("f", "Forward", 2),
+                hint("J", "Next", 2),
+                hint("K", "Previous", 2),
+                hint("n", "Next unread", 2),
+                hint("d", "Delete", 2),
+                hint("o", "Open HTML", 1),
+                hint("z", "Zen mode", 3),
+                hint("W", "Wrap", 3),
+                hint("u", "Links", 2),
+                hint("gf", "Link hints", 3),
+                hint("a", "Attachments", 2),
+                hint("e", "Export", 3),
+                hint(">", "Save text", 3),
+                hint("|", "Pipe", 3),
+                hint("y", "Copy", 2),
+                hint("#", "Tag", 3),
+                hint("L", "Remote content", 3),
+                hint("w", "Watch thread", 3),
+                hint("x", "Mute thread", 3),
+                hint("X", "Mute sender", 3),
+            ]);
+            if app.bounce().is_some_and(|bounce| bounce.original.is_some()) {
+                hints.push(hint("O", "Original", 1));
+            }
+            if app.can_rsvp() {
+                hints.extend([
+                    hint("A", "Accept", 1),
+                    hint("T", "Tentative", 1),
+                    hint("D", "Decline", 1),
+                ]);
+            }
+            hints
+        }
+        Mode::Attachment(..) => vec![
+            hint("q", "Back", 0),
+            hint("j", "Down", 1),
+            hint("k", "Up", 1),
+            hint("Tab", "Next attachment", 2),
+        ],
+        Mode::Links(..) => vec![
+            hint("Esc", "Back", 0),
+            hint("1-9", "Open", 1),
+            hint("j", "Down", 2),
+            hint("k", "Up", 2),
+            hint("Enter", "Open", 0),
+            hint("y", "Copy", 0),
+        ],
+        Mode::Copy(_) => vec![
+            hint("Esc", "Back", 0),
+            hint("f", "Copy sender address", 0),
+            hint("s", "Copy subject", 0),
+            hint("b", "Copy body", 0),
+        ],
+        Mode::Go(_) => vec![
+            hint("Esc", "Back", 0),
+            hint("f", "Open link", 0),
+            hint("y", "Copy link", 0),
+        ],
+        Mode::Hints(_, action, _) => vec![
+            hint("Esc", "Back", 0),
+            hint("Backspace", "Undo", 1),
+            match action {
+                HintAction::Open => hint("a-l", "Open the labelled link", 0),
+                HintAction::Copy => hint("a-l", "Copy the labelled link", 0),
+            },
+        ],
+        Mode::Compose(focus) => match focus {
+            ComposeFocus::To(ComposeMode::Editing) | ComposeFocus::Cc(ComposeMode::Editing)
+                if !app.completions().is_empty() =>
+            {
+                vec![
+                    hint("Tab", "Complete", 0),
+                    hint("Up/Down", "Choose", 1),
+                    hint("Esc", "Dismiss", 1),
+                ]
+            }
+            ComposeFocus::To(ComposeMode::Editing)
+            | ComposeFocus::Cc(ComposeMode::Editing)
+            | ComposeFocus::Subject(ComposeMode::Editing) => {
+                vec![hint("Esc", "Stop editing", 0), hint("Tab", "Next", 1)]
+            }
+            ComposeFocus::Message(ComposeMode::Editing) => vec![hint("Esc", "Stop editing", 0)],
+            _ => {
+                let mut hints = vec![
+                    hint("q", "Back", 0),
+                    hint("Tab", "Next", 1),
+                    hint("Enter", "Edit", 1),
+                    hint("S", "Send", 0),
+                    hint("L", "Send later", 2),
+                    hint("M", "Mail merge", 2),
+                    hint("g", "Signature", 3),
+                    hint("p", "PGP", 3),
+                    hint("b", "Address book", 2),
+                    hint("C-e", "Editor", 2),
+                    hint(":", "Command", 3),
+                    hint("D", "Discard", 3),
+                ];
+                if app.has_identities() {
+                    hints.insert(4, hint("f", "From", 2));
+                }
+                hints
+            }
+        },
+        Mode::Settings(SettingsMode::Normal(_)) => vec![
+            hint("q", "Back", 0),
+            hint("j", "Down", 2),
+            hint("k", "Up", 2),
+            hint("Enter", "Change", 1),
+        ],
+        Mode::Settings(SettingsMode::Editing(index)) if SETTINGS[*index].is_multiline() => {
+            vec![hint("Ctrl+s", "Save", 0), hint("Esc", "Cancel", 0)]
+        }
+        Mode::Settings(SettingsMode::Editing(_)) => {
+            vec![hint("Enter", "Save", 0), hint("Esc", "Cancel", 0)]
+        }
+        Mode::Contacts(ContactsMode::Normal(_)) => vec![
+            hint("q", "Back", 0),
+            hint("j", "Down", 2),
+            hint("k", "Up", 2),
+            hint("a", "Add", 1),
+            hint("e", "Edit", 1),
+            hint("d", "Delete", 1),
+            hint("i", "Import vCard", 2),
+        ],
+        Mode::Contacts(ContactsMode::Editing(_)) => {
+            vec![hint("Esc", "Cancel", 0), hint("Enter", "Save", 0)]
+        }
+        Mode::Contacts(ContactsMode::Importing) => {
+            vec![hint("Esc", "Cancel", 0), hint("Enter", "Import", 0)]
+        }
+        Mode::Contacts(ContactsMode::Picking(..)) => vec![
+            hint("Esc", "Back", 0),
+            hint("j", "Down", 2),
+            hint("k", "Up", 2),
+            hint("Enter", "Add recipient", 0),
+        ],
+        Mode::Scheduled(_) => vec![
+            hint("q", "Back", 0),
+            hint("j", "Down", 2),
+            hint("k", "Up", 2),
+            hint("s", "Send now", 1),
+            hint("d", "Cancel", 1),
+        ],
+        Mode::MailMerge(MailMergeMode::SelectCsv(_)) => {
+            vec![hint("Esc", "Back", 0), hint("Enter", "Preview", 0)]
+        }
+        Mode::MailMerge(MailMergeMode::Preview(_)) => vec![
+            hint("q", "Back", 0),
+            hint("j", "Next", 1),
+            hint("k", "Previous", 1),
+            hint("S", "Send all", 0),
+        ],
+    }
+}
+
+/// Lay out `hints` as a single line no wider than `width`, dropping the least important hints
+/// that don't fit while keeping the rest in order.
+pub fn hint_line(hints: &[Hint], width: u16) -> String {
+    let text_width = |hint: &Hint| hint.key.len() + hint.action.len() + 3;
+    // leave room for the two spaces that end the line
+    let mut remaining = (width as usize).saturating_sub(2);
+
+    let mut by_priority: Vec<usize> = (0..hints.len()).collect();
+    by_priority.sort_by_key(|&i| hints[i].priority);
+
+    let mut shown = vec![false; hints.len()];
+    for i in by_priority {
+        let needed = text_width(&hints[i]);
+        if needed <= remaining {
+            remaining -= needed;
+            shown[i] = true;
+        }
+    }
+
+    let mut line: String = hints
+        .iter()
+        .zip(shown)
+        .filter(|(_, shown)| *shown)
+        .map(|(hint, _)| format!("  {}:{}", hint.key, hint.action))
+        .collect();
+    if !line.is_empty() {
+        line.push_str("  ");
+    }
+    line
+}
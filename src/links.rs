@@ -0,0 +1,168 @@
+use linkify::{LinkFinder, LinkKind};
+use regex::Regex;
+use serde::Deserialize;
+
+/// A link found in a message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Link {
+    /// What the message says, e.g. the URL itself or a ticket reference like `JIRA-123`.
+    pub text: String,
+    pub url: String,
+}
+
+/// Turns references to tickets in an issue tracker into links.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TicketPattern {
+    /// Regular expression matching a reference, e.g. `JIRA-\d+` or `#(\d+)`.
+    pub pattern: String,
+    /// URL of the ticket, where `$0` is the whole reference and `$1`, `$2`, ... are its groups.
+    pub url: String,
+}
+
+/// Every URL and ticket reference in `text`, in the order they first appear, without duplicates.
+///
+/// Patterns that are not valid regular expressions are ignored; [`crate::config::Config`] checks
+/// them when it is loaded.
+pub fn find_links(text: &str, tickets: &[TicketPattern]) -> Vec<Link> {
+    let mut finder = LinkFinder::new();
+    finder.kinds(&[LinkKind::Url]);
+
+    let urls: Vec<(usize, usize, Link)> = finder
+        .links(text)
+        .map(|link| {
+            let url = link.as_str().to_string();
+            (
+                link.start(),
+                link.end(),
+                Link {
+                    text: url.clone(),
+                    url,
+                },
+            )
+        })
+        .collect();
+
+    let mut found = urls.clone();
+    for ticket in tickets {
+        let Ok(regex) = Regex::new(&ticket.pattern) else {
+            continue;
+        };
+        for captures in regex.captures_iter(text) {
+            let reference = captures.get(0).expect("group 0 is the whole match");
+            // e.g. the #123 in https://example.com/page#123 is part of the URL
+            let in_url = urls
+                .iter()
+                .any(|(start, end, _)| reference.start() < *end && *start < reference.end());
+            if in_url {
+                continue;
+            }
+
+            let mut url = String::new();
+            captures.expand(&ticket.url, &mut url);
+            let link = Link {
+                text: reference.as_str().to_string(),
+                url,
+            };
+            found.push((reference.start(), reference.end(), link));
+        }
+    }
+    found.sort_by_key(|(start, _, _)| *start);
+
+    let mut links: Vec<Link> = vec![];
+    for (_, _, link) in found {
+        if !links.iter().any(|l| l.url == link.url) {
+            links.push(link);
+        }
+    }
+    links
+}
+
+/// A URL or email address shown in a message, which link hints can be followed to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Url(String),
+    Email(String),
+}
+
+impl Target {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Target::Url(text) | Target::Email(text) => text,
+        }
+    }
+}
+
+/// Every URL and email address in `text`, with the byte it starts at.
+pub fn find_targets(text: &str) -> Vec<(usize, Target)> {
+    let mut finder = LinkFinder::new();
+    finder.kinds(&[LinkKind::Url, LinkKind::Email]);
+    finder
+        .links(text)
+        .map(|link| {
+            let text = link.as_str().to_string();
+            let target = match link.kind() {
+                LinkKind::Email => Target::Email(text),
+                _ => Target::Url(text),
+            };
+            (link.start(), target)
+        })
+        .collect()
+}
+
+/// Keys link hints are labelled with, those of the home row.
+const HINT_KEYS: &[char] = &['a', 's', 'd', 'f', 'g', 'h', 'j', 'k', 'l'];
+
+/// Labels for `count` link hints, all of the same length so that none starts another, and as
+/// short as they can be.
+pub fn hint_labels(count: usize) -> Vec<String> {
+    let mut length = 1;
+    while HINT_KEYS.len().pow(length) < count {
+        length += 1;
+    }
+    (0..count)
+        .map(|mut i| {
+            let mut label = vec![];
+            for _ in 0..length {
+                label.push(HINT_KEYS[i % HINT_KEYS.len()]);
+                i /= HINT_KEYS.len();
+            }
+            label.iter().rev().collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_are_as_short_as_they_can_be() {
+        assert_eq!(hint_labels(3), ["a", "s", "d"]);
+        assert_eq!(hint_labels(9).last().map(String::as_str), Some("l"));
+        assert!(hint_labels(0).is_empty());
+    }
+
+    #[test]
+    fn labels_all_have_the_same_length_so_none_starts_another() {
+        let labels = hint_labels(10);
+
+        assert_eq!(&labels[..3], ["aa", "as", "ad"]);
+        assert_eq!(labels[9], "sa");
+        assert!(labels.iter().all(|label| label.len() == 2));
+        let mut unique = labels.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), labels.len());
+    }
+
+    #[test]
+    fn urls_and_addresses_are_targets() {
+        assert_eq!(
+            find_targets("Mail jane@example.com or see https://example.com/a."),
+            [
+                (5, Target::Email(String::from("jane@example.com"))),
+                (29, Target::Url(String::from("https://example.com/a"))),
+            ]
+        );
+    }
+}
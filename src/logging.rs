@@ -0,0 +1,31 @@
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::filter::LevelFilter;
+
+use crate::profile;
+
+/// How many days of logs are kept.
+const LOG_DAYS: usize = 7;
+
+/// Log to `eta.log` in `$XDG_STATE_HOME/eta/`, starting a new file each day, since the terminal
+/// is taken up by the interface. Logs are written in the background until the returned guard is
+/// dropped.
+pub fn init(level: LevelFilter) -> color_eyre::Result<Option<WorkerGuard>> {
+    if level == LevelFilter::OFF {
+        return Ok(None);
+    }
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("eta.log")
+        .max_log_files(LOG_DAYS)
+        .build(profile::state_home())?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+    tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_max_level(level)
+        .init();
+    Ok(Some(guard))
+}
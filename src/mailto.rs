@@ -0,0 +1,112 @@
+use crate::draft::Draft;
+
+/// The message a `mailto:` link (RFC 6068) asks for, e.g.
+/// `mailto:jane@example.com?subject=Quarterly%20review&cc=joe@example.com`. Headers other than
+/// To, Cc, Subject and the body are left out, since the composer has nowhere to put them.
+pub fn parse(link: &str) -> Result<Draft, String> {
+    let rest = link
+        .get(..7)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+        .map(|_| &link[7..])
+        .ok_or_else(|| format!("not a mailto: link: {link}"))?;
+    let (to, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+    let mut draft = Draft::default();
+    let mut recipients = vec![decode(to)];
+    let mut cc = vec![];
+    for field in query.split('&') {
+        let (name, value) = field.split_once('=').unwrap_or((field, ""));
+        let value = decode(value);
+        match name.to_ascii_lowercase().as_str() {
+            "to" => recipients.push(value),
+            "cc" => cc.push(value),
+            "subject" => draft.subject = value,
+            // lines are broken with CRLF in links, but with LF in the composer
+            "body" => draft.body = value.replace("\r\n", "\n"),
+            _ => {}
+        }
+    }
+    draft.to = join(recipients);
+    draft.cc = join(cc);
+    Ok(draft)
+}
+
+/// Addresses from several fields, each of which may list several, as the composer lists them.
+fn join(fields: Vec<String>) -> String {
+    fields
+        .iter()
+        .flat_map(|field| field.split(','))
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Undo percent-encoding, e.g. `%20` for a space. Anything that isn't valid encoding is kept as
+/// it is.
+fn decode(text: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = text.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| after.get(..2))
+            .flatten()
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &after[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = after;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_read_from_the_link() {
+        let draft = parse(
+            "MAILTO:jane@example.com,%20joe@example.com?Subject=Quarterly%20review\
+             &cc=boss@example.com&to=team@example.com&body=Hi%2C%0D%0Athanks&in-reply-to=x",
+        )
+        .unwrap();
+
+        assert_eq!(
+            draft,
+            Draft {
+                to: String::from("jane@example.com, joe@example.com, team@example.com"),
+                cc: String::from("boss@example.com"),
+                subject: String::from("Quarterly review"),
+                body: String::from("Hi,\nthanks"),
+                ..Draft::default()
+            }
+        );
+    }
+
+    #[test]
+    fn a_link_may_have_no_recipient() {
+        let draft = parse("mailto:?subject=Hello").unwrap();
+
+        assert_eq!((draft.to.as_str(), draft.subject.as_str()), ("", "Hello"));
+    }
+
+    #[test]
+    fn other_links_are_refused() {
+        assert!(parse("https://example.com").is_err());
+        assert!(parse("mail").is_err());
+    }
+
+    #[test]
+    fn invalid_encoding_is_kept() {
+        assert_eq!(decode("100%25 %zz %4"), "100% %zz %4");
+        assert_eq!(decode("caf%C3%A9"), "café");
+    }
+}
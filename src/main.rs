@@ -1,15 +1,66 @@
-use crate::app::App;
+use clap::Parser;
+
+use crate::{app::App, cli::Args};
 
 mod app;
+mod attach;
+mod bounce;
+mod calendar;
+mod cli;
+mod clipboard;
+mod command;
+mod config;
+mod contacts;
+mod credentials;
+mod draft;
+mod editor;
 mod event;
+mod keymap;
+mod links;
+mod logging;
+mod mailto;
+mod merge;
 mod message;
+mod mime;
+mod notify;
+mod outbox;
+mod passphrase;
+mod pgp;
+mod pipe;
+mod preview;
+mod profile;
+mod query;
+mod rules;
+mod settings;
+mod signature;
+mod sync;
+mod terminal;
+mod theme;
+mod thread;
+mod tls;
 mod ui;
+mod vcard;
+mod wrap;
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
+    let args = Args::parse();
+    let _log = logging::init(args.log_level)?;
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "starting");
+    // read before the terminal is taken over, which reads keys from the terminal itself
+    let draft = args.draft()?;
+    let profile = args.profile()?;
+    profile.adopt_old_files()?;
+    let mut app = App::new(profile, args.dry_run_send)?;
+    if let Some(draft) = draft {
+        app.compose_draft(draft);
+    }
     let terminal = ratatui::init();
-    let result = App::new().run(terminal).await;
+    let result = app.run(terminal).await;
     ratatui::restore();
+    if let Err(e) = &result {
+        tracing::error!("{e}");
+    }
     result
 }
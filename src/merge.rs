@@ -0,0 +1,165 @@
+use color_eyre::eyre::eyre;
+
+use crate::message::Message;
+
+/// A mail merge: one template message rendered once for every record of a CSV file.
+///
+/// Placeholders take the form `{{column}}` and may appear in the To, Subject, and body of the
+/// template. Every placeholder must name a column in the CSV header row.
+#[derive(Debug, Default, Clone)]
+pub struct MailMerge {
+    template: Message,
+    headers: Vec<String>,
+    records: Vec<Vec<String>>,
+}
+
+impl MailMerge {
+    /// Read the CSV file at `path` and validate the template's placeholders against its header.
+    pub fn from_csv(template: Message, path: &str) -> color_eyre::Result<Self> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let headers: Vec<String> = reader
+            .headers()?
+            .iter()
+            .map(|h| h.trim().to_string())
+            .collect();
+
+        let mut records = vec![];
+        for record in reader.records() {
+            records.push(record?.iter().map(String::from).collect());
+        }
+
+        if records.is_empty() {
+            return Err(eyre!("{path} has no recipients"));
+        }
+
+        for text in [template.to(), template.subject(), template.body()] {
+            for name in placeholders(text) {
+                if !headers.iter().any(|h| h == name) {
+                    return Err(eyre!("no column named {name} in {path}"));
+                }
+            }
+        }
+
+        Ok(Self {
+            template,
+            headers,
+            records,
+        })
+    }
+
+    /// Render the template for every record. Everything else about the template, such as the
+    /// identity it is sent from, its Cc and its attachments, is kept as it is.
+    pub fn messages(&self) -> Vec<Message> {
+        self.records
+            .iter()
+            .map(|record| {
+                let mut message = self.template.clone();
+                message.set_to(self.render(self.template.to(), record));
+                message.set_subject(self.render(self.template.subject(), record));
+                message.set_body(self.render(self.template.body(), record));
+                message
+            })
+            .collect()
+    }
+
+    fn render(&self, text: &str, record: &[String]) -> String {
+        let mut rendered = String::from(text);
+        for (header, value) in self.headers.iter().zip(record.iter()) {
+            rendered = rendered.replace(&format!("{{{{{header}}}}}"), value);
+        }
+        rendered
+    }
+}
+
+/// Find the names of all `{{placeholder}}`s in `text`.
+fn placeholders(text: &str) -> Vec<&str> {
+    let mut names = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        rest = &rest[start + 2..];
+        match rest.find("}}") {
+            Some(end) => {
+                names.push(&rest[..end]);
+                rest = &rest[end + 2..];
+            }
+            None => break,
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::message::MessagePart;
+
+    fn template() -> Message {
+        let mut template = Message::default();
+        template.set_to(String::from("{{email}}"));
+        template.set_subject(String::from("Hello {{name}}"));
+        template.set_body(String::from("Dear {{name}},\nyour code is {{code}}."));
+        template.set_from(String::from("Sales <sales@example.com>"));
+        template.set_cc(String::from("boss@example.com"));
+        template.set_parts(vec![MessagePart::new(
+            String::from("application/pdf"),
+            Some(String::from("offer.pdf")),
+            b"%PDF".to_vec(),
+        )]);
+        template
+    }
+
+    fn csv(text: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn placeholders_are_found_in_order() {
+        assert_eq!(
+            placeholders("{{a}} and {{b c}}, {{a}} {{unclosed"),
+            ["a", "b c", "a"]
+        );
+        assert!(placeholders("no placeholders {here}").is_empty());
+    }
+
+    #[test]
+    fn each_record_gets_a_message() {
+        let file = csv("email, name ,code\njane@example.com,Jane,J1\nbob@example.com,Bob,B2\n");
+        let merge = MailMerge::from_csv(template(), file.path().to_str().unwrap()).unwrap();
+
+        let messages = merge.messages();
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].to(), "bob@example.com");
+        assert_eq!(messages[1].subject(), "Hello Bob");
+        assert_eq!(messages[1].body(), "Dear Bob,\nyour code is B2.");
+        assert_eq!(messages[1].from(), "Sales <sales@example.com>");
+        assert_eq!(messages[1].cc(), "boss@example.com");
+        let attachments: Vec<_> = messages[1]
+            .attachments()
+            .filter_map(|a| a.filename())
+            .collect();
+        assert_eq!(attachments, ["offer.pdf"]);
+    }
+
+    #[test]
+    fn unknown_columns_and_empty_files_are_refused() {
+        let file = csv("email,name\n");
+        let path = file.path().to_str().unwrap();
+        let error = MailMerge::from_csv(template(), path).unwrap_err();
+        assert_eq!(error.to_string(), format!("{path} has no recipients"));
+
+        let mut template = template();
+        template.set_subject(String::from("Hello {{first_name}}"));
+        let file = csv("email,name\njane@example.com,Jane\n");
+        let path = file.path().to_str().unwrap();
+        let error = MailMerge::from_csv(template, path).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("no column named first_name in {path}")
+        );
+    }
+}
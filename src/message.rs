@@ -1,24 +1,128 @@
-use std::{str::FromStr, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+};
 
 use lettre::{
-    AsyncSmtpTransport, AsyncTransport, Tokio1Executor, message::header::ContentType,
-    transport::smtp::authentication::Credentials,
+    Address,
+    address::AddressError,
+    address::Envelope,
+    message::{Attachment, Mailbox, MultiPart, SinglePart, header::ContentType},
+    transport::smtp::{
+        self, SUBMISSIONS_PORT,
+        authentication::{Credentials, Mechanism},
+        client::AsyncSmtpConnection,
+        commands::{Data, Mail, Rcpt},
+        extension::{ClientId, Extension, MailBodyParameter, MailParameter},
+    },
 };
 use serde::Deserialize;
-use tokio::sync::mpsc;
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{Notify, mpsc, watch},
+};
+use tracing::{Instrument, Span, info_span};
+use zeroize::Zeroizing;
 
-use crate::event::{AppEvent, Event};
+use crate::{
+    bounce::{Bounce, DeliveryFailure},
+    contacts::{self, Contact},
+    credentials::Password,
+    draft::Draft,
+    event::{AppEvent, Event},
+    mime,
+    outbox::Scheduled,
+    pgp::{self, Content, Protection, Verification},
+    query::{self, Condition},
+    rules::{self, Fields, Outcome, Rules},
+    signature::SignatureText,
+    sync::Schedule,
+    tls::TlsConfig,
+    wrap::{self, BodyFormat},
+};
 
-use futures::TryStreamExt;
+use chrono::{DateTime, Local, TimeDelta};
 use sqlx::{Row, sqlite::SqliteRow};
 
+/// Something whose new messages are marked as read without being shown as new.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mute {
+    /// The thread starting with the message with this `Message-ID`.
+    Thread(String),
+    /// Everything from this address.
+    Sender(String),
+}
+
+impl Mute {
+    fn kind(&self) -> &'static str {
+        match self {
+            Mute::Thread(_) => "thread",
+            Mute::Sender(_) => "sender",
+        }
+    }
+
+    fn value(&self) -> &str {
+        match self {
+            Mute::Thread(value) | Mute::Sender(value) => value,
+        }
+    }
+
+    fn from_row(kind: &str, value: String) -> Option<Self> {
+        match kind {
+            "thread" => Some(Mute::Thread(value)),
+            "sender" => Some(Mute::Sender(value)),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Message {
     id: u64,
     from: String,
     to: String,
+    /// Addresses copied on the message, separated by commas.
+    cc: String,
     subject: String,
     body: String,
+    /// When the message was sent, in seconds since the Unix epoch.
+    date: Option<i64>,
+    unread: bool,
+    flagged: bool,
+    /// The folder the message was loaded from.
+    folder: String,
+    /// The size of the message as it was received, in bytes.
+    size: Option<u64>,
+    /// The `Message-ID` header, which replies refer to.
+    message_id: Option<String>,
+    /// The `Message-ID` of the message this replies to.
+    in_reply_to: Option<String>,
+    /// The `Message-ID`s of earlier messages in the conversation, oldest first.
+    references: Vec<String>,
+    /// Decoded MIME parts, once the full message has been loaded.
+    parts: Vec<MessagePart>,
+    /// How the message is to be signed or encrypted when it is sent.
+    protection: Protection,
+    /// What decrypting or verifying the message found, if it was signed or encrypted.
+    verification: Option<Verification>,
+    /// Tags the message has been given, by hand or by rules, e.g. `finance`.
+    tags: Vec<String>,
+    /// The start of the body, on one line, shown after the subject in the table.
+    snippet: String,
+}
+
+/// A decoded leaf part of a MIME message.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MessagePart {
+    /// Lowercase MIME type, e.g. `text/plain`.
+    content_type: String,
+    /// Attachment filename, if the part is an attachment.
+    filename: Option<String>,
+    /// Content with any transfer encoding removed. Text is converted to UTF-8.
+    content: Vec<u8>,
+    /// For an image shown inline, the `Content-ID` the HTML body refers to it by as `cid:`.
+    content_id: Option<String>,
 }
 
 impl Message {
@@ -27,7 +131,21 @@ impl Message {
             id,
             from,
             to,
+            cc: String::new(),
             subject,
+            date: None,
+            unread: false,
+            flagged: false,
+            folder: String::new(),
+            size: None,
+            message_id: None,
+            in_reply_to: None,
+            references: vec![],
+            parts: vec![],
+            protection: Protection::None,
+            verification: None,
+            tags: vec![],
+            snippet: snippet(&body),
             body,
         }
     }
@@ -44,6 +162,10 @@ impl Message {
         &self.to
     }
 
+    pub fn cc(&self) -> &str {
+        &self.cc
+    }
+
     pub fn subject(&self) -> &str {
         &self.subject
     }
@@ -52,6 +174,130 @@ impl Message {
         &self.body
     }
 
+    pub fn date(&self) -> Option<DateTime<Local>> {
+        self.date
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map(|date| date.with_timezone(&Local))
+    }
+
+    pub fn folder(&self) -> &str {
+        &self.folder
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    pub fn is_unread(&self) -> bool {
+        self.unread
+    }
+
+    pub fn is_flagged(&self) -> bool {
+        self.flagged
+    }
+
+    pub fn message_id(&self) -> Option<&str> {
+        self.message_id.as_deref()
+    }
+
+    pub fn in_reply_to(&self) -> Option<&str> {
+        self.in_reply_to.as_deref()
+    }
+
+    pub fn references(&self) -> &[String] {
+        &self.references
+    }
+
+    /// The `Message-ID` of the first message in this one's conversation, as far as its headers
+    /// tell.
+    pub fn thread_root(&self) -> Option<&str> {
+        self.references
+            .first()
+            .map(String::as_str)
+            .or(self.in_reply_to())
+            .or(self.message_id())
+    }
+
+    /// Whether this message is, or replies to, the message with the given `Message-ID`.
+    pub fn is_in_thread(&self, root: &str) -> bool {
+        self.message_id() == Some(root)
+            || self.in_reply_to() == Some(root)
+            || self.references.iter().any(|id| id == root)
+    }
+
+    pub fn parts(&self) -> &[MessagePart] {
+        &self.parts
+    }
+
+    /// The first part with the given MIME type.
+    pub fn part(&self, content_type: &str) -> Option<&MessagePart> {
+        self.parts.iter().find(|p| p.content_type() == content_type)
+    }
+
+    /// The text to show as the body of the message: the text/plain part when there is one, then
+    /// the text/html part converted to text, then any other inline text part apart from
+    /// calendar invites, which are summed up instead, and finally the plain body.
+    pub fn text_body(&self) -> String {
+        let inline = || self.parts.iter().filter(|p| !p.is_attachment());
+
+        inline()
+            .find(|p| p.content_type() == "text/plain")
+            .map(|p| p.text())
+            .or_else(|| {
+                inline()
+                    .find(|p| p.content_type() == "text/html")
+                    .map(|p| mime::html_to_text(&p.text()))
+            })
+            .or_else(|| {
+                inline()
+                    .find(|p| {
+                        p.content_type().starts_with("text/") && p.content_type() != "text/calendar"
+                    })
+                    .map(|p| p.text())
+            })
+            .unwrap_or_else(|| self.body.clone())
+    }
+
+    /// What went wrong, if this is a delivery status notification. Bounces that aren't
+    /// `multipart/report` messages may still give the fields in their text.
+    pub fn bounce(&self) -> Option<Bounce> {
+        let report = match self.part("message/delivery-status") {
+            Some(part) => part.text(),
+            None => self.text_body(),
+        };
+        let failures = DeliveryFailure::from_dsn(&report);
+        if failures.is_empty() {
+            return None;
+        }
+        let original = ["message/rfc822", "text/rfc822-headers"]
+            .into_iter()
+            .find_map(|content_type| self.part(content_type))
+            .and_then(|part| mime::headers(part.content()))
+            .and_then(|headers| headers.message_id);
+        Some(Bounce { failures, original })
+    }
+
+    /// Parts which are attachments, rather than the body.
+    pub fn attachments(&self) -> impl Iterator<Item = &MessagePart> {
+        self.parts.iter().filter(|p| p.is_attachment())
+    }
+
+    pub fn protection(&self) -> Protection {
+        self.protection
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn snippet(&self) -> &str {
+        &self.snippet
+    }
+
+    pub fn verification(&self) -> Option<&Verification> {
+        self.verification.as_ref()
+    }
+
     pub fn set_id(&mut self, id: u64) {
         self.id = id;
     }
@@ -64,6 +310,10 @@ impl Message {
         self.to = to;
     }
 
+    pub fn set_cc(&mut self, cc: String) {
+        self.cc = cc;
+    }
+
     pub fn set_subject(&mut self, subject: String) {
         self.subject = subject;
     }
@@ -71,12 +321,199 @@ impl Message {
     pub fn set_body(&mut self, body: String) {
         self.body = body;
     }
+
+    /// Set when the message was sent, in seconds since the Unix epoch.
+    pub fn set_date(&mut self, date: Option<i64>) {
+        self.date = date;
+    }
+
+    pub fn set_in_reply_to(&mut self, in_reply_to: Option<String>) {
+        self.in_reply_to = in_reply_to;
+    }
+
+    pub fn set_references(&mut self, references: Vec<String>) {
+        self.references = references;
+    }
+
+    pub fn set_unread(&mut self, unread: bool) {
+        self.unread = unread;
+    }
+
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = tags;
+    }
+
+    pub fn set_flagged(&mut self, flagged: bool) {
+        self.flagged = flagged;
+    }
+
+    pub fn set_parts(&mut self, parts: Vec<MessagePart>) {
+        self.parts = parts;
+    }
+
+    pub fn set_protection(&mut self, protection: Protection) {
+        self.protection = protection;
+    }
+
+    pub fn set_verification(&mut self, verification: Option<Verification>) {
+        self.verification = verification;
+    }
+}
+
+impl MessagePart {
+    pub fn new(content_type: String, filename: Option<String>, content: Vec<u8>) -> Self {
+        MessagePart {
+            content_type,
+            filename,
+            content,
+            content_id: None,
+        }
+    }
+
+    /// This part as an image shown inline, referred to as `cid:{content_id}`.
+    pub fn with_content_id(self, content_id: String) -> Self {
+        MessagePart {
+            content_id: Some(content_id),
+            ..self
+        }
+    }
+
+    /// A single text/plain part, for messages stored without their MIME structure.
+    pub fn plain(text: String) -> Self {
+        Self::new(String::from("text/plain"), None, text.into_bytes())
+    }
+
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn content_id(&self) -> Option<&str> {
+        self.content_id.as_deref()
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.content).into_owned()
+    }
+
+    pub fn is_attachment(&self) -> bool {
+        self.filename.is_some()
+    }
 }
 
+/// Folders every account has, in the order they are listed.
+pub const STANDARD_FOLDERS: [&str; 5] = ["Inbox", "Sent", "Drafts", TRASH, ARCHIVE];
+
+/// Deleted messages are moved here, until they are deleted for good.
+pub const TRASH: &str = "Trash";
+
+/// Messages that are dealt with, but worth keeping, are moved here.
+pub const ARCHIVE: &str = "Archive";
+
+/// How many characters of a message's body are kept as its snippet.
+const SNIPPET_LENGTH: usize = 100;
+
+/// How many messages are loaded into the table at a time.
+pub const PAGE_SIZE: usize = 200;
+
 pub trait MessageProvider {
-    fn get_messages(&self);
+    /// List the standard folders, followed by any others the provider has.
+    fn get_folders(&self);
+    /// Count the messages in `folder`, and load the newest [`PAGE_SIZE`] of them.
+    fn get_messages(&self, folder: &str);
+    /// Load the [`PAGE_SIZE`] messages in `folder` that come before the message with id `before`.
+    fn get_older_messages(&self, folder: &str, before: u64);
+    /// Check each folder for messages that have arrived since, in the background and on
+    /// `schedule`, replacing the schedule of any checks already going. The messages found in a
+    /// folder are sent as [`AppEvent::NewMessagesLoaded`].
+    fn poll_folders(&self, schedule: Schedule);
+    /// List every address messages have been exchanged with, to check recipients against.
+    fn get_contacts(&self);
+    /// List everyone in the address book.
+    fn get_address_book(&self);
+    /// Add a contact to the address book, or update it if it has been saved before.
+    fn save_contact(&self, contact: &Contact);
+    /// Remove a contact from the address book.
+    fn delete_contact(&self, id: u64);
+    /// Add contacts to the address book, skipping any whose address is already in it.
+    fn import_contacts(&self, contacts: Vec<Contact>);
+    /// Look up contacts and previously seen addresses to complete a partly typed address with.
+    fn complete_address(&self, query: &str);
     fn get_message(&self, id: u64);
+    /// Write the message with id `id` to a new file at `path`, as it was received or sent.
+    fn export_message(&self, id: u64, path: &Path);
+    /// Add the messages in the `.eml` files at `paths` to `folder`, as read.
+    fn import_messages(&self, paths: Vec<PathBuf>, folder: &str);
+    /// Find the messages in any folder whose subject, body or sender contain every word of
+    /// `query`, best matches first.
+    fn search_messages(&self, query: &str);
     fn send_message(&self, message: &Message);
+    /// Mark messages as read or unread.
+    fn set_unread(&self, ids: &[u64], unread: bool);
+    /// Flag a message for attention, or clear the flag.
+    fn set_flagged(&self, id: u64, flagged: bool);
+    /// Move messages to another folder.
+    fn move_messages(&self, ids: &[u64], folder: &str);
+    /// Delete messages for good.
+    fn delete_messages(&self, ids: &[u64]);
+    /// Delete messages that were moved to the trash more than `days` days ago.
+    fn empty_trash(&self, days: u32);
+    /// List the `Message-ID`s of the first messages of watched threads.
+    fn get_watched_threads(&self);
+    /// Watch the thread starting with the message with the given `Message-ID`, or stop watching.
+    fn set_thread_watched(&self, root: &str, watched: bool);
+    /// List the muted threads and senders.
+    fn get_mutes(&self);
+    /// Mute a thread or sender, or unmute it.
+    fn set_muted(&self, mute: &Mute, muted: bool);
+    /// List every tag some message has.
+    fn get_tags(&self);
+    /// Give the message with the given id `tag`, or take it away.
+    fn set_tagged(&self, id: u64, tag: &str, tagged: bool);
+    /// List the senders whose messages may load remote content.
+    fn get_remote_content_senders(&self);
+    /// Allow or block remote content in messages from `sender`.
+    fn set_remote_content_allowed(&self, sender: &str, allowed: bool);
+    /// Put a batch of messages in the outbox, e.g. for a mail merge, to be sent one after another
+    /// no faster than `merge_rate_limit` allows.
+    fn send_messages(&self, messages: Vec<Message>);
+    /// Keep a message in the outbox, to be sent at `send_at`.
+    fn schedule_message(&self, message: &Message, send_at: DateTime<Local>);
+    /// List the messages waiting in the outbox.
+    fn get_scheduled(&self);
+    /// Send the messages in the outbox as they fall due, in the background, for as long as the
+    /// provider is kept.
+    fn send_outbox(&self);
+    /// Send a message from the outbox straight away, even if sending it failed before.
+    fn send_scheduled_now(&self, id: u64);
+    /// Take a message out of the outbox without sending it.
+    fn cancel_scheduled(&self, id: u64);
+    /// Keep a message being composed as the draft with id `id`, replacing what was kept of it
+    /// before.
+    fn save_draft(&self, id: u64, draft: &Draft);
+    /// Forget the draft with id `id`, once it has been sent or discarded.
+    fn delete_draft(&self, id: u64);
+    /// Load every draft kept by `save_draft`, oldest first.
+    fn get_drafts(&self);
+    /// Check that the message store and the SMTP server can be reached.
+    fn check_connection(&self);
+}
+
+/// Whether the provider can reach what it depends on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Being checked, for the first time or again.
+    Connecting,
+    Online,
+    /// Something couldn't be reached, and why.
+    Offline(String),
 }
 
 #[derive(Debug)]
@@ -90,45 +527,222 @@ pub struct SqliteMessageProvider {
     connection: Arc<sqlx::SqlitePool>,
     event_sender: mpsc::UnboundedSender<Event>,
     smtp_config: SmtpConfig,
+    /// Whether the database is opened with a passphrase.
+    encrypted: bool,
+    /// Applied to messages as they arrive or are imported.
+    rules: Arc<Rules>,
+    /// When folders are checked for new messages, once checks have started. Checks stop when
+    /// this is dropped with the provider.
+    polling: watch::Sender<Option<Schedule>>,
+    /// Wakes the outbox to look for messages that are due. The outbox stops once this is dropped
+    /// with the provider.
+    outbox: Arc<Notify>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
-struct SmtpConfig {
+pub struct SmtpConfig {
     ip: String,
     username: String,
-    password: String,
+    /// Asked for on the first send when left out.
+    #[serde(default)]
+    password: Option<Password>,
+    /// Maximum number of messages sent per minute during a mail merge.
+    #[serde(default = "default_merge_rate_limit")]
+    merge_rate_limit: u32,
+    /// Write messages to files in this directory instead of sending them.
+    #[serde(default)]
+    dry_run_send: Option<PathBuf>,
+    /// How many more times a message is sent after a temporary failure before giving up.
+    #[serde(default = "default_send_retries")]
+    send_retries: u32,
+    /// How the server's certificate is checked.
+    #[serde(default)]
+    tls: TlsConfig,
+    /// Whether long lines of the body are broken before sending, and how.
+    #[serde(default)]
+    body_format: BodyFormat,
+    /// Other addresses messages may be sent from.
+    #[serde(default)]
+    identities: Vec<Identity>,
+}
+
+/// Another address messages may be sent from, from an `[[identities]]` table, such as a
+/// plus address or a role address like `support@`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Identity {
+    /// The address messages are sent from as this identity, e.g. `bob+lists@example.com` or
+    /// `Bob <bob@work.example.com>`.
+    address: String,
+    /// The name shown with the address, in place of any given in `address`.
+    #[serde(default)]
+    name: Option<String>,
+    /// Where replies are to go, if not to `address`.
+    #[serde(default)]
+    reply_to: Option<String>,
+    /// Used in place of the `[signature]` table's.
+    #[serde(default)]
+    signature: SignatureText,
+    /// The SMTP server to send through, when it isn't the account's own.
+    #[serde(default)]
+    host: Option<String>,
+    /// Who to log in to `host` as, if not the identity's address.
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    password: Option<Password>,
+    #[serde(default)]
+    tls: TlsConfig,
+}
+
+impl Identity {
+    /// The From field of messages sent as this identity, with its name.
+    pub fn from(&self) -> String {
+        match (&self.name, self.address.parse::<Mailbox>()) {
+            (Some(name), Ok(mailbox)) => {
+                Mailbox::new(Some(name.clone()), mailbox.email).to_string()
+            }
+            _ => self.address.clone(),
+        }
+    }
+
+    pub fn signature(&self) -> Option<&str> {
+        self.signature.text.as_deref()
+    }
+
+    /// Whether the identity is an account of its own, on another server, rather than another
+    /// address on the account's.
+    pub fn is_own_account(&self) -> bool {
+        self.host.is_some()
+    }
+
+    /// The identity's address without its name, in lowercase.
+    fn email(&self) -> Option<String> {
+        let mailbox: Mailbox = self.address.parse().ok()?;
+        Some(mailbox.email.to_string().to_lowercase())
+    }
+}
+
+fn default_merge_rate_limit() -> u32 {
+    20
+}
+
+fn default_send_retries() -> u32 {
+    3
+}
+
+impl SmtpConfig {
+    /// The account's own address, which messages are sent from.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// How long to wait between the messages of a mail merge, to send no more than
+    /// `merge_rate_limit` a minute. Dry runs don't wait.
+    fn merge_interval(&self) -> std::time::Duration {
+        if self.dry_run_send.is_some() {
+            return std::time::Duration::ZERO;
+        }
+        std::time::Duration::from_secs(60) / self.merge_rate_limit.max(1)
+    }
+
+    /// Write messages to files in `dir` instead of sending them.
+    pub fn set_dry_run_send(&mut self, dir: PathBuf) {
+        self.dry_run_send = Some(dir);
+    }
+
+    /// Whether sending has to wait for the password to be entered.
+    pub fn needs_password(&self) -> bool {
+        self.password.as_ref().is_none_or(Password::is_missing) && self.dry_run_send.is_none()
+    }
+
+    pub fn tls(&self) -> &TlsConfig {
+        &self.tls
+    }
+
+    pub fn identities(&self) -> &[Identity] {
+        &self.identities
+    }
+
+    /// Read the identities' signatures that are given as files.
+    pub fn load_signature_files(&mut self, dir: &Path) -> std::io::Result<()> {
+        for identity in &mut self.identities {
+            identity.signature.load_file(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Where the identity whose address `address` is comes in `identities`. The address may be
+    /// given with or without a name.
+    pub fn identity_index(&self, address: &str) -> Option<usize> {
+        let mailbox = parse_addresses(address).ok()?.into_iter().next()?;
+        let email = mailbox.email.to_string().to_lowercase();
+        self.identities
+            .iter()
+            .position(|identity| identity.email().as_ref() == Some(&email))
+    }
+
+    /// The account's own address and every identity's, without names and in lowercase.
+    pub fn own_addresses(&self) -> Vec<String> {
+        std::iter::once(bare_address(&self.username))
+            .chain(
+                self.identities
+                    .iter()
+                    .map(|identity| bare_address(&identity.address)),
+            )
+            .collect()
+    }
+
+    fn identity(&self, address: &str) -> Option<&Identity> {
+        self.identities.get(self.identity_index(address)?)
+    }
+
+    /// How to send a message from `sender`: through the server of the identity with that
+    /// address, if it has one of its own, or else the account's.
+    fn sending_as(&self, sender: &str) -> SmtpConfig {
+        let mut smtp_config = self.clone();
+        if let Some(identity) = self.identity(sender)
+            && let Some(host) = &identity.host
+        {
+            smtp_config.ip = host.clone();
+            smtp_config.username = match &identity.username {
+                Some(username) => username.clone(),
+                None => identity.email().unwrap_or_default(),
+            };
+            smtp_config.password = identity.password.clone();
+            smtp_config.tls = identity.tls.clone();
+        }
+        smtp_config
+    }
 }
 
 impl DefaultMessageProvider {
     #[allow(dead_code)]
     pub fn new(event_sender: mpsc::UnboundedSender<Event>) -> Self {
-        DefaultMessageProvider {
-            event_sender,
-            messages: vec![
-                Message::new(
-                    1,
-                    String::from("bob@bob.me"),
-                    String::from("me@me.me"),
-                    String::from("Hi"),
-                    String::from("Hello there"),
-                ),
-                Message::new(
-                    2,
-                    String::from("alice@alice.me"),
-                    String::from("me@me.me"),
-                    String::from("TPS Reports"),
-                    String::from(
-                        "So uhh...if you could just get those done\n\
+        let mut messages = vec![
+            Message::new(
+                1,
+                String::from("bob@bob.me"),
+                String::from("me@me.me"),
+                String::from("Hi"),
+                String::from("Hello there"),
+            ),
+            Message::new(
+                2,
+                String::from("alice@alice.me"),
+                String::from("me@me.me"),
+                String::from("TPS Reports"),
+                String::from(
+                    "So uhh...if you could just get those done\n\
                         yeahh....that'd be greaaaat.",
-                    ),
                 ),
-                Message::new(
-                    3,
-                    String::from("derek@dcn.dev"),
-                    String::from("me@me.me"),
-                    String::from("Big message"),
-                    String::from(
-                        "Lorem ipsum dolor sit amet, consectetur adipiscing \
+            ),
+            Message::new(
+                3,
+                String::from("derek@dcn.dev"),
+                String::from("me@me.me"),
+                String::from("Big message"),
+                String::from(
+                    "Lorem ipsum dolor sit amet, consectetur adipiscing \
                         elit. Sed do eiusmod tempor incididunt ut labore et \
                         dolore magna aliqua. Ut enim ad minim veniam, quis \
                         nostrud exercitation ullamco laboris nisi ut aliquip ex\
@@ -149,32 +763,32 @@ impl DefaultMessageProvider {
                            ipsum quia dolor sit amet, consectetur, adipisci \
                            velit.\n\
                         ",
-                    ),
                 ),
-                Message::new(
-                    4,
-                    String::from("bob@bob.me"),
-                    String::from("me@me.me"),
-                    String::from("Hi"),
-                    String::from("Hello there"),
-                ),
-                Message::new(
-                    5,
-                    String::from("alice@alice.me"),
-                    String::from("me@me.me"),
-                    String::from("TPS Reports"),
-                    String::from(
-                        "So uhh...if you could just get those done\n\
+            ),
+            Message::new(
+                4,
+                String::from("bob@bob.me"),
+                String::from("me@me.me"),
+                String::from("Hi"),
+                String::from("Hello there"),
+            ),
+            Message::new(
+                5,
+                String::from("alice@alice.me"),
+                String::from("me@me.me"),
+                String::from("TPS Reports"),
+                String::from(
+                    "So uhh...if you could just get those done\n\
                         yeahh....that'd be greaaaat.",
-                    ),
                 ),
-                Message::new(
-                    6,
-                    String::from("derek@dcn.dev"),
-                    String::from("me@me.me"),
-                    String::from("Big message"),
-                    String::from(
-                        "Lorem ipsum dolor sit amet, consectetur adipiscing \
+            ),
+            Message::new(
+                6,
+                String::from("derek@dcn.dev"),
+                String::from("me@me.me"),
+                String::from("Big message"),
+                String::from(
+                    "Lorem ipsum dolor sit amet, consectetur adipiscing \
                         elit. Sed do eiusmod tempor incididunt ut labore et \
                         dolore magna aliqua. Ut enim ad minim veniam, quis \
                         nostrud exercitation ullamco laboris nisi ut aliquip ex\
@@ -195,32 +809,32 @@ impl DefaultMessageProvider {
                            ipsum quia dolor sit amet, consectetur, adipisci \
                            velit.\n\
                         ",
-                    ),
-                ),
-                Message::new(
-                    7,
-                    String::from("bob@bob.me"),
-                    String::from("me@me.me"),
-                    String::from("Hi"),
-                    String::from("Hello there"),
                 ),
-                Message::new(
-                    8,
-                    String::from("alice@alice.me"),
-                    String::from("me@me.me"),
-                    String::from("TPS Reports"),
-                    String::from(
-                        "So uhh...if you could just get those done\n\
+            ),
+            Message::new(
+                7,
+                String::from("bob@bob.me"),
+                String::from("me@me.me"),
+                String::from("Hi"),
+                String::from("Hello there"),
+            ),
+            Message::new(
+                8,
+                String::from("alice@alice.me"),
+                String::from("me@me.me"),
+                String::from("TPS Reports"),
+                String::from(
+                    "So uhh...if you could just get those done\n\
                         yeahh....that'd be greaaaat.",
-                    ),
                 ),
-                Message::new(
-                    9,
-                    String::from("derek@dcn.dev"),
-                    String::from("me@me.me"),
-                    String::from("Big message"),
-                    String::from(
-                        "Lorem ipsum dolor sit amet, consectetur adipiscing \
+            ),
+            Message::new(
+                9,
+                String::from("derek@dcn.dev"),
+                String::from("me@me.me"),
+                String::from("Big message"),
+                String::from(
+                    "Lorem ipsum dolor sit amet, consectetur adipiscing \
                         elit. Sed do eiusmod tempor incididunt ut labore et \
                         dolore magna aliqua. Ut enim ad minim veniam, quis \
                         nostrud exercitation ullamco laboris nisi ut aliquip ex\
@@ -241,32 +855,32 @@ impl DefaultMessageProvider {
                            ipsum quia dolor sit amet, consectetur, adipisci \
                            velit.\n\
                         ",
-                    ),
                 ),
-                Message::new(
-                    10,
-                    String::from("bob@bob.me"),
-                    String::from("me@me.me"),
-                    String::from("Hi"),
-                    String::from("Hello there"),
-                ),
-                Message::new(
-                    11,
-                    String::from("alice@alice.me"),
-                    String::from("me@me.me"),
-                    String::from("TPS Reports"),
-                    String::from(
-                        "So uhh...if you could just get those done\n\
+            ),
+            Message::new(
+                10,
+                String::from("bob@bob.me"),
+                String::from("me@me.me"),
+                String::from("Hi"),
+                String::from("Hello there"),
+            ),
+            Message::new(
+                11,
+                String::from("alice@alice.me"),
+                String::from("me@me.me"),
+                String::from("TPS Reports"),
+                String::from(
+                    "So uhh...if you could just get those done\n\
                         yeahh....that'd be greaaaat.",
-                    ),
                 ),
-                Message::new(
-                    12,
-                    String::from("derek@dcn.dev"),
-                    String::from("me@me.me"),
-                    String::from("Big message"),
-                    String::from(
-                        "Lorem ipsum dolor sit amet, consectetur adipiscing \
+            ),
+            Message::new(
+                12,
+                String::from("derek@dcn.dev"),
+                String::from("me@me.me"),
+                String::from("Big message"),
+                String::from(
+                    "Lorem ipsum dolor sit amet, consectetur adipiscing \
                         elit. Sed do eiusmod tempor incididunt ut labore et \
                         dolore magna aliqua. Ut enim ad minim veniam, quis \
                         nostrud exercitation ullamco laboris nisi ut aliquip ex\
@@ -287,9 +901,20 @@ impl DefaultMessageProvider {
                            ipsum quia dolor sit amet, consectetur, adipisci \
                            velit.\n\
                         ",
-                    ),
                 ),
-            ],
+            ),
+        ];
+
+        // spread the messages over the last few days, oldest first
+        let now = Local::now().timestamp();
+        let count = messages.len() as i64;
+        for (i, message) in messages.iter_mut().enumerate() {
+            message.set_date(Some(now - (count - i as i64) * 5 * 60 * 60));
+        }
+
+        DefaultMessageProvider {
+            event_sender,
+            messages,
         }
     }
 
@@ -297,22 +922,123 @@ impl DefaultMessageProvider {
     pub fn init(&mut self) -> color_eyre::Result<()> {
         Ok(())
     }
+
+    /// The newest [`PAGE_SIZE`] messages with an id below `before`, oldest first.
+    fn page(&self, before: u64) -> Vec<Message> {
+        let older: Vec<&Message> = self.messages.iter().filter(|m| m.id() < before).collect();
+        let start = older.len().saturating_sub(PAGE_SIZE);
+        older[start..].iter().map(|&m| m.clone()).collect()
+    }
 }
 
 impl MessageProvider for DefaultMessageProvider {
-    fn get_messages(&self) {
+    fn get_folders(&self) {
+        let folders = STANDARD_FOLDERS.map(String::from).to_vec();
+        let _ = self
+            .event_sender
+            .send(Event::App(AppEvent::FoldersLoaded(folders)));
+    }
+
+    fn get_messages(&self, folder: &str) {
         let event_sender = self.event_sender.clone();
-        let messages = self.messages.clone();
+        // every static message is in the inbox
+        let (count, unread, messages) = match folder {
+            "Inbox" => (
+                self.messages.len(),
+                self.messages.iter().filter(|m| m.is_unread()).count(),
+                self.page(u64::MAX),
+            ),
+            _ => (0, 0, vec![]),
+        };
+        let folder = folder.to_string();
         tokio::spawn(async move {
             // bake in some delay
             tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
 
+            let app_event = AppEvent::MessageCountLoaded(folder, count, unread);
+            let _ = event_sender.send(Event::App(app_event));
             let app_event = AppEvent::MessagesLoaded(messages);
             let event = Event::App(app_event);
             let _ = event_sender.send(event);
         });
     }
 
+    fn get_older_messages(&self, folder: &str, before: u64) {
+        let messages = match folder {
+            "Inbox" => self.page(before),
+            _ => vec![],
+        };
+        let app_event = AppEvent::OlderMessagesLoaded(folder.to_string(), messages);
+        let _ = self.event_sender.send(Event::App(app_event));
+    }
+
+    fn poll_folders(&self, _schedule: Schedule) {
+        // these messages are static, so nothing new ever arrives
+    }
+
+    fn get_contacts(&self) {
+        let contacts = contacts::collect(
+            self.messages
+                .iter()
+                .flat_map(|m| [m.from(), m.to(), m.cc()]),
+        );
+        let _ = self
+            .event_sender
+            .send(Event::App(AppEvent::ContactsLoaded(contacts)));
+    }
+
+    fn get_address_book(&self) {
+        let _ = self
+            .event_sender
+            .send(Event::App(AppEvent::AddressBookLoaded(vec![])));
+    }
+
+    fn save_contact(&self, _contact: &Contact) {
+        // nothing is stored, so there is nothing to update
+    }
+
+    fn delete_contact(&self, _id: u64) {
+        // nothing is stored, so there is nothing to update
+    }
+
+    fn import_contacts(&self, _contacts: Vec<Contact>) {
+        let _ = self
+            .event_sender
+            .send(Event::App(AppEvent::ContactsImported(0)));
+    }
+
+    fn complete_address(&self, query: &str) {
+        let seen = contacts::collect(
+            self.messages
+                .iter()
+                .flat_map(|m| [m.from(), m.to(), m.cc()]),
+        );
+        let completions = contacts::completions(query, &[], &seen);
+        let _ = self
+            .event_sender
+            .send(Event::App(AppEvent::AddressCompletionsLoaded(
+                query.to_string(),
+                completions,
+            )));
+    }
+
+    fn search_messages(&self, query: &str) {
+        let search = query::parse(query);
+        let now = Local::now();
+        let results = self
+            .messages
+            .iter()
+            .filter(|m| search.matches(m, now))
+            .cloned()
+            .collect();
+        let _ = self
+            .event_sender
+            .send(Event::App(AppEvent::SearchResultsLoaded(
+                query.to_string(),
+                results,
+            )));
+    }
+
     fn get_message(&self, id: u64) {
         let event_sender = self.event_sender.clone();
         // count from zero since I'm using a vector for these "static" messages.
@@ -323,12 +1049,26 @@ impl MessageProvider for DefaultMessageProvider {
             // bake in some delay
             tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
 
-            let app_event = AppEvent::MessageBodyLoaded(id, message_body);
+            let parts = vec![MessagePart::plain(message_body)];
+            let app_event = AppEvent::MessageBodyLoaded(id, parts, None);
             let event = Event::App(app_event);
             let _ = event_sender.send(event);
         });
     }
 
+    fn export_message(&self, _id: u64, _path: &Path) {
+        let app_event = AppEvent::MessageExported(Err(String::from(
+            "Exporting messages needs the message database",
+        )));
+        let _ = self.event_sender.send(Event::App(app_event));
+    }
+
+    fn import_messages(&self, _paths: Vec<PathBuf>, _folder: &str) {
+        let failure = String::from("Importing messages needs the message database");
+        let app_event = AppEvent::MessagesImported(0, vec![failure]);
+        let _ = self.event_sender.send(Event::App(app_event));
+    }
+
     fn send_message(&self, message: &Message) {
         let event_sender = self.event_sender.clone();
         let message = message.clone();
@@ -342,26 +1082,167 @@ impl MessageProvider for DefaultMessageProvider {
             let _ = event_sender.send(event);
         });
     }
+
+    fn set_unread(&self, _ids: &[u64], _unread: bool) {
+        // these messages are static, so there is nothing to update
+    }
+
+    fn set_flagged(&self, _id: u64, _flagged: bool) {
+        // these messages are static, so there is nothing to update
+    }
+
+    fn move_messages(&self, _ids: &[u64], _folder: &str) {
+        // these messages are static, so there is nothing to update
+    }
+
+    fn delete_messages(&self, _ids: &[u64]) {
+        // these messages are static, so there is nothing to update
+    }
+
+    fn empty_trash(&self, _days: u32) {
+        // these messages are static, so there is nothing to update
+    }
+
+    fn get_watched_threads(&self) {
+        let _ = self
+            .event_sender
+            .send(Event::App(AppEvent::WatchedThreadsLoaded(vec![])));
+    }
+
+    fn set_thread_watched(&self, _root: &str, _watched: bool) {
+        // nothing is stored, so there is nothing to update
+    }
+
+    fn get_mutes(&self) {
+        let _ = self
+            .event_sender
+            .send(Event::App(AppEvent::MutesLoaded(vec![])));
+    }
+
+    fn set_muted(&self, _mute: &Mute, _muted: bool) {
+        // nothing is stored, so there is nothing to update
+    }
+
+    fn get_tags(&self) {
+        let _ = self
+            .event_sender
+            .send(Event::App(AppEvent::TagsLoaded(vec![])));
+    }
+
+    fn set_tagged(&self, _id: u64, _tag: &str, _tagged: bool) {
+        // these messages are static, so there is nothing to update
+    }
+
+    fn get_remote_content_senders(&self) {
+        let _ = self
+            .event_sender
+            .send(Event::App(AppEvent::RemoteContentSendersLoaded(vec![])));
+    }
+
+    fn set_remote_content_allowed(&self, _sender: &str, _allowed: bool) {
+        // nothing is stored, so there is nothing to update
+    }
+
+    fn send_messages(&self, _messages: Vec<Message>) {
+        let app_event = AppEvent::MailMergeQueued(
+            0,
+            vec![String::from("Mail merges need the message database")],
+        );
+        let _ = self.event_sender.send(Event::App(app_event));
+    }
+
+    fn schedule_message(&self, _message: &Message, _send_at: DateTime<Local>) {
+        let app_event = AppEvent::MessageScheduled(Err(String::from(
+            "Sending later needs the message database",
+        )));
+        let _ = self.event_sender.send(Event::App(app_event));
+    }
+
+    fn get_scheduled(&self) {
+        let _ = self
+            .event_sender
+            .send(Event::App(AppEvent::ScheduledLoaded(vec![])));
+    }
+
+    fn send_outbox(&self) {
+        // nothing can be scheduled, so nothing is ever due
+    }
+
+    fn send_scheduled_now(&self, _id: u64) {
+        // nothing can be scheduled, so there is nothing to send
+    }
+
+    fn cancel_scheduled(&self, _id: u64) {
+        // nothing can be scheduled, so there is nothing to cancel
+    }
+
+    fn save_draft(&self, _id: u64, _draft: &Draft) {
+        // nothing is kept once eta closes
+    }
+
+    fn delete_draft(&self, _id: u64) {
+        // nothing is kept, so there is nothing to forget
+    }
+
+    fn get_drafts(&self) {
+        // nothing is kept, so there are never drafts to load
+    }
+
+    fn check_connection(&self) {
+        let _ = self.event_sender.send(Event::App(AppEvent::ConnectionState(
+            ConnectionState::Online,
+        )));
+    }
 }
 
 impl SqliteMessageProvider {
-    pub fn new(event_sender: mpsc::UnboundedSender<Event>) -> color_eyre::Result<Self> {
-        let opts = sqlx::sqlite::SqliteConnectOptions::from_str("sqlite://messages.db")?
+    pub fn new(
+        event_sender: mpsc::UnboundedSender<Event>,
+        smtp_config: SmtpConfig,
+        rules: Rules,
+        database: &Path,
+        passphrase: Option<&str>,
+    ) -> color_eyre::Result<Self> {
+        let mut smtp_config = smtp_config;
+        if smtp_config.password.is_none() {
+            smtp_config.password = Some(Password::entered());
+        }
+        let mut opts = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(database)
             .create_if_missing(true);
+        if let Some(passphrase) = passphrase {
+            // SQLCipher's key, which sqlx sets before anything else on each connection
+            opts = opts.pragma("key", format!("'{}'", passphrase.replace('\'', "''")));
+        }
         let connection = Arc::new(sqlx::SqlitePool::connect_lazy_with(opts));
 
-        let smtp_config: SmtpConfig =
-            toml::from_str(std::fs::read_to_string("eta.toml")?.as_str())?;
-
         let provider = Self {
             connection,
             event_sender,
             smtp_config,
+            encrypted: passphrase.is_some(),
+            rules: Arc::new(rules),
+            polling: watch::Sender::new(None),
+            outbox: Arc::new(Notify::new()),
         };
 
         Ok(provider)
     }
 
+    pub fn needs_password(&self) -> bool {
+        self.smtp_config.needs_password()
+    }
+
+    /// Send with `password` for the rest of the session, or until the server turns it down. It
+    /// is only kept in memory.
+    pub fn set_password(&self, password: Zeroizing<String>) {
+        if let Some(slot) = &self.smtp_config.password {
+            slot.enter(password);
+        }
+        // messages due meanwhile have waited for it
+        self.outbox.notify_one();
+    }
+
     /// Create the necessary schema if it does not already exist.
     pub async fn init(&self) -> color_eyre::Result<()> {
         // deref to get the protected type, then make a reference
@@ -369,41 +1250,312 @@ impl SqliteMessageProvider {
         // concurrency
         let conn = &*self.connection;
 
+        // a wrong passphrase only shows once the database is read
+        let read = sqlx::query("SELECT count(*) FROM sqlite_master")
+            .execute(conn)
+            .await;
+        if let Err(e) = &read
+            && self.encrypted
+        {
+            return Err(color_eyre::eyre::eyre!(
+                "Couldn't open the message database, is the passphrase right? ({e})"
+            ));
+        }
+        read?;
+
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS messages (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 from_addr TEXT NOT NULL,
                 to_addr TEXT NOT NULL,
+                cc_addr TEXT NOT NULL DEFAULT '',
                 subject TEXT NOT NULL,
-                body TEXT NOT NULL
+                body TEXT NOT NULL,
+                raw BLOB,
+                unread INTEGER NOT NULL DEFAULT 1,
+                flagged INTEGER NOT NULL DEFAULT 0,
+                folder TEXT NOT NULL DEFAULT 'Inbox',
+                date INTEGER,
+                trashed_at INTEGER,
+                message_id TEXT,
+                in_reply_to TEXT,
+                refs TEXT,
+                snippet TEXT,
+                addresses_known INTEGER NOT NULL DEFAULT 0,
+                processed INTEGER NOT NULL DEFAULT 0,
+                size INTEGER,
+                has_attachment INTEGER NOT NULL DEFAULT 0
             )",
         )
         .execute(conn)
         .await?;
 
-        let result: (u64,) = sqlx::query_as("SELECT COUNT(id) FROM messages")
-            .fetch_one(conn)
-            .await?;
-        let count = result.0;
-
-        if count != 0 {
-            return Ok(());
+        // the full message as received, when there is one, so its MIME structure can be parsed
+        add_column_if_missing(conn, "messages", "raw", "BLOB").await?;
+        add_column_if_missing(conn, "messages", "unread", "INTEGER NOT NULL DEFAULT 1").await?;
+        add_column_if_missing(conn, "messages", "flagged", "INTEGER NOT NULL DEFAULT 0").await?;
+        add_column_if_missing(conn, "messages", "folder", "TEXT NOT NULL DEFAULT 'Inbox'").await?;
+        // seconds since the Unix epoch
+        add_column_if_missing(conn, "messages", "date", "INTEGER").await?;
+        // when the message was moved to the trash, in seconds since the Unix epoch
+        add_column_if_missing(conn, "messages", "trashed_at", "INTEGER").await?;
+        // threading headers; refs holds the References header's ids, separated by spaces
+        add_column_if_missing(conn, "messages", "message_id", "TEXT").await?;
+        add_column_if_missing(conn, "messages", "in_reply_to", "TEXT").await?;
+        add_column_if_missing(conn, "messages", "refs", "TEXT").await?;
+        add_column_if_missing(conn, "messages", "cc_addr", "TEXT NOT NULL DEFAULT ''").await?;
+        // the start of the body, shown in the table; filled in below for messages without one
+        add_column_if_missing(conn, "messages", "snippet", "TEXT").await?;
+        // whether the message's addresses are in known_addresses yet
+        add_column_if_missing(
+            conn,
+            "messages",
+            "addresses_known",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+        // whether the rules have run on the message since it arrived; those kept from before
+        // they ran on every arrival are left as they are
+        if add_column_if_missing(conn, "messages", "processed", "INTEGER NOT NULL DEFAULT 0")
+            .await?
+        {
+            sqlx::query("UPDATE messages SET processed = 1")
+                .execute(conn)
+                .await?;
+        }
+        // the size of the message as received, in bytes, or of its body when it wasn't kept
+        if add_column_if_missing(conn, "messages", "size", "INTEGER").await? {
+            sqlx::query("UPDATE messages SET size = length(COALESCE(raw, CAST(body AS BLOB)))")
+                .execute(conn)
+                .await?;
+        }
+        add_has_attachment_column(conn).await?;
+
+        // folders are loaded a page at a time, newest first
+        sqlx::query("CREATE INDEX IF NOT EXISTS messages_folder ON messages (folder, id)")
+            .execute(conn)
+            .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS messages_unprocessed ON messages (id) WHERE processed = 0",
+        )
+        .execute(conn)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS messages_without_snippet ON messages (id)
+            WHERE snippet IS NULL",
+        )
+        .execute(conn)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS messages_addresses_unknown ON messages (id)
+            WHERE addresses_known = 0",
+        )
+        .execute(conn)
+        .await?;
+
+        // full-text index of the messages, kept up to date by triggers
+        let (indexed,): (bool,) = sqlx::query_as(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'messages_fts_update'",
+        )
+        .fetch_one(conn)
+        .await?;
+        sqlx::query(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+                subject, body, from_addr, content = 'messages', content_rowid = 'id'
+            )",
+        )
+        .execute(conn)
+        .await?;
+        sqlx::query(
+            "CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+                INSERT INTO messages_fts (rowid, subject, body, from_addr)
+                    VALUES (new.id, new.subject, new.body, new.from_addr);
+            END",
+        )
+        .execute(conn)
+        .await?;
+        sqlx::query(
+            "CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+                INSERT INTO messages_fts (messages_fts, rowid, subject, body, from_addr)
+                    VALUES ('delete', old.id, old.subject, old.body, old.from_addr);
+            END",
+        )
+        .execute(conn)
+        .await?;
+        sqlx::query(
+            "CREATE TRIGGER IF NOT EXISTS messages_fts_update
+                AFTER UPDATE OF subject, body, from_addr ON messages BEGIN
+                INSERT INTO messages_fts (messages_fts, rowid, subject, body, from_addr)
+                    VALUES ('delete', old.id, old.subject, old.body, old.from_addr);
+                INSERT INTO messages_fts (rowid, subject, body, from_addr)
+                    VALUES (new.id, new.subject, new.body, new.from_addr);
+            END",
+        )
+        .execute(conn)
+        .await?;
+        // index anything stored before the triggers existed
+        if !indexed {
+            sqlx::query("INSERT INTO messages_fts (messages_fts) VALUES ('rebuild')")
+                .execute(conn)
+                .await?;
+        }
+
+        // Message-IDs of the first messages of threads to be told about replies to
+        sqlx::query("CREATE TABLE IF NOT EXISTS watched_threads (root TEXT PRIMARY KEY NOT NULL)")
+            .execute(conn)
+            .await?;
+
+        // the address book
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS contacts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL DEFAULT '',
+                email TEXT NOT NULL
+            )",
+        )
+        .execute(conn)
+        .await?;
+
+        // threads and senders whose new messages are marked as read straight away
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS mutes (
+                kind TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (kind, value)
+            )",
+        )
+        .execute(conn)
+        .await?;
+
+        // tags, which any number of messages may be given, whatever folder they are in
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE
+            )",
+        )
+        .execute(conn)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS message_tags (
+                message_id INTEGER NOT NULL,
+                tag_id INTEGER NOT NULL,
+                PRIMARY KEY (message_id, tag_id)
+            )",
+        )
+        .execute(conn)
+        .await?;
+        sqlx::query(
+            "CREATE TRIGGER IF NOT EXISTS message_tags_delete AFTER DELETE ON messages BEGIN
+                DELETE FROM message_tags WHERE message_id = old.id;
+            END",
+        )
+        .execute(conn)
+        .await?;
+        move_tags_column(conn).await?;
+
+        // every address mail has been exchanged with, with when it last was (in seconds since the
+        // Unix epoch) and how many times, to rank address completions by
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS known_addresses (
+                address TEXT PRIMARY KEY NOT NULL,
+                last_used INTEGER NOT NULL,
+                uses INTEGER NOT NULL DEFAULT 0
+            )",
+        )
+        .execute(conn)
+        .await?;
+
+        // senders whose messages may load images and other content from the internet
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS remote_content_senders (address TEXT PRIMARY KEY NOT NULL)",
+        )
+        .execute(conn)
+        .await?;
+
+        // messages to send later, ready to go as they will be sent; recipients are separated by
+        // spaces, and error says why sending failed, after which they wait to be sent by hand
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                send_at INTEGER NOT NULL,
+                to_addr TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                sender TEXT NOT NULL,
+                recipients TEXT NOT NULL,
+                raw BLOB NOT NULL,
+                error TEXT
+            )",
+        )
+        .execute(conn)
+        .await?;
+        // messages of a mail merge are sent no faster than the rate limit allows
+        add_column_if_missing(conn, "outbox", "paced", "INTEGER NOT NULL DEFAULT 0").await?;
+
+        // messages being composed, saved every so often in case eta closes before they are sent;
+        // there used to be only one, kept as id 1
+        let result: Option<(String,)> = sqlx::query_as(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'drafts'",
+        )
+        .fetch_optional(conn)
+        .await?;
+        if let Some((sql,)) = result
+            && sql.contains("CHECK (id = 1)")
+        {
+            let mut tx = conn.begin().await?;
+            sqlx::query("ALTER TABLE drafts RENAME TO drafts_single")
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(DRAFTS_TABLE).execute(&mut *tx).await?;
+            sqlx::query("INSERT INTO drafts SELECT * FROM drafts_single")
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DROP TABLE drafts_single")
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
         }
+        sqlx::query(DRAFTS_TABLE).execute(conn).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS draft_attachments (
+                draft_id INTEGER NOT NULL,
+                position INTEGER NOT NULL,
+                content_type TEXT NOT NULL,
+                filename TEXT,
+                content_id TEXT,
+                content BLOB NOT NULL,
+                PRIMARY KEY (draft_id, position)
+            )",
+        )
+        .execute(conn)
+        .await?;
+
+        let result: (u64,) = sqlx::query_as("SELECT COUNT(id) FROM messages")
+            .fetch_one(conn)
+            .await?;
+        let count = result.0;
 
-        self.seed_messages().await?;
+        if count == 0 {
+            self.seed_messages().await?;
+        }
+        fill_snippets(conn).await?;
+        let own = self.smtp_config.own_addresses();
+        know_addresses(conn, &own).await?;
+        // mail that arrived while eta was closed
+        process_arrivals(conn, &self.rules, &own).await?;
 
         Ok(())
     }
 
     async fn seed_messages(&self) -> Result<(), sqlx::Error> {
         let _ = sqlx::query(
-            "INSERT INTO messages (id, from_addr, to_addr, subject, body) VALUES
+            "INSERT INTO messages (id, from_addr, to_addr, subject, body, date) VALUES
                 (1, 'alice@example.com', 'bob@example.com', 'Hello there', 'Bob,\n\
                     \n\
                     I hope you are well.\n\
                     \n\
                     Regards,\n\
-                    Alice'),
+                    Alice', unixepoch('now', '-20 minutes')),
                 (2, 'alice@example.com', 'bob@example.com', 'Meeting reminder', 'Dear Team,\n\
                     \n\
                     I hope this message finds you well. I am writing to remind you about our upcoming meeting\n\
@@ -414,7 +1566,7 @@ impl SqliteMessageProvider {
                     Looking forward to a productive discussion.\n\
                     Best regards,\n\
                     \n\
-                    Alice'),
+                    Alice', unixepoch('now', '-3 hours')),
                 (3, 'eve@example.com', 'bob@example.com', 'Important Notice: System Maintenance', 'Dear Users,\n\
                     \n\
                     We would like to inform you that our system will undergo scheduled maintenance this Saturday\n\
@@ -427,7 +1579,7 @@ impl SqliteMessageProvider {
                     \n\
                     Sincerely,\n\
                     \n\
-                    Eve'),
+                    Eve', unixepoch('now', '-1 day')),
                 (4, 'sarah@example.com', 'bob@example.com', 'Reminder: Submit Your Timesheets by EOD', 'Hi Everyone,\n\
                     \n\
                     This is a friendly reminder to please submit your timesheets for the past week by the end of\n\
@@ -438,7 +1590,43 @@ impl SqliteMessageProvider {
                     \n\
                     Best,\n\
                     \n\
-                    Sarah')",
+                    Sarah', unixepoch('now', '-3 days'))",
+        )
+        .execute(&*self.connection)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO messages (id, from_addr, to_addr, subject, body, raw, date) VALUES
+                (5, 'digest@example.com', 'bob@example.com', 'Your weekly digest', ?, ?,
+                    unixepoch('now', '-6 days')),
+                (6, 'orders@example.com', 'bob@example.com', 'Your order receipt', ?, ?,
+                    unixepoch('now', '-20 days'))",
+        )
+        .bind(text_of(DIGEST_MESSAGE.as_bytes()))
+        .bind(DIGEST_MESSAGE.as_bytes())
+        .bind(text_of(RECEIPT_MESSAGE.as_bytes()))
+        .bind(RECEIPT_MESSAGE.as_bytes())
+        .execute(&*self.connection)
+        .await?;
+
+        // a conversation, for threading
+        sqlx::query("UPDATE messages SET message_id = '<meeting-1@example.com>' WHERE id = 2")
+            .execute(&*self.connection)
+            .await?;
+        sqlx::query(
+            "INSERT INTO messages
+                (id, from_addr, to_addr, cc_addr, subject, body, date, message_id, in_reply_to, refs)
+                VALUES
+                (7, 'carol@example.com', 'alice@example.com', 'bob@example.com, dave@example.com',
+                    'Re: Meeting reminder',
+                    'Could we push it back to 11? I have a call until then.\n\nCarol',
+                    unixepoch('now', '-2 hours'), '<meeting-2@example.com>',
+                    '<meeting-1@example.com>', '<meeting-1@example.com>'),
+                (8, 'alice@example.com', 'carol@example.com', 'bob@example.com, dave@example.com',
+                    'Re: Meeting reminder',
+                    'Sure, 11 it is.\n\nAlice',
+                    unixepoch('now', '-1 hour'), '<meeting-3@example.com>',
+                    '<meeting-2@example.com>', '<meeting-1@example.com> <meeting-2@example.com>')",
         )
         .execute(&*self.connection)
         .await?;
@@ -447,60 +1635,365 @@ impl SqliteMessageProvider {
     }
 }
 
+/// Plain text of a raw message, for the `body` column.
+fn text_of(raw: &[u8]) -> String {
+    let mut message = Message::default();
+    message.set_parts(mime::parse(raw).unwrap_or_default());
+    message.text_body()
+}
+
+/// The first [`SNIPPET_LENGTH`] characters of `body`, on one line, leaving out quoted lines.
+/// Encrypted messages have none, as their text is only ciphertext until opened.
+fn snippet(body: &str) -> String {
+    if body.trim_start().starts_with("-----BEGIN PGP MESSAGE-----") {
+        return String::new();
+    }
+    let words = body
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('>'))
+        .flat_map(str::split_whitespace);
+    let mut snippet = String::new();
+    for word in words {
+        if !snippet.is_empty() {
+            snippet.push(' ');
+        }
+        snippet.push_str(word);
+        if snippet.chars().count() >= SNIPPET_LENGTH {
+            break;
+        }
+    }
+    snippet.chars().take(SNIPPET_LENGTH).collect()
+}
+
+const DRAFTS_TABLE: &str = "CREATE TABLE IF NOT EXISTS drafts (
+    id INTEGER PRIMARY KEY,
+    to_addr TEXT NOT NULL,
+    cc_addr TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    reply_to INTEGER
+)";
+
+/// Add the has_attachment column, set for the messages kept already that have attachments, which
+/// are only found by parsing each raw message.
+async fn add_has_attachment_column(conn: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
+    let (kept,): (bool,) = sqlx::query_as(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('messages') WHERE name = 'has_attachment'",
+    )
+    .fetch_one(conn)
+    .await?;
+    if kept {
+        return Ok(());
+    }
+
+    // the column is only added once every message has been looked at
+    let mut tx = conn.begin().await?;
+    sqlx::query("ALTER TABLE messages ADD COLUMN has_attachment INTEGER NOT NULL DEFAULT 0")
+        .execute(&mut *tx)
+        .await?;
+    let ids: Vec<(i64,)> = sqlx::query_as("SELECT id FROM messages WHERE raw IS NOT NULL")
+        .fetch_all(&mut *tx)
+        .await?;
+    for (id,) in ids {
+        let raw: Vec<u8> = sqlx::query_scalar("SELECT raw FROM messages WHERE id = ?")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if mime::has_attachment(&raw) {
+            sqlx::query("UPDATE messages SET has_attachment = 1 WHERE id = ?")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    tx.commit().await
+}
+
+/// Move the tags rules used to give in the `messages.tags` column, separated by spaces, to the
+/// tags and message_tags tables, and drop the column.
+async fn move_tags_column(conn: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
+    let (kept,): (bool,) = sqlx::query_as(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('messages') WHERE name = 'tags'",
+    )
+    .fetch_one(conn)
+    .await?;
+    if !kept {
+        return Ok(());
+    }
+
+    let mut tx = conn.begin().await?;
+    let tagged: Vec<(i64, String)> =
+        sqlx::query_as("SELECT id, tags FROM messages WHERE tags != ''")
+            .fetch_all(&mut *tx)
+            .await?;
+    for (id, tags) in tagged {
+        for tag in tags.split_whitespace() {
+            sqlx::query("INSERT OR IGNORE INTO tags (name) VALUES (?)")
+                .bind(tag)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                "INSERT OR IGNORE INTO message_tags (message_id, tag_id)
+                    SELECT ?, id FROM tags WHERE name = ?",
+            )
+            .bind(id)
+            .bind(tag)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    sqlx::query("ALTER TABLE messages DROP COLUMN tags")
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// Keep `draft` as the draft with id `id`, along with its attachments, in place of what was kept
+/// of it before.
+async fn store_draft(
+    connection: &sqlx::SqlitePool,
+    id: u64,
+    draft: &Draft,
+) -> Result<(), sqlx::Error> {
+    let mut tx = connection.begin().await?;
+    sqlx::query(
+        "INSERT OR REPLACE INTO drafts (id, to_addr, cc_addr, subject, body, reply_to)
+        VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(id as i64)
+    .bind(&draft.to)
+    .bind(&draft.cc)
+    .bind(&draft.subject)
+    .bind(&draft.body)
+    .bind(draft.reply_to.map(|id| id as i64))
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM draft_attachments WHERE draft_id = ?")
+        .bind(id as i64)
+        .execute(&mut *tx)
+        .await?;
+    for (position, part) in draft.attachments.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO draft_attachments
+                (draft_id, position, content_type, filename, content_id, content)
+            VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(id as i64)
+        .bind(position as i64)
+        .bind(&part.content_type)
+        .bind(&part.filename)
+        .bind(&part.content_id)
+        .bind(&part.content)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// Forget the draft with id `id` and its attachments.
+async fn discard_draft(connection: &sqlx::SqlitePool, id: u64) -> Result<(), sqlx::Error> {
+    let mut tx = connection.begin().await?;
+    sqlx::query("DELETE FROM draft_attachments WHERE draft_id = ?")
+        .bind(id as i64)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM drafts WHERE id = ?")
+        .bind(id as i64)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// Every draft kept, oldest first, with its id.
+async fn load_drafts(connection: &sqlx::SqlitePool) -> Result<Vec<(u64, Draft)>, sqlx::Error> {
+    let mut drafts =
+        sqlx::query("SELECT id, to_addr, cc_addr, subject, body, reply_to FROM drafts ORDER BY id")
+            .map(|row: SqliteRow| {
+                let draft = Draft {
+                    to: row.get(1),
+                    cc: row.get(2),
+                    subject: row.get(3),
+                    body: row.get(4),
+                    reply_to: row.get::<Option<i64>, _>(5).map(|id| id as u64),
+                    attachments: vec![],
+                };
+                (row.get::<i64, _>(0) as u64, draft)
+            })
+            .fetch_all(connection)
+            .await?;
+    for (id, draft) in &mut drafts {
+        draft.attachments = sqlx::query(
+            "SELECT content_type, filename, content_id, content FROM draft_attachments
+            WHERE draft_id = ? ORDER BY position",
+        )
+        .bind(*id as i64)
+        .map(|row: SqliteRow| MessagePart {
+            content_type: row.get(0),
+            filename: row.get(1),
+            content_id: row.get(2),
+            content: row.get(3),
+        })
+        .fetch_all(connection)
+        .await?;
+    }
+    Ok(drafts)
+}
+
+/// Work out the snippet of every message without one, such as those kept before snippets were.
+/// Messages that arrive later are given theirs as they are processed.
+async fn fill_snippets(connection: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
+    let mut tx = connection.begin().await?;
+    let rows: Vec<(i64, String)> =
+        sqlx::query_as("SELECT id, body FROM messages WHERE snippet IS NULL")
+            .fetch_all(&mut *tx)
+            .await?;
+    for (id, body) in rows {
+        sqlx::query("UPDATE messages SET snippet = ? WHERE id = ?")
+            .bind(snippet(&body))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await
+}
+
+/// Add the addresses of every message not yet in `known_addresses`, such as those kept before it
+/// was, as used at the message's date. Messages that arrive later have theirs added as they are
+/// processed.
+async fn know_addresses(connection: &sqlx::SqlitePool, own: &[String]) -> Result<(), sqlx::Error> {
+    let mut tx = connection.begin().await?;
+    let rows: Vec<(i64, String, String, String, Option<i64>)> = sqlx::query_as(
+        "SELECT id, from_addr, to_addr, cc_addr, date FROM messages WHERE addresses_known = 0",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for (id, from, to, cc, date) in rows {
+        remember_addresses(
+            &mut *tx,
+            message_addresses(own, [from.as_str(), &to, &cc]),
+            date.unwrap_or_else(|| Local::now().timestamp()),
+        )
+        .await?;
+        sqlx::query("UPDATE messages SET addresses_known = 1 WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await
+}
+
+/// The addresses in a message's address `lists`, such as From, To and Cc, leaving out the
+/// account's own addresses, `own`, as they would otherwise be on almost every message.
+fn message_addresses<'l>(own: &[String], lists: impl IntoIterator<Item = &'l str>) -> Vec<String> {
+    contacts::collect(lists)
+        .into_iter()
+        .filter(|address| !own.contains(address))
+        .collect()
+}
+
+/// Count `addresses` as used once more, at `at` seconds since the Unix epoch.
+async fn remember_addresses<'c>(
+    connection: impl sqlx::SqliteExecutor<'c>,
+    addresses: impl IntoIterator<Item = String>,
+    at: i64,
+) -> Result<(), sqlx::Error> {
+    let addresses: Vec<String> = addresses.into_iter().collect();
+    if addresses.is_empty() {
+        return Ok(());
+    }
+    let values = vec!["(?, ?, 1)"; addresses.len()].join(", ");
+    let statement = format!(
+        "INSERT INTO known_addresses (address, last_used, uses) VALUES {values}
+            ON CONFLICT (address) DO UPDATE
+            SET uses = uses + 1, last_used = max(last_used, excluded.last_used)"
+    );
+    let mut query = sqlx::query(&statement);
+    for address in addresses {
+        query = query.bind(address).bind(at);
+    }
+    query.execute(connection).await?;
+    Ok(())
+}
+
+/// Remember the recipients in the address `lists` of a message that has just been sent, other
+/// than the account's own addresses, `own`.
+async fn remember_recipients<'l>(
+    connection: &sqlx::SqlitePool,
+    own: &[String],
+    lists: impl IntoIterator<Item = &'l str>,
+    event_sender: &mpsc::UnboundedSender<Event>,
+) {
+    let recipients = message_addresses(own, lists);
+    if let Err(e) = remember_addresses(connection, recipients, Local::now().timestamp()).await {
+        let _ = event_sender.send(Event::App(error_event(e)));
+    }
+}
+
+/// Add a column to a table created by an older version of eta.
+async fn add_column_if_missing(
+    conn: &sqlx::SqlitePool,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<bool, sqlx::Error> {
+    let result: (u64,) = sqlx::query_as("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+        .bind(table)
+        .bind(column)
+        .fetch_one(conn)
+        .await?;
+
+    if result.0 == 0 {
+        sqlx::query(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))
+        .execute(conn)
+        .await?;
+    }
+
+    Ok(result.0 == 0)
+}
+
 impl MessageProvider for SqliteMessageProvider {
-    fn get_messages(&self) {
+    fn get_folders(&self) {
         let event_sender = self.event_sender.clone();
         let connection = self.connection.clone();
 
-        tokio::spawn(async move {
-            let mut messages = vec![];
-
-            let mut stream =
-                sqlx::query("SELECT id, from_addr, to_addr, subject FROM messages ORDER BY id")
-                    .map(|row: SqliteRow| Message {
-                        id: row.get(0),
-                        from: row.get(1),
-                        to: row.get(2),
-                        subject: row.get(3),
-                        body: String::from(""),
-                    })
-                    .fetch(&*connection);
-
-            loop {
-                let result = stream.try_next().await;
-                if let Err(e) = result {
-                    let app_event = AppEvent::Error(e.to_string());
-                    let event = Event::App(app_event);
-                    let _ = event_sender.send(event);
-                    return;
-                }
+        spawn(info_span!("get_folders"), async move {
+            let result: Result<Vec<(String,)>, sqlx::Error> =
+                sqlx::query_as("SELECT DISTINCT folder FROM messages ORDER BY folder")
+                    .fetch_all(&*connection)
+                    .await;
 
-                if let Some(message) = result.unwrap() {
-                    messages.push(message);
-                } else {
-                    break;
+            let app_event = match result {
+                Ok(rows) => {
+                    let mut folders = STANDARD_FOLDERS.map(String::from).to_vec();
+                    for (folder,) in rows {
+                        if !folders.contains(&folder) {
+                            folders.push(folder);
+                        }
+                    }
+                    AppEvent::FoldersLoaded(folders)
                 }
-            }
+                Err(e) => error_event(e),
+            };
 
-            let app_event = AppEvent::MessagesLoaded(messages);
             let event = Event::App(app_event);
             let _ = event_sender.send(event);
         });
     }
 
-    fn get_message(&self, id: u64) {
+    fn get_contacts(&self) {
         let event_sender = self.event_sender.clone();
         let connection = self.connection.clone();
 
-        tokio::spawn(async move {
-            let result = sqlx::query("SELECT body FROM messages WHERE id = ?")
-                .bind(id as i64)
-                .fetch_one(&*connection)
+        spawn(info_span!("get_contacts"), async move {
+            let result = sqlx::query_scalar("SELECT address FROM known_addresses ORDER BY address")
+                .fetch_all(&*connection)
                 .await;
 
             let app_event = match result {
-                Ok(row) => AppEvent::MessageBodyLoaded(id, row.get("body")),
-                Err(e) => AppEvent::Error(e.to_string()),
+                Ok(addresses) => AppEvent::ContactsLoaded(addresses),
+                Err(e) => error_event(e),
             };
 
             let event = Event::App(app_event);
@@ -508,41 +2001,2420 @@ impl MessageProvider for SqliteMessageProvider {
         });
     }
 
-    fn send_message(&self, message: &Message) {
+    fn get_address_book(&self) {
         let event_sender = self.event_sender.clone();
-        let message = message.clone();
-        let smtp_config = self.smtp_config.clone();
+        let connection = self.connection.clone();
 
-        tokio::spawn(async move {
-            // TODO handle validation
-
-            let email = lettre::Message::builder()
-                .from(smtp_config.username.parse().unwrap())
-                .to(message.to().parse().unwrap())
-                .subject(message.subject())
-                .header(ContentType::TEXT_PLAIN)
-                .body(message.body().to_string())
-                .unwrap();
+        spawn(info_span!("get_address_book"), async move {
+            let app_event = match load_address_book(&connection).await {
+                Ok(contacts) => AppEvent::AddressBookLoaded(contacts),
+                Err(e) => error_event(e),
+            };
 
-            let creds = Credentials::new(
-                smtp_config.username.to_owned(),
-                smtp_config.password.to_owned(),
-            );
+            let event = Event::App(app_event);
+            let _ = event_sender.send(event);
+        });
+    }
+
+    fn save_contact(&self, contact: &Contact) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let contact = contact.clone();
+
+        spawn(info_span!("save_contact"), async move {
+            let query = match contact.id {
+                Some(id) => sqlx::query("UPDATE contacts SET name = ?, email = ? WHERE id = ?")
+                    .bind(contact.name)
+                    .bind(contact.email)
+                    .bind(id as i64),
+                None => sqlx::query("INSERT INTO contacts (name, email) VALUES (?, ?)")
+                    .bind(contact.name)
+                    .bind(contact.email),
+            };
+            let result = match query.execute(&*connection).await {
+                // reload, so a new contact gets its id
+                Ok(_) => load_address_book(&connection).await,
+                Err(e) => Err(e),
+            };
+
+            let app_event = match result {
+                Ok(contacts) => AppEvent::AddressBookLoaded(contacts),
+                Err(e) => error_event(e),
+            };
+            let event = Event::App(app_event);
+            let _ = event_sender.send(event);
+        });
+    }
+
+    fn delete_contact(&self, id: u64) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("delete_contact", id), async move {
+            let result = sqlx::query("DELETE FROM contacts WHERE id = ?")
+                .bind(id as i64)
+                .execute(&*connection)
+                .await;
+
+            if let Err(e) = result {
+                let app_event = error_event(e);
+                let event = Event::App(app_event);
+                let _ = event_sender.send(event);
+            }
+        });
+    }
 
-            let mailer: AsyncSmtpTransport<Tokio1Executor> =
-                AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp_config.ip)
-                    .unwrap()
-                    .credentials(creds)
-                    .build();
+    fn import_contacts(&self, contacts: Vec<Contact>) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(
+            info_span!("import_contacts", count = contacts.len()),
+            async move {
+                let result = async {
+                    let mut added = 0;
+                    for contact in contacts {
+                        let result = sqlx::query(
+                            "INSERT INTO contacts (name, email) SELECT ?1, ?2
+                                WHERE NOT EXISTS
+                                    (SELECT 1 FROM contacts WHERE email = ?2 COLLATE NOCASE)",
+                        )
+                        .bind(&contact.name)
+                        .bind(&contact.email)
+                        .execute(&*connection)
+                        .await?;
+                        added += result.rows_affected() as usize;
+
+                        // a known address may still be missing its name
+                        sqlx::query(
+                            "UPDATE contacts SET name = ?1
+                                WHERE email = ?2 COLLATE NOCASE AND name = ''",
+                        )
+                        .bind(&contact.name)
+                        .bind(&contact.email)
+                        .execute(&*connection)
+                        .await?;
+                    }
+                    Ok::<_, sqlx::Error>((added, load_address_book(&connection).await?))
+                }
+                .await;
+
+                let app_events = match result {
+                    Ok((added, contacts)) => vec![
+                        AppEvent::ContactsImported(added),
+                        AppEvent::AddressBookLoaded(contacts),
+                    ],
+                    Err(e) => vec![error_event(e)],
+                };
+                for app_event in app_events {
+                    let _ = event_sender.send(Event::App(app_event));
+                }
+            },
+        );
+    }
+
+    fn complete_address(&self, query: &str) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let query = query.to_string();
 
-            let app_event = AppEvent::MessageSent(match mailer.send(email).await {
-                Ok(_) => None,
-                Err(e) => Some(e.to_string()),
+        spawn(info_span!("complete_address"), async move {
+            let pattern = like_pattern(&query.trim().to_lowercase());
+            // only the contacts that match, rather than the whole address book on every key
+            let address_book = sqlx::query_as(
+                "SELECT id, name, email FROM contacts
+                    WHERE name LIKE ?1 ESCAPE '\\' OR email LIKE ?1 ESCAPE '\\'
+                    ORDER BY name COLLATE NOCASE, email",
+            )
+            .bind(&pattern)
+            .fetch_all(&*connection)
+            .await
+            .map(|rows: Vec<(i64, String, String)>| {
+                rows.into_iter()
+                    .map(|(id, name, email)| Contact {
+                        id: Some(id as u64),
+                        name,
+                        email,
+                    })
+                    .collect::<Vec<Contact>>()
             });
+            // the most used first, each month since an address was last used counting as much
+            // against it as never having used it again; contacts found by name are ranked too
+            let seen = sqlx::query_scalar(
+                "SELECT address FROM known_addresses
+                    WHERE address LIKE ?1 ESCAPE '\\'
+                        OR address IN
+                            (SELECT lower(email) FROM contacts WHERE name LIKE ?1 ESCAPE '\\')
+                    ORDER BY uses / (1.0 + (unixepoch() - last_used) / 2592000.0) DESC, address",
+            )
+            .bind(&pattern)
+            .fetch_all(&*connection)
+            .await;
+
+            let app_event = match (address_book, seen) {
+                (Ok(address_book), Ok(seen)) => {
+                    let completions = contacts::completions(&query, &address_book, &seen);
+                    AppEvent::AddressCompletionsLoaded(query, completions)
+                }
+                (Err(e), _) | (_, Err(e)) => error_event(e),
+            };
 
-            // let app_event = AppEvent::MessageSent(None);
             let event = Event::App(app_event);
             let _ = event_sender.send(event);
         });
     }
+
+    fn get_messages(&self, folder: &str) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let folder = folder.to_string();
+
+        spawn(info_span!("get_messages", %folder), async move {
+            send_message_count(&connection, folder.clone(), &event_sender).await;
+
+            let app_event = match load_page(&connection, &folder, None).await {
+                Ok(messages) => AppEvent::MessagesLoaded(messages),
+                Err(e) => error_event(e),
+            };
+            let event = Event::App(app_event);
+            let _ = event_sender.send(event);
+        });
+    }
+
+    fn get_older_messages(&self, folder: &str, before: u64) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let folder = folder.to_string();
+
+        spawn(
+            info_span!("get_older_messages", %folder, before),
+            async move {
+                let app_event = match load_page(&connection, &folder, Some(before)).await {
+                    Ok(messages) => AppEvent::OlderMessagesLoaded(folder, messages),
+                    Err(e) => error_event(e),
+                };
+                let event = Event::App(app_event);
+                let _ = event_sender.send(event);
+            },
+        );
+    }
+
+    fn poll_folders(&self, schedule: Schedule) {
+        // checks already going pick the new schedule up
+        if self.polling.send_replace(Some(schedule)).is_some() {
+            return;
+        }
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let rules = self.rules.clone();
+        let own = self.smtp_config.own_addresses();
+        let updates = self.polling.subscribe();
+
+        spawn(
+            info_span!("poll_folders"),
+            poll(connection, rules, own, updates, event_sender),
+        );
+    }
+
+    fn search_messages(&self, query: &str) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let query = query.to_string();
+
+        spawn(info_span!("search_messages"), async move {
+            let search = query::parse(&query);
+            // the words are searched for in the full-text index, the conditions in the table
+            let (mut sql, mut binds) = if search.words.is_empty() {
+                (
+                    format!("SELECT {MESSAGE_COLUMNS} FROM messages WHERE 1"),
+                    vec![],
+                )
+            } else {
+                let sql = format!(
+                    "SELECT {MESSAGE_COLUMNS} FROM messages_fts
+                        JOIN messages ON messages.id = messages_fts.rowid
+                        WHERE messages_fts MATCH ?"
+                );
+                (sql, vec![fts_query(&search.words.join(" "))])
+            };
+            for condition in &search.conditions {
+                let (condition, values) = condition_sql(condition);
+                sql.push_str(" AND ");
+                sql.push_str(condition);
+                binds.extend(values);
+            }
+            sql.push_str(if search.words.is_empty() {
+                " ORDER BY messages.date DESC"
+            } else {
+                " ORDER BY rank"
+            });
+
+            let mut statement = sqlx::query(&sql);
+            for value in binds {
+                statement = statement.bind(value);
+            }
+            let result = statement
+                .map(|row: SqliteRow| message_from_row(&row))
+                .fetch_all(&*connection)
+                .await;
+
+            let app_event = match result {
+                Ok(messages) => AppEvent::SearchResultsLoaded(query, messages),
+                Err(e) => error_event(e),
+            };
+            let event = Event::App(app_event);
+            let _ = event_sender.send(event);
+        });
+    }
+
+    fn get_message(&self, id: u64) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("get_message", id), async move {
+            let result = sqlx::query("SELECT body, raw FROM messages WHERE id = ?")
+                .bind(id as i64)
+                .fetch_one(&*connection)
+                .await;
+
+            let app_event = match result {
+                Ok(row) => {
+                    let mut raw: Option<Vec<u8>> = row.get("raw");
+                    let mut verification = None;
+                    if let Some(opened) = &raw
+                        && let Some((content, found)) = pgp::open(opened).await
+                    {
+                        raw = Some(content);
+                        verification = Some(found);
+                    }
+                    let parts = raw
+                        .and_then(|raw| mime::parse(&raw))
+                        .unwrap_or_else(|| vec![MessagePart::plain(row.get("body"))]);
+                    AppEvent::MessageBodyLoaded(id, parts, verification)
+                }
+                Err(e) => error_event(e),
+            };
+
+            let event = Event::App(app_event);
+            let _ = event_sender.send(event);
+        });
+    }
+
+    fn export_message(&self, id: u64, path: &Path) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let path = path.to_path_buf();
+
+        spawn(info_span!("export_message", id), async move {
+            let result = export(&connection, id, &path).await.map(|_| path);
+            let _ = event_sender.send(Event::App(AppEvent::MessageExported(result)));
+        });
+    }
+
+    fn import_messages(&self, paths: Vec<PathBuf>, folder: &str) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let rules = self.rules.clone();
+        let own = self.smtp_config.own_addresses();
+        let folder = folder.to_string();
+
+        spawn(
+            info_span!("import_messages", count = paths.len(), folder),
+            async move {
+                let mut imported = 0;
+                let mut failures = vec![];
+                for path in paths {
+                    let name = path
+                        .file_name()
+                        .unwrap_or(path.as_os_str())
+                        .to_string_lossy();
+                    match import(&connection, &rules, &own, &path, &folder).await {
+                        Ok(()) => imported += 1,
+                        Err(e) => failures.push(format!("{name}: {e}")),
+                    }
+                }
+                let app_event = AppEvent::MessagesImported(imported, failures);
+                let _ = event_sender.send(Event::App(app_event));
+            },
+        );
+    }
+
+    fn send_message(&self, message: &Message) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let message = message.clone();
+        let smtp_config = self.smtp_config.clone();
+
+        spawn(info_span!("send_message"), async move {
+            let result = deliver(&smtp_config, &message).await;
+            if result.is_ok() {
+                let own = smtp_config.own_addresses();
+                let lists = [message.to(), message.cc()];
+                remember_recipients(&connection, &own, lists, &event_sender).await;
+            }
+            let app_event = match result {
+                Ok(Some(path)) => AppEvent::MessageWritten(path),
+                result => AppEvent::MessageSent(result.err()),
+            };
+
+            let event = Event::App(app_event);
+            let _ = event_sender.send(event);
+        });
+    }
+
+    fn set_unread(&self, ids: &[u64], unread: bool) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let ids = id_list(ids);
+
+        spawn(info_span!("set_unread", %ids, unread), async move {
+            let result = sqlx::query(
+                "UPDATE messages SET unread = ? WHERE id IN (SELECT value FROM json_each(?))",
+            )
+            .bind(unread)
+            .bind(&ids)
+            .execute(&*connection)
+            .await;
+
+            if let Err(e) = result {
+                let app_event = error_event(e);
+                let event = Event::App(app_event);
+                let _ = event_sender.send(event);
+                return;
+            }
+            for folder in folders_of(&connection, &ids).await {
+                send_message_count(&connection, folder, &event_sender).await;
+            }
+        });
+    }
+
+    fn set_flagged(&self, id: u64, flagged: bool) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("set_flagged", id, flagged), async move {
+            let result = sqlx::query("UPDATE messages SET flagged = ? WHERE id = ?")
+                .bind(flagged)
+                .bind(id as i64)
+                .execute(&*connection)
+                .await;
+
+            if let Err(e) = result {
+                let app_event = error_event(e);
+                let event = Event::App(app_event);
+                let _ = event_sender.send(event);
+            }
+        });
+    }
+
+    fn move_messages(&self, ids: &[u64], folder: &str) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let folder = folder.to_string();
+        let ids = id_list(ids);
+
+        spawn(info_span!("move_messages", %ids, %folder), async move {
+            let sources = folders_of(&connection, &ids).await;
+            if let Err(e) = set_folder(&connection, &ids, &folder).await {
+                let app_event = error_event(e);
+                let event = Event::App(app_event);
+                let _ = event_sender.send(event);
+                return;
+            }
+            for folder in sources.into_iter().chain([folder]) {
+                send_message_count(&connection, folder, &event_sender).await;
+            }
+        });
+    }
+
+    fn delete_messages(&self, ids: &[u64]) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let ids = id_list(ids);
+
+        spawn(info_span!("delete_messages", %ids), async move {
+            let folders = folders_of(&connection, &ids).await;
+            let result =
+                sqlx::query("DELETE FROM messages WHERE id IN (SELECT value FROM json_each(?))")
+                    .bind(&ids)
+                    .execute(&*connection)
+                    .await;
+
+            if let Err(e) = result {
+                let app_event = error_event(e);
+                let event = Event::App(app_event);
+                let _ = event_sender.send(event);
+                return;
+            }
+            for folder in folders {
+                send_message_count(&connection, folder, &event_sender).await;
+            }
+        });
+    }
+
+    fn empty_trash(&self, days: u32) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("empty_trash", days), async move {
+            let result = sqlx::query(
+                "DELETE FROM messages WHERE folder = ? AND trashed_at <= unixepoch() - ? * 86400",
+            )
+            .bind(TRASH)
+            .bind(days)
+            .execute(&*connection)
+            .await;
+
+            if let Err(e) = result {
+                let app_event = error_event(e);
+                let event = Event::App(app_event);
+                let _ = event_sender.send(event);
+            } else {
+                send_message_count(&connection, TRASH.to_string(), &event_sender).await;
+            }
+        });
+    }
+
+    fn get_watched_threads(&self) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("get_watched_threads"), async move {
+            let result: Result<Vec<(String,)>, sqlx::Error> =
+                sqlx::query_as("SELECT root FROM watched_threads")
+                    .fetch_all(&*connection)
+                    .await;
+
+            let app_event = match result {
+                Ok(rows) => {
+                    AppEvent::WatchedThreadsLoaded(rows.into_iter().map(|(root,)| root).collect())
+                }
+                Err(e) => error_event(e),
+            };
+
+            let event = Event::App(app_event);
+            let _ = event_sender.send(event);
+        });
+    }
+
+    fn set_thread_watched(&self, root: &str, watched: bool) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let root = root.to_string();
+
+        spawn(info_span!("set_thread_watched", watched), async move {
+            let query = if watched {
+                "INSERT OR IGNORE INTO watched_threads (root) VALUES (?)"
+            } else {
+                "DELETE FROM watched_threads WHERE root = ?"
+            };
+            let result = sqlx::query(query).bind(root).execute(&*connection).await;
+
+            if let Err(e) = result {
+                let app_event = error_event(e);
+                let event = Event::App(app_event);
+                let _ = event_sender.send(event);
+            }
+        });
+    }
+
+    fn get_mutes(&self) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("get_mutes"), async move {
+            let result: Result<Vec<(String, String)>, sqlx::Error> =
+                sqlx::query_as("SELECT kind, value FROM mutes")
+                    .fetch_all(&*connection)
+                    .await;
+
+            let app_event = match result {
+                Ok(rows) => AppEvent::MutesLoaded(
+                    rows.into_iter()
+                        .filter_map(|(kind, value)| Mute::from_row(&kind, value))
+                        .collect(),
+                ),
+                Err(e) => error_event(e),
+            };
+
+            let event = Event::App(app_event);
+            let _ = event_sender.send(event);
+        });
+    }
+
+    fn set_muted(&self, mute: &Mute, muted: bool) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let kind = mute.kind();
+        let value = mute.value().to_string();
+
+        spawn(info_span!("set_muted", muted), async move {
+            let query = if muted {
+                "INSERT OR IGNORE INTO mutes (kind, value) VALUES (?, ?)"
+            } else {
+                "DELETE FROM mutes WHERE kind = ? AND value = ?"
+            };
+            let result = sqlx::query(query)
+                .bind(kind)
+                .bind(value)
+                .execute(&*connection)
+                .await;
+
+            if let Err(e) = result {
+                let app_event = error_event(e);
+                let event = Event::App(app_event);
+                let _ = event_sender.send(event);
+            }
+        });
+    }
+
+    fn get_tags(&self) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("get_tags"), async move {
+            let app_event = match load_tags(&connection).await {
+                Ok(tags) => AppEvent::TagsLoaded(tags),
+                Err(e) => error_event(e),
+            };
+            let _ = event_sender.send(Event::App(app_event));
+        });
+    }
+
+    fn set_tagged(&self, id: u64, tag: &str, tagged: bool) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let tag = tag.to_string();
+
+        spawn(info_span!("set_tagged", id, %tag, tagged), async move {
+            // the tags to pick from change as the last message with one loses it
+            let result = match set_tagged(&connection, id, &tag, tagged).await {
+                Ok(()) => load_tags(&connection).await,
+                Err(e) => Err(e),
+            };
+            let app_event = match result {
+                Ok(tags) => AppEvent::TagsLoaded(tags),
+                Err(e) => error_event(e),
+            };
+            let _ = event_sender.send(Event::App(app_event));
+        });
+    }
+
+    fn get_remote_content_senders(&self) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("get_remote_content_senders"), async move {
+            let result: Result<Vec<(String,)>, sqlx::Error> =
+                sqlx::query_as("SELECT address FROM remote_content_senders")
+                    .fetch_all(&*connection)
+                    .await;
+
+            let app_event = match result {
+                Ok(rows) => AppEvent::RemoteContentSendersLoaded(
+                    rows.into_iter().map(|(address,)| address).collect(),
+                ),
+                Err(e) => error_event(e),
+            };
+
+            let event = Event::App(app_event);
+            let _ = event_sender.send(event);
+        });
+    }
+
+    fn set_remote_content_allowed(&self, sender: &str, allowed: bool) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let sender = sender.to_string();
+
+        spawn(
+            info_span!("set_remote_content_allowed", allowed),
+            async move {
+                let query = if allowed {
+                    "INSERT OR IGNORE INTO remote_content_senders (address) VALUES (?)"
+                } else {
+                    "DELETE FROM remote_content_senders WHERE address = ?"
+                };
+                let result = sqlx::query(query).bind(sender).execute(&*connection).await;
+
+                if let Err(e) = result {
+                    let app_event = error_event(e);
+                    let event = Event::App(app_event);
+                    let _ = event_sender.send(event);
+                }
+            },
+        );
+    }
+
+    fn send_messages(&self, messages: Vec<Message>) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let smtp_config = self.smtp_config.clone();
+        let outbox = self.outbox.clone();
+
+        spawn(
+            info_span!("send_messages", count = messages.len()),
+            async move {
+                // spread out so the outbox sends them no faster than the rate limit allows
+                let interval = smtp_config.merge_interval();
+                let start = Local::now();
+                let mut queued = 0;
+                let mut failures = vec![];
+
+                for message in &messages {
+                    let send_at =
+                        start + TimeDelta::from_std(interval * queued).unwrap_or_default();
+                    match schedule(&connection, &smtp_config, message, send_at, true).await {
+                        Ok(()) => queued += 1,
+                        Err(e) => failures.push(format!("{}: {e}", message.to())),
+                    }
+                }
+                outbox.notify_one();
+
+                let app_event = AppEvent::MailMergeQueued(queued as usize, failures);
+                let _ = event_sender.send(Event::App(app_event));
+                send_scheduled(&connection, &event_sender).await;
+            },
+        );
+    }
+
+    fn schedule_message(&self, message: &Message, send_at: DateTime<Local>) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let smtp_config = self.smtp_config.clone();
+        let message = message.clone();
+        let outbox = self.outbox.clone();
+
+        spawn(info_span!("schedule_message", %send_at), async move {
+            let result = schedule(&connection, &smtp_config, &message, send_at, false).await;
+            // it may be due sooner than anything the outbox is waiting for
+            outbox.notify_one();
+            let app_event = AppEvent::MessageScheduled(result.map(|()| send_at));
+            let _ = event_sender.send(Event::App(app_event));
+            send_scheduled(&connection, &event_sender).await;
+        });
+    }
+
+    fn get_scheduled(&self) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("get_scheduled"), async move {
+            send_scheduled(&connection, &event_sender).await;
+        });
+    }
+
+    fn send_outbox(&self) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let smtp_config = self.smtp_config.clone();
+        let wake = Arc::downgrade(&self.outbox);
+
+        spawn(
+            info_span!("send_outbox"),
+            send_outbox(connection, smtp_config, wake, event_sender),
+        );
+    }
+
+    fn send_scheduled_now(&self, id: u64) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let outbox = self.outbox.clone();
+
+        spawn(info_span!("send_scheduled_now", id), async move {
+            let result =
+                sqlx::query("UPDATE outbox SET send_at = unixepoch(), error = NULL WHERE id = ?")
+                    .bind(id as i64)
+                    .execute(&*connection)
+                    .await;
+
+            match result {
+                // the outbox sends it along with anything else due
+                Ok(_) => outbox.notify_one(),
+                Err(e) => {
+                    let _ = event_sender.send(Event::App(error_event(e)));
+                }
+            }
+        });
+    }
+
+    fn cancel_scheduled(&self, id: u64) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("cancel_scheduled", id), async move {
+            let result = sqlx::query("DELETE FROM outbox WHERE id = ?")
+                .bind(id as i64)
+                .execute(&*connection)
+                .await;
+
+            if let Err(e) = result {
+                let _ = event_sender.send(Event::App(error_event(e)));
+            }
+            send_scheduled(&connection, &event_sender).await;
+        });
+    }
+
+    fn save_draft(&self, id: u64, draft: &Draft) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let draft = draft.clone();
+
+        spawn(info_span!("save_draft"), async move {
+            if let Err(e) = store_draft(&connection, id, &draft).await {
+                let _ = event_sender.send(Event::App(error_event(e)));
+            }
+        });
+    }
+
+    fn delete_draft(&self, id: u64) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("delete_draft"), async move {
+            if let Err(e) = discard_draft(&connection, id).await {
+                let _ = event_sender.send(Event::App(error_event(e)));
+            }
+        });
+    }
+
+    fn get_drafts(&self) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+
+        spawn(info_span!("get_drafts"), async move {
+            let drafts = match load_drafts(&connection).await {
+                Ok(drafts) if drafts.is_empty() => return,
+                Ok(drafts) => drafts,
+                Err(e) => {
+                    let _ = event_sender.send(Event::App(error_event(e)));
+                    return;
+                }
+            };
+            let mut loaded = Vec::with_capacity(drafts.len());
+            for (id, draft) in drafts {
+                // the message replied to may have been deleted since, leaving a reply that isn't
+                // threaded
+                let reply_to = match draft.reply_to {
+                    Some(reply_to) => sqlx::query(&format!(
+                        "SELECT {MESSAGE_COLUMNS} FROM messages WHERE id = ?"
+                    ))
+                    .bind(reply_to as i64)
+                    .fetch_optional(&*connection)
+                    .await
+                    .ok()
+                    .flatten()
+                    .map(|row| Box::new(message_from_row(&row))),
+                    None => None,
+                };
+                loaded.push((id, draft, reply_to));
+            }
+            let _ = event_sender.send(Event::App(AppEvent::DraftsLoaded(loaded)));
+        });
+    }
+
+    fn check_connection(&self) {
+        let event_sender = self.event_sender.clone();
+        let connection = self.connection.clone();
+        let smtp_config = self.smtp_config.clone();
+
+        spawn(info_span!("check_connection"), async move {
+            let state = match check_connection(&connection, &smtp_config).await {
+                Ok(()) => ConnectionState::Online,
+                Err(e) => {
+                    tracing::warn!("{e}");
+                    ConnectionState::Offline(e)
+                }
+            };
+            let _ = event_sender.send(Event::App(AppEvent::ConnectionState(state)));
+        });
+    }
+}
+
+/// How long to wait before sending again after the first temporary failure, doubled after each
+/// further one.
+const RETRY_BACKOFF: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// The longest to wait before sending again, however many times it failed.
+const MAX_RETRY_BACKOFF: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// How long the SMTP server has to answer while sending, as long as lettre gives it by default.
+const SMTP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How long the SMTP server has to answer a connection check.
+const SMTP_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Whether the database answers, and the SMTP server too unless messages are only written to
+/// files. Logging in is left to sending, so the password isn't asked for just to check.
+async fn check_connection(
+    connection: &sqlx::SqlitePool,
+    smtp_config: &SmtpConfig,
+) -> Result<(), String> {
+    sqlx::query("SELECT 1")
+        .execute(connection)
+        .await
+        .map_err(|e| format!("Database: {e}"))?;
+    if smtp_config.dry_run_send.is_some() {
+        return Ok(());
+    }
+
+    let mut connection = connect_smtp(smtp_config, SMTP_CHECK_TIMEOUT)
+        .await
+        .map_err(|e| format!("SMTP: {e}"))?
+        .map_err(|e| format!("SMTP: {e}"))?;
+    if !connection.test_connected().await {
+        return Err(format!("SMTP: {} isn't answering", smtp_config.ip));
+    }
+    let _ = connection.quit().await;
+    Ok(())
+}
+
+/// Connect to the SMTP server over TLS, checking its certificate as the `[tls]` table says. The
+/// outer error is for settings or a certificate that trying again won't help with.
+async fn connect_smtp(
+    smtp_config: &SmtpConfig,
+    timeout: std::time::Duration,
+) -> Result<Result<AsyncSmtpConnection, smtp::Error>, String> {
+    let tls = smtp_config.tls.parameters(&smtp_config.ip)?;
+    if smtp_config.tls.accepts_invalid_certs() {
+        tracing::warn!(
+            host = smtp_config.ip,
+            "not checking the server's certificate"
+        );
+    }
+    let connection = match AsyncSmtpConnection::connect_tokio1(
+        (smtp_config.ip.as_str(), SUBMISSIONS_PORT),
+        Some(timeout),
+        &ClientId::default(),
+        Some(tls),
+        None,
+    )
+    .await
+    {
+        Ok(connection) => connection,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let certificate = connection
+        .peer_certificate()
+        .map_err(|e| format!("Couldn't read the server's certificate: {e}"))?;
+    smtp_config.tls.check_fingerprint(&certificate)?;
+    Ok(Ok(connection))
+}
+
+/// How far sending got before it failed.
+enum SendError {
+    /// The server turned down the username and password.
+    Login(smtp::Error),
+    /// Before the server was given the message, so it can safely be sent again.
+    BeforeData(smtp::Error),
+    /// Once the message was being handed over, after which the server may have it.
+    AfterData(smtp::Error),
+}
+
+/// Log in over `connection` and send `email`.
+///
+/// This goes through the commands as lettre's `send` does, to tell how far it got.
+async fn send_email(
+    mut connection: AsyncSmtpConnection,
+    creds: &Credentials,
+    envelope: &Envelope,
+    raw: &[u8],
+) -> Result<(), SendError> {
+    let login = connection
+        .auth(&[Mechanism::Plain, Mechanism::Login], creds)
+        .await;
+    let result = match login {
+        Ok(_) => match send_envelope(&mut connection, envelope, raw).await {
+            Ok(()) => connection.message(raw).await.map_err(SendError::AfterData),
+            Err(e) => Err(SendError::BeforeData(e)),
+        },
+        Err(e) if e.is_permanent() => Err(SendError::Login(e)),
+        Err(e) => Err(SendError::BeforeData(e)),
+    };
+    match result {
+        Ok(_) => {
+            let _ = connection.quit().await;
+            Ok(())
+        }
+        Err(e) => {
+            connection.abort().await;
+            Err(e)
+        }
+    }
+}
+
+/// Send everything up to the `DATA` command, after which the server expects `email`.
+async fn send_envelope(
+    connection: &mut AsyncSmtpConnection,
+    envelope: &Envelope,
+    email: &[u8],
+) -> Result<(), smtp::Error> {
+    // ask for the extensions non-ASCII addresses and content need, where the server has them
+    let mut options = vec![];
+    let mut addresses = envelope.from().into_iter().chain(envelope.to());
+    if addresses.any(|address| !address.to_string().is_ascii())
+        && connection
+            .server_info()
+            .supports_feature(Extension::SmtpUtfEight)
+    {
+        options.push(MailParameter::SmtpUtfEight);
+    }
+    if !email.is_ascii()
+        && connection
+            .server_info()
+            .supports_feature(Extension::EightBitMime)
+    {
+        options.push(MailParameter::Body(MailBodyParameter::EightBitMime));
+    }
+
+    connection
+        .command(Mail::new(envelope.from().cloned(), options))
+        .await?;
+    for recipient in envelope.to() {
+        connection
+            .command(Rcpt::new(recipient.clone(), vec![]))
+            .await?;
+    }
+    connection.command(Data).await?;
+    Ok(())
+}
+
+/// Run a provider operation in the background, within `span` so that what it logs, including
+/// the queries it runs, can be told apart from other operations'.
+fn spawn(span: Span, task: impl Future<Output = ()> + Send + 'static) {
+    tokio::spawn(task.instrument(span));
+}
+
+/// An error to show in place of an operation's result, logged along with the operation.
+fn error_event(error: impl std::fmt::Display) -> AppEvent {
+    tracing::error!("{error}");
+    AppEvent::Error(error.to_string())
+}
+
+/// The columns `message_from_row` reads, in order.
+const MESSAGE_COLUMNS: &str = "messages.id, messages.from_addr, messages.to_addr,
+    messages.subject, messages.unread, messages.flagged, messages.date, messages.message_id,
+    messages.in_reply_to, messages.refs, messages.cc_addr, messages.folder,
+    messages.size,
+    (SELECT group_concat(tags.name, ' ') FROM message_tags
+        JOIN tags ON tags.id = message_tags.tag_id WHERE message_tags.message_id = messages.id),
+    messages.snippet";
+
+/// A message listed in the table, read from a row of `MESSAGE_COLUMNS`. The body is loaded
+/// separately once the message is opened.
+fn message_from_row(row: &SqliteRow) -> Message {
+    Message {
+        id: row.get(0),
+        from: row.get(1),
+        to: row.get(2),
+        cc: row.get(10),
+        subject: row.get(3),
+        body: String::from(""),
+        date: row.get(6),
+        unread: row.get(4),
+        flagged: row.get(5),
+        folder: row.get(11),
+        size: row.get::<Option<i64>, _>(12).map(|size| size as u64),
+        message_id: row.get(7),
+        in_reply_to: row.get(8),
+        references: row
+            .get::<Option<String>, _>(9)
+            .map(|refs| refs.split_whitespace().map(String::from).collect())
+            .unwrap_or_default(),
+        parts: vec![],
+        protection: Protection::None,
+        verification: None,
+        tags: row
+            .get::<Option<String>, _>(13)
+            .map(|tags| tags.split_whitespace().map(String::from).collect())
+            .unwrap_or_default(),
+        snippet: row.get::<Option<String>, _>(14).unwrap_or_default(),
+    }
+}
+
+/// Turn the words of a search into an FTS5 query matching all of them, where each word may also
+/// be the start of a longer one. Quoting the words keeps FTS5's own syntax out of the way.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A search condition as SQL about a row of `messages`, and the values it binds, in order.
+fn condition_sql(condition: &Condition) -> (&'static str, Vec<String>) {
+    let contains = |value: &String| vec![value.to_lowercase()];
+    match condition {
+        Condition::From(value) => ("instr(lower(messages.from_addr), ?) > 0", contains(value)),
+        Condition::To(value) => (
+            "(instr(lower(messages.to_addr), ?) > 0 OR instr(lower(messages.cc_addr), ?) > 0)",
+            [contains(value), contains(value)].concat(),
+        ),
+        Condition::Subject(value) => ("instr(lower(messages.subject), ?) > 0", contains(value)),
+        Condition::Folder(folder) => ("messages.folder = ? COLLATE NOCASE", vec![folder.clone()]),
+        Condition::Tag(tag) => (
+            "EXISTS (SELECT 1 FROM message_tags JOIN tags ON tags.id = message_tags.tag_id
+                WHERE message_tags.message_id = messages.id AND tags.name = ? COLLATE NOCASE)",
+            vec![tag.clone()],
+        ),
+        Condition::Unread(true) => ("messages.unread = 1", vec![]),
+        Condition::Unread(false) => ("messages.unread = 0", vec![]),
+        Condition::Flagged => ("messages.flagged = 1", vec![]),
+        Condition::HasAttachment => ("messages.has_attachment = 1", vec![]),
+        Condition::Newer(seconds) => (
+            "messages.date >= unixepoch() - CAST(? AS INTEGER)",
+            vec![seconds.to_string()],
+        ),
+        Condition::Older(seconds) => (
+            "messages.date < unixepoch() - CAST(? AS INTEGER)",
+            vec![seconds.to_string()],
+        ),
+        Condition::MessageId(id) => ("messages.message_id = ?", vec![id.clone()]),
+    }
+}
+
+/// The newest [`PAGE_SIZE`] messages in `folder`, or those before the message with id `before`,
+/// oldest first.
+async fn load_page(
+    connection: &sqlx::SqlitePool,
+    folder: &str,
+    before: Option<u64>,
+) -> Result<Vec<Message>, sqlx::Error> {
+    let query = format!(
+        "SELECT {MESSAGE_COLUMNS} FROM messages WHERE folder = ? AND id < ?
+            ORDER BY id DESC LIMIT ?"
+    );
+    let mut messages: Vec<Message> = sqlx::query(&query)
+        .bind(folder)
+        .bind(before.map_or(i64::MAX, |id| id as i64))
+        .bind(PAGE_SIZE as i64)
+        .map(|row: SqliteRow| message_from_row(&row))
+        .fetch_all(connection)
+        .await?;
+    messages.reverse();
+    Ok(messages)
+}
+
+/// How long to wait at most before looking for folders to check again, e.g. for one that is
+/// only just being asked about.
+const POLL_IDLE: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Check each folder for new messages whenever the schedule from `updates` says it is due, until
+/// the provider the schedule comes from is dropped. Messages stored before the checks started are
+/// not new.
+async fn poll(
+    connection: Arc<sqlx::SqlitePool>,
+    rules: Arc<Rules>,
+    own: Vec<String>,
+    mut updates: watch::Receiver<Option<Schedule>>,
+    event_sender: mpsc::UnboundedSender<Event>,
+) {
+    let mut schedule = updates.borrow_and_update().clone().unwrap_or_default();
+    let start: (i64,) = match sqlx::query_as("SELECT COALESCE(MAX(id), 0) FROM messages")
+        .fetch_one(&*connection)
+        .await
+    {
+        Ok(start) => start,
+        Err(e) => {
+            let _ = event_sender.send(Event::App(error_event(e)));
+            return;
+        }
+    };
+    // the newest message seen in each folder
+    let mut newest: HashMap<String, i64> = HashMap::new();
+
+    loop {
+        let folders: Result<Vec<(String,)>, _> =
+            sqlx::query_as("SELECT DISTINCT folder FROM messages")
+                .fetch_all(&*connection)
+                .await;
+        match folders {
+            Ok(folders) => {
+                let now = std::time::Instant::now();
+                for (folder,) in folders {
+                    if !schedule.is_due(&folder, now) {
+                        continue;
+                    }
+                    let after = newest.get(&folder).copied().unwrap_or(start.0);
+                    let checked =
+                        check_folder(&connection, &rules, &own, &folder, after, &event_sender);
+                    if let Some(id) = checked.await {
+                        newest.insert(folder, id);
+                    }
+                }
+            }
+            Err(e) => {
+                let _ = event_sender.send(Event::App(error_event(e)));
+            }
+        }
+
+        let wait = schedule.next_check().map_or(POLL_IDLE, |at| {
+            at.saturating_duration_since(std::time::Instant::now())
+                .min(POLL_IDLE)
+        });
+        tokio::select! {
+            changed = updates.changed() => match changed {
+                Ok(()) => schedule = updates.borrow_and_update().clone().unwrap_or_default(),
+                // the provider is gone, and the account with it
+                Err(_) => return,
+            },
+            () = tokio::time::sleep(wait) => {}
+        }
+    }
+}
+
+/// Send the messages stored in `folder` after the message with id `after`, as the rules leave
+/// them, followed by the folder's counts. Returns the id of the newest of them, if there are any.
+async fn check_folder(
+    connection: &sqlx::SqlitePool,
+    rules: &Rules,
+    own: &[String],
+    folder: &str,
+    after: i64,
+    event_sender: &mpsc::UnboundedSender<Event>,
+) -> Option<i64> {
+    tracing::debug!(folder, after, "checking for new messages");
+    // the rules run first, so the new messages are shown as they leave them
+    if let Err(e) = process_arrivals(connection, rules, own).await {
+        let _ = event_sender.send(Event::App(error_event(e)));
+    }
+    let query =
+        format!("SELECT {MESSAGE_COLUMNS} FROM messages WHERE folder = ? AND id > ? ORDER BY id");
+    let result = sqlx::query(&query)
+        .bind(folder)
+        .bind(after)
+        .map(|row: SqliteRow| message_from_row(&row))
+        .fetch_all(connection)
+        .await;
+    let newest = result
+        .as_ref()
+        .ok()
+        .and_then(|messages| messages.iter().map(|m| m.id() as i64).max());
+    let app_event = match result {
+        Ok(messages) => AppEvent::NewMessagesLoaded(folder.to_string(), messages),
+        Err(e) => error_event(e),
+    };
+    let _ = event_sender.send(Event::App(app_event));
+    send_message_count(connection, folder.to_string(), event_sender).await;
+    newest
+}
+
+/// Send how many messages there are in `folder`, and how many of them are unread, as an
+/// [`AppEvent::MessageCountLoaded`].
+async fn send_message_count(
+    connection: &sqlx::SqlitePool,
+    folder: String,
+    event_sender: &mpsc::UnboundedSender<Event>,
+) {
+    let counts: Result<(i64, i64), _> =
+        sqlx::query_as("SELECT COUNT(*), COALESCE(SUM(unread), 0) FROM messages WHERE folder = ?")
+            .bind(&folder)
+            .fetch_one(connection)
+            .await;
+    let app_event = match counts {
+        Ok((total, unread)) => {
+            AppEvent::MessageCountLoaded(folder, total as usize, unread as usize)
+        }
+        Err(e) => error_event(e),
+    };
+    let _ = event_sender.send(Event::App(app_event));
+}
+
+/// Build `message` to be sent at `send_at`, and keep it in the outbox until then. A `paced`
+/// message, such as one of a mail merge, is sent no sooner than the rate limit allows after the
+/// one before it.
+async fn schedule(
+    connection: &sqlx::SqlitePool,
+    smtp_config: &SmtpConfig,
+    message: &Message,
+    send_at: DateTime<Local>,
+    paced: bool,
+) -> Result<(), String> {
+    let email = build_email(smtp_config, message, Some(send_at)).await?;
+    let envelope = email.envelope();
+    let sender = envelope.from().map(ToString::to_string).unwrap_or_default();
+    let recipients: Vec<String> = envelope.to().iter().map(ToString::to_string).collect();
+
+    sqlx::query(
+        "INSERT INTO outbox (send_at, to_addr, subject, sender, recipients, raw, paced)
+            VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(send_at.timestamp())
+    .bind(message.to())
+    .bind(message.subject())
+    .bind(sender)
+    .bind(recipients.join(" "))
+    .bind(email.formatted())
+    .bind(paced)
+    .execute(connection)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// How long the outbox waits at most before looking for due messages again.
+const OUTBOX_IDLE: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Send the messages in the outbox as they fall due, and whenever `wake` is notified, until the
+/// provider holding it is dropped. Without a password to send with, due messages wait until one
+/// has been entered.
+async fn send_outbox(
+    connection: Arc<sqlx::SqlitePool>,
+    smtp_config: SmtpConfig,
+    wake: Weak<Notify>,
+    event_sender: mpsc::UnboundedSender<Event>,
+) {
+    // when the last message of a mail merge was sent, to keep to the rate limit
+    let mut last_paced = None;
+    while let Some(wake) = wake.upgrade() {
+        if !smtp_config.needs_password() {
+            send_due(&connection, &smtp_config, &mut last_paced, &event_sender).await;
+        }
+
+        let next: Result<Option<i64>, _> =
+            sqlx::query_scalar("SELECT MIN(send_at) FROM outbox WHERE error IS NULL")
+                .fetch_one(&*connection)
+                .await;
+        let wait = match next {
+            Ok(Some(send_at)) if !smtp_config.needs_password() => {
+                // a second at least, so a message that can't be claimed isn't retried in a loop
+                let seconds = (send_at - Local::now().timestamp()).max(1) as u64;
+                std::time::Duration::from_secs(seconds).min(OUTBOX_IDLE)
+            }
+            _ => OUTBOX_IDLE,
+        };
+        tokio::select! {
+            () = wake.notified() => {}
+            () = tokio::time::sleep(wait) => {}
+        }
+    }
+}
+
+/// Send the messages in the outbox that are due, one after another. Each is marked as it is
+/// taken out to be sent, so it isn't sent twice should this run again meanwhile, and stays
+/// marked if `eta` quits before it has gone. A paced message waits until the rate limit allows
+/// it after `last_paced`, when the last paced message was sent, which is then updated.
+async fn send_due(
+    connection: &sqlx::SqlitePool,
+    smtp_config: &SmtpConfig,
+    last_paced: &mut Option<std::time::Instant>,
+    event_sender: &mpsc::UnboundedSender<Event>,
+) {
+    loop {
+        let due = sqlx::query(
+            "UPDATE outbox SET error = 'Interrupted while sending'
+                WHERE id = (
+                    SELECT id FROM outbox WHERE send_at <= unixepoch() AND error IS NULL
+                        ORDER BY send_at, id LIMIT 1
+                )
+                RETURNING id, sender, recipients, raw, paced",
+        )
+        .fetch_optional(connection)
+        .await;
+        let row = match due {
+            Ok(Some(row)) => row,
+            Ok(None) => return,
+            Err(e) => {
+                let _ = event_sender.send(Event::App(error_event(e)));
+                return;
+            }
+        };
+
+        let paced: bool = row.get(4);
+        if paced {
+            if let Some(last) = *last_paced {
+                let ready = last + smtp_config.merge_interval();
+                tokio::time::sleep_until(ready.into()).await;
+            }
+            *last_paced = Some(std::time::Instant::now());
+        }
+
+        let id: i64 = row.get(0);
+        let raw: Vec<u8> = row.get(3);
+        let result = match envelope(row.get(1), row.get(2)) {
+            Ok(envelope) => {
+                let smtp_config = smtp_config.sending_as(row.get(1));
+                transmit(&smtp_config, &envelope, &raw).await
+            }
+            Err(e) => Err(e),
+        };
+        let update = match &result {
+            Ok(_) => {
+                // the envelope's recipients, separated by spaces
+                let recipients: &str = row.get(2);
+                let own = smtp_config.own_addresses();
+                let lists = recipients.split_whitespace();
+                remember_recipients(connection, &own, lists, event_sender).await;
+                sqlx::query("DELETE FROM outbox WHERE id = ?").bind(id)
+            }
+            Err(e) => sqlx::query("UPDATE outbox SET error = ? WHERE id = ?")
+                .bind(e)
+                .bind(id),
+        };
+        if let Err(e) = update.execute(connection).await {
+            let _ = event_sender.send(Event::App(error_event(e)));
+        }
+
+        let app_event = match result {
+            Ok(Some(path)) => AppEvent::MessageWritten(path),
+            result => AppEvent::MessageSent(result.err()),
+        };
+        let _ = event_sender.send(Event::App(app_event));
+        send_scheduled(connection, event_sender).await;
+    }
+}
+
+/// The envelope of a message in the outbox, from the addresses stored with it.
+fn envelope(sender: &str, recipients: &str) -> Result<Envelope, String> {
+    let sender: Address = sender.parse().map_err(|e: AddressError| e.to_string())?;
+    let recipients = recipients
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<Address>, _>>()
+        .map_err(|e| e.to_string())?;
+    Envelope::new(Some(sender), recipients).map_err(|e| e.to_string())
+}
+
+/// Send the app the messages in the outbox, soonest first.
+async fn send_scheduled(
+    connection: &sqlx::SqlitePool,
+    event_sender: &mpsc::UnboundedSender<Event>,
+) {
+    let scheduled =
+        sqlx::query("SELECT id, send_at, to_addr, subject, error FROM outbox ORDER BY send_at, id")
+            .map(|row: SqliteRow| Scheduled {
+                id: row.get::<i64, _>(0) as u64,
+                send_at: DateTime::from_timestamp(row.get(1), 0)
+                    .unwrap_or_default()
+                    .with_timezone(&Local),
+                to: row.get(2),
+                subject: row.get(3),
+                error: row.get(4),
+            })
+            .fetch_all(connection)
+            .await;
+    let app_event = match scheduled {
+        Ok(scheduled) => AppEvent::ScheduledLoaded(scheduled),
+        Err(e) => error_event(e),
+    };
+    let _ = event_sender.send(Event::App(app_event));
+}
+
+/// Message ids as a JSON array, e.g. `[3,5,8]`, so that a statement can act on all of them at
+/// once through `json_each`.
+fn id_list(ids: &[u64]) -> String {
+    let ids: Vec<String> = ids.iter().map(u64::to_string).collect();
+    format!("[{}]", ids.join(","))
+}
+
+/// The folders the messages in `ids`, an [`id_list`], are in, leaving out any that are gone.
+async fn folders_of(connection: &sqlx::SqlitePool, ids: &str) -> Vec<String> {
+    sqlx::query_scalar(
+        "SELECT DISTINCT folder FROM messages WHERE id IN (SELECT value FROM json_each(?))",
+    )
+    .bind(ids)
+    .fetch_all(connection)
+    .await
+    .unwrap_or_default()
+}
+
+/// Everyone in the address book, ordered by name.
+async fn load_address_book(connection: &sqlx::SqlitePool) -> Result<Vec<Contact>, sqlx::Error> {
+    let rows: Vec<(i64, String, String)> =
+        sqlx::query_as("SELECT id, name, email FROM contacts ORDER BY name COLLATE NOCASE, email")
+            .fetch_all(connection)
+            .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, name, email)| Contact {
+            id: Some(id as u64),
+            name,
+            email,
+        })
+        .collect())
+}
+
+/// A `LIKE` pattern matching text that contains `text`, with the `%` and `_` in it taken
+/// literally, for use with `ESCAPE '\'`.
+fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+/// Parse a comma separated list of addresses, such as a To or Cc field. Blank entries are
+/// skipped, so a blank list is empty.
+pub fn parse_addresses(list: &str) -> Result<Vec<Mailbox>, AddressError> {
+    split_addresses(list)
+        .into_iter()
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// The entries of a comma separated list of addresses that aren't valid addresses.
+pub fn invalid_addresses(list: &str) -> Vec<&str> {
+    split_addresses(list)
+        .into_iter()
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty() && entry.parse::<Mailbox>().is_err())
+        .collect()
+}
+
+/// The address in an entry of an address list, without any name and in lowercase: the part in
+/// angle brackets of an entry that doesn't parse, or else the whole entry.
+pub fn bare_address(entry: &str) -> String {
+    if let Ok(mailbox) = entry.trim().parse::<Mailbox>() {
+        return mailbox.email.to_string().to_lowercase();
+    }
+    let address = match (entry.rfind('<'), entry.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &entry[start + 1..end],
+        _ => entry,
+    };
+    address.trim().to_lowercase()
+}
+
+/// Split a list of addresses at the commas between them, leaving any whitespace around the
+/// entries. Commas in quoted names, like `"Doe, Jane" <jane@example.com>`, are kept.
+pub fn split_addresses(list: &str) -> Vec<&str> {
+    let mut entries = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in list.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                entries.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(&list[start..]);
+    entries
+}
+
+/// Send `message` as the identity its From field names, through that identity's server if it has
+/// one, or else from the account's address. In a dry run it is written to a file instead, and the
+/// file's path is returned.
+#[tracing::instrument(skip_all, fields(to = message.to()), err)]
+async fn deliver(smtp_config: &SmtpConfig, message: &Message) -> Result<Option<PathBuf>, String> {
+    let email = build_email(smtp_config, message, None).await?;
+    let smtp_config = smtp_config.sending_as(message.from());
+    transmit(&smtp_config, email.envelope(), &email.formatted()).await
+}
+
+/// Build `message` as it is sent from the identity its From field names, or the account's
+/// address, signed or encrypted if it is to be, and dated `date` if it isn't sent straight away.
+async fn build_email(
+    smtp_config: &SmtpConfig,
+    message: &Message,
+    date: Option<DateTime<Local>>,
+) -> Result<lettre::Message, String> {
+    let identity = smtp_config.identity(message.from());
+    let from = match identity {
+        Some(identity) => identity.from(),
+        None => smtp_config.username.clone(),
+    };
+    let from = from.parse::<Mailbox>().map_err(|e| e.to_string())?;
+    let reply_to = identity.and_then(|identity| identity.reply_to.as_deref());
+    let reply_to = parse_addresses(reply_to.unwrap_or_default()).map_err(|e| e.to_string())?;
+    let to = parse_addresses(message.to()).map_err(|e| e.to_string())?;
+    let cc = parse_addresses(message.cc()).map_err(|e| e.to_string())?;
+    let recipients: Vec<String> = to
+        .iter()
+        .chain(&cc)
+        .map(|mailbox| mailbox.email.to_string())
+        .collect();
+
+    let mut builder = lettre::Message::builder()
+        .from(from.clone())
+        .subject(message.subject());
+    if let Some(date) = date {
+        builder = builder.date(date.into());
+    }
+    for mailbox in to {
+        builder = builder.to(mailbox);
+    }
+    for mailbox in cc {
+        builder = builder.cc(mailbox);
+    }
+    for mailbox in reply_to {
+        builder = builder.reply_to(mailbox);
+    }
+    // keep replies in the same thread
+    if let Some(id) = message.in_reply_to() {
+        builder = builder.in_reply_to(id.to_string());
+    }
+    if !message.references().is_empty() {
+        builder = builder.references(message.references().join(" "));
+    }
+    let text = match smtp_config.body_format {
+        BodyFormat::Plain => SinglePart::plain(message.body().to_string()),
+        BodyFormat::Wrap(width) => SinglePart::plain(wrap::hard_wrap(message.body(), width)),
+        BodyFormat::Flowed => SinglePart::builder()
+            .header(
+                ContentType::parse("text/plain; charset=utf-8; format=flowed")
+                    .expect("a valid content type"),
+            )
+            .body(wrap::flowed(message.body())),
+    };
+    // inline parts, such as the calendar of a reply to an invite, are other forms of the text
+    let inline: Vec<&MessagePart> = message
+        .parts()
+        .iter()
+        .filter(|part| !part.is_attachment())
+        .collect();
+    // images shown inline need an HTML body to show them in
+    let (images, attachments): (Vec<&MessagePart>, Vec<&MessagePart>) = message
+        .attachments()
+        .partition(|part| part.content_id().is_some());
+    let body = if inline.is_empty() && images.is_empty() {
+        Content::Single(text)
+    } else {
+        let mut alternative = MultiPart::alternative().singlepart(text);
+        for part in inline {
+            let content_type =
+                ContentType::parse(part.content_type()).map_err(|e| e.to_string())?;
+            alternative = alternative.singlepart(
+                SinglePart::builder()
+                    .header(content_type)
+                    .body(part.content().to_vec()),
+            );
+        }
+        if !images.is_empty() {
+            let html = mime::text_to_html(message.body(), &images);
+            let mut related = MultiPart::related().singlepart(SinglePart::html(html));
+            for image in images {
+                let content_type =
+                    ContentType::parse(image.content_type()).map_err(|e| e.to_string())?;
+                let content_id = image.content_id().unwrap_or_default().to_string();
+                let filename = image.filename().unwrap_or_default().to_string();
+                related = related.singlepart(
+                    Attachment::new_inline_with_name(content_id, filename)
+                        .body(image.content().to_vec(), content_type),
+                );
+            }
+            alternative = alternative.multipart(related);
+        }
+        Content::Multi(alternative)
+    };
+    let content = if attachments.is_empty() {
+        body
+    } else {
+        let mut multipart = body.add_to(MultiPart::mixed());
+        for part in attachments {
+            let content_type =
+                ContentType::parse(part.content_type()).map_err(|e| e.to_string())?;
+            let filename = part.filename().unwrap_or_default().to_string();
+            multipart = multipart
+                .singlepart(Attachment::new(filename).body(part.content().to_vec(), content_type));
+        }
+        Content::Multi(multipart)
+    };
+    match (message.protection(), content) {
+        (Protection::None, Content::Single(part)) => builder.singlepart(part),
+        (Protection::None, Content::Multi(multipart)) => builder.multipart(multipart),
+        (protection, content) => {
+            let signer = from.email.to_string();
+            builder.multipart(pgp::protect(content, protection, &signer, &recipients).await?)
+        }
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Send `raw`, a whole message, to the recipients of `envelope`, trying again after temporary
+/// failures. In a dry run it is written to a file instead, and the file's path is returned.
+async fn transmit(
+    smtp_config: &SmtpConfig,
+    envelope: &Envelope,
+    raw: &[u8],
+) -> Result<Option<PathBuf>, String> {
+    if let Some(dir) = &smtp_config.dry_run_send {
+        let path = write_dry_run(dir, raw).await?;
+        tracing::info!(path = %path.display(), "written instead of sent");
+        return Ok(Some(path));
+    }
+
+    let password = match &smtp_config.password {
+        Some(password) => password.read(&smtp_config.username).await?,
+        None => return Err(String::from("No password has been entered")),
+    };
+    let creds = Credentials::new(smtp_config.username.to_owned(), password);
+
+    let mut attempts = 1;
+    loop {
+        let sent = match connect_smtp(smtp_config, SMTP_TIMEOUT).await? {
+            Ok(connection) => send_email(connection, &creds, envelope, raw).await,
+            Err(e) => Err(SendError::BeforeData(e)),
+        };
+        let failure = match sent {
+            Ok(()) => {
+                tracing::info!(attempts, "sent");
+                return Ok(None);
+            }
+            Err(SendError::BeforeData(e)) => DeliveryFailure::from_smtp_error(&e),
+            // so that a password typed in wrong is asked for again, rather than kept
+            Err(SendError::Login(e)) => {
+                if let Some(password) = &smtp_config.password {
+                    password.forget();
+                }
+                let failure = DeliveryFailure::from_smtp_error(&e);
+                return Err(format!("The server turned down the login: {failure}"));
+            }
+            // the server may have the message by now, and sending it again could deliver it twice
+            Err(SendError::AfterData(e)) => {
+                let failure = DeliveryFailure::from_smtp_error(&e);
+                return Err(if e.is_response() {
+                    format!("Not sent: {failure}")
+                } else {
+                    format!(
+                        "Failed once the message was handed over, it may have been sent: {failure}"
+                    )
+                });
+            }
+        };
+        if !failure.is_transient() {
+            return Err(format!("Permanent failure: {failure}"));
+        }
+        if attempts > smtp_config.send_retries {
+            return Err(format!(
+                "Temporary failure, gave up after {attempts} attempts: {failure}"
+            ));
+        }
+
+        let wait = RETRY_BACKOFF
+            .saturating_mul(2u32.saturating_pow(attempts - 1))
+            .min(MAX_RETRY_BACKOFF);
+        tracing::warn!(attempts, "{failure}, trying again in {}s", wait.as_secs());
+        tokio::time::sleep(wait).await;
+        attempts += 1;
+    }
+}
+
+/// Write the message with id `id` to a new file at `path`: the message as it was received or sent
+/// when it was kept, or else one made up from its headers and body.
+async fn export(connection: &sqlx::SqlitePool, id: u64, path: &Path) -> Result<(), String> {
+    let row = sqlx::query(&format!(
+        "SELECT {MESSAGE_COLUMNS}, messages.body, messages.raw FROM messages WHERE id = ?"
+    ))
+    .bind(id as i64)
+    .fetch_one(connection)
+    .await
+    .map_err(|e| e.to_string())?;
+    let raw = match row.get::<Option<Vec<u8>>, _>(16) {
+        Some(raw) => raw,
+        None => {
+            let mut message = message_from_row(&row);
+            message.set_body(row.get(15));
+            reconstruct(&message)?
+        }
+    };
+
+    // rather than overwrite whatever is there
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .await
+        .map_err(|e| e.to_string())?;
+    file.write_all(&raw).await.map_err(|e| e.to_string())
+}
+
+/// Add the message in the file at `path` to `folder`, as read, unless a message with the same
+/// `Message-ID` is already kept, remembering its addresses other than the account's own, `own`.
+/// Then apply the rules to it.
+async fn import(
+    connection: &sqlx::SqlitePool,
+    rules: &Rules,
+    own: &[String],
+    path: &Path,
+    folder: &str,
+) -> Result<(), String> {
+    let raw = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+    let headers = mime::headers(&raw).ok_or("not an email message")?;
+    if let Some(id) = &headers.message_id {
+        let kept: Option<i64> = sqlx::query_scalar("SELECT id FROM messages WHERE message_id = ?")
+            .bind(id)
+            .fetch_optional(connection)
+            .await
+            .map_err(|e| e.to_string())?;
+        if kept.is_some() {
+            return Err(String::from("already imported"));
+        }
+    }
+
+    let body = text_of(&raw);
+    let mut tx = connection.begin().await.map_err(|e| e.to_string())?;
+    let inserted = sqlx::query(
+        "INSERT INTO messages (from_addr, to_addr, cc_addr, subject, body, raw, unread, folder,
+            date, message_id, in_reply_to, refs, snippet, processed, size, has_attachment,
+            addresses_known)
+            VALUES (?, ?, ?, ?, ?, ?, 0, ?, ?, ?, ?, ?, ?, 1, ?, ?, 1)",
+    )
+    .bind(&headers.from)
+    .bind(&headers.to)
+    .bind(&headers.cc)
+    .bind(&headers.subject)
+    .bind(&body)
+    .bind(&raw)
+    .bind(folder)
+    .bind(headers.date)
+    .bind(headers.message_id)
+    .bind(headers.in_reply_to)
+    .bind(Some(headers.references.join(" ")).filter(|refs| !refs.is_empty()))
+    .bind(snippet(&body))
+    .bind(raw.len() as i64)
+    .bind(mime::has_attachment(&raw))
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    remember_addresses(
+        &mut *tx,
+        message_addresses(own, [headers.from.as_str(), &headers.to, &headers.cc]),
+        headers.date.unwrap_or_else(|| Local::now().timestamp()),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    let fields = Fields {
+        from: &headers.from,
+        to: &headers.to,
+        cc: &headers.cc,
+        subject: &headers.subject,
+        raw: Some(&raw),
+    };
+    let id = inserted.last_insert_rowid() as u64;
+    apply_rules(connection, rules, id, folder, &fields)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Apply the rules to every message that has arrived since they last ran, however it arrived:
+/// while eta was open or closed, in any folder. The size of a message added by something other
+/// than eta is noted too, along with its snippet, its addresses and whether it has attachments,
+/// leaving out the account's own addresses, `own`.
+async fn process_arrivals(
+    connection: &sqlx::SqlitePool,
+    rules: &Rules,
+    own: &[String],
+) -> Result<(), sqlx::Error> {
+    loop {
+        // a batch at a time, as each comes with the whole message
+        let arrivals = sqlx::query(
+            "SELECT id, folder, from_addr, to_addr, cc_addr, subject, raw, body, snippet IS NULL,
+                date, addresses_known
+            FROM messages WHERE processed = 0 ORDER BY id LIMIT 100",
+        )
+        .fetch_all(connection)
+        .await?;
+        if arrivals.is_empty() {
+            return Ok(());
+        }
+        for row in arrivals {
+            let id: i64 = row.get(0);
+            let folder: String = row.get(1);
+            let raw: Option<Vec<u8>> = row.get(6);
+            let fields = Fields {
+                from: row.get(2),
+                to: row.get(3),
+                cc: row.get(4),
+                subject: row.get(5),
+                raw: raw.as_deref(),
+            };
+            apply_rules(connection, rules, id as u64, &folder, &fields).await?;
+            let snippet = row
+                .get::<bool, _>(8)
+                .then(|| snippet(row.get::<&str, _>(7)));
+            let mut tx = connection.begin().await?;
+            if !row.get::<bool, _>(10) {
+                let date: Option<i64> = row.get(9);
+                remember_addresses(
+                    &mut *tx,
+                    message_addresses(own, [fields.from, fields.to, fields.cc]),
+                    date.unwrap_or_else(|| Local::now().timestamp()),
+                )
+                .await?;
+            }
+            sqlx::query(
+                "UPDATE messages
+                SET processed = 1, size = COALESCE(size, length(COALESCE(raw, CAST(body AS BLOB)))),
+                    has_attachment = ?, snippet = COALESCE(snippet, ?), addresses_known = 1
+                WHERE id = ?",
+            )
+            .bind(raw.as_deref().is_some_and(mime::has_attachment))
+            .bind(snippet)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+        }
+    }
+}
+
+/// Apply the rules matching `fields` to the message with id `id` in `folder`, and log what they
+/// did. Returns what was done, which is nothing when the rules are only being tried out.
+async fn apply_rules(
+    connection: &sqlx::SqlitePool,
+    rules: &Rules,
+    id: u64,
+    folder: &str,
+    fields: &Fields<'_>,
+) -> Result<Outcome, sqlx::Error> {
+    let mut outcome = rules::apply(&rules.rules, fields);
+    if outcome.applied.is_empty() {
+        return Ok(outcome);
+    }
+    log_rules(rules, fields, &outcome).await;
+    if rules.dry_run {
+        return Ok(Outcome::default());
+    }
+
+    if outcome.mark_read {
+        sqlx::query("UPDATE messages SET unread = 0 WHERE id = ?")
+            .bind(id as i64)
+            .execute(connection)
+            .await?;
+    }
+    for tag in &outcome.tags {
+        set_tagged(connection, id, tag, true).await?;
+    }
+    // moving a message to the folder it is in leaves it where it is
+    outcome.folder = outcome.folder.filter(|to| to != folder);
+    if let Some(to) = &outcome.folder {
+        set_folder(connection, &id_list(&[id]), to).await?;
+    }
+    Ok(outcome)
+}
+
+/// Add a line to the rules log for each rule applied to a message, e.g.
+/// `2026-01-15 09:30:00 Invoices: tagged finance ("March invoice" from billing@example.com)`.
+async fn log_rules(rules: &Rules, fields: &Fields<'_>, outcome: &Outcome) {
+    let now = Local::now().format("%Y-%m-%d %H:%M:%S");
+    let dry_run = if rules.dry_run { " (dry run)" } else { "" };
+    let lines: String = outcome
+        .applied
+        .iter()
+        .map(|applied| {
+            format!(
+                "{now}{dry_run} {applied} (\"{}\" from {})\n",
+                fields.subject, fields.from
+            )
+        })
+        .collect();
+    let written = async {
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&rules.log)
+            .await?
+            .write_all(lines.as_bytes())
+            .await
+    };
+    if let Err(e) = written.await {
+        tracing::warn!("couldn't write to {}: {e}", rules.log.display());
+    }
+}
+
+/// Give the message with id `id` `tag`, or take it away.
+async fn set_tagged(
+    connection: &sqlx::SqlitePool,
+    id: u64,
+    tag: &str,
+    tagged: bool,
+) -> Result<(), sqlx::Error> {
+    if !tagged {
+        sqlx::query(
+            "DELETE FROM message_tags
+                WHERE message_id = ? AND tag_id = (SELECT id FROM tags WHERE name = ?)",
+        )
+        .bind(id as i64)
+        .bind(tag)
+        .execute(connection)
+        .await?;
+        return Ok(());
+    }
+
+    sqlx::query("INSERT OR IGNORE INTO tags (name) VALUES (?)")
+        .bind(tag)
+        .execute(connection)
+        .await?;
+    sqlx::query(
+        "INSERT OR IGNORE INTO message_tags (message_id, tag_id)
+            SELECT ?, id FROM tags WHERE name = ?",
+    )
+    .bind(id as i64)
+    .bind(tag)
+    .execute(connection)
+    .await?;
+    Ok(())
+}
+
+/// Every tag some message has, in alphabetical order.
+async fn load_tags(connection: &sqlx::SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT name FROM tags WHERE id IN (SELECT tag_id FROM message_tags) ORDER BY name",
+    )
+    .fetch_all(connection)
+    .await
+}
+
+/// Move the messages in `ids`, an [`id_list`], to `folder`, noting when they were put in the
+/// trash.
+async fn set_folder(
+    connection: &sqlx::SqlitePool,
+    ids: &str,
+    folder: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE messages SET folder = ?1,
+            trashed_at = CASE WHEN ?1 = ?2 THEN unixepoch() END
+            WHERE id IN (SELECT value FROM json_each(?3))",
+    )
+    .bind(folder)
+    .bind(TRASH)
+    .bind(ids)
+    .execute(connection)
+    .await?;
+    Ok(())
+}
+
+/// A message as it might have been sent, for those kept without their original, such as the
+/// sample messages.
+fn reconstruct(message: &Message) -> Result<Vec<u8>, String> {
+    let from = message
+        .from()
+        .parse::<Mailbox>()
+        .map_err(|e| e.to_string())?;
+    let mut builder = lettre::Message::builder()
+        .from(from)
+        .subject(message.subject())
+        .message_id(message.message_id().map(String::from));
+    if let Some(date) = message.date() {
+        builder = builder.date(date.into());
+    }
+    for mailbox in parse_addresses(message.to()).map_err(|e| e.to_string())? {
+        builder = builder.to(mailbox);
+    }
+    for mailbox in parse_addresses(message.cc()).map_err(|e| e.to_string())? {
+        builder = builder.cc(mailbox);
+    }
+    if let Some(id) = message.in_reply_to() {
+        builder = builder.in_reply_to(id.to_string());
+    }
+    if !message.references().is_empty() {
+        builder = builder.references(message.references().join(" "));
+    }
+    builder
+        .singlepart(SinglePart::plain(message.body().to_string()))
+        .map(|email| email.formatted())
+        .map_err(|e| e.to_string())
+}
+
+/// Write `raw`, a message exactly as it would be sent, to a new file in `dir` named after the
+/// time.
+async fn write_dry_run(dir: &Path, raw: &[u8]) -> Result<PathBuf, String> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let stamp = Local::now().format("%Y%m%d-%H%M%S");
+    let mut path = dir.join(format!("{stamp}.eml"));
+    // several messages of a mail merge may be written in the same second
+    let mut n = 1;
+    while tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        n += 1;
+        path = dir.join(format!("{stamp}-{n}.eml"));
+    }
+
+    tokio::fs::write(&path, raw)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// A sample multipart message, with quoted-printable and base64 encoded parts and an attachment.
+const DIGEST_MESSAGE: &str = r#"From: The Digest Team <digest@example.com>
+To: bob@example.com
+Subject: Your weekly digest
+MIME-Version: 1.0
+Content-Type: multipart/mixed; boundary="mixed"
+
+--mixed
+Content-Type: multipart/alternative; boundary="alternative"
+
+--alternative
+Content-Type: text/plain; charset=utf-8
+Content-Transfer-Encoding: quoted-printable
+
+Bob,
+
+Here is this week's digest =E2=80=94 three new articles on terminal user in=
+terfaces, plus the attendance numbers from last week's event, attached as a=
+ spreadsheet.
+
+Cheers,
+The Digest Team
+
+--alternative
+Content-Type: text/html; charset=utf-8
+Content-Transfer-Encoding: base64
+
+PGh0bWw+PGJvZHk+PHA+Qm9iLDwvcD48cD5IZXJlIGlzIHRoaXMgd2VlaydzIGRpZ2VzdCAmbWRh
+c2g7IHRocmVlIG5ldyBhcnRpY2xlcyBvbiA8Yj50ZXJtaW5hbCB1c2VyIGludGVyZmFjZXM8L2I+
+LCBwbHVzIHRoZSBhdHRlbmRhbmNlIG51bWJlcnMgZnJvbSBsYXN0IHdlZWsncyBldmVudCwgYXR0
+YWNoZWQgYXMgYSBzcHJlYWRzaGVldC48L3A+PHA+Q2hlZXJzLDxicj5UaGUgRGlnZXN0IFRlYW08
+L3A+PC9ib2R5PjwvaHRtbD4K
+--alternative--
+
+--mixed
+Content-Type: text/csv; name="attendance.csv"
+Content-Disposition: attachment; filename="attendance.csv"
+Content-Transfer-Encoding: base64
+
+ZGF0ZSxhdHRlbmRlZXMKMjAyNS0wNS0wMSw0MgoyMDI1LTA1LTA4LDU3Cg==
+--mixed--
+"#;
+
+/// A sample HTML-only message, as many senders of receipts and newsletters produce.
+const RECEIPT_MESSAGE: &str = r#"From: Example Shop <orders@example.com>
+To: bob@example.com
+Subject: Your order receipt
+MIME-Version: 1.0
+Content-Type: text/html; charset=utf-8
+
+<html><body><img src="https://example.com/logo.png" alt="Example Shop"><h1>Thanks for your order!</h1><p>Hi Bob, your order <b>#1042</b> has shipped and should arrive within 3&ndash;5 days.</p><table><tr><th>Item</th><th>Qty</th><th>Price</th></tr><tr><td>Mechanical keyboard</td><td>1</td><td>$89.00</td></tr><tr><td>USB-C cable</td><td>2</td><td>$12.00</td></tr></table><p>Track your package at <a href="https://example.com/track/1042">example.com/track</a>.</p></body></html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_words_are_quoted_prefixes() {
+        assert_eq!(fts_query("tps report"), "\"tps\"* \"report\"*");
+        assert_eq!(fts_query("  say \"hi\" "), "\"say\"* \"\"\"hi\"\"\"*");
+        assert_eq!(fts_query("a OR b*"), "\"a\"* \"OR\"* \"b*\"*");
+        assert_eq!(fts_query(""), "");
+    }
+
+    #[test]
+    fn in_memory_search_sends_the_matching_messages() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let provider = DefaultMessageProvider::new(sender);
+
+        provider.search_messages("tps from:alice");
+
+        let Ok(Event::App(AppEvent::SearchResultsLoaded(query, messages))) = receiver.try_recv()
+        else {
+            panic!("expected search results");
+        };
+        assert_eq!(query, "tps from:alice");
+        assert!(!messages.is_empty());
+        assert!(
+            messages
+                .iter()
+                .all(|m| m.subject() == "TPS Reports" && m.from() == "alice@alice.me")
+        );
+    }
+
+    #[tokio::test]
+    async fn drafts_are_kept_apart_with_their_attachments() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("eta.db");
+        // a database from when there was only ever one draft
+        let old = sqlx::SqlitePool::connect_with(
+            sqlx::sqlite::SqliteConnectOptions::new()
+                .filename(&database)
+                .create_if_missing(true),
+        )
+        .await
+        .unwrap();
+        sqlx::query(
+            "CREATE TABLE drafts (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                to_addr TEXT NOT NULL,
+                cc_addr TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                body TEXT NOT NULL,
+                reply_to INTEGER
+            )",
+        )
+        .execute(&old)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO drafts VALUES (1, 'jane@example.com', '', 'Old', 'Hi', NULL)")
+            .execute(&old)
+            .await
+            .unwrap();
+        old.close().await;
+
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        let provider = SqliteMessageProvider::new(
+            sender,
+            SmtpConfig::default(),
+            Rules::default(),
+            &database,
+            None,
+        )
+        .unwrap();
+        provider.init().await.unwrap();
+        let connection = &provider.connection;
+
+        let new = Draft {
+            subject: String::from("New"),
+            attachments: vec![MessagePart::new(
+                String::from("text/plain"),
+                Some(String::from("notes.txt")),
+                b"notes".to_vec(),
+            )],
+            ..Draft::default()
+        };
+        store_draft(connection, 42, &new).await.unwrap();
+        let drafts = load_drafts(connection).await.unwrap();
+        assert_eq!(drafts.len(), 2);
+        assert_eq!((drafts[0].0, drafts[0].1.subject.as_str()), (1, "Old"));
+        assert_eq!(drafts[1], (42, new));
+
+        discard_draft(connection, 42).await.unwrap();
+        let drafts = load_drafts(connection).await.unwrap();
+        assert_eq!(drafts.len(), 1);
+        let left: (u64,) = sqlx::query_as("SELECT COUNT(*) FROM draft_attachments")
+            .fetch_one(&**connection)
+            .await
+            .unwrap();
+        assert_eq!(left.0, 0);
+    }
+
+    #[tokio::test]
+    async fn rules_run_once_on_mail_that_arrived_while_closed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rule: rules::Rule =
+            toml::from_str("name = \"Lists\"\nsubject = \"digest\"\nmove = \"Lists\"").unwrap();
+        rule.check().unwrap();
+        let rules = Rules {
+            rules: vec![rule],
+            dry_run: false,
+            log: dir.path().join("rules.log"),
+        };
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        let provider = SqliteMessageProvider::new(
+            sender,
+            SmtpConfig::default(),
+            rules,
+            &dir.path().join("eta.db"),
+            None,
+        )
+        .unwrap();
+        provider.init().await.unwrap();
+        let connection = &*provider.connection;
+
+        // added by something other than eta
+        sqlx::query(
+            "INSERT INTO messages (id, from_addr, to_addr, subject, body)
+            VALUES (100, 'list@example.com', 'me@example.com', 'Weekly digest', 'Hi  all')",
+        )
+        .execute(connection)
+        .await
+        .unwrap();
+        process_arrivals(connection, &provider.rules, &[])
+            .await
+            .unwrap();
+        // moved back by hand, where it stays
+        sqlx::query("UPDATE messages SET folder = 'Inbox' WHERE id = 100")
+            .execute(connection)
+            .await
+            .unwrap();
+        process_arrivals(connection, &provider.rules, &[])
+            .await
+            .unwrap();
+
+        let log = std::fs::read_to_string(dir.path().join("rules.log")).unwrap();
+        assert_eq!(log.matches("(\"Weekly digest\"").count(), 1);
+        let kept: (String, Option<i64>, Option<String>) =
+            sqlx::query_as("SELECT folder, size, snippet FROM messages WHERE id = 100")
+                .fetch_one(connection)
+                .await
+                .unwrap();
+        assert_eq!(
+            kept,
+            (String::from("Inbox"), Some(7), Some(String::from("Hi all")))
+        );
+    }
+
+    #[tokio::test]
+    async fn tags_kept_in_the_old_column_are_moved() {
+        let dir = tempfile::tempdir().unwrap();
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        let provider = SqliteMessageProvider::new(
+            sender,
+            SmtpConfig::default(),
+            Rules::default(),
+            &dir.path().join("eta.db"),
+            None,
+        )
+        .unwrap();
+        provider.init().await.unwrap();
+        let connection = &*provider.connection;
+        // as rules used to tag messages
+        sqlx::query("ALTER TABLE messages ADD COLUMN tags TEXT NOT NULL DEFAULT ''")
+            .execute(connection)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE messages SET tags = 'finance  work' WHERE id = 1")
+            .execute(connection)
+            .await
+            .unwrap();
+
+        provider.init().await.unwrap();
+
+        let tags: Vec<(String,)> = sqlx::query_as(
+            "SELECT tags.name FROM message_tags JOIN tags ON tags.id = message_tags.tag_id
+            WHERE message_tags.message_id = 1 ORDER BY tags.name",
+        )
+        .fetch_all(connection)
+        .await
+        .unwrap();
+        assert_eq!(tags, [(String::from("finance"),), (String::from("work"),)]);
+        let (kept,): (bool,) = sqlx::query_as(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('messages') WHERE name = 'tags'",
+        )
+        .fetch_one(connection)
+        .await
+        .unwrap();
+        assert!(!kept);
+    }
+
+    #[test]
+    fn every_own_address_is_left_out_of_known_addresses() {
+        let smtp: SmtpConfig = toml::from_str(
+            r#"
+            ip = "smtp.example.com"
+            username = "Me <ME@example.com>"
+            [[identities]]
+            address = "Me <me+lists@example.com>"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            message_addresses(
+                &smtp.own_addresses(),
+                [
+                    "Jane <jane@example.com>",
+                    "me@example.com, Me <Me+Lists@example.com>",
+                    "bob@example.com, JANE@example.com",
+                ],
+            ),
+            ["bob@example.com", "jane@example.com"]
+        );
+    }
+
+    #[tokio::test]
+    async fn own_addresses_are_not_remembered_as_recipients() {
+        let dir = tempfile::tempdir().unwrap();
+        let smtp: SmtpConfig = toml::from_str(&format!(
+            r#"
+            ip = "smtp.example.com"
+            username = "Me <me@example.com>"
+            dry_run_send = {:?}
+            [[identities]]
+            address = "me+lists@example.com"
+            "#,
+            dir.path()
+        ))
+        .unwrap();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let provider = SqliteMessageProvider::new(
+            sender,
+            smtp,
+            Rules::default(),
+            &dir.path().join("eta.db"),
+            None,
+        )
+        .unwrap();
+        provider.init().await.unwrap();
+        let mut message = Message::new(
+            0,
+            String::from("me+lists@example.com"),
+            String::from("friend@example.com"),
+            String::from("Hello"),
+            String::from("Hi"),
+        );
+        message.set_cc(String::from("Me <ME@example.com>, me+lists@example.com"));
+
+        provider.send_message(&message);
+        while let Some(event) = receiver.recv().await {
+            if let Event::App(AppEvent::MessageWritten(_)) = event {
+                break;
+            }
+        }
+
+        let known: Vec<(String,)> = sqlx::query_as(
+            "SELECT address FROM known_addresses
+            WHERE address IN ('friend@example.com', 'me@example.com', 'me+lists@example.com')",
+        )
+        .fetch_all(&*provider.connection)
+        .await
+        .unwrap();
+        assert_eq!(known, [(String::from("friend@example.com"),)]);
+    }
+
+    #[test]
+    fn addresses_are_split_outside_quotes() {
+        assert_eq!(
+            split_addresses(r#"jane@example.com, "Doe, John" <john@example.com>,"#),
+            ["jane@example.com", r#" "Doe, John" <john@example.com>"#, ""]
+        );
+        assert_eq!(
+            split_addresses(r#""A \"quoted, name\"" <a@example.com>, b@example.com"#),
+            [r#""A \"quoted, name\"" <a@example.com>"#, " b@example.com"]
+        );
+        assert_eq!(split_addresses(""), [""]);
+    }
+
+    #[test]
+    fn invalid_entries_are_found() {
+        let list = "jane@example.com, not an address, , Bob <bob@example.com>, bob@";
+
+        assert_eq!(invalid_addresses(list), ["not an address", "bob@"]);
+        assert!(parse_addresses(list).is_err());
+        assert_eq!(parse_addresses(" , jane@example.com ,").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn bare_address_drops_the_name() {
+        assert_eq!(bare_address("Jane <Jane@Example.com>"), "jane@example.com");
+        assert_eq!(bare_address(" JOHN@example.com "), "john@example.com");
+        assert_eq!(bare_address("Broken <john@>"), "john@");
+    }
+
+    #[tokio::test]
+    async fn like_pattern_matches_wildcards_literally() {
+        assert_eq!(like_pattern("a_b%c\\"), "%a\\_b\\%c\\\\%");
+
+        let connection = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let mut matches = vec![];
+        for (text, query) in [("jane_doe", "e_d"), ("janeXdoe", "e_d"), ("100%", "0%")] {
+            let found: bool = sqlx::query_scalar("SELECT ?1 LIKE ?2 ESCAPE '\\'")
+                .bind(text)
+                .bind(like_pattern(query))
+                .fetch_one(&connection)
+                .await
+                .unwrap();
+            matches.push(found);
+        }
+
+        assert_eq!(matches, [true, false, true]);
+    }
 }
@@ -0,0 +1,198 @@
+use std::sync::LazyLock;
+
+use mail_parser::{Address, MessageParser, MimeHeaders};
+use regex::Regex;
+
+use crate::message::MessagePart;
+
+/// Width that HTML bodies are laid out to when converted to text.
+const HTML_TEXT_WIDTH: usize = 80;
+
+/// Attributes and CSS `url()`s that load something from another server, e.g. tracking pixels.
+static REMOTE_CONTENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)(?P<attr>\s(?:src|srcset|background|poster)\s*=\s*["']?)(?:https?:)?//[^"'\s>]*|url\(\s*["']?(?:https?:)?//[^)]*\)"#,
+    )
+    .unwrap()
+});
+
+/// Parse a raw RFC 5322 message into its decoded leaf parts.
+///
+/// Multipart containers are flattened away; the leaves keep the order in which they appear in the
+/// message. Returns `None` if `raw` cannot be parsed as a message at all.
+pub fn parse(raw: &[u8]) -> Option<Vec<MessagePart>> {
+    let message = MessageParser::default().parse(raw)?;
+
+    let parts = message
+        .parts
+        .iter()
+        .filter(|part| !part.is_multipart())
+        .map(|part| {
+            let content_type = match part.content_type() {
+                Some(content_type) => match content_type.subtype() {
+                    Some(subtype) => format!("{}/{}", content_type.ctype(), subtype),
+                    None => content_type.ctype().to_string(),
+                },
+                None if part.is_message() => String::from("message/rfc822"),
+                None => String::from("text/plain"),
+            };
+
+            MessagePart::new(
+                content_type.to_lowercase(),
+                part.attachment_name().map(String::from),
+                part.contents().to_vec(),
+            )
+        })
+        .collect();
+
+    Some(parts)
+}
+
+/// Whether the raw message has a part with a file name, as `Message::attachments` lists.
+pub fn has_attachment(raw: &[u8]) -> bool {
+    parse(raw).is_some_and(|parts| parts.iter().any(MessagePart::is_attachment))
+}
+
+/// The headers of a raw message that are kept alongside it, with addresses listed as the
+/// composer lists them and message ids in angle brackets.
+#[derive(Debug, Default)]
+pub struct Headers {
+    pub from: String,
+    pub to: String,
+    pub cc: String,
+    pub subject: String,
+    /// Seconds since the Unix epoch.
+    pub date: Option<i64>,
+    pub message_id: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+}
+
+/// Read the headers of a raw RFC 5322 message. Returns `None` if it has neither a sender nor a
+/// subject, as anything that isn't a message still parses, to no headers.
+pub fn headers(raw: &[u8]) -> Option<Headers> {
+    let message = MessageParser::default().parse_headers(raw)?;
+    let headers = Headers {
+        from: addresses(message.from()),
+        to: addresses(message.to()),
+        cc: addresses(message.cc()),
+        subject: message.subject().unwrap_or_default().to_string(),
+        date: message.date().map(|date| date.to_timestamp()),
+        message_id: message.message_id().map(|id| format!("<{id}>")),
+        in_reply_to: message.in_reply_to().as_text().map(|id| format!("<{id}>")),
+        references: message
+            .references()
+            .as_text_list()
+            .unwrap_or_default()
+            .iter()
+            .map(|id| format!("<{id}>"))
+            .collect(),
+    };
+    (!headers.from.is_empty() || !headers.subject.is_empty()).then_some(headers)
+}
+
+/// The value of the header `name` in a raw RFC 5322 message, unfolded onto one line, e.g. the
+/// `List-Id` of a message from a mailing list.
+pub fn header(raw: &[u8], name: &str) -> Option<String> {
+    let message = MessageParser::default().parse_headers(raw)?;
+    let value = message.header_raw(name)?;
+    Some(value.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Addresses as the composer lists them, e.g. `"Doe, Jane" <jane@example.com>, joe@example.com`.
+fn addresses(address: Option<&Address>) -> String {
+    let Some(address) = address else {
+        return String::new();
+    };
+    address
+        .iter()
+        .filter_map(|addr| {
+            let email = addr.address()?;
+            Some(match addr.name() {
+                Some(name) if name.contains(|c| "()<>[]:;@\\,.\"".contains(c)) => {
+                    format!(
+                        "\"{}\" <{email}>",
+                        name.replace('\\', "\\\\").replace('"', "\\\"")
+                    )
+                }
+                Some(name) if !name.is_empty() => format!("{name} <{email}>"),
+                _ => email.to_string(),
+            })
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The text standing in for an image shown inline, marking where it goes in the body.
+pub fn image_marker(filename: &str) -> String {
+    format!("[image: {filename}]")
+}
+
+/// An HTML version of a plain text body, showing each of `images` in place of its marker, or
+/// after the text if the marker was taken out.
+pub fn text_to_html(text: &str, images: &[&MessagePart]) -> String {
+    let mut html = escape_html(text);
+    for image in images {
+        let (Some(filename), Some(content_id)) = (image.filename(), image.content_id()) else {
+            continue;
+        };
+        let img = format!(
+            "<img src=\"cid:{}\" alt=\"{}\">",
+            escape_html(content_id),
+            escape_html(filename)
+        );
+        let marker = escape_html(&image_marker(filename));
+        if html.contains(&marker) {
+            html = html.replacen(&marker, &img, 1);
+        } else {
+            html.push('\n');
+            html.push_str(&img);
+        }
+    }
+    format!("<html><body><div style=\"white-space: pre-wrap\">{html}</div></body></html>")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Convert an HTML body to readable text, so it isn't shown as raw markup.
+pub fn html_to_text(html: &str) -> String {
+    html2text::from_read(html.as_bytes(), HTML_TEXT_WIDTH).unwrap_or_else(|_| html.to_string())
+}
+
+/// Whether an HTML body loads anything from another server when it is displayed.
+pub fn has_remote_content(html: &str) -> bool {
+    REMOTE_CONTENT.is_match(html)
+}
+
+/// An HTML body with everything it would load from other servers removed.
+pub fn block_remote_content(html: &str) -> String {
+    REMOTE_CONTENT
+        .replace_all(html, |captures: &regex::Captures| {
+            match captures.name("attr") {
+                Some(attr) => attr.as_str().to_string(),
+                None => String::from("url()"),
+            }
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attachments_are_parts_with_a_file_name() {
+        let attached = b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+            --b\r\nContent-Type: text/plain\r\n\r\nSee attached.\r\n\
+            --b\r\nContent-Type: application/pdf; name=\"report.pdf\"\r\n\r\nPDF\r\n--b--\r\n";
+        let plain = b"Content-Type: text/plain\r\n\r\ncontent-disposition: attachment\r\n";
+
+        assert!(has_attachment(attached));
+        assert!(!has_attachment(plain));
+    }
+}
@@ -0,0 +1,117 @@
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeDelta, TimeZone};
+
+/// How times are shown in the list of scheduled messages, e.g. "Thu 15 Jan 17:30".
+pub const SEND_AT_FORMAT: &str = "%a %e %b %H:%M";
+
+/// A message waiting in the outbox to be sent later.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scheduled {
+    pub id: u64,
+    /// When the message is due to be sent.
+    pub send_at: DateTime<Local>,
+    pub to: String,
+    pub subject: String,
+    /// Why sending it failed, in which case it waits to be sent again by hand.
+    pub error: Option<String>,
+}
+
+/// When to send a message, read from text such as `17:30`, `tomorrow 9:00`, `2026-03-01 08:00`
+/// or `+2h`, which is two hours from `now`. A time of day that has passed today means tomorrow.
+pub fn parse_send_at(text: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let text = text.trim().to_lowercase();
+    if let Some(delay) = text.strip_prefix('+') {
+        return now.checked_add_signed(delay_of(delay)?);
+    }
+    if let Some(time) = text.strip_prefix("tomorrow") {
+        let tomorrow = now.date_naive().succ_opt()?;
+        return local(tomorrow.and_time(time_of_day(time)?));
+    }
+    if let Ok(at) = NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M") {
+        return local(at);
+    }
+
+    let time = time_of_day(&text)?;
+    match local(now.date_naive().and_time(time)) {
+        Some(today) if today > now => Some(today),
+        _ => local(now.date_naive().succ_opt()?.and_time(time)),
+    }
+}
+
+/// A delay such as `30m`, `2h` or `1d`.
+fn delay_of(text: &str) -> Option<TimeDelta> {
+    let (i, unit) = text.trim().char_indices().last()?;
+    let count: i64 = text.trim()[..i].trim().parse().ok()?;
+    match unit {
+        'm' => TimeDelta::try_minutes(count),
+        'h' => TimeDelta::try_hours(count),
+        'd' => TimeDelta::try_days(count),
+        _ => None,
+    }
+}
+
+fn time_of_day(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M").ok()
+}
+
+/// `at` in local time, taking the earlier of the two when clocks go back.
+fn local(at: NaiveDateTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&at).earliest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 3, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn times_of_day_are_the_next_to_come() {
+        let now = at(1, 10, 0);
+
+        assert_eq!(parse_send_at("17:30", now), Some(at(1, 17, 30)));
+        assert_eq!(parse_send_at("09:15", now), Some(at(2, 9, 15)));
+        assert_eq!(parse_send_at("10:00", now), Some(at(2, 10, 0)));
+        assert_eq!(parse_send_at("Tomorrow 9:00", now), Some(at(2, 9, 0)));
+        assert_eq!(parse_send_at("2026-03-05 08:00", now), Some(at(5, 8, 0)));
+    }
+
+    #[test]
+    fn delays_count_from_now() {
+        let now = at(1, 10, 0);
+
+        assert_eq!(parse_send_at("+30m", now), Some(at(1, 10, 30)));
+        assert_eq!(parse_send_at(" +2h ", now), Some(at(1, 12, 0)));
+        assert_eq!(parse_send_at("+1d", now), Some(at(2, 10, 0)));
+    }
+
+    #[test]
+    fn nonsense_is_refused() {
+        let now = at(1, 10, 0);
+
+        for text in [
+            "",
+            "soon",
+            "25:00",
+            "+2w",
+            "+h",
+            "tomorrow",
+            "2026-02-30 08:00",
+        ] {
+            assert_eq!(parse_send_at(text, now), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn delays_have_a_count_and_unit() {
+        assert_eq!(delay_of("45m"), TimeDelta::try_minutes(45));
+        assert_eq!(delay_of(" 3 h"), TimeDelta::try_hours(3));
+        assert_eq!(delay_of("2d"), TimeDelta::try_days(2));
+        assert_eq!(delay_of("d"), None);
+        assert_eq!(delay_of(""), None);
+        assert_eq!(delay_of("1.5h"), None);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{config::Config, profile::Profile};
+
+/// How many times a new passphrase may be typed differently the second time before giving up.
+const ATTEMPTS: usize = 3;
+
+/// Ask for the passphrase `profile`'s message database is encrypted with, if `config` says it
+/// is, on the terminal and without echoing it. A database that doesn't exist yet will be created
+/// with the passphrase, so it is asked for twice to make sure it was typed as meant.
+pub fn prompt(profile: &Profile, config: &Config) -> color_eyre::Result<Option<String>> {
+    if !config.encrypt_database {
+        return Ok(None);
+    }
+    if !cfg!(feature = "encryption") {
+        return Err(color_eyre::eyre::eyre!(
+            "{}: encrypt_database needs eta to be built with the encryption feature",
+            profile.config_path().display()
+        ));
+    }
+
+    let database = profile.database_path();
+    if database.exists() {
+        return ask(&format!("Passphrase for {}: ", database.display())).map(Some);
+    }
+    for _ in 0..ATTEMPTS {
+        let passphrase = ask(&format!("New passphrase for {}: ", database.display()))?;
+        if rpassword::prompt_password("Type it again: ")? == passphrase {
+            return Ok(Some(passphrase));
+        }
+        eprintln!("The passphrases don't match.");
+    }
+    Err(color_eyre::eyre::eyre!(
+        "{}: the passphrases didn't match",
+        database.display()
+    ))
+}
+
+/// Ask for a passphrase with `prompt` until one that isn't empty is typed, since SQLCipher takes
+/// an empty one to mean the database isn't encrypted.
+fn ask(prompt: &str) -> color_eyre::Result<String> {
+    loop {
+        let passphrase = rpassword::prompt_password(prompt)?;
+        if !passphrase.is_empty() {
+            return Ok(passphrase);
+        }
+        eprintln!("The passphrase can't be empty.");
+    }
+}
@@ -0,0 +1,555 @@
+use std::{io::Write, process::Output, process::Stdio};
+
+use lettre::message::{MultiPart, MultiPartBuilder, SinglePart, header::ContentType};
+use mail_parser::{MessageParser, MimeHeaders};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+/// How an outgoing message is protected with OpenPGP, toggled in the composer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Protection {
+    #[default]
+    None,
+    Sign,
+    Encrypt,
+    SignAndEncrypt,
+}
+
+impl Protection {
+    /// The protection after this one, going round them all.
+    pub fn next(self) -> Self {
+        match self {
+            Protection::None => Protection::Sign,
+            Protection::Sign => Protection::Encrypt,
+            Protection::Encrypt => Protection::SignAndEncrypt,
+            Protection::SignAndEncrypt => Protection::None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Protection::None => "Off",
+            Protection::Sign => "Sign",
+            Protection::Encrypt => "Encrypt",
+            Protection::SignAndEncrypt => "Sign and encrypt",
+        }
+    }
+
+    fn signs(self) -> bool {
+        matches!(self, Protection::Sign | Protection::SignAndEncrypt)
+    }
+}
+
+/// What opening a PGP/MIME message found, shown above its body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verification {
+    /// Whether the message was encrypted, even if it couldn't be decrypted.
+    pub encrypted: bool,
+    pub outcome: Outcome,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Nothing was signed.
+    Unsigned,
+    /// A good signature by a fully trusted key with this user ID, which is the sender's.
+    Good(String),
+    /// A good signature by the key with this user ID, which isn't fully trusted.
+    Unverified(String),
+    /// A good signature by a trusted key with this user ID, none of whose user IDs is the
+    /// sender's address.
+    Mismatch(String),
+    /// A signature by the key with this ID, which isn't in the keyring.
+    UnknownKey(String),
+    /// A bad signature, or a message that couldn't be decrypted, and why.
+    Failed(String),
+}
+
+impl Verification {
+    pub fn text(&self) -> String {
+        let signature = match &self.outcome {
+            Outcome::Unsigned => String::from("Not signed"),
+            Outcome::Good(user) => format!("Good signature from {user}"),
+            Outcome::Unverified(user) => {
+                format!("Signed by {user}, but the key isn't trusted")
+            }
+            Outcome::Mismatch(user) => format!("Signed by {user}, who isn't the sender"),
+            Outcome::UnknownKey(key) => format!("Signed by unknown key {key}"),
+            Outcome::Failed(reason) => reason.clone(),
+        };
+        match (self.encrypted, &self.outcome) {
+            (true, Outcome::Failed(_)) => signature,
+            (true, _) => format!("Decrypted. {signature}"),
+            (false, _) => signature,
+        }
+    }
+}
+
+/// The body of an outgoing message, as lettre builds it.
+pub enum Content {
+    Single(SinglePart),
+    Multi(MultiPart),
+}
+
+impl Content {
+    /// The part as it will appear in the message, headers and all. The line break at the end is
+    /// left off, as it belongs to the boundary that follows.
+    fn formatted(&self) -> Vec<u8> {
+        let mut formatted = match self {
+            Content::Single(part) => part.formatted(),
+            Content::Multi(part) => part.formatted(),
+        };
+        if formatted.ends_with(b"\r\n") {
+            formatted.truncate(formatted.len() - 2);
+        }
+        formatted
+    }
+
+    pub fn add_to(self, multipart: MultiPartBuilder) -> MultiPart {
+        match self {
+            Content::Single(part) => multipart.singlepart(part),
+            Content::Multi(part) => multipart.multipart(part),
+        }
+    }
+}
+
+/// Protect `content` as PGP/MIME (RFC 3156), signed by `signer`'s key and encrypted to every
+/// address in `recipients`, as `protection` asks. An encrypted message is also encrypted to the
+/// `signer`, so that the sent copy can still be read.
+pub async fn protect(
+    content: Content,
+    protection: Protection,
+    signer: &str,
+    recipients: &[String],
+) -> Result<MultiPart, String> {
+    let key = format!("<{signer}>");
+    if protection == Protection::Sign {
+        let signature = gpg(
+            &["--detach-sign", "--local-user", &key],
+            &content.formatted(),
+        )
+        .await?;
+        let signed = MultiPart::signed(
+            String::from("application/pgp-signature"),
+            String::from("pgp-sha256"),
+        );
+        return Ok(content
+            .add_to(signed)
+            .singlepart(part("application/pgp-signature", signature)));
+    }
+
+    let mut addresses = recipients.to_vec();
+    addresses.push(signer.to_string());
+    let missing = missing_keys(&addresses).await?;
+    if !missing.is_empty() {
+        return Err(format!("No trusted PGP key for {}", missing.join(", ")));
+    }
+    let mut args = vec![
+        String::from("--encrypt"),
+        String::from("--encrypt-to"),
+        key.clone(),
+    ];
+    if protection.signs() {
+        args.extend([String::from("--sign"), String::from("--local-user"), key]);
+    }
+    for recipient in recipients {
+        args.extend([String::from("--recipient"), format!("<{recipient}>")]);
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let encrypted = gpg(&args, &content.formatted()).await?;
+
+    Ok(
+        MultiPart::encrypted(String::from("application/pgp-encrypted"))
+            .singlepart(part("application/pgp-encrypted", b"Version: 1".to_vec()))
+            .singlepart(part("application/octet-stream", encrypted)),
+    )
+}
+
+fn part(content_type: &str, body: Vec<u8>) -> SinglePart {
+    SinglePart::builder()
+        .header(ContentType::parse(content_type).expect("a valid content type"))
+        .body(body)
+}
+
+/// Decrypt or verify `raw` if it is a PGP/MIME message, returning the MIME entity to show in its
+/// place and what was found. Returns `None` for any other message.
+pub async fn open(raw: &[u8]) -> Option<(Vec<u8>, Verification)> {
+    let message = MessageParser::default().parse(raw)?;
+    let sender = message
+        .from()
+        .and_then(|from| from.first())
+        .and_then(|from| from.address())
+        .map(str::to_lowercase);
+    open_from(raw, sender.as_deref()).await
+}
+
+/// Decrypt or verify `raw` as [`open()`] does, checking signatures against the `sender`'s address,
+/// which an entity decrypted from a message doesn't have headers for.
+async fn open_from(raw: &[u8], sender: Option<&str>) -> Option<(Vec<u8>, Verification)> {
+    let message = MessageParser::default().parse(raw)?;
+    let content_type = message.content_type()?;
+    if !content_type.ctype().eq_ignore_ascii_case("multipart") {
+        return None;
+    }
+    let part_of_type = |wanted: &str| {
+        message.parts.iter().find(|part| {
+            part.content_type().is_some_and(|content_type| {
+                let subtype = content_type.subtype().unwrap_or_default();
+                format!("{}/{subtype}", content_type.ctype()).eq_ignore_ascii_case(wanted)
+            })
+        })
+    };
+
+    match content_type.subtype()?.to_lowercase().as_str() {
+        "signed" => {
+            let content = signed_content(raw, content_type.attribute("boundary")?)?;
+            let signature = part_of_type("application/pgp-signature")?.contents();
+            let outcome = verify(&content, signature, sender).await;
+            Some((
+                content,
+                Verification {
+                    encrypted: false,
+                    outcome,
+                },
+            ))
+        }
+        "encrypted" => {
+            let encrypted = part_of_type("application/octet-stream")?.contents();
+            let (content, outcome) = match decrypt(encrypted, sender).await {
+                // the decrypted entity may be signed on its own, rather than in the same pass
+                Ok((content, Outcome::Unsigned)) => {
+                    match Box::pin(open_from(&content, sender)).await {
+                        Some((inner, verification)) => (inner, verification.outcome),
+                        None => (content, Outcome::Unsigned),
+                    }
+                }
+                Ok(decrypted) => decrypted,
+                Err(reason) => (
+                    raw.to_vec(),
+                    Outcome::Failed(format!("Couldn't decrypt: {reason}")),
+                ),
+            };
+            Some((
+                content,
+                Verification {
+                    encrypted: true,
+                    outcome,
+                },
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// The first part of a multipart/signed message, exactly as it was signed: with its headers, and
+/// with lines ending in CRLF, as stored messages sometimes lose the CRs.
+fn signed_content(raw: &[u8], boundary: &str) -> Option<Vec<u8>> {
+    let delimiter = format!("\n--{boundary}");
+    let start = find(raw, delimiter.as_bytes())? + delimiter.len();
+    let start = start + raw[start..].iter().position(|&b| b == b'\n')? + 1;
+    let end = start + find(&raw[start..], delimiter.as_bytes())?;
+
+    let mut content = Vec::with_capacity(end - start);
+    for line in raw[start..end].split(|&b| b == b'\n') {
+        if !content.is_empty() {
+            content.extend_from_slice(b"\r\n");
+        }
+        content.extend_from_slice(line.strip_suffix(b"\r").unwrap_or(line));
+    }
+    Some(content)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Check the detached `signature` over `content`, made by the `sender`.
+async fn verify(content: &[u8], signature: &[u8], sender: Option<&str>) -> Outcome {
+    // a new file with a random name that only the user can read, removed once it is dropped
+    let file = tempfile::Builder::new()
+        .prefix("eta-")
+        .suffix(".asc")
+        .tempfile()
+        .and_then(|mut file| file.write_all(signature).map(|_| file));
+    let file = match file {
+        Ok(file) => file,
+        Err(e) => return Outcome::Failed(format!("Couldn't check the signature: {e}")),
+    };
+    let path = file.path().to_string_lossy();
+    let result = run_gpg(&["--status-fd", "2", "--verify", &path, "-"], content).await;
+
+    match result {
+        Ok(output) => signature_outcome(&String::from_utf8_lossy(&output.stderr), sender).await,
+        Err(e) => Outcome::Failed(format!("Couldn't check the signature: {e}")),
+    }
+}
+
+/// Decrypt `encrypted`, checking any signature made in the same pass by the `sender`.
+async fn decrypt(encrypted: &[u8], sender: Option<&str>) -> Result<(Vec<u8>, Outcome), String> {
+    let output = run_gpg(&["--status-fd", "2", "--decrypt"], encrypted).await?;
+    let status = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(failure(&status));
+    }
+    Ok((output.stdout, signature_outcome(&status, sender).await))
+}
+
+/// What gpg's status lines say about a signature, with a good one only counted as such when
+/// its key is trusted and has a user ID with the `sender`'s address.
+async fn signature_outcome(status: &str, sender: Option<&str>) -> Outcome {
+    let signature = match signature_status(status) {
+        Ok(signature) => signature,
+        Err(outcome) => return outcome,
+    };
+    if !signature.trusted {
+        return Outcome::Unverified(signature.user);
+    }
+
+    let user_ids = match &signature.fingerprint {
+        Some(fingerprint) => user_ids(fingerprint).await,
+        None => vec![signature.user.clone()],
+    };
+    if sender.is_some_and(|sender| user_ids.iter().any(|id| address_of(id) == sender)) {
+        Outcome::Good(signature.user)
+    } else {
+        Outcome::Mismatch(signature.user)
+    }
+}
+
+/// A good signature, as gpg's status lines describe it.
+#[derive(Debug, PartialEq, Eq)]
+struct GoodSignature {
+    /// The primary user ID of the key that made it.
+    user: String,
+    /// The fingerprint of the key's primary key.
+    fingerprint: Option<String>,
+    /// Whether the key is fully or ultimately trusted.
+    trusted: bool,
+}
+
+/// Read gpg's status lines (described in gnupg's doc/DETAILS), returning the good signature they
+/// report, or else what they say instead.
+fn signature_status(status: &str) -> Result<GoodSignature, Outcome> {
+    let mut good = None;
+    let mut fingerprint = None;
+    let mut trusted = false;
+    let mut unchecked = false;
+    for line in status
+        .lines()
+        .filter_map(|line| line.strip_prefix("[GNUPG:] "))
+    {
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        // after the key ID comes the key's user ID
+        let user = rest
+            .split_once(' ')
+            .map_or(rest, |(_, user)| user)
+            .to_string();
+        match keyword {
+            "GOODSIG" => good = Some(user),
+            // the fingerprint of the signing key comes first, and that of its primary key last
+            "VALIDSIG" => fingerprint = rest.split(' ').next_back().map(String::from),
+            "TRUST_FULLY" | "TRUST_ULTIMATE" => trusted = true,
+            "TRUST_UNDEFINED" | "TRUST_NEVER" | "TRUST_MARGINAL" => trusted = false,
+            "BADSIG" => return Err(Outcome::Failed(format!("Bad signature from {user}"))),
+            "EXPKEYSIG" => {
+                return Err(Outcome::Failed(format!(
+                    "Signed by {user}, whose key expired"
+                )));
+            }
+            "REVKEYSIG" => {
+                return Err(Outcome::Failed(format!(
+                    "Signed by {user}, whose key is revoked"
+                )));
+            }
+            "NO_PUBKEY" => return Err(Outcome::UnknownKey(rest.to_string())),
+            "ERRSIG" => unchecked = true,
+            _ => {}
+        }
+    }
+    match good {
+        Some(user) => Ok(GoodSignature {
+            user,
+            fingerprint,
+            trusted,
+        }),
+        None if unchecked => Err(Outcome::Failed(String::from(
+            "The signature couldn't be checked",
+        ))),
+        None => Err(Outcome::Unsigned),
+    }
+}
+
+/// The valid user IDs of the key with `fingerprint`.
+async fn user_ids(fingerprint: &str) -> Vec<String> {
+    let Ok(output) = run_gpg(&["--with-colons", "--list-keys", fingerprint], b"").await else {
+        return vec![];
+    };
+    // the second field is the user ID's validity, the tenth the user ID, with colons escaped
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            (fields.first() == Some(&"uid") && matches!(fields.get(1), Some(&("f" | "u"))))
+                .then(|| fields.get(9).map(|id| id.replace("\\x3a", ":")))
+                .flatten()
+        })
+        .collect()
+}
+
+/// The address in a user ID such as `Jane Doe <jane@example.com>`, in lowercase.
+fn address_of(user_id: &str) -> String {
+    let address = match (user_id.rfind('<'), user_id.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &user_id[start + 1..end],
+        _ => user_id,
+    };
+    address.trim().to_lowercase()
+}
+
+/// The addresses in `addresses` that have no public key which is valid and can encrypt.
+async fn missing_keys(addresses: &[String]) -> Result<Vec<String>, String> {
+    let mut missing = vec![];
+    for address in addresses {
+        // gpg fails when there is no key, which only matters for the listing being empty
+        let listing = run_gpg(
+            &["--with-colons", "--list-keys", &format!("<{address}>")],
+            b"",
+        )
+        .await?
+        .stdout;
+        // fields are described in gnupg's doc/DETAILS: the second is the key's validity, the
+        // twelfth what the key as a whole can be used for
+        let usable = String::from_utf8_lossy(&listing).lines().any(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            fields.first() == Some(&"pub")
+                && matches!(fields.get(1), Some(&("m" | "f" | "u")))
+                && fields.get(11).is_some_and(|usage| usage.contains('E'))
+        });
+        if !usable {
+            missing.push(address.clone());
+        }
+    }
+    Ok(missing)
+}
+
+/// Run `gpg` with `args`, giving it `input` and returning its armored output.
+async fn gpg(args: &[&str], input: &[u8]) -> Result<Vec<u8>, String> {
+    let output = run_gpg(args, input).await?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        let reason = failure(&String::from_utf8_lossy(&output.stderr));
+        Err(format!("gpg failed: {reason}"))
+    }
+}
+
+/// Why gpg failed, from the last message it printed.
+fn failure(stderr: &str) -> String {
+    let line = stderr
+        .lines()
+        .rfind(|line| !line.starts_with("[GNUPG:]"))
+        .unwrap_or_default();
+    line.trim_start_matches("gpg: ").trim().to_string()
+}
+
+/// Run `gpg`, whether it succeeds or not.
+async fn run_gpg(args: &[&str], input: &[u8]) -> Result<Output, String> {
+    let mut child = Command::new("gpg")
+        .args(["--batch", "--armor"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("couldn't run gpg: {e}"))?;
+
+    // written alongside reading the output, so neither pipe fills up waiting for the other
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_vec();
+    let writer = tokio::spawn(async move { stdin.write_all(&input).await });
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("couldn't run gpg: {e}"))?;
+    let _ = writer.await;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOD: &str = "[GNUPG:] NEWSIG
+[GNUPG:] GOODSIG 0123456789ABCDEF Jane Doe <jane@example.com>
+[GNUPG:] VALIDSIG 89ABCDEF89ABCDEF89ABCDEF89ABCDEF89ABCDEF 2026-10-01 1790000000 0 4 0 1 10 00 \
+0123456789ABCDEF0123456789ABCDEF01234567
+";
+
+    #[test]
+    fn good_signature_needs_a_trusted_key() {
+        let signature = signature_status(GOOD).unwrap();
+        assert_eq!(signature.user, "Jane Doe <jane@example.com>");
+        assert_eq!(
+            signature.fingerprint.as_deref(),
+            Some("0123456789ABCDEF0123456789ABCDEF01234567")
+        );
+        assert!(!signature.trusted);
+
+        for (trust, trusted) in [
+            ("TRUST_ULTIMATE 0 pgp", true),
+            ("TRUST_FULLY 0 pgp", true),
+            ("TRUST_MARGINAL 0 pgp", false),
+            ("TRUST_UNDEFINED 0 pgp", false),
+        ] {
+            let status = format!("{GOOD}[GNUPG:] {trust}\n");
+            assert_eq!(
+                signature_status(&status).unwrap().trusted,
+                trusted,
+                "{trust}"
+            );
+        }
+    }
+
+    #[test]
+    fn other_signatures_are_reported() {
+        let outcome = |status: &str| signature_status(status).unwrap_err();
+
+        assert_eq!(
+            outcome("[GNUPG:] BADSIG 0123456789ABCDEF Jane Doe <jane@example.com>"),
+            Outcome::Failed(String::from(
+                "Bad signature from Jane Doe <jane@example.com>"
+            ))
+        );
+        assert_eq!(
+            outcome(
+                "[GNUPG:] ERRSIG 0123456789ABCDEF 1 10 00 1790000000 9 -\n\
+                     [GNUPG:] NO_PUBKEY 0123456789ABCDEF"
+            ),
+            Outcome::UnknownKey(String::from("0123456789ABCDEF"))
+        );
+        assert!(matches!(
+            outcome("[GNUPG:] EXPKEYSIG 0123456789ABCDEF Jane Doe <jane@example.com>"),
+            Outcome::Failed(_)
+        ));
+        assert_eq!(outcome("gpg: no signed data"), Outcome::Unsigned);
+    }
+
+    #[tokio::test]
+    async fn untrusted_signature_is_unverified() {
+        let outcome = signature_outcome(GOOD, Some("jane@example.com")).await;
+
+        assert_eq!(
+            outcome,
+            Outcome::Unverified(String::from("Jane Doe <jane@example.com>"))
+        );
+    }
+
+    #[test]
+    fn address_of_user_id() {
+        assert_eq!(
+            address_of("Jane Doe <Jane@Example.com>"),
+            "jane@example.com"
+        );
+        assert_eq!(address_of("jane@example.com"), "jane@example.com");
+    }
+}
@@ -0,0 +1,38 @@
+use std::{
+    io::{BufRead, Write},
+    process::{Command, Stdio},
+};
+
+use color_eyre::eyre::eyre;
+use ratatui::DefaultTerminal;
+
+use crate::terminal;
+
+/// Run `command` with the shell, with `text` on its standard input, handing the terminal over to
+/// it so that it may show output or be used, e.g. `lpr` or `less`. Whatever it printed stays up
+/// until Enter is pressed.
+pub fn pipe(terminal: &mut DefaultTerminal, command: &str, text: &str) -> color_eyre::Result<()> {
+    let status = terminal::suspend(terminal, || {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // commands such as `head` may stop reading before the end
+            let _ = stdin.write_all(text.as_bytes());
+        }
+        let status = child.wait();
+
+        print!("\nPress Enter to return to eta");
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stdin().lock().read_line(&mut String::new());
+        status
+    })?;
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(eyre!("{command} exited with {status}")),
+        Err(e) => Err(eyre!("couldn't run {command}: {e}")),
+    }
+}
@@ -0,0 +1,96 @@
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span, Text},
+};
+
+use crate::message::MessagePart;
+
+/// Extensions of attachments that are worth reading as text, whatever their MIME type says.
+const TEXT_EXTENSIONS: [&str; 9] = [
+    "txt", "csv", "log", "patch", "diff", "json", "md", "toml", "yaml",
+];
+
+/// Colours given to CSV columns in turn, so fields can be told apart.
+const COLUMN_COLORS: [Color; 5] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Green,
+    Color::Magenta,
+    Color::Blue,
+];
+
+/// Whether `part` is an attachment that can be shown as text.
+pub fn is_previewable(part: &MessagePart) -> bool {
+    let content_type = part.content_type();
+
+    part.is_attachment()
+        && (content_type.starts_with("text/")
+            || matches!(
+                content_type,
+                "application/json" | "application/x-patch" | "application/x-diff"
+            )
+            || extension(part).is_some_and(|ext| TEXT_EXTENSIONS.contains(&ext.as_str())))
+}
+
+/// The text of an attachment, highlighted when it is a patch or a CSV file.
+pub fn highlight(part: &MessagePart) -> Text<'static> {
+    let text = part.text();
+    let ext = extension(part);
+
+    match (part.content_type(), ext.as_deref()) {
+        ("text/x-diff" | "text/x-patch" | "application/x-diff" | "application/x-patch", _)
+        | (_, Some("diff" | "patch")) => text.lines().map(diff_line).collect(),
+        ("text/csv", _) | (_, Some("csv")) => text.lines().map(csv_line).collect(),
+        _ => Text::from(text),
+    }
+}
+
+fn extension(part: &MessagePart) -> Option<String> {
+    let (_, ext) = part.filename()?.rsplit_once('.')?;
+    Some(ext.to_lowercase())
+}
+
+fn diff_line(line: &str) -> Line<'static> {
+    let style = if line.starts_with("+++") || line.starts_with("---") {
+        Style::new().fg(Color::White)
+    } else if line.starts_with('+') {
+        Style::new().fg(Color::Green)
+    } else if line.starts_with('-') {
+        Style::new().fg(Color::Red)
+    } else if line.starts_with("@@") {
+        Style::new().fg(Color::Cyan)
+    } else {
+        Style::new()
+    };
+    Line::styled(line.to_string(), style)
+}
+
+fn csv_line(line: &str) -> Line<'static> {
+    let mut spans = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                field.push(c);
+            }
+            ',' if !quoted => {
+                spans.push(csv_field(std::mem::take(&mut field), spans.len() / 2));
+                spans.push(Span::raw(","));
+            }
+            _ => field.push(c),
+        }
+    }
+    spans.push(csv_field(field, spans.len() / 2));
+
+    Line::from(spans)
+}
+
+fn csv_field(field: String, column: usize) -> Span<'static> {
+    Span::styled(
+        field,
+        Style::new().fg(COLUMN_COLORS[column % COLUMN_COLORS.len()]),
+    )
+}
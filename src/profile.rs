@@ -0,0 +1,171 @@
+use std::path::{Path, PathBuf};
+
+/// Directory in the configuration and data directories holding a folder for each named profile.
+const PROFILES_DIR: &str = "profiles";
+
+/// A complete set of settings and messages, e.g. for work or personal mail, kept apart from any
+/// other. The default profile keeps `eta.toml` in `$XDG_CONFIG_HOME/eta/` and `messages.db` in
+/// `$XDG_DATA_HOME/eta/`, and named profiles keep theirs in `profiles/<name>/` below those.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    name: Option<String>,
+    /// Set with `--config`, in place of the profile's own `eta.toml`.
+    config: Option<PathBuf>,
+    /// Set with `--database`, in place of the profile's own `messages.db`.
+    database: Option<PathBuf>,
+}
+
+impl Profile {
+    /// The profile called `name`, which becomes the name of its folders and so can't be a path.
+    pub fn named(name: &str) -> Result<Self, String> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(format!("{name:?} can't be the name of a profile"));
+        }
+        Ok(Profile {
+            name: Some(name.to_string()).filter(|name| name != "default"),
+            ..Profile::default()
+        })
+    }
+
+    /// This profile, reading its settings from `config` and its messages from `database` where
+    /// given.
+    pub fn with_paths(self, config: Option<PathBuf>, database: Option<PathBuf>) -> Self {
+        Profile {
+            config: config.or(self.config),
+            database: database.or(self.database),
+            ..self
+        }
+    }
+
+    /// The default profile, followed by the named profiles in alphabetical order.
+    pub fn list() -> Vec<Profile> {
+        let mut names: Vec<String> = std::fs::read_dir(config_home().join(PROFILES_DIR))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        names.sort();
+
+        std::iter::once(Profile::default())
+            .chain(names.iter().filter_map(|name| Profile::named(name).ok()))
+            .collect()
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("default")
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.name.is_none()
+    }
+
+    /// `base`, or the profile's folder in it for a named profile.
+    fn dir(&self, base: PathBuf) -> PathBuf {
+        match &self.name {
+            Some(name) => base.join(PROFILES_DIR).join(name),
+            None => base,
+        }
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.config
+            .clone()
+            .unwrap_or_else(|| self.dir(config_home()).join("eta.toml"))
+    }
+
+    pub fn database_path(&self) -> PathBuf {
+        self.database
+            .clone()
+            .unwrap_or_else(|| self.dir(data_home()).join("messages.db"))
+    }
+
+    /// Move the `eta.toml` and `messages.db` that older versions kept in the working directory
+    /// to where the default profile keeps them, unless it already has its own.
+    pub fn adopt_old_files(&self) -> std::io::Result<()> {
+        if *self != Profile::default() {
+            return Ok(());
+        }
+        self.create_dirs()?;
+        adopt(Path::new("eta.toml"), &self.config_path())?;
+        let database = self.database_path();
+        if adopt(Path::new("messages.db"), &database)? {
+            // along with any changes SQLite hasn't written into the database itself yet
+            for suffix in ["-wal", "-shm"] {
+                let mut to = database.clone().into_os_string();
+                to.push(suffix);
+                adopt(Path::new(&format!("messages.db{suffix}")), Path::new(&to))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the directories the profile's files go in, if they don't exist yet.
+    pub fn create_dirs(&self) -> std::io::Result<()> {
+        for path in [self.config_path(), self.database_path()] {
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Move the file at `from` to `to` if there is one and `to` doesn't exist yet, copying it when
+/// they are on different file systems. Returns whether it was moved.
+fn adopt(from: &Path, to: &Path) -> std::io::Result<bool> {
+    if !from.is_file() || to.exists() {
+        return Ok(false);
+    }
+    if std::fs::rename(from, to).is_err() {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)?;
+    }
+    tracing::info!(from = %from.display(), to = %to.display(), "moved");
+    Ok(true)
+}
+
+/// `$XDG_CONFIG_HOME/eta`, or `~/.config/eta`.
+fn config_home() -> PathBuf {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_DATA_HOME/eta`, or `~/.local/share/eta`.
+fn data_home() -> PathBuf {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// `$XDG_STATE_HOME/eta`, or `~/.local/state/eta`, where logs are kept.
+pub fn state_home() -> PathBuf {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// `eta`'s directory in the base directory named by `variable`, falling back to `fallback` in the
+/// home directory when it isn't set to an absolute path, as the XDG base directory spec says.
+fn xdg_dir(variable: &str, fallback: &str) -> PathBuf {
+    let base = std::env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .unwrap_or_else(|| {
+            let home = std::env::var_os("HOME")
+                .map(PathBuf::from)
+                .unwrap_or_default();
+            home.join(fallback)
+        });
+    base.join("eta")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_names_stay_in_their_folder() {
+        assert_eq!(Profile::named("work").unwrap().name(), "work");
+        assert!(Profile::named("default").unwrap().is_default());
+        for name in ["", ".", "..", "../work", "a/b", "a\\b"] {
+            assert!(Profile::named(name).is_err(), "{name}");
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+
+use crate::message::Message;
+
+/// A search as it is typed: words to find in messages, and conditions they have to meet, such as
+/// `from:boss`, `is:unread`, `has:attachment`, `newer:7d` or `tag:finance`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Query {
+    /// Words found in the subject, body or sender, or the start of longer ones.
+    pub words: Vec<String>,
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// `from:text`: the sender contains the text, ignoring case, and likewise for the others.
+    From(String),
+    /// `to:text`, which also looks at the Cc addresses.
+    To(String),
+    Subject(String),
+    /// `in:folder`.
+    Folder(String),
+    Tag(String),
+    /// `is:unread`, or `is:read` for `false`.
+    Unread(bool),
+    /// `is:flagged`.
+    Flagged,
+    /// `has:attachment`.
+    HasAttachment,
+    /// `newer:7d`: sent within so many seconds, and `older:` before that.
+    Newer(i64),
+    Older(i64),
+    /// `msgid:<id@example.com>`: the message with this `Message-ID`, kept in angle brackets
+    /// whether or not it was typed with them.
+    MessageId(String),
+}
+
+/// A search kept in the folder sidebar, opened like a folder, as a `[[searches]]` table.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
+}
+
+/// Read a search, where anything that isn't a condition is a word to search for.
+pub fn parse(text: &str) -> Query {
+    let mut query = Query::default();
+    for word in text.split_whitespace() {
+        match condition(word) {
+            Some(condition) => query.conditions.push(condition),
+            None => query.words.push(word.to_string()),
+        }
+    }
+    query
+}
+
+fn condition(word: &str) -> Option<Condition> {
+    let (key, value) = word.split_once(':')?;
+    if value.is_empty() {
+        return None;
+    }
+    Some(
+        match (key.to_lowercase().as_str(), value.to_lowercase().as_str()) {
+            ("from", _) => Condition::From(value.to_string()),
+            ("to", _) => Condition::To(value.to_string()),
+            ("subject", _) => Condition::Subject(value.to_string()),
+            ("in", _) => Condition::Folder(value.to_string()),
+            ("tag", _) => Condition::Tag(value.to_string()),
+            ("is", "unread") => Condition::Unread(true),
+            ("is", "read") => Condition::Unread(false),
+            ("is", "flagged") => Condition::Flagged,
+            ("has", "attachment") => Condition::HasAttachment,
+            ("newer", age) => Condition::Newer(seconds(age)?),
+            ("older", age) => Condition::Older(seconds(age)?),
+            ("msgid", _) => Condition::MessageId(format!(
+                "<{}>",
+                value.trim_start_matches('<').trim_end_matches('>')
+            )),
+            _ => return None,
+        },
+    )
+}
+
+/// An age such as `7d`, in seconds: a number of days, weeks (`w`), months of 30 days (`m`) or
+/// years of 365 days (`y`).
+fn seconds(age: &str) -> Option<i64> {
+    let (i, unit) = age.char_indices().last()?;
+    let count: i64 = age[..i].parse().ok()?;
+    let days = match unit {
+        'd' => 1,
+        'w' => 7,
+        'm' => 30,
+        'y' => 365,
+        _ => return None,
+    };
+    count.checked_mul(days * 86400)
+}
+
+impl Query {
+    /// Whether `message` is found by the search at `now`, for messages that aren't kept in the
+    /// database and so can't be searched there.
+    pub fn matches(&self, message: &Message, now: DateTime<Local>) -> bool {
+        let contains = |text: &str, part: &str| text.to_lowercase().contains(&part.to_lowercase());
+        let age = message.date().map(|date| (now - date).num_seconds());
+        let text = format!(
+            "{} {} {}",
+            message.subject(),
+            message.body(),
+            message.from()
+        );
+        self.words.iter().all(|word| contains(&text, word))
+            && self.conditions.iter().all(|condition| match condition {
+                Condition::From(part) => contains(message.from(), part),
+                Condition::To(part) => contains(message.to(), part) || contains(message.cc(), part),
+                Condition::Subject(part) => contains(message.subject(), part),
+                Condition::Folder(folder) => message.folder().eq_ignore_ascii_case(folder),
+                Condition::Tag(tag) => message.tags().iter().any(|t| t.eq_ignore_ascii_case(tag)),
+                Condition::Unread(unread) => message.is_unread() == *unread,
+                Condition::Flagged => message.is_flagged(),
+                Condition::HasAttachment => message.attachments().next().is_some(),
+                Condition::Newer(seconds) => age.is_some_and(|age| age <= *seconds),
+                Condition::Older(seconds) => age.is_some_and(|age| age > *seconds),
+                Condition::MessageId(id) => message.message_id() == Some(id.as_str()),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditions_are_kept_apart_from_words() {
+        assert_eq!(
+            parse("tps From:Boss is:unread report has:attachment"),
+            Query {
+                words: vec![String::from("tps"), String::from("report")],
+                conditions: vec![
+                    Condition::From(String::from("Boss")),
+                    Condition::Unread(true),
+                    Condition::HasAttachment,
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_or_empty_conditions_are_words() {
+        assert_eq!(
+            parse("is:everything from: re:subject newer:soon").words,
+            ["is:everything", "from:", "re:subject", "newer:soon"]
+        );
+    }
+
+    #[test]
+    fn message_ids_are_kept_in_angle_brackets() {
+        assert_eq!(
+            parse("msgid:abc@example.com msgid:<def@example.com>").conditions,
+            [
+                Condition::MessageId(String::from("<abc@example.com>")),
+                Condition::MessageId(String::from("<def@example.com>")),
+            ]
+        );
+    }
+
+    #[test]
+    fn ages_are_in_seconds() {
+        assert_eq!(seconds("1d"), Some(86400));
+        assert_eq!(seconds("2w"), Some(14 * 86400));
+        assert_eq!(seconds("1m"), Some(30 * 86400));
+        assert_eq!(seconds("1y"), Some(365 * 86400));
+        assert_eq!(seconds("7"), None);
+        assert_eq!(seconds("d"), None);
+        assert_eq!(seconds("-1x"), None);
+        assert_eq!(seconds("99999999999999999d"), None);
+    }
+}
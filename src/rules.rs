@@ -0,0 +1,278 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+use crate::{message::TRASH, mime};
+
+/// A rule from a `[[rules]]` table, sorting messages as they arrive or are imported. A message
+/// matches when every pattern given matches it; patterns are regular expressions, found anywhere
+/// in the text regardless of case.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Rule {
+    /// What the rule is called in the log.
+    pub name: String,
+    #[serde(default)]
+    pub from: Option<String>,
+    /// Matched against both the To and Cc addresses.
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default)]
+    pub subject: Option<String>,
+    /// Patterns for any other headers, by name, e.g. `{ List-Id = "rust-users" }`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// The folder to move matching messages to.
+    #[serde(default, rename = "move")]
+    pub move_to: Option<String>,
+    #[serde(default)]
+    pub mark_read: bool,
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Move matching messages to the trash.
+    #[serde(default)]
+    pub delete: bool,
+    /// The patterns, compiled by `check`; a rule that hasn't been checked matches nothing.
+    #[serde(skip)]
+    patterns: Option<Patterns>,
+}
+
+/// A rule's patterns, compiled to match regardless of case.
+#[derive(Clone, Debug)]
+struct Patterns {
+    from: Option<Regex>,
+    to: Option<Regex>,
+    subject: Option<Regex>,
+    headers: Vec<(String, Regex)>,
+}
+
+/// A profile's rules, and where what they do is logged.
+#[derive(Clone, Debug, Default)]
+pub struct Rules {
+    pub rules: Vec<Rule>,
+    /// Only log what the rules would do, leaving messages as they are.
+    pub dry_run: bool,
+    pub log: PathBuf,
+}
+
+/// The parts of a message that rules match against.
+pub struct Fields<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub cc: &'a str,
+    pub subject: &'a str,
+    /// The message as it was received, for matching other headers, if it was kept.
+    pub raw: Option<&'a [u8]>,
+}
+
+/// What the rules matching a message do to it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Outcome {
+    pub folder: Option<String>,
+    pub mark_read: bool,
+    pub tags: Vec<String>,
+    /// What each rule that matched did, for the log, e.g. `Invoices: tagged finance`.
+    pub applied: Vec<String>,
+}
+
+impl Rule {
+    /// Compile the rule's patterns, or say why the rule can't be used: a pattern that isn't a
+    /// valid regular expression, a tag with spaces in it, no patterns at all, which would match
+    /// every message, or nothing to do.
+    pub fn check(&mut self) -> Result<(), String> {
+        if self.from.is_none()
+            && self.to.is_none()
+            && self.subject.is_none()
+            && self.headers.is_empty()
+        {
+            return Err(format!("rule {} has nothing to match", self.name));
+        }
+        let compile = |pattern: &str| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| format!("rule {}: {e}", self.name))
+        };
+        let patterns = Patterns {
+            from: self.from.as_deref().map(compile).transpose()?,
+            to: self.to.as_deref().map(compile).transpose()?,
+            subject: self.subject.as_deref().map(compile).transpose()?,
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, pattern)| Ok((name.clone(), compile(pattern)?)))
+                .collect::<Result<_, String>>()?,
+        };
+        if !self.mark_read && self.tag.is_none() && self.move_to.is_none() && !self.delete {
+            return Err(format!("rule {} does nothing", self.name));
+        }
+        if let Some(tag) = &self.tag
+            && (tag.is_empty() || tag.contains(char::is_whitespace))
+        {
+            return Err(format!("rule {}: tags are single words", self.name));
+        }
+        self.patterns = Some(patterns);
+        Ok(())
+    }
+
+    fn matches(&self, fields: &Fields) -> bool {
+        let Some(patterns) = &self.patterns else {
+            return false;
+        };
+        let found = |regex: &Option<Regex>, texts: &[&str]| match regex {
+            Some(regex) => texts.iter().any(|text| regex.is_match(text)),
+            None => true,
+        };
+        found(&patterns.from, &[fields.from])
+            && found(&patterns.to, &[fields.to, fields.cc])
+            && found(&patterns.subject, &[fields.subject])
+            && patterns.headers.iter().all(|(name, regex)| {
+                fields
+                    .raw
+                    .and_then(|raw| mime::header(raw, name))
+                    .is_some_and(|value| regex.is_match(&value))
+            })
+    }
+}
+
+/// Apply each rule that matches `fields` in turn. A rule that moves or deletes the message is the
+/// last to apply to it.
+pub fn apply(rules: &[Rule], fields: &Fields) -> Outcome {
+    let mut outcome = Outcome::default();
+    for rule in rules.iter().filter(|rule| rule.matches(fields)) {
+        let mut done = vec![];
+        if rule.mark_read {
+            outcome.mark_read = true;
+            done.push(String::from("marked read"));
+        }
+        if let Some(tag) = &rule.tag {
+            if !outcome.tags.contains(tag) {
+                outcome.tags.push(tag.clone());
+            }
+            done.push(format!("tagged {tag}"));
+        }
+        if rule.delete {
+            outcome.folder = Some(String::from(TRASH));
+            done.push(String::from("deleted"));
+        } else if let Some(folder) = &rule.move_to {
+            outcome.folder = Some(folder.clone());
+            done.push(format!("moved to {folder}"));
+        }
+        outcome
+            .applied
+            .push(format!("{}: {}", rule.name, done.join(", ")));
+        if outcome.folder.is_some() {
+            break;
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(toml: &str) -> Rule {
+        let mut rule: Rule = toml::from_str(toml).unwrap();
+        rule.check().unwrap();
+        rule
+    }
+
+    fn fields<'a>(from: &'a str, subject: &'a str, raw: Option<&'a [u8]>) -> Fields<'a> {
+        Fields {
+            from,
+            to: "me@example.com",
+            cc: "team@example.com",
+            subject,
+            raw,
+        }
+    }
+
+    #[test]
+    fn every_pattern_must_match_regardless_of_case() {
+        let rule = rule(
+            r#"
+            name = "Invoices"
+            from = "billing@"
+            subject = "invoice"
+            tag = "finance"
+            "#,
+        );
+
+        assert!(rule.matches(&fields("Billing@example.com", "March INVOICE", None)));
+        assert!(!rule.matches(&fields("billing@example.com", "Hello", None)));
+        assert!(!rule.matches(&fields("alice@example.com", "Invoice", None)));
+    }
+
+    #[test]
+    fn to_matches_cc_and_headers_need_the_raw_message() {
+        let rule = rule(
+            r#"
+            name = "Team"
+            to = "^team@"
+            headers = { List-Id = "rust-users" }
+            mark_read = true
+            "#,
+        );
+        let raw = b"List-Id: <rust-users.example.com>\r\nSubject: Hi\r\n\r\nbody";
+
+        assert!(rule.matches(&fields("a@example.com", "Hi", Some(raw))));
+        assert!(!rule.matches(&fields("a@example.com", "Hi", None)));
+    }
+
+    #[test]
+    fn an_unchecked_rule_matches_nothing() {
+        let rule: Rule = toml::from_str("name = \"All\"\nsubject = \"\"\ndelete = true").unwrap();
+
+        assert!(!rule.matches(&fields("a@example.com", "Hi", None)));
+    }
+
+    #[test]
+    fn rules_that_cannot_be_used_are_refused() {
+        let check = |toml: &str| toml::from_str::<Rule>(toml).unwrap().check();
+
+        assert!(check("name = \"A\"\nmark_read = true").is_err());
+        assert!(check("name = \"B\"\nsubject = \"(\"\nmark_read = true").is_err());
+        assert!(check("name = \"C\"\nsubject = \"x\"").is_err());
+        assert!(check("name = \"D\"\nsubject = \"x\"\ntag = \"two words\"").is_err());
+    }
+
+    #[test]
+    fn rules_apply_in_order_until_one_moves_the_message() {
+        let rules = [
+            rule("name = \"Read\"\nfrom = \"alice\"\nmark_read = true"),
+            rule("name = \"Tag\"\nfrom = \"alice\"\ntag = \"friends\""),
+            rule("name = \"Move\"\nsubject = \"party\"\nmove = \"Social\""),
+            rule("name = \"Bin\"\nsubject = \"party\"\ndelete = true"),
+        ];
+
+        let outcome = apply(&rules, &fields("alice@example.com", "Party!", None));
+
+        assert_eq!(
+            outcome,
+            Outcome {
+                folder: Some(String::from("Social")),
+                mark_read: true,
+                tags: vec![String::from("friends")],
+                applied: vec![
+                    String::from("Read: marked read"),
+                    String::from("Tag: tagged friends"),
+                    String::from("Move: moved to Social"),
+                ],
+            }
+        );
+        assert_eq!(
+            apply(&rules, &fields("bob@example.com", "Hi", None)),
+            Outcome::default()
+        );
+    }
+
+    #[test]
+    fn delete_moves_to_the_trash() {
+        let rules = [rule("name = \"Spam\"\nsubject = \"winner\"\ndelete = true")];
+
+        let outcome = apply(&rules, &fields("x@example.com", "You are a WINNER", None));
+
+        assert_eq!(outcome.folder.as_deref(), Some(TRASH));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::config::Confirmation;
+
+/// An option that can be changed from the settings screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    GroupByDate,
+    RelativeDates,
+    EmptyTrashAfterDays,
+    MarkReadAfterSeconds,
+    PollInterval,
+    AutosaveSeconds,
+    DesktopNotifications,
+    ReplyAttribution,
+    ReplyPosting,
+    Signature,
+    RemoteContentSenders,
+    /// Whether an action goes ahead, asks first or is refused.
+    Confirm(Confirmation),
+}
+
+/// The settings screen's options, in the order they are listed.
+pub const SETTINGS: [Setting; 16] = [
+    Setting::GroupByDate,
+    Setting::RelativeDates,
+    Setting::EmptyTrashAfterDays,
+    Setting::MarkReadAfterSeconds,
+    Setting::PollInterval,
+    Setting::AutosaveSeconds,
+    Setting::DesktopNotifications,
+    Setting::ReplyAttribution,
+    Setting::ReplyPosting,
+    Setting::Signature,
+    Setting::RemoteContentSenders,
+    Setting::Confirm(Confirmation::Send),
+    Setting::Confirm(Confirmation::Delete),
+    Setting::Confirm(Confirmation::EmptyTrash),
+    Setting::Confirm(Confirmation::DiscardDraft),
+    Setting::Confirm(Confirmation::RemoteContent),
+];
+
+impl Setting {
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::GroupByDate => "Group messages by date",
+            Setting::RelativeDates => "Relative dates",
+            Setting::EmptyTrashAfterDays => "Empty trash after days",
+            Setting::MarkReadAfterSeconds => "Mark read after seconds",
+            Setting::PollInterval => "Check for mail every seconds",
+            Setting::AutosaveSeconds => "Save drafts every seconds",
+            Setting::DesktopNotifications => "Desktop notifications",
+            Setting::ReplyAttribution => "Reply attribution",
+            Setting::ReplyPosting => "Reply posting",
+            Setting::Signature => "Signature",
+            Setting::RemoteContentSenders => "Load remote content from",
+            Setting::Confirm(Confirmation::Send) => "Confirm sending",
+            Setting::Confirm(Confirmation::Delete) => "Confirm deleting for good",
+            Setting::Confirm(Confirmation::EmptyTrash) => "Confirm emptying trash",
+            Setting::Confirm(Confirmation::DiscardDraft) => "Confirm discarding",
+            Setting::Confirm(Confirmation::RemoteContent) => "Confirm remote content",
+        }
+    }
+
+    /// Whether choosing the setting switches it to its next value, rather than editing it.
+    pub fn is_toggle(self) -> bool {
+        matches!(
+            self,
+            Setting::GroupByDate
+                | Setting::RelativeDates
+                | Setting::DesktopNotifications
+                | Setting::ReplyPosting
+                | Setting::Confirm(_)
+        )
+    }
+
+    /// Whether the setting's value may span several lines.
+    pub fn is_multiline(self) -> bool {
+        self == Setting::Signature
+    }
+}
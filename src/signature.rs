@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use whatlang::{Detector, Lang};
+
+/// Line separating a signature from the rest of a message, so mail clients can recognise it.
+const DELIMITER: &str = "-- ";
+
+/// How sure language detection must be before a reply gets that language's signature.
+const MIN_CONFIDENCE: f64 = 0.5;
+
+/// The `[signature]` table: a signature given inline or read from a file.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SignatureConfig {
+    #[serde(flatten)]
+    pub default: SignatureText,
+    /// Variants used when replying to a message in another language, keyed by ISO 639-3 code,
+    /// e.g. `fra` or `deu`.
+    #[serde(default)]
+    pub languages: HashMap<String, SignatureText>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SignatureText {
+    pub text: Option<String>,
+    /// Read the signature from this file instead.
+    pub file: Option<PathBuf>,
+}
+
+impl SignatureText {
+    /// Read the signature from its file, if it is given as one. A relative path is taken from
+    /// `dir`, the directory `eta.toml` is in.
+    pub fn load_file(&mut self, dir: &Path) -> std::io::Result<()> {
+        if let Some(file) = &self.file {
+            self.text = Some(std::fs::read_to_string(dir.join(file))?);
+        }
+        Ok(())
+    }
+}
+
+impl SignatureConfig {
+    /// Read any signatures given as files, so they don't need reading for every message.
+    pub fn load_files(&mut self, dir: &Path) -> std::io::Result<()> {
+        for signature in std::iter::once(&mut self.default).chain(self.languages.values_mut()) {
+            signature.load_file(dir)?;
+        }
+        Ok(())
+    }
+
+    /// The signature for a new message, if one is configured.
+    pub fn default_text(&self) -> Option<&str> {
+        self.default.text.as_deref()
+    }
+
+    /// The signature for a reply to `original`: the variant for its language when there is one,
+    /// and the default signature otherwise.
+    pub fn for_reply(&self, original: &str) -> Option<&str> {
+        // only telling apart the languages there are signatures for, and English, makes short
+        // messages much easier to place
+        let mut languages: Vec<Lang> = self.languages.keys().filter_map(Lang::from_code).collect();
+        languages.push(Lang::Eng);
+
+        Detector::with_allowlist(languages)
+            .detect(original)
+            .filter(|info| info.confidence() >= MIN_CONFIDENCE)
+            .and_then(|info| self.languages.get(info.lang().code()))
+            .and_then(|signature| signature.text.as_deref())
+            .or_else(|| self.default_text())
+    }
+}
+
+/// The lines a signature adds to the end of a message, starting with the delimiter.
+pub fn block(text: &str) -> Vec<String> {
+    std::iter::once(DELIMITER)
+        .chain(text.trim_end().lines())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_starts_with_the_delimiter() {
+        assert_eq!(
+            block("Bob\nExample Corp\n\n"),
+            ["-- ", "Bob", "Example Corp"]
+        );
+    }
+
+    #[test]
+    fn file_is_read_next_to_the_config() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("signature.txt"), "Bob\n").unwrap();
+        let mut signature = SignatureText {
+            text: None,
+            file: Some(PathBuf::from("signature.txt")),
+        };
+
+        signature.load_file(dir.path()).unwrap();
+
+        assert_eq!(signature.text.as_deref(), Some("Bob\n"));
+        assert_eq!(signature.file, Some(PathBuf::from("signature.txt")));
+    }
+
+    #[test]
+    fn default_is_used_without_a_file() {
+        let signature: SignatureConfig = toml::from_str("text = 'Bob'").unwrap();
+
+        assert_eq!(signature.default_text(), Some("Bob"));
+        assert_eq!(SignatureConfig::default().default_text(), None);
+    }
+
+    #[test]
+    fn reply_gets_the_signature_in_its_language() {
+        let signature: SignatureConfig =
+            toml::from_str("text = 'Bob'\n[languages.fra]\ntext = 'Bob, avec mes salutations'")
+                .unwrap();
+
+        assert_eq!(
+            signature.for_reply("Bonjour, pourriez-vous m'envoyer le rapport avant vendredi ?"),
+            Some("Bob, avec mes salutations")
+        );
+        assert_eq!(
+            signature.for_reply("Hello, could you send me the report before Friday?"),
+            Some("Bob")
+        );
+        assert_eq!(signature.for_reply(""), Some("Bob"));
+    }
+}
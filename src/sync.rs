@@ -0,0 +1,63 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// When each folder is next checked for new messages.
+#[derive(Clone, Debug, Default)]
+pub struct Schedule {
+    /// How often folders not listed in `intervals` are checked, if at all.
+    default_interval: Option<Duration>,
+    /// How often each folder is checked, if at all.
+    intervals: HashMap<String, Option<Duration>>,
+    /// When each folder is next due to be checked.
+    next_check: HashMap<String, Instant>,
+}
+
+impl Schedule {
+    /// A schedule checking each folder every so many seconds, as set in the `[polling]` table, and
+    /// any other folder every `poll_interval` seconds. An interval of 0 means never.
+    pub fn new(polling: &HashMap<String, u64>, poll_interval: u64) -> Self {
+        let intervals = polling
+            .iter()
+            .map(|(folder, &seconds)| (folder.clone(), every(seconds)))
+            .collect();
+        Schedule {
+            default_interval: every(poll_interval),
+            intervals,
+            next_check: HashMap::new(),
+        }
+    }
+
+    /// Whether `folder` is due to be checked at `now`, in which case its next check is scheduled.
+    /// A folder's first check comes one interval after it is first asked about.
+    pub fn is_due(&mut self, folder: &str, now: Instant) -> bool {
+        let Some(interval) = self
+            .intervals
+            .get(folder)
+            .copied()
+            .unwrap_or(self.default_interval)
+        else {
+            return false;
+        };
+        let next_check = self
+            .next_check
+            .entry(folder.to_string())
+            .or_insert(now + interval);
+        if *next_check > now {
+            return false;
+        }
+        *next_check = now + interval;
+        true
+    }
+
+    /// When the next folder asked about so far is due to be checked, if any is.
+    pub fn next_check(&self) -> Option<Instant> {
+        self.next_check.values().min().copied()
+    }
+}
+
+/// An interval of `seconds`, or none for 0.
+fn every(seconds: u64) -> Option<Duration> {
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
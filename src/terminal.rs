@@ -0,0 +1,21 @@
+use std::io::stdout;
+
+use crossterm::{
+    execute,
+    terminal::{EnterAlternateScreen, enable_raw_mode},
+};
+use ratatui::DefaultTerminal;
+
+/// Hand the terminal back to the shell while `run` runs, e.g. for an editor or a prompt, then
+/// take it over again for the interface.
+pub fn suspend<T>(
+    terminal: &mut DefaultTerminal,
+    run: impl FnOnce() -> T,
+) -> color_eyre::Result<T> {
+    ratatui::restore();
+    let result = run();
+    enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen)?;
+    terminal.clear()?;
+    Ok(result)
+}
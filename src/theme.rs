@@ -0,0 +1,121 @@
+use ratatui::style::{Color, Style, Stylize};
+use serde::{Deserialize, Deserializer, de::Error};
+
+/// Colours from the `[theme]` table. Anything left out keeps the terminal's own colours, picked
+/// out with bold or reverse video as before.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    header: Colours,
+    selection: Colours,
+    status_bar: Colours,
+    unread: Colours,
+    compose_label: Colours,
+    tag: Colours,
+    marked: Colours,
+    /// The colours of quoted lines, taken in turn for each level of quoting.
+    quotes: Vec<Colours>,
+    signature: Colours,
+}
+
+/// The colours quoted lines go round when `quotes` isn't set.
+const QUOTES: [Colours; 3] = [
+    Colours::fg(Color::Cyan),
+    Colours::fg(Color::Green),
+    Colours::fg(Color::Yellow),
+];
+
+impl Theme {
+    /// Column headings, e.g. of the message table.
+    pub fn header(&self) -> Style {
+        self.header.style().bold()
+    }
+
+    /// The selected row of a table or list.
+    pub fn selection(&self) -> Style {
+        self.selection.style_or(Style::new().reversed())
+    }
+
+    /// Statuses and other notices in the status bar.
+    pub fn status_bar(&self) -> Style {
+        self.status_bar.style_or(Style::new().reversed())
+    }
+
+    /// Unread messages in the message table.
+    pub fn unread(&self) -> Style {
+        self.unread.style().bold()
+    }
+
+    /// The label of the composer's selected field.
+    pub fn compose_label(&self) -> Style {
+        self.compose_label.style_or(Style::new().reversed())
+    }
+
+    /// Tags shown before a message's subject in the message table.
+    pub fn tag(&self) -> Style {
+        self.tag.style_or(Style::new().reversed())
+    }
+
+    /// Messages marked in the message table, to act on together.
+    pub fn marked(&self) -> Style {
+        self.marked.style_or(Style::new().underlined())
+    }
+
+    /// Lines of a message body quoted `depth` times, which is at least once.
+    pub fn quote(&self, depth: usize) -> Style {
+        let colours = if self.quotes.is_empty() {
+            &QUOTES[..]
+        } else {
+            &self.quotes
+        };
+        colours[(depth - 1) % colours.len()].style()
+    }
+
+    /// The signature at the end of a message body.
+    pub fn signature(&self) -> Style {
+        self.signature.style_or(Style::new().dim())
+    }
+}
+
+/// A foreground and background colour, e.g. `{ fg = "black", bg = "#ffaf00" }`. Colours are
+/// named as ratatui names them: `red`, `lightblue`, `#rrggbb` or an index such as `208`.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct Colours {
+    #[serde(default, deserialize_with = "colour")]
+    fg: Option<Color>,
+    #[serde(default, deserialize_with = "colour")]
+    bg: Option<Color>,
+}
+
+impl Colours {
+    const fn fg(fg: Color) -> Self {
+        Colours {
+            fg: Some(fg),
+            bg: None,
+        }
+    }
+
+    fn style(self) -> Style {
+        Style {
+            fg: self.fg,
+            bg: self.bg,
+            ..Style::new()
+        }
+    }
+
+    /// These colours, or `fallback` when neither is set.
+    fn style_or(self, fallback: Style) -> Style {
+        if self.fg.is_none() && self.bg.is_none() {
+            fallback
+        } else {
+            self.style()
+        }
+    }
+}
+
+fn colour<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Color>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse()
+        .map(Some)
+        .map_err(|_| D::Error::custom(format!("unknown colour: {name}")))
+}
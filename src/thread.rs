@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use crate::message::Message;
+
+/// A message's place in a conversation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ThreadEntry {
+    /// Index of the message in the list that was threaded.
+    pub index: usize,
+    /// How many replies deep the message is; the first message of a thread is at depth 0.
+    pub depth: usize,
+    /// Index of the first message of the thread.
+    pub root: usize,
+}
+
+/// Order `messages` by conversation. Each thread starts with its first message, and is followed
+/// by the replies to it, depth first. Threads, and replies to the same message, keep the order
+/// they had in `messages`.
+///
+/// A message's parent is the latest message in its `References` that is present, or failing
+/// that its `In-Reply-To`, so a thread with a missing message is kept together when possible.
+pub fn thread(messages: &[&Message]) -> Vec<ThreadEntry> {
+    let ids: HashMap<&str, usize> = messages
+        .iter()
+        .enumerate()
+        .filter_map(|(i, m)| Some((m.message_id()?, i)))
+        .collect();
+
+    let mut parents: Vec<Option<usize>> = vec![None; messages.len()];
+    for (i, message) in messages.iter().enumerate() {
+        let parent = message
+            .references()
+            .iter()
+            .rev()
+            .map(String::as_str)
+            .chain(message.in_reply_to())
+            .find_map(|id| ids.get(id).copied())
+            .filter(|&parent| !is_ancestor(&parents, i, parent));
+        parents[i] = parent;
+    }
+
+    let mut children: Vec<Vec<usize>> = vec![vec![]; messages.len()];
+    for (i, parent) in parents.iter().enumerate() {
+        if let Some(parent) = parent {
+            children[*parent].push(i);
+        }
+    }
+
+    let mut entries = Vec::with_capacity(messages.len());
+    for root in (0..messages.len()).filter(|&i| parents[i].is_none()) {
+        let mut stack = vec![(root, 0)];
+        while let Some((index, depth)) = stack.pop() {
+            entries.push(ThreadEntry { index, depth, root });
+            stack.extend(
+                children[index]
+                    .iter()
+                    .rev()
+                    .map(|&child| (child, depth + 1)),
+            );
+        }
+    }
+    entries
+}
+
+/// Whether `message` is `other`, or one of its ancestors, so making it the parent of `other`
+/// would create a loop.
+fn is_ancestor(parents: &[Option<usize>], message: usize, other: usize) -> bool {
+    let mut current = Some(other);
+    while let Some(i) = current {
+        if i == message {
+            return true;
+        }
+        current = parents[i];
+    }
+    false
+}
@@ -1,137 +1,1229 @@
+use std::cell::RefCell;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate};
 use ratatui::{
     buffer::Buffer,
-    layout::{Constraint, Layout, Rect},
-    style::{Style, Stylize},
-    text::Line,
-    widgets::{Block, Borders, Paragraph, Row, StatefulWidget, Table, Widget},
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListState, Paragraph, Row, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, StatefulWidget, Table, TableState, Widget, Wrap,
+    },
+};
+
+use crate::{
+    app::{
+        App, ComposeFocus, ComposeMode, ComposePrompt, ContactsMode, MailMergeMode, MessagePrompt,
+        MessageRow, MessageTableMode, Mode, SettingsMode,
+    },
+    bounce::Bounce,
+    config::{ColumnConfig, PreviewPane, TableColumn},
+    keymap,
+    links::{self, Target},
+    message::{self, ConnectionState, Message},
+    outbox::SEND_AT_FORMAT,
+    pgp::{Outcome, Protection, Verification},
+    preview,
+    settings::SETTINGS,
+    theme::Theme,
 };
+use tui_textarea::TextArea;
+
+/// Width of the settings screen's labels.
+const SETTING_LABEL_WIDTH: u16 = 26;
+
+/// Width of the address book's Name column.
+const CONTACT_NAME_WIDTH: u16 = 30;
+/// Width of the time in the list of scheduled messages, e.g. "Thu 15 Jan 17:30".
+const SEND_AT_WIDTH: u16 = 16;
 
-use crate::app::{App, ComposeFocus, ComposeMode, MessageTableMode, Mode};
+/// Width of the folder sidebar, including its border.
+const SIDEBAR_WIDTH: u16 = 16;
 
 impl Widget for &App<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        match self.mode() {
-            Mode::LoadingMessages => render_loading(area, buf),
-            Mode::MessageTable(mode) => render_message_table(self, mode, area, buf),
-            Mode::Message(_) => render_message(self, area, buf),
-            Mode::Compose(focus) => render_compose(self, focus, area, buf),
-        };
+        render_mode(self, self.mode(), area, buf);
     }
 }
 
-fn render_loading(area: Rect, buf: &mut Buffer) {
-    Paragraph::new("Loading . . .").render(area, buf);
+/// Draw the screen for `mode`, which is the app's mode or, under a popup, the one before it.
+fn render_mode(app: &App, mode: &Mode, area: Rect, buf: &mut Buffer) {
+    match mode {
+        Mode::LoadingMessages => render_loading(app, area, buf),
+        Mode::MessageTable(mode) => render_message_table(app, mode, area, buf),
+        Mode::Folders => render_message_table(app, &MessageTableMode::Normal, area, buf),
+        Mode::MoveMessage(_, index) => {
+            render_message_table(app, &MessageTableMode::Normal, area, buf);
+            render_move_popup(app, *index, area, buf);
+        }
+        Mode::Search => {
+            render_message_table(app, &MessageTableMode::Normal, area, buf);
+            render_table_prompt("Search: ", app.search_input(), area, buf);
+        }
+        Mode::Filter => {
+            render_message_table(app, &MessageTableMode::Normal, area, buf);
+            render_table_prompt("Filter: ", app.filter_input(), area, buf);
+        }
+        Mode::CommandLine => {
+            render_message_table(app, &MessageTableMode::Normal, area, buf);
+            render_table_prompt(":", app.command_input(), area, buf);
+        }
+        Mode::Profiles(index) => {
+            render_message_table(app, &MessageTableMode::Normal, area, buf);
+            render_profiles_popup(app, *index, area, buf);
+        }
+        Mode::Message(_) | Mode::Copy(_) | Mode::Go(_) | Mode::Hints(..) => {
+            render_message(app, area, buf)
+        }
+        Mode::MessagePrompt(_, prompt) => {
+            render_message(app, area, buf);
+            let label = match prompt {
+                MessagePrompt::Export => "Export to: ",
+                MessagePrompt::SaveText => "Save text to: ",
+                MessagePrompt::Pipe => "Pipe to: ",
+            };
+            render_table_prompt(label, app.message_prompt_input(), area, buf);
+        }
+        Mode::Attachment(_, index) => render_attachment(app, *index, area, buf),
+        Mode::Links(_, selected) => {
+            render_message(app, area, buf);
+            render_links_popup(app, *selected, area, buf);
+        }
+        Mode::Compose(focus) => render_compose(app, focus, None, area, buf),
+        Mode::ComposePrompt(focus, prompt) => render_compose(app, focus, Some(prompt), area, buf),
+        Mode::MailMerge(mode) => render_mail_merge(app, mode, area, buf),
+        Mode::Settings(mode) => render_settings(app, mode, area, buf),
+        Mode::Contacts(mode) => render_contacts(app, mode, area, buf),
+        Mode::Scheduled(index) => render_scheduled(app, *index, area, buf),
+        Mode::Tags(id, selected, previous) => {
+            render_mode(app, previous, area, buf);
+            render_tags_popup(app, *id, *selected, area, buf);
+        }
+        Mode::Confirm(action, previous) => {
+            render_mode(app, previous, area, buf);
+            render_confirm_popup(&action.question(), area, buf);
+        }
+        Mode::Password(_, previous) => {
+            render_mode(app, previous, area, buf);
+            render_password_popup(app, area, buf);
+        }
+        Mode::Error(error, previous) => {
+            render_mode(app, previous, area, buf);
+            render_error_popup(error, area, buf);
+        }
+    };
+}
+
+/// Frames of the spinner shown while waiting on something, one per tick.
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+fn spinner(app: &App) -> char {
+    SPINNER[app.spinner_frame() % SPINNER.len()]
+}
+
+fn render_loading(app: &App, area: Rect, buf: &mut Buffer) {
+    Paragraph::new(format!("{} Loading…", spinner(app))).render(area, buf);
 }
 
 fn render_message_table(app: &App, mode: &MessageTableMode, area: Rect, buf: &mut Buffer) {
-    let keybinds_text = "  q:Quit  j:Down  k:Up  Enter:View  c:Compose  ";
-    let keybinds_text_len = keybinds_text.len() as u16;
     let status_text = match mode {
         MessageTableMode::Normal => String::from(""),
+        MessageTableMode::Status(status) => format!(" {status} "),
         MessageTableMode::MessageSent(status) => match status {
             crate::app::MessageSentStatus::Success => String::from(" Message sent "),
             crate::app::MessageSentStatus::Failed(e) => format!(" Error: {e} "),
+            crate::app::MessageSentStatus::MailMergeQueued(queued, failures) => {
+                match failures.first() {
+                    Some(e) => format!(
+                        " Mail merge: {queued} in the outbox, {} failed ({e}) ",
+                        failures.len()
+                    ),
+                    None => format!(" Mail merge: {queued} in the outbox "),
+                }
+            }
         },
     };
-    let status_text_len = status_text.len() as u16;
+    // what's going on in the background comes first, and stays up while it lasts
+    let status_text = match app.activity() {
+        Some(activity) => format!(" {} {} {status_text}", spinner(app), activity.text()),
+        None => status_text,
+    };
+    let status_text_len = status_text.chars().count() as u16;
+    let count_text = match app.message_counts() {
+        Some(counts) => {
+            let mut text = format!(
+                "{}: {} unread / {} total ",
+                app.current_folder(),
+                counts.unread,
+                counts.total
+            );
+            if counts.loaded < counts.total {
+                text.push_str(&format!("({} loaded) ", counts.loaded));
+            }
+            text
+        }
+        // search results span folders, so only those found are counted
+        None => match app.unread_count() {
+            0 => String::new(),
+            count => format!("{count} unread "),
+        },
+    };
+    let mut filter_text = String::new();
+    if !app.pending_keys().is_empty() {
+        filter_text.push_str(&format!(" {} ", app.pending_keys()));
+    }
+    if let Some(query) = app.search() {
+        filter_text.push_str(&format!(" Search: {query} "));
+    }
+    if !app.filter().is_empty() {
+        filter_text.push_str(&format!(" Filter: {} ", app.filter()));
+    }
+    if app.flagged_only() {
+        filter_text.push_str(" Flagged only ");
+    }
+    if !app.marked().is_empty() {
+        filter_text.push_str(&format!(" {} marked ", app.marked().len()));
+    }
+    let connection_text = connection_text(app);
+    let connection_text_len = connection_text.chars().count() as u16;
+    let clock_text_len = app.clock().len() as u16 + 4;
+    let keybinds_text = keymap::hint_line(
+        &keymap::hints(app),
+        area.width.saturating_sub(
+            status_text_len
+                + filter_text.len() as u16
+                + count_text.len() as u16
+                + connection_text_len
+                + clock_text_len,
+        ),
+    );
 
     let layout = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]);
-    let [table_area, status_bar_area] = layout.areas(area);
+    let [main_area, status_bar_area] = layout.areas(area);
+    let main_layout = Layout::horizontal([Constraint::Length(SIDEBAR_WIDTH), Constraint::Fill(1)]);
+    let [sidebar_area, table_area] = main_layout.areas(main_area);
+    // announcements get a line of their own above the table, so they aren't missed
+    let alert_layout = Layout::vertical([
+        Constraint::Length(u16::from(app.alert().is_some())),
+        Constraint::Fill(1),
+    ]);
+    let [alert_area, table_area] = alert_layout.areas(table_area);
+    let [table_area, preview_area] = match app.preview_pane() {
+        PreviewPane::Off => [table_area, Rect::default()],
+        PreviewPane::Right => Layout::horizontal([Constraint::Fill(1); 2]).areas(table_area),
+        PreviewPane::Below => Layout::vertical([Constraint::Fill(1); 2]).areas(table_area),
+    };
     let status_bar_layout = Layout::horizontal([
-        Constraint::Max(keybinds_text_len + 10),
-        Constraint::Max(status_text_len as u16),
+        Constraint::Length(keybinds_text.len() as u16),
+        Constraint::Max(status_text_len),
+        Constraint::Fill(1),
+        Constraint::Length(filter_text.len() as u16),
+        Constraint::Length(count_text.len() as u16),
+        Constraint::Length(connection_text_len),
+        Constraint::Length(clock_text_len),
     ]);
-    let [keybinds_area, status_area] = status_bar_layout.areas(status_bar_area);
+    let [
+        keybinds_area,
+        status_area,
+        _,
+        filter_area,
+        count_area,
+        connection_area,
+        clock_area,
+    ] = status_bar_layout.areas(status_bar_area);
+    // asked before the table state is borrowed for rendering
+    let preview_loaded = app.preview_loaded();
     let mut table_state = app.message_table_state().borrow_mut();
-    let rows = app
-        .messages()
-        .iter()
-        .map(|m| {
-            Row::new(vec![
-                format!("{:4}", m.id()),
-                m.from().into(),
-                m.subject().into(),
-            ])
-        })
-        .collect::<Vec<Row>>();
-    let widths = [
-        Constraint::Length(5),
-        Constraint::Length(25),
-        Constraint::Length(50),
-    ];
-    let table = Table::new(rows, widths)
-        .column_spacing(1)
-        .style(Style::new())
-        .header(Row::new(vec!["ID", "From", "Subject"]).style(Style::new().bold()))
-        .row_highlight_style(Style::new().reversed());
+    let columns = &app.columns().show;
+    let widths = column_widths(app.columns());
+    let compact = table_area.width < full_table_width(&widths);
+    let message_rows = app.message_rows();
+    let messages: Vec<&Message> = message_rows.iter().map(|row| row.message).collect();
+    let relative = app.relative_dates();
+    let theme = app.theme();
+    let rows = message_rows.iter().map(|row| {
+        if compact {
+            compact_message_row(row, table_area.width, relative, theme)
+        } else {
+            message_row(row, columns, relative, theme)
+        }
+    });
+    let (rows, message_rows) = if app.group_by_date() {
+        // put the headers in the first column wide enough for them
+        let label_column = if compact {
+            1
+        } else {
+            widths
+                .iter()
+                .position(|width| *width >= DATE_WIDTH)
+                .unwrap_or(0)
+        };
+        date_grouped_rows(&messages, rows, label_column)
+    } else {
+        (rows.collect(), (0..messages.len()).collect())
+    };
+    let row_count = rows.len();
+    let table = if compact {
+        compact_message_table(rows, theme)
+    } else {
+        message_table(rows, columns, &widths, theme)
+    };
+    // the app selects messages rather than rows, so skip over any headers before rendering,
+    // then keep the scroll offset for the next frame
+    let mut row_state = table_state.clone().with_selected(
+        table_state
+            .selected()
+            .and_then(|i| message_rows.get(i).copied()),
+    );
 
     let keybinds = Paragraph::new(keybinds_text);
     let status = Paragraph::new(status_text).style(if status_text_len == 0 {
         Style::default()
     } else {
-        Style::default().reversed()
+        theme.status_bar()
     });
 
-    StatefulWidget::render(table, table_area, buf, &mut *table_state);
+    render_folder_sidebar(app, sidebar_area, buf);
+    if let Some(alert) = app.alert() {
+        Line::from(format!(" {alert} "))
+            .style(theme.status_bar().bold())
+            .render(alert_area, buf);
+    }
+    StatefulWidget::render(table, table_area, buf, &mut row_state);
+    *table_state.offset_mut() = row_state.offset();
+    // compact rows take two lines, and the full table a line for its header
+    let table_height = if compact {
+        table_area.height / 2
+    } else {
+        table_area.height.saturating_sub(1)
+    };
+    app.table_height().set(table_height);
+    // only big folders need a scrollbar, down the table's right edge below its header
+    if row_count > table_height as usize {
+        let scrollbar_area = if compact {
+            table_area
+        } else {
+            Rect {
+                y: table_area.y + 1,
+                height: table_area.height.saturating_sub(1),
+                ..table_area
+            }
+        };
+        let mut scroll_state = app.message_scroll_state().borrow_mut();
+        *scroll_state = scroll_state
+            .content_length(row_count)
+            .viewport_content_length(table_height as usize)
+            .position(row_state.selected().unwrap_or(0));
+        StatefulWidget::render(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight),
+            scrollbar_area,
+            buf,
+            &mut scroll_state,
+        );
+    }
+    if app.preview_pane() != PreviewPane::Off {
+        render_preview(app, preview_loaded, preview_area, buf);
+    }
     keybinds.render(keybinds_area, buf);
     status.render(status_area, buf);
+    Line::from(filter_text)
+        .style(theme.status_bar())
+        .render(filter_area, buf);
+    Line::from(count_text).render(count_area, buf);
+    Line::from(connection_text).render(connection_area, buf);
+    Line::from(app.clock())
+        .right_aligned()
+        .render(clock_area, buf);
+}
+
+/// Whether the provider can reach everything, and when messages were last loaded from it, e.g.
+/// "● Online, synced 12:45".
+fn connection_text(app: &App) -> String {
+    let synced = app
+        .last_sync()
+        .map(|time| format!(", synced {}", time.format("%H:%M")))
+        .unwrap_or_default();
+    match app.connection() {
+        ConnectionState::Connecting => String::from("◌ Connecting "),
+        ConnectionState::Online => format!("● Online{synced} "),
+        ConnectionState::Offline(_) => format!("○ Offline{synced} "),
+    }
+}
+
+/// Widths of the flag, ID, Date and Size columns. The others can be resized.
+const FLAG_WIDTH: u16 = 2;
+const ID_WIDTH: u16 = 5;
+const DATE_WIDTH: u16 = 9;
+const SIZE_WIDTH: u16 = 6;
+const COLUMN_SPACING: u16 = 1;
+
+/// Widths of the columns shown in the message table, in order.
+fn column_widths(columns: &ColumnConfig) -> Vec<u16> {
+    columns
+        .show
+        .iter()
+        .map(|column| match column {
+            TableColumn::Flags => FLAG_WIDTH,
+            TableColumn::Id => ID_WIDTH,
+            TableColumn::Date => DATE_WIDTH,
+            TableColumn::Size => SIZE_WIDTH,
+            TableColumn::From => columns.from,
+            TableColumn::To => columns.to,
+            TableColumn::Subject => columns.subject,
+            TableColumn::Folder => columns.folder,
+        })
+        .collect()
+}
+
+/// Width needed to show every column of the message table in full.
+fn full_table_width(widths: &[u16]) -> u16 {
+    widths.iter().sum::<u16>() + COLUMN_SPACING * (widths.len() as u16).saturating_sub(1)
+}
+
+fn column_title(column: TableColumn) -> &'static str {
+    match column {
+        TableColumn::Flags => "",
+        TableColumn::Id => "ID",
+        TableColumn::Date => "Date",
+        TableColumn::From => "From",
+        TableColumn::To => "To",
+        TableColumn::Subject => "Subject",
+        TableColumn::Size => "Size",
+        TableColumn::Folder => "Folder",
+    }
 }
 
+fn column_text(column: TableColumn, row: &MessageRow, relative: bool) -> String {
+    let message = row.message;
+    match column {
+        TableColumn::Flags => flag_text(row),
+        TableColumn::Id => format!("{:4}", message.id()),
+        TableColumn::Date => table_date(message, relative),
+        TableColumn::From => message.from().into(),
+        TableColumn::To => message.to().into(),
+        TableColumn::Subject => thread_subject(row),
+        TableColumn::Size => format!("{:>5}", size_text(message.size())),
+        TableColumn::Folder => message.folder().into(),
+    }
+}
+
+/// A message's size, e.g. "512B", "14K" or "2.3M".
+fn size_text(size: Option<u64>) -> String {
+    match size {
+        None => String::new(),
+        Some(bytes @ ..1024) => format!("{bytes}B"),
+        Some(bytes @ ..1_048_576) => format!("{}K", bytes.div_ceil(1024)),
+        Some(bytes) => format!("{:.1}M", bytes as f64 / 1_048_576.0),
+    }
+}
+
+fn message_style(row: &MessageRow, theme: &Theme) -> Style {
+    let style = if row.message.is_unread() {
+        theme.unread()
+    } else {
+        Style::new()
+    };
+    if row.marked {
+        style.patch(theme.marked())
+    } else {
+        style
+    }
+}
+
+/// `*` for a flagged message, then `~` for a muted one.
+fn flag_text(row: &MessageRow) -> String {
+    let flagged = if row.message.is_flagged() { '*' } else { ' ' };
+    let muted = if row.muted { "~" } else { "" };
+    format!("{flagged}{muted}")
+}
+
+/// The subject, indented to show a reply's place in its thread, or with the number of messages
+/// in a collapsed thread.
+fn thread_subject(row: &MessageRow) -> String {
+    let subject = row.message.subject();
+    match (row.depth, row.hidden_replies) {
+        (0, 0) => subject.to_string(),
+        (0, replies) => format!("{subject} ({})", replies + 1),
+        (depth, _) => format!("{}└ {subject}", "  ".repeat(depth - 1)),
+    }
+}
+
+/// The subject of a message in the table, after its tags and followed by the start of its body,
+/// dimmed.
+fn subject_line(row: &MessageRow, theme: &Theme) -> Line<'static> {
+    let mut spans = vec![];
+    for tag in row.message.tags() {
+        spans.push(Span::styled(format!(" {tag} "), theme.tag()));
+        spans.push(Span::raw(" "));
+    }
+    spans.push(Span::raw(thread_subject(row)));
+    if !row.message.snippet().is_empty() {
+        spans.push(Span::raw(format!(" {}", row.message.snippet())).dim());
+    }
+    Line::from(spans)
+}
+
+fn message_row(
+    row: &MessageRow,
+    columns: &[TableColumn],
+    relative: bool,
+    theme: &Theme,
+) -> Row<'static> {
+    Row::new(columns.iter().map(|column| match column {
+        TableColumn::Subject => Cell::from(subject_line(row, theme)),
+        column => Cell::from(column_text(*column, row, relative)),
+    }))
+    .style(message_style(row, theme))
+}
+
+fn message_table(
+    rows: Vec<Row<'static>>,
+    columns: &[TableColumn],
+    widths: &[u16],
+    theme: &Theme,
+) -> Table<'static> {
+    let header = Row::new(columns.iter().map(|column| column_title(*column)));
+    Table::new(rows, widths.iter().copied().map(Constraint::Length))
+        .column_spacing(COLUMN_SPACING)
+        .style(Style::new())
+        .header(header.style(theme.header()))
+        .row_highlight_style(theme.selection())
+}
+
+/// A row for terminals too narrow for every column: each message takes two lines, with the
+/// sender and date above the subject.
+fn compact_message_row(
+    row: &MessageRow,
+    width: u16,
+    relative: bool,
+    theme: &Theme,
+) -> Row<'static> {
+    let message = row.message;
+    let text_width = width.saturating_sub(FLAG_WIDTH + COLUMN_SPACING) as usize;
+    let date = table_date(message, relative);
+    let from_width = text_width.saturating_sub(date.chars().count() + 1);
+    let from: String = message.from().chars().take(from_width).collect();
+    let text = Text::from(vec![
+        Line::from(format!("{from:from_width$} {date}")),
+        subject_line(row, theme),
+    ]);
+
+    Row::new(vec![Cell::from(flag_text(row)), Cell::from(text)])
+        .height(2)
+        .style(message_style(row, theme))
+}
+
+fn compact_message_table(rows: Vec<Row<'static>>, theme: &Theme) -> Table<'static> {
+    Table::new(rows, [Constraint::Length(FLAG_WIDTH), Constraint::Fill(1)])
+        .column_spacing(COLUMN_SPACING)
+        .style(Style::new())
+        .row_highlight_style(theme.selection())
+}
+
+/// Insert a header row before each run of messages from the same period ("Today", "Yesterday",
+/// ...), with its label in `label_column`. Also returns the row index of each message.
+fn date_grouped_rows<'a>(
+    messages: &[&Message],
+    rows: impl Iterator<Item = Row<'a>>,
+    label_column: usize,
+) -> (Vec<Row<'a>>, Vec<usize>) {
+    let today = Local::now().date_naive();
+    let mut grouped = vec![];
+    let mut message_rows = vec![];
+    let mut previous_group = None;
+
+    for (message, row) in messages.iter().zip(rows) {
+        let group = date_group(message.date(), today);
+        if previous_group != Some(group) {
+            let mut cells = vec![Cell::from(""); label_column];
+            cells.push(Cell::from(group));
+            grouped.push(Row::new(cells).style(Style::new().italic().dim()));
+            previous_group = Some(group);
+        }
+        message_rows.push(grouped.len());
+        grouped.push(row);
+    }
+
+    (grouped, message_rows)
+}
+
+fn date_group(date: Option<DateTime<Local>>, today: NaiveDate) -> &'static str {
+    let Some(date) = date else {
+        return "Older";
+    };
+
+    match (today - date.date_naive()).num_days() {
+        ..=0 => "Today",
+        1 => "Yesterday",
+        2..=7 => "Last week",
+        _ => "Older",
+    }
+}
+
+/// The date shown in the message table: how long ago the message was sent when `relative`,
+/// otherwise the time for today's messages and the day for older ones.
+fn table_date(message: &Message, relative: bool) -> String {
+    let Some(date) = message.date() else {
+        return String::new();
+    };
+    let now = Local::now();
+    let today = now.date_naive();
+
+    if !relative {
+        let format = if date.date_naive() == today {
+            "%H:%M"
+        } else {
+            "%b %e"
+        };
+        return date.format(format).to_string();
+    }
+
+    let age = now - date;
+    match age.num_minutes() {
+        ..1 => String::from("Just now"),
+        minutes @ 1..60 => format!("{minutes}m ago"),
+        _ if date.date_naive() == today => format!("{}h ago", age.num_hours()),
+        _ if today.pred_opt() == Some(date.date_naive()) => String::from("Yesterday"),
+        _ if date.year() == now.year() => date.format("%b %-d").to_string(),
+        _ => date.format("%b %Y").to_string(),
+    }
+}
+
+/// The selected message's body, beside or below the message table.
+fn render_preview(app: &App, loaded: Option<bool>, area: Rect, buf: &mut Buffer) {
+    let block = Block::default().borders(Borders::ALL).title(" Preview ");
+    let inner = block.inner(area);
+    block.render(area, buf);
+
+    match loaded {
+        Some(true) => {}
+        Some(false) => return Line::from("Loading...").render(inner, buf),
+        None => return,
+    }
+    render_message_body(app, inner, buf);
+}
+
+fn render_folder_sidebar(app: &App, area: Rect, buf: &mut Buffer) {
+    let folders = app.folders().iter().map(|folder| {
+        let line = Line::from(folder.as_str());
+        if folder == app.current_folder() {
+            line.bold()
+        } else {
+            line
+        }
+    });
+    // saved searches follow, in bold while their results are shown
+    let searches = app.saved_searches().iter().map(|search| {
+        let line = Line::from(format!("⌕ {}", search.name));
+        if app.search() == Some(search.query.as_str()) {
+            line.bold()
+        } else {
+            line
+        }
+    });
+    let items = folders.chain(searches);
+    // name the profile, unless it's the only one most people have
+    let block = if app.profile().is_default() {
+        Block::default()
+    } else {
+        Block::default().title(format!(" {} ", app.profile().name()))
+    };
+    let list = List::new(items)
+        .block(block.borders(Borders::RIGHT))
+        .highlight_style(match app.mode() {
+            Mode::Folders => app.theme().selection(),
+            _ => Style::new(),
+        });
+
+    StatefulWidget::render(list, area, buf, &mut *app.folder_list_state().borrow_mut());
+}
+
+/// A list of folders to move the selected message to, over the middle of the message table.
+fn render_move_popup(app: &App, selected: usize, area: Rect, buf: &mut Buffer) {
+    let popup_area = centered(area, SIDEBAR_WIDTH + 8, app.folders().len() as u16 + 2);
+    let list = List::new(app.folders().iter().map(String::as_str))
+        .block(Block::default().borders(Borders::ALL).title(" Move to "))
+        .highlight_style(app.theme().selection());
+
+    Clear.render(popup_area, buf);
+    StatefulWidget::render(
+        list,
+        popup_area,
+        buf,
+        &mut ListState::default().with_selected(Some(selected)),
+    );
+}
+
+/// A search or filter being typed, in place of the message table's status bar.
+fn render_table_prompt(label: &str, input: &RefCell<TextArea>, area: Rect, buf: &mut Buffer) {
+    let prompt_area = Rect {
+        y: area.bottom().saturating_sub(1),
+        height: 1.min(area.height),
+        ..area
+    };
+    let layout = Layout::horizontal([Constraint::Length(label.len() as u16), Constraint::Fill(1)]);
+    let [label_area, input_area] = layout.areas(prompt_area);
+
+    let mut input = input.borrow_mut();
+    input.set_cursor_line_style(Style::default());
+    input.set_cursor_style(Style::default().reversed());
+    Clear.render(prompt_area, buf);
+    Line::from(label).render(label_area, buf);
+    input.render(input_area, buf);
+}
+
+/// The profiles that can be switched to, over the middle of the message table.
+fn render_profiles_popup(app: &App, selected: usize, area: Rect, buf: &mut Buffer) {
+    let names: Vec<&str> = app
+        .profiles()
+        .iter()
+        .map(|profile| profile.name())
+        .collect();
+    let longest = names.iter().map(|name| name.len()).max().unwrap_or(0) as u16;
+    let popup_area = centered(
+        area,
+        (longest + 4).max(SIDEBAR_WIDTH + 8),
+        names.len() as u16 + 2,
+    );
+    let list = List::new(names)
+        .block(Block::default().borders(Borders::ALL).title(" Profiles "))
+        .highlight_style(app.theme().selection());
+
+    Clear.render(popup_area, buf);
+    StatefulWidget::render(
+        list,
+        popup_area,
+        buf,
+        &mut ListState::default().with_selected(Some(selected)),
+    );
+}
+
+/// The links in the current message, numbered so they can be opened with a single key.
+fn render_links_popup(app: &App, selected: usize, area: Rect, buf: &mut Buffer) {
+    let items: Vec<String> = app
+        .links()
+        .iter()
+        .enumerate()
+        .map(|(i, link)| {
+            let number = if i < 9 {
+                (i + 1).to_string()
+            } else {
+                String::from(" ")
+            };
+            if link.text == link.url {
+                format!("{number}  {}", link.url)
+            } else {
+                format!("{number}  {}  {}", link.text, link.url)
+            }
+        })
+        .collect();
+    let longest = items.iter().map(|item| item.len()).max().unwrap_or(0) as u16;
+    let popup_area = centered(
+        area,
+        (longest + 2).min(area.width.saturating_sub(4)),
+        (app.links().len() as u16 + 2).min(area.height.saturating_sub(2)),
+    );
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(" Links "))
+        .highlight_style(app.theme().selection());
+
+    Clear.render(popup_area, buf);
+    StatefulWidget::render(
+        list,
+        popup_area,
+        buf,
+        &mut ListState::default().with_selected(Some(selected)),
+    );
+}
+
+/// Width of the tag picker, which is wide enough for most tags.
+const TAGS_WIDTH: u16 = 30;
+
+/// The tag being typed, above the tags to pick from, over the middle of the screen. Tags the
+/// message has are ticked.
+fn render_tags_popup(app: &App, id: u64, selected: usize, area: Rect, buf: &mut Buffer) {
+    let tagged = app
+        .loaded_message(id)
+        .map(|message| message.tags().to_vec())
+        .unwrap_or_default();
+    let choices = app.tag_choices();
+    let items: Vec<String> = choices
+        .iter()
+        .map(|tag| {
+            let tick = if tagged.contains(tag) { "✔" } else { " " };
+            let new = if app.tags().contains(tag) || tagged.contains(tag) {
+                ""
+            } else {
+                " (new)"
+            };
+            format!("{tick} {tag}{new}")
+        })
+        .collect();
+
+    let height = (choices.len() as u16 + 3).min(area.height.saturating_sub(2));
+    let popup_area = centered(area, TAGS_WIDTH.min(area.width), height);
+    let block = Block::default().borders(Borders::ALL).title(" Tag ");
+    let [input_area, list_area] = Layout::vertical([Constraint::Length(1), Constraint::Min(0)])
+        .areas(block.inner(popup_area));
+    Clear.render(popup_area, buf);
+    block.render(popup_area, buf);
+
+    let mut input = app.tag_input().borrow_mut();
+    input.set_cursor_line_style(Style::default());
+    input.set_cursor_style(Style::default().reversed());
+    input.render(input_area, buf);
+    StatefulWidget::render(
+        List::new(items).highlight_style(app.theme().selection()),
+        list_area,
+        buf,
+        &mut ListState::default().with_selected(Some(selected)),
+    );
+}
+
+/// A `width` by `height` area in the middle of `area`.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
+}
+
+/// A yes or no question about an action, over the middle of the screen.
+fn render_confirm_popup(question: &str, area: Rect, buf: &mut Buffer) {
+    let popup_area = centered(area, question.len() as u16 + 4, 3);
+    Clear.render(popup_area, buf);
+    Paragraph::new(question)
+        .block(Block::default().borders(Borders::ALL).title(" Confirm "))
+        .render(popup_area, buf);
+}
+
+/// Widest an error gets before it wraps onto more lines.
+const ERROR_WIDTH: u16 = 60;
+
+/// An error over the middle of the screen, wrapped onto as many lines as it needs.
+fn render_error_popup(error: &str, area: Rect, buf: &mut Buffer) {
+    let width = (error.chars().count() as u16 + 4).clamp(20, ERROR_WIDTH);
+    let paragraph = Paragraph::new(error)
+        .wrap(Wrap { trim: true })
+        .block(Block::default().borders(Borders::ALL).title(" Error "));
+    let popup_area = centered(area, width, paragraph.line_count(width - 2) as u16);
+    Clear.render(popup_area, buf);
+    paragraph.render(popup_area, buf);
+}
+
+/// Width of the password prompt, leaving room for a long password.
+const PASSWORD_WIDTH: u16 = 40;
+
+/// The SMTP password being typed, masked, over the middle of the screen.
+fn render_password_popup(app: &App, area: Rect, buf: &mut Buffer) {
+    let popup_area = centered(area, PASSWORD_WIDTH, 3);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" SMTP password ");
+    let input_area = block.inner(popup_area);
+    Clear.render(popup_area, buf);
+    block.render(popup_area, buf);
+
+    let mut input = app.password_input().borrow_mut();
+    input.set_cursor_line_style(Style::default());
+    input.set_cursor_style(Style::default().reversed());
+    input.render(input_area, buf);
+}
+
+/// Widest the message body gets in zen mode, so lines stay easy to read.
+const ZEN_WIDTH: u16 = 80;
+
 fn render_message(app: &App, area: Rect, buf: &mut Buffer) {
-    let default_style = Style::default();
+    if app.zen_mode() {
+        let [column] = Layout::horizontal([Constraint::Max(ZEN_WIDTH)])
+            .flex(Flex::Center)
+            .horizontal_margin(4)
+            .vertical_margin(2)
+            .areas(area);
+        render_message_body(app, column, buf);
+        return;
+    }
+
+    let layout = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]);
+    let [message_area, status_bar_area] = layout.areas(area);
+    // where the body is scrolled to is only known once it is drawn
+    render_message_body(app, message_area, buf);
+
+    let status_text = app
+        .message_status()
+        .map(|status| format!(" {status} "))
+        .unwrap_or_default();
+    let position_text = message_position(app);
+    let keybinds_text = keymap::hint_line(
+        &keymap::hints(app),
+        area.width
+            .saturating_sub(status_text.len() as u16 + position_text.len() as u16),
+    );
+    let status_bar_layout = Layout::horizontal([
+        Constraint::Length(keybinds_text.len() as u16),
+        Constraint::Max(status_text.len() as u16),
+        Constraint::Fill(1),
+        Constraint::Length(position_text.len() as u16),
+    ]);
+    let [keybinds_area, status_area, _, position_area] = status_bar_layout.areas(status_bar_area);
+
+    Paragraph::new(keybinds_text).render(keybinds_area, buf);
+    Paragraph::new(status_text)
+        .style(app.theme().status_bar())
+        .render(status_area, buf);
+    Paragraph::new(position_text).render(position_area, buf);
+}
+
+/// The lines of a message, each quoted line coloured by how deeply it is quoted and everything
+/// after the signature separator dimmed.
+fn styled_body<'a>(lines: &'a [String], theme: &Theme) -> Vec<Line<'a>> {
+    let mut in_signature = false;
+    lines
+        .iter()
+        .map(|line| {
+            in_signature |= line == "-- ";
+            let depth = line
+                .chars()
+                .filter(|&c| c != ' ')
+                .take_while(|&c| c == '>')
+                .count();
+            let style = match depth {
+                0 if in_signature => theme.signature(),
+                0 => Style::new(),
+                depth => theme.quote(depth),
+            };
+            Line::styled(line.as_str(), style)
+        })
+        .collect()
+}
 
-    let keybinds_text = "  q:Quit  j:Down  k:Up  h:Left  l:Right  ";
+/// Which lines of the message body are on screen, out of how many, and how far through it they
+/// reach, e.g. "Lines 41-80 of 200 (40%)". Nothing when it all fits.
+fn message_position(app: &App) -> String {
+    let lines = app.message_lines().get() as usize;
+    let height = app.message_height().get() as usize;
+    if lines <= height {
+        return String::new();
+    }
+    let first = app.message_scroll().get() as usize + 1;
+    let last = (first + height - 1).min(lines);
+    format!(
+        " Lines {first}-{last} of {lines} ({}%) ",
+        last * 100 / lines
+    )
+}
 
+/// The headers and body of the current message, with long lines wrapped to fit `area` unless
+/// they are to be scrolled to sideways.
+fn render_message_body(app: &App, area: Rect, buf: &mut Buffer) {
+    let area = match app.current_message().verification() {
+        Some(verification) => {
+            let [banner_area, body_area] =
+                Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(area);
+            verification_banner(verification).render(banner_area, buf);
+            body_area
+        }
+        None => area,
+    };
+    let area = match app.bounce() {
+        Some(bounce) => {
+            let lines = bounce_banner(bounce);
+            let [banner_area, body_area] =
+                Layout::vertical([Constraint::Length(lines.len() as u16), Constraint::Min(0)])
+                    .areas(area);
+            Paragraph::new(lines).render(banner_area, buf);
+            body_area
+        }
+        None => area,
+    };
+
+    // a column is kept for the scrollbar, so that it never covers the text
+    let [area, scrollbar_area] =
+        Layout::horizontal([Constraint::Fill(1), Constraint::Length(1)]).areas(area);
+    let textarea = app.message_textarea().borrow();
+    let lines = styled_body(textarea.lines(), app.theme());
+    let (paragraph, side_scroll) = if app.wrap_message() {
+        (Paragraph::new(lines).wrap(Wrap { trim: false }), 0)
+    } else {
+        let widest = lines.iter().map(Line::width).max().unwrap_or(0) as u16;
+        let side_scroll = app
+            .message_side_scroll()
+            .get()
+            .min(widest.saturating_sub(area.width));
+        app.message_side_scroll().set(side_scroll);
+        (Paragraph::new(lines), side_scroll)
+    };
+    // the wrapped length changes with the width, e.g. when the terminal is resized
+    let line_count = paragraph.line_count(area.width) as u16;
+    let last_scroll = line_count.saturating_sub(area.height);
+    let scroll = app.message_scroll().get().min(last_scroll);
+    app.message_scroll().set(scroll);
+    app.message_height().set(area.height);
+    app.message_lines().set(line_count);
+    paragraph.scroll((scroll, side_scroll)).render(area, buf);
+
+    if last_scroll > 0 {
+        let mut scroll_state = ScrollbarState::new(last_scroll as usize).position(scroll as usize);
+        Scrollbar::new(ScrollbarOrientation::VerticalRight).render(
+            scrollbar_area,
+            buf,
+            &mut scroll_state,
+        );
+    }
+
+    if let Mode::Hints(_, _, typed) = app.mode() {
+        render_link_hints(app, &textarea.lines().join("\n"), typed, area, buf);
+    }
+}
+
+/// How link hint labels stand out from the message they are drawn over.
+const HINT_STYLE: Style = Style::new()
+    .fg(Color::Black)
+    .bg(Color::Yellow)
+    .add_modifier(Modifier::BOLD);
+
+/// Labels over the links and email addresses drawn in `area`, found in the text of the rows
+/// as drawn so that only those in view are labelled. A link cut off by the edge of the screen,
+/// or where a long line wraps, is labelled where it starts.
+fn render_link_hints(app: &App, text: &str, typed: &str, area: Rect, buf: &mut Buffer) {
+    let targets = links::find_targets(text);
+    let mut labelled: Vec<Target> = vec![];
+    let mut positions = vec![];
+    for y in area.top()..area.bottom() {
+        // the row as text, and the column each of its bytes is drawn in
+        let mut row = String::new();
+        let mut columns = vec![];
+        for x in area.left()..area.right() {
+            let symbol = buf[(x, y)].symbol();
+            columns.extend(std::iter::repeat_n(x, symbol.len()));
+            row.push_str(symbol);
+        }
+
+        for (start, shown) in links::find_targets(&row) {
+            let target = targets
+                .iter()
+                .map(|(_, target)| target)
+                .find(|target| *target == &shown)
+                .or_else(|| {
+                    targets
+                        .iter()
+                        .map(|(_, target)| target)
+                        .find(|target| target.as_str().starts_with(shown.as_str()))
+                });
+            let Some(target) = target else {
+                continue;
+            };
+            let index = match labelled.iter().position(|t| t == target) {
+                Some(index) => index,
+                None => {
+                    labelled.push(target.clone());
+                    labelled.len() - 1
+                }
+            };
+            positions.push((columns[start], y, index));
+        }
+    }
+
+    let labels = links::hint_labels(labelled.len());
+    for (x, y, index) in positions {
+        let label = &labels[index];
+        if label.starts_with(typed) {
+            let width = (area.right() - x) as usize;
+            buf.set_stringn(x, y, label, width, HINT_STYLE);
+        }
+    }
+    *app.hint_targets().borrow_mut() = labelled;
+}
+
+/// What decrypting or verifying a PGP/MIME message found, coloured by how much it can be trusted.
+fn verification_banner(verification: &Verification) -> Line<'static> {
+    let (symbol, style) = match verification.outcome {
+        Outcome::Good(_) => ("✔", Style::new().green()),
+        Outcome::Unsigned | Outcome::Unverified(_) | Outcome::UnknownKey(_) => {
+            ("?", Style::new().yellow())
+        }
+        Outcome::Mismatch(_) | Outcome::Failed(_) => ("✘", Style::new().red()),
+    };
+    Line::from(format!("{symbol} {}", verification.text())).style(style.bold())
+}
+
+/// A line for each recipient a bounced message didn't reach, and how to find the message.
+fn bounce_banner(bounce: &Bounce) -> Vec<Line<'static>> {
+    let mut lines: Vec<Line> = bounce
+        .lines()
+        .into_iter()
+        .map(|line| Line::from(format!("✘ {line}")).style(Style::new().red().bold()))
+        .collect();
+    if bounce.original.is_some() {
+        lines.push(Line::from("  O finds the message that bounced").dim());
+    }
+    lines
+}
+
+fn render_attachment(app: &App, index: usize, area: Rect, buf: &mut Buffer) {
     let layout = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]);
-    let [message_area, keybinds_area] = layout.areas(area);
+    let [preview_area, keybinds_area] = layout.areas(area);
+    let part = &app.current_message().parts()[index];
+    let title = format!(
+        " {} ({}) ",
+        part.filename().unwrap_or_default(),
+        part.content_type()
+    );
 
-    let mut textarea = app.message_textarea().borrow_mut();
+    Paragraph::new(preview::highlight(part))
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .scroll((app.preview_scroll(), 0))
+        .render(preview_area, buf);
+    Line::from(keymap::hint_line(&keymap::hints(app), area.width)).render(keybinds_area, buf);
+}
 
-    textarea.set_cursor_line_style(default_style);
-    textarea.set_cursor_style(default_style);
+/// A list of addresses, with any that aren't valid highlighted.
+fn address_line(list: &str) -> Line<'static> {
+    let invalid = message::invalid_addresses(list);
+    let mut spans = vec![];
+    for (i, entry) in message::split_addresses(list).into_iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(","));
+        }
+        let span = Span::raw(entry.to_string());
+        spans.push(if invalid.contains(&entry.trim()) {
+            span.red().underlined()
+        } else {
+            span
+        });
+    }
+    Line::from(spans)
+}
 
-    textarea.render(message_area, buf);
-    Paragraph::new(keybinds_text).render(keybinds_area, buf);
+/// Addresses that complete the one being typed, in a popup under the field it is typed in.
+fn render_completions(app: &App, field_area: Rect, buf: &mut Buffer) {
+    let completions = app.completions();
+    if completions.is_empty() {
+        return;
+    }
+
+    let longest = completions
+        .iter()
+        .map(|completion| completion.chars().count())
+        .max()
+        .unwrap_or(0) as u16;
+    let popup_area = Rect {
+        x: field_area.x,
+        y: field_area.y + 1,
+        width: longest + 2,
+        height: completions.len() as u16 + 2,
+    }
+    .intersection(buf.area);
+    let list = List::new(completions.iter().map(String::as_str))
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(app.theme().selection());
+
+    Clear.render(popup_area, buf);
+    StatefulWidget::render(
+        list,
+        popup_area,
+        buf,
+        &mut ListState::default().with_selected(Some(app.completion_index())),
+    );
 }
 
-fn render_compose(app: &App, focus: &ComposeFocus, area: Rect, buf: &mut Buffer) {
+fn render_compose(
+    app: &App,
+    focus: &ComposeFocus,
+    prompt: Option<&ComposePrompt>,
+    area: Rect,
+    buf: &mut Buffer,
+) {
     let default_style = Style::default();
     let reversed_style = default_style.reversed();
+    let label_style = app.theme().compose_label();
 
+    let from_height = u16::from(app.has_identities());
+    let attachments_height = u16::from(!app.compose_attachments().is_empty());
+    let protection_height = u16::from(app.compose_protection() != Protection::None);
     let layout = Layout::vertical([
+        Constraint::Length(from_height),
         Constraint::Length(1),
         Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(attachments_height),
+        Constraint::Length(protection_height),
         Constraint::Min(0),
         Constraint::Length(1),
     ]);
-    let [to_area, subject_area, message_area, keybind_area] = layout.areas(area);
+    let [
+        from_area,
+        to_area,
+        cc_area,
+        subject_area,
+        attachments_area,
+        protection_area,
+        message_area,
+        keybind_area,
+    ] = layout.areas(area);
 
+    let from_layout = Layout::horizontal([Constraint::Length(9), Constraint::Max(71)]);
+    let [from_label_area, from_value_area] = from_layout.areas(from_area);
     let to_layout = Layout::horizontal([Constraint::Length(9), Constraint::Max(71)]);
     let [to_label_area, to_input_area] = to_layout.areas(to_area);
+    let cc_layout = Layout::horizontal([Constraint::Length(9), Constraint::Max(71)]);
+    let [cc_label_area, cc_input_area] = cc_layout.areas(cc_area);
     let subject_layout = Layout::horizontal([Constraint::Length(9), Constraint::Max(71)]);
     let [subject_label_area, subject_input_area] = subject_layout.areas(subject_area);
+    let attachments_layout = Layout::horizontal([Constraint::Length(9), Constraint::Fill(1)]);
+    let [attachments_label_area, attachments_list_area] =
+        attachments_layout.areas(attachments_area);
+    let protection_layout = Layout::horizontal([Constraint::Length(9), Constraint::Fill(1)]);
+    let [protection_label_area, protection_value_area] = protection_layout.areas(protection_area);
 
     let to_label = Line::from("To: ").right_aligned().style(match focus {
-        ComposeFocus::To(ComposeMode::Normal) => reversed_style,
+        ComposeFocus::To(ComposeMode::Normal) => label_style,
         _ => default_style, // hide cursor
     });
     let mut to_input = app.compose_to_input().borrow_mut();
+    let cc_label = Line::from("Cc: ").right_aligned().style(match focus {
+        ComposeFocus::Cc(ComposeMode::Normal) => label_style,
+        _ => default_style, // hide cursor
+    });
+    let mut cc_input = app.compose_cc_input().borrow_mut();
     let subject_label = Line::from("Subject: ").right_aligned().style(match focus {
-        ComposeFocus::Subject(ComposeMode::Normal) => reversed_style,
+        ComposeFocus::Subject(ComposeMode::Normal) => label_style,
         _ => default_style, // hide cursor
     });
     let mut subject_input = app.compose_subject_input().borrow_mut();
     let mut message = app.compose_message_input().borrow_mut();
-    let keybinds = match focus {
-        ComposeFocus::Message(ComposeMode::Editing) => Line::from("  Esc:Stop editing  "),
-        _ => Line::from("  q:Back  Tab:Next  Enter:Edit  S:Send  "),
+    let status_text = app
+        .message_status()
+        .map(|status| format!(" {status} "))
+        .unwrap_or_default();
+    let keybinds = Line::from(keymap::hint_line(
+        &keymap::hints(app),
+        area.width.saturating_sub(status_text.len() as u16),
+    ));
+    let keybind_layout = Layout::horizontal([
+        Constraint::Length(keybinds.width() as u16),
+        Constraint::Max(status_text.len() as u16),
+    ]);
+    let prompt_area = Rect {
+        width: keybind_area.width.saturating_sub(status_text.len() as u16),
+        ..keybind_area
     };
+    let [keybind_area, status_area] = keybind_layout.areas(keybind_area);
 
     to_input.set_cursor_line_style(default_style);
     to_input.set_cursor_style(match focus {
         ComposeFocus::To(ComposeMode::Editing) => reversed_style,
         _ => default_style, // hide cursor
     });
+    cc_input.set_cursor_line_style(default_style);
+    cc_input.set_cursor_style(match focus {
+        ComposeFocus::Cc(ComposeMode::Editing) => reversed_style,
+        _ => default_style, // hide cursor
+    });
     subject_input.set_cursor_line_style(default_style);
     subject_input.set_cursor_style(match focus {
         ComposeFocus::Subject(ComposeMode::Editing) => reversed_style,
@@ -143,7 +1235,7 @@ fn render_compose(app: &App, focus: &ComposeFocus, area: Rect, buf: &mut Buffer)
             .borders(Borders::ALL)
             .title(" Message ")
             .title_style(match focus {
-                ComposeFocus::Message(ComposeMode::Normal) => reversed_style,
+                ComposeFocus::Message(ComposeMode::Normal) => label_style,
                 _ => default_style,
             }),
     );
@@ -152,10 +1244,309 @@ fn render_compose(app: &App, focus: &ComposeFocus, area: Rect, buf: &mut Buffer)
         _ => default_style, // hide cursor
     });
 
+    Line::from("From: ")
+        .right_aligned()
+        .render(from_label_area, buf);
+    Line::from(app.compose_from()).render(from_value_area, buf);
     to_label.render(to_label_area, buf);
-    to_input.render(to_input_area, buf);
+    if matches!(focus, ComposeFocus::To(ComposeMode::Editing)) {
+        to_input.render(to_input_area, buf);
+    } else {
+        address_line(&to_input.lines()[0]).render(to_input_area, buf);
+    }
+    cc_label.render(cc_label_area, buf);
+    if matches!(focus, ComposeFocus::Cc(ComposeMode::Editing)) {
+        cc_input.render(cc_input_area, buf);
+    } else {
+        address_line(&cc_input.lines()[0]).render(cc_input_area, buf);
+    }
     subject_label.render(subject_label_area, buf);
     subject_input.render(subject_input_area, buf);
+    Line::from("Attach: ")
+        .right_aligned()
+        .render(attachments_label_area, buf);
+    let attachments: Vec<String> = app
+        .compose_attachments()
+        .iter()
+        .map(|part| {
+            let inline = if part.content_id().is_some() {
+                ", inline"
+            } else {
+                ""
+            };
+            format!(
+                "{} ({}, {} bytes{inline})",
+                part.filename().unwrap_or_default(),
+                part.content_type(),
+                part.content().len()
+            )
+        })
+        .collect();
+    let total = format!(
+        " — {} to send",
+        size_text(Some(app.compose_attachments_size()))
+    );
+    let total = if app.attachments_too_large() {
+        Span::styled(
+            format!("{total}, over the limit"),
+            Style::new().red().bold(),
+        )
+    } else {
+        Span::raw(total).dim()
+    };
+    Line::from(vec![Span::raw(attachments.join(", ")), total]).render(attachments_list_area, buf);
+    Line::from("PGP: ")
+        .right_aligned()
+        .render(protection_label_area, buf);
+    Line::from(app.compose_protection().label()).render(protection_value_area, buf);
     message.render(message_area, buf);
-    keybinds.render(keybind_area, buf);
+    match prompt {
+        Some(prompt) => render_compose_prompt(app, prompt, prompt_area, buf),
+        None => keybinds.render(keybind_area, buf),
+    }
+    Paragraph::new(status_text)
+        .style(app.theme().status_bar())
+        .render(status_area, buf);
+
+    match focus {
+        ComposeFocus::To(ComposeMode::Editing) => render_completions(app, to_input_area, buf),
+        ComposeFocus::Cc(ComposeMode::Editing) => render_completions(app, cc_input_area, buf),
+        _ => {}
+    }
+}
+
+/// The prompt below the composer, in place of the key hints.
+fn render_compose_prompt(app: &App, prompt: &ComposePrompt, area: Rect, buf: &mut Buffer) {
+    let label = match prompt {
+        ComposePrompt::Command => ":",
+        ComposePrompt::Filename => "Filename: ",
+        ComposePrompt::ContentType => "Type: ",
+        ComposePrompt::SendAt => "Send at: ",
+    };
+    let layout = Layout::horizontal([Constraint::Length(label.len() as u16), Constraint::Fill(1)]);
+    let [label_area, input_area] = layout.areas(area);
+
+    let mut input = app.compose_prompt_input().borrow_mut();
+    input.set_cursor_line_style(Style::default());
+    input.set_cursor_style(Style::default().reversed());
+    Line::from(label).render(label_area, buf);
+    input.render(input_area, buf);
+}
+
+/// The keys that can be pressed, followed by any status message.
+fn render_status_bar(app: &App, area: Rect, buf: &mut Buffer) {
+    let status_text = app
+        .message_status()
+        .map(|status| format!(" {status} "))
+        .unwrap_or_default();
+    let keybinds = Line::from(keymap::hint_line(
+        &keymap::hints(app),
+        area.width.saturating_sub(status_text.len() as u16),
+    ));
+    let status_bar_layout = Layout::horizontal([
+        Constraint::Length(keybinds.width() as u16),
+        Constraint::Max(status_text.len() as u16),
+    ]);
+    let [keybinds_area, status_area] = status_bar_layout.areas(area);
+    keybinds.render(keybinds_area, buf);
+    Paragraph::new(status_text)
+        .style(app.theme().status_bar())
+        .render(status_area, buf);
+}
+
+fn render_settings(app: &App, mode: &SettingsMode, area: Rect, buf: &mut Buffer) {
+    let (selected, editing) = match mode {
+        SettingsMode::Normal(index) => (*index, None),
+        SettingsMode::Editing(index) => (*index, Some(SETTINGS[*index])),
+    };
+    let editor_height = match editing {
+        Some(setting) if setting.is_multiline() => 10,
+        Some(_) => 3,
+        None => 0,
+    };
+    let layout = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(editor_height),
+        Constraint::Length(1),
+    ]);
+    let [settings_area, editor_area, status_bar_area] = layout.areas(area);
+
+    let rows = SETTINGS.iter().map(|&setting| {
+        let value = app.setting_value(setting);
+        // multi-line values are edited in full below the list
+        let value = match value.split_once('\n') {
+            Some((first, _)) => format!("{first} ..."),
+            None => value,
+        };
+        Row::new([Cell::from(setting.label()), Cell::from(value)])
+    });
+    let table = Table::new(
+        rows,
+        [Constraint::Length(SETTING_LABEL_WIDTH), Constraint::Min(0)],
+    )
+    .block(Block::default().borders(Borders::ALL).title(" Settings "))
+    .row_highlight_style(app.theme().selection());
+    StatefulWidget::render(
+        table,
+        settings_area,
+        buf,
+        &mut TableState::default().with_selected(Some(selected)),
+    );
+
+    if let Some(setting) = editing {
+        let mut input = app.settings_input().borrow_mut();
+        input.set_cursor_line_style(Style::default());
+        input.set_cursor_style(Style::default().reversed());
+        input.set_block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" {} ", setting.label())),
+        );
+        input.render(editor_area, buf);
+    }
+
+    render_status_bar(app, status_bar_area, buf);
+}
+
+fn render_contacts(app: &App, mode: &ContactsMode, area: Rect, buf: &mut Buffer) {
+    let (selected, editor_title, title) = match mode {
+        ContactsMode::Normal(index) => (*index, None, " Address book "),
+        ContactsMode::Editing(index) => (*index, Some(" Name <email> "), " Address book "),
+        ContactsMode::Importing => (0, Some(" Path to vCard file "), " Address book "),
+        ContactsMode::Picking(index, _) => (*index, None, " Choose a recipient "),
+    };
+    let layout = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(if editor_title.is_some() { 3 } else { 0 }),
+        Constraint::Length(1),
+    ]);
+    let [contacts_area, editor_area, status_bar_area] = layout.areas(area);
+
+    let rows = app
+        .address_book()
+        .iter()
+        .map(|contact| Row::new([contact.name.clone(), contact.email.clone()]));
+    let table = Table::new(
+        rows,
+        [Constraint::Length(CONTACT_NAME_WIDTH), Constraint::Min(0)],
+    )
+    .header(Row::new(["Name", "Email"]).style(app.theme().header()))
+    .block(Block::default().borders(Borders::ALL).title(title))
+    .row_highlight_style(app.theme().selection());
+    // a new contact has no row to select yet
+    let selected = (selected < app.address_book().len()).then_some(selected);
+    StatefulWidget::render(
+        table,
+        contacts_area,
+        buf,
+        &mut TableState::default().with_selected(selected),
+    );
+
+    if let Some(editor_title) = editor_title {
+        let mut input = app.contact_input().borrow_mut();
+        input.set_cursor_line_style(Style::default());
+        input.set_cursor_style(Style::default().reversed());
+        input.set_block(Block::default().borders(Borders::ALL).title(editor_title));
+        input.render(editor_area, buf);
+    }
+
+    render_status_bar(app, status_bar_area, buf);
+}
+
+/// The messages waiting in the outbox, soonest first, with why sending failed for any that did.
+fn render_scheduled(app: &App, selected: usize, area: Rect, buf: &mut Buffer) {
+    let layout = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]);
+    let [table_area, status_bar_area] = layout.areas(area);
+
+    let rows = app.scheduled().iter().map(|scheduled| {
+        let status = match &scheduled.error {
+            Some(error) => format!("Failed: {error}"),
+            None => String::from("Waiting"),
+        };
+        Row::new([
+            scheduled.send_at.format(SEND_AT_FORMAT).to_string(),
+            scheduled.to.clone(),
+            scheduled.subject.clone(),
+            status,
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(SEND_AT_WIDTH),
+            Constraint::Length(CONTACT_NAME_WIDTH),
+            Constraint::Fill(2),
+            Constraint::Fill(1),
+        ],
+    )
+    .header(Row::new(["Send at", "To", "Subject", "Status"]).style(app.theme().header()))
+    .block(Block::default().borders(Borders::ALL).title(" Scheduled "))
+    .row_highlight_style(app.theme().selection());
+    let selected = (!app.scheduled().is_empty()).then_some(selected);
+    StatefulWidget::render(
+        table,
+        table_area,
+        buf,
+        &mut TableState::default().with_selected(selected),
+    );
+
+    render_status_bar(app, status_bar_area, buf);
+}
+
+fn render_mail_merge(app: &App, mode: &MailMergeMode, area: Rect, buf: &mut Buffer) {
+    let default_style = Style::default();
+    let reversed_style = default_style.reversed();
+
+    match mode {
+        MailMergeMode::SelectCsv(error) => {
+            let layout = Layout::vertical([
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ]);
+            let [csv_area, error_area, _, keybind_area] = layout.areas(area);
+            let csv_layout = Layout::horizontal([Constraint::Length(9), Constraint::Max(71)]);
+            let [csv_label_area, csv_input_area] = csv_layout.areas(csv_area);
+
+            let mut csv_input = app.merge_csv_input().borrow_mut();
+            csv_input.set_cursor_line_style(default_style);
+            csv_input.set_cursor_style(reversed_style);
+
+            Line::from("CSV: ")
+                .right_aligned()
+                .render(csv_label_area, buf);
+            csv_input.render(csv_input_area, buf);
+            if let Some(e) = error {
+                Line::from(format!(" Error: {e} "))
+                    .style(app.theme().status_bar())
+                    .render(error_area, buf);
+            }
+            Line::from(keymap::hint_line(&keymap::hints(app), area.width))
+                .render(keybind_area, buf);
+        }
+        MailMergeMode::Preview(index) => {
+            let layout = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]);
+            let [message_area, keybind_area] = layout.areas(area);
+
+            let messages = app.merge_messages();
+            let message = &messages[*index];
+            let text = format!(
+                "To: {}\nSubject: {}\n\n{}",
+                message.to(),
+                message.subject(),
+                message.body()
+            );
+
+            Paragraph::new(text)
+                .block(Block::default().borders(Borders::ALL).title(format!(
+                    " Message {} of {} ",
+                    index + 1,
+                    messages.len()
+                )))
+                .render(message_area, buf);
+            Line::from(keymap::hint_line(&keymap::hints(app), area.width))
+                .render(keybind_area, buf);
+        }
+    }
 }
@@ -0,0 +1,178 @@
+use color_eyre::eyre::eyre;
+use lettre::Address;
+
+use crate::contacts::Contact;
+
+/// Read the contacts from a vCard file, which may hold any number of cards.
+pub fn read(path: &str) -> color_eyre::Result<Vec<Contact>> {
+    let text = std::fs::read_to_string(path)?;
+    let contacts = parse(&text);
+    if contacts.is_empty() {
+        return Err(eyre!("no contacts with an email address in {path}"));
+    }
+    Ok(contacts)
+}
+
+/// A contact for every email address of every card in `text`, without duplicate addresses.
+pub fn parse(text: &str) -> Vec<Contact> {
+    let mut contacts: Vec<Contact> = vec![];
+    let mut card: Option<Card> = None;
+
+    for line in unfold(text) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        // drop any group prefix and parameters, e.g. `item1.EMAIL;TYPE=work`
+        let property = name.split(';').next().unwrap_or_default();
+        let property = property.rsplit('.').next().unwrap_or_default();
+
+        match (property.to_ascii_uppercase().as_str(), &mut card) {
+            ("BEGIN", _) if value.eq_ignore_ascii_case("VCARD") => card = Some(Card::default()),
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VCARD") => {
+                let Some(finished) = card.take() else {
+                    continue;
+                };
+                let name = finished.full_name.or(finished.name).unwrap_or_default();
+                for email in finished.emails {
+                    let duplicate = contacts
+                        .iter()
+                        .any(|contact| contact.email.eq_ignore_ascii_case(&email));
+                    if !duplicate {
+                        contacts.push(Contact {
+                            id: None,
+                            name: name.clone(),
+                            email,
+                        });
+                    }
+                }
+            }
+            ("FN", Some(card)) => card.full_name = Some(unescape(value)).filter(|n| !n.is_empty()),
+            ("N", Some(card)) => card.name = structured_name(value),
+            ("EMAIL", Some(card)) => {
+                let email = unescape(value).trim().to_string();
+                if email.parse::<Address>().is_ok() {
+                    card.emails.push(email);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    contacts
+}
+
+/// The properties of a card that make up contacts.
+#[derive(Debug, Default)]
+struct Card {
+    full_name: Option<String>,
+    /// The name put together from the `N` property, for cards without a formatted name.
+    name: Option<String>,
+    emails: Vec<String>,
+}
+
+/// Join lines that were folded onto the next, which start with a space or tab. iCalendar folds
+/// lines the same way.
+pub fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Turn `Family;Given;Additional;Prefix;Suffix` into a name like "Given Additional Family".
+fn structured_name(value: &str) -> Option<String> {
+    let parts: Vec<String> = value.split(';').map(unescape).collect();
+    let words: Vec<&str> = [1, 2, 0]
+        .iter()
+        .filter_map(|&i| parts.get(i))
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Undo the escaping of special characters in a property value, as in iCalendar too.
+pub fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(name: &str, email: &str) -> Contact {
+        Contact {
+            id: None,
+            name: String::from(name),
+            email: String::from(email),
+        }
+    }
+
+    #[test]
+    fn every_address_of_every_card_is_a_contact() {
+        let text = "BEGIN:VCARD\r\n\
+            VERSION:3.0\r\n\
+            FN:Jane Doe\r\n\
+            N:Doe;Jane;;;\r\n\
+            EMAIL;TYPE=work:jane@example.com\r\n\
+            item1.EMAIL:jane.doe@example.org\r\n\
+            END:VCARD\r\n\
+            BEGIN:VCARD\r\n\
+            N:Smith;John;Paul;Dr;\r\n\
+            EMAIL:john@example.com\r\n\
+            EMAIL:not an address\r\n\
+            END:VCARD\r\n";
+
+        assert_eq!(
+            parse(text),
+            [
+                contact("Jane Doe", "jane@example.com"),
+                contact("Jane Doe", "jane.doe@example.org"),
+                contact("John Paul Smith", "john@example.com"),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_addresses_and_cards_without_any_are_left_out() {
+        let text = "BEGIN:VCARD\nFN:Jane\nEMAIL:jane@example.com\nEND:VCARD\n\
+            BEGIN:VCARD\nFN:Jane again\nEMAIL:JANE@example.com\nEND:VCARD\n\
+            BEGIN:VCARD\nFN:Nobody\nEND:VCARD\n\
+            EMAIL:outside@example.com\n";
+
+        assert_eq!(parse(text), [contact("Jane", "jane@example.com")]);
+    }
+
+    #[test]
+    fn folded_lines_are_joined() {
+        assert_eq!(
+            unfold("FN:Jane\r\n  Doe\r\nEMAIL:jane@exa\r\n\tmple.com"),
+            ["FN:Jane Doe", "EMAIL:jane@example.com"]
+        );
+    }
+
+    #[test]
+    fn escapes_are_undone() {
+        assert_eq!(
+            unescape(r"Doe\, Jane\; Esq.\nLine\\two\"),
+            "Doe, Jane; Esq.\nLine\\two\\"
+        );
+    }
+}
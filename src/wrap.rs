@@ -0,0 +1,150 @@
+use serde::Deserialize;
+
+/// How long lines of the body are sent, so that recipients' plain-text readers don't show each
+/// paragraph as one long line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyFormat {
+    /// As written.
+    #[default]
+    Plain,
+    /// Broken into lines of at most this many columns.
+    Wrap(usize),
+    /// As `format=flowed` (RFC 3676): broken at [`FLOWED_WIDTH`] with a space before each break,
+    /// which readers that understand it join up again to fit their window.
+    Flowed,
+}
+
+/// Where flowed lines are broken, leaving room for a few levels of quoting in replies.
+const FLOWED_WIDTH: usize = 72;
+
+/// The signature separator, which is left exactly as it is.
+const SIGNATURE_SEPARATOR: &str = "-- ";
+
+/// `body` broken into lines of at most `width` columns where they have spaces to break at.
+/// Quoted lines stay quoted.
+pub fn hard_wrap(body: &str, width: usize) -> String {
+    wrap(body, width, false)
+}
+
+/// `body` as `format=flowed` text: long lines are broken with a trailing space, trailing spaces
+/// are taken off the lines the writer ended, and lines that would be read as quoted or as an
+/// mbox `From ` line are space-stuffed.
+pub fn flowed(body: &str) -> String {
+    wrap(body, FLOWED_WIDTH, true)
+}
+
+fn wrap(body: &str, width: usize, flowed: bool) -> String {
+    let mut lines = vec![];
+    for line in body.lines() {
+        if line == SIGNATURE_SEPARATOR {
+            lines.push(line.to_string());
+            continue;
+        }
+        // quoted lines keep their `>`s on every line they are broken into
+        let content = line.trim_start_matches('>');
+        let depth = line.len() - content.len();
+        let (prefix, content) = match depth {
+            0 => (String::new(), content),
+            depth => (
+                format!("{} ", ">".repeat(depth)),
+                content.strip_prefix(' ').unwrap_or(content),
+            ),
+        };
+        let content = if flowed {
+            content.trim_end_matches(' ')
+        } else {
+            content
+        };
+
+        for chunk in break_line(content, width.saturating_sub(prefix.len()).max(1), flowed) {
+            let stuffed = flowed
+                && depth == 0
+                && (chunk.starts_with(' ') || chunk.starts_with('>') || chunk.starts_with("From "));
+            let stuffing = if stuffed { " " } else { "" };
+            let line = format!("{prefix}{stuffing}{chunk}");
+            // an empty quoted line is only its `>`s
+            lines.push(match line.trim_end() {
+                trimmed if trimmed.len() == prefix.trim_end().len() => trimmed.to_string(),
+                _ => line,
+            });
+        }
+    }
+    lines.join("\n")
+}
+
+/// `line` in pieces of at most `width` characters, broken after a space. A word longer than that,
+/// such as a link, gets a piece of its own. Flowed pieces keep the space they were broken after.
+fn break_line(line: &str, width: usize, flowed: bool) -> Vec<&str> {
+    let mut chunks = vec![];
+    let mut rest = line;
+    while rest.chars().count() > width {
+        let limit = rest
+            .char_indices()
+            .nth(width)
+            .map_or(rest.len(), |(i, _)| i);
+        // break at the last space that fits, or else the first one after
+        let fits = if rest[limit..].starts_with(' ') {
+            Some(limit)
+        } else {
+            rest[..limit].rfind(' ').filter(|&i| i > 0)
+        };
+        let Some(space) = fits.or_else(|| rest[limit..].find(' ').map(|i| i + limit)) else {
+            break;
+        };
+        let (chunk, next) = rest.split_at(space + 1);
+        chunks.push(if flowed { chunk } else { chunk.trim_end() });
+        rest = next;
+    }
+    chunks.push(rest);
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_lines_break_at_the_last_space_that_fits() {
+        assert_eq!(hard_wrap("aaa bbb ccc", 7), "aaa bbb\nccc");
+        assert_eq!(hard_wrap("short\n\nlines", 7), "short\n\nlines");
+    }
+
+    #[test]
+    fn long_words_get_a_line_of_their_own() {
+        assert_eq!(
+            hard_wrap("see https://example.com/very/long ok", 10),
+            "see\nhttps://example.com/very/long\nok"
+        );
+    }
+
+    #[test]
+    fn quoted_lines_stay_quoted() {
+        assert_eq!(hard_wrap("> aaa bbb ccc", 9), "> aaa bbb\n> ccc");
+        assert_eq!(hard_wrap(">", 9), ">");
+    }
+
+    #[test]
+    fn the_signature_separator_is_kept() {
+        assert_eq!(hard_wrap("-- \nJane", 5), "-- \nJane");
+        assert_eq!(flowed("-- \nJane"), "-- \nJane");
+    }
+
+    #[test]
+    fn flowed_lines_keep_the_space_they_break_after() {
+        let body = ["word"; 20].join(" ");
+
+        assert_eq!(
+            flowed(&body),
+            format!("{}\n{}", "word ".repeat(14), ["word"; 6].join(" "))
+        );
+    }
+
+    #[test]
+    fn flowed_lines_are_trimmed_and_stuffed() {
+        assert_eq!(
+            flowed("end   \nFrom here\n indented"),
+            "end\n From here\n  indented"
+        );
+    }
+}